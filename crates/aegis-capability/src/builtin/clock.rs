@@ -1,12 +1,15 @@
 //! Clock capability for time access.
 
-use std::time::SystemTime;
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
 
 use serde::{Deserialize, Serialize};
 
 use crate::capability::{
     Action, Capability, CapabilityId, DenialReason, PermissionResult, standard_ids,
 };
+use crate::policy::CapabilityPolicy;
 
 /// Type of clock to provide.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -14,16 +17,27 @@ pub enum ClockType {
     /// Real system time.
     RealTime,
     /// Monotonic clock (for duration measurement).
+    ///
+    /// Readings are nanoseconds since the capability was created, not since
+    /// the Unix epoch.
     #[default]
     Monotonic,
     /// Fixed/mocked time (for deterministic execution).
     Fixed(u64), // Unix timestamp in nanoseconds
+    /// Simulated time that advances by a fixed step on every read.
+    ///
+    /// The n-th read returns `start_nanos + n * step_nanos`, starting at 0.
+    Stepped {
+        /// Time returned by the first read, in nanoseconds.
+        start_nanos: u64,
+        /// Amount the time advances after each read, in nanoseconds.
+        step_nanos: u64,
+    },
     /// No clock access (time functions return errors).
     None,
 }
 
 /// Actions related to clock/time operations.
-#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum ClockAction {
     /// Get the current time.
@@ -48,6 +62,10 @@ impl Action for ClockAction {
             }
         }
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /// Capability for clock/time access.
@@ -67,8 +85,11 @@ impl Action for ClockAction {
 ///
 /// // Fixed time for deterministic tests
 /// let cap = ClockCapability::fixed(1704067200_000_000_000); // 2024-01-01 00:00:00 UTC
+///
+/// // Time that advances by 1ms on every read
+/// let cap = ClockCapability::stepped(1704067200_000_000_000, 1_000_000);
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct ClockCapability {
     /// Type of clock to provide.
     clock_type: ClockType,
@@ -76,6 +97,23 @@ pub struct ClockCapability {
     allow_realtime: bool,
     /// Allow monotonic clock access.
     allow_monotonic: bool,
+    /// Origin for monotonic readings, captured at construction.
+    baseline: Instant,
+    /// Number of reads of a `Stepped` clock so far.
+    steps: AtomicU64,
+}
+
+impl Clone for ClockCapability {
+    /// Clones continue from the same simulated time but advance independently.
+    fn clone(&self) -> Self {
+        Self {
+            clock_type: self.clock_type.clone(),
+            allow_realtime: self.allow_realtime,
+            allow_monotonic: self.allow_monotonic,
+            baseline: self.baseline,
+            steps: AtomicU64::new(self.steps.load(Ordering::Relaxed)),
+        }
+    }
 }
 
 impl ClockCapability {
@@ -85,6 +123,7 @@ impl ClockCapability {
             ClockType::RealTime => (true, true),
             ClockType::Monotonic => (false, true),
             ClockType::Fixed(_) => (true, true), // Fixed provides both
+            ClockType::Stepped { .. } => (true, true),
             ClockType::None => (false, false),
         };
 
@@ -92,6 +131,8 @@ impl ClockCapability {
             clock_type,
             allow_realtime,
             allow_monotonic,
+            baseline: Instant::now(),
+            steps: AtomicU64::new(0),
         }
     }
 
@@ -110,6 +151,14 @@ impl ClockCapability {
         Self::new(ClockType::Fixed(timestamp_nanos))
     }
 
+    /// Create a capability whose time advances by `step_nanos` on every read.
+    pub fn stepped(start_nanos: u64, step_nanos: u64) -> Self {
+        Self::new(ClockType::Stepped {
+            start_nanos,
+            step_nanos,
+        })
+    }
+
     /// Create a capability that denies all clock access.
     pub fn none() -> Self {
         Self::new(ClockType::None)
@@ -130,25 +179,81 @@ impl ClockCapability {
         self.allow_monotonic
     }
 
-    /// Get the current time value.
+    /// Get the current time value for the configured clock type.
     ///
-    /// Returns the timestamp in nanoseconds, or None if clock access is denied.
+    /// Returns nanoseconds since the Unix epoch for `RealTime`, nanoseconds
+    /// since the capability was created for `Monotonic`, the fixed value for
+    /// `Fixed`, the next value in the sequence for `Stepped`, or None if
+    /// clock access is denied.
     pub fn get_time(&self) -> Option<u64> {
         match &self.clock_type {
-            ClockType::RealTime => SystemTime::now()
+            ClockType::RealTime => self.get_realtime_nanos(),
+            ClockType::Monotonic => self.get_monotonic_nanos(),
+            ClockType::Fixed(timestamp) => Some(*timestamp),
+            ClockType::Stepped { .. } => self.simulated_time(true),
+            ClockType::None => None,
+        }
+    }
+
+    /// Get the time the next read will return without advancing a `Stepped`
+    /// clock.
+    ///
+    /// For other clock types this is the same as [`get_time`](Self::get_time).
+    pub fn peek_time(&self) -> Option<u64> {
+        match &self.clock_type {
+            ClockType::Stepped { .. } => self.simulated_time(false),
+            _ => self.get_time(),
+        }
+    }
+
+    /// Read a `Fixed` or `Stepped` clock, optionally advancing it.
+    fn simulated_time(&self, advance: bool) -> Option<u64> {
+        match &self.clock_type {
+            ClockType::Fixed(timestamp) => Some(*timestamp),
+            ClockType::Stepped {
+                start_nanos,
+                step_nanos,
+            } => {
+                let n = if advance {
+                    self.steps.fetch_add(1, Ordering::Relaxed)
+                } else {
+                    self.steps.load(Ordering::Relaxed)
+                };
+                Some(start_nanos.saturating_add(n.saturating_mul(*step_nanos)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the monotonic time in nanoseconds since the capability was created.
+    ///
+    /// Successive readings never decrease. `Fixed` and `Stepped` clocks
+    /// return their simulated time. Returns None if monotonic access is not
+    /// allowed.
+    pub fn get_monotonic_nanos(&self) -> Option<u64> {
+        if !self.allow_monotonic {
+            return None;
+        }
+        match &self.clock_type {
+            ClockType::Fixed(_) | ClockType::Stepped { .. } => self.simulated_time(true),
+            _ => Some(u64::try_from(self.baseline.elapsed().as_nanos()).unwrap_or(u64::MAX)),
+        }
+    }
+
+    /// Get the real time in nanoseconds since the Unix epoch.
+    ///
+    /// `Fixed` and `Stepped` clocks return their simulated time. Returns None
+    /// if real-time access is not allowed.
+    pub fn get_realtime_nanos(&self) -> Option<u64> {
+        if !self.allow_realtime {
+            return None;
+        }
+        match &self.clock_type {
+            ClockType::Fixed(_) | ClockType::Stepped { .. } => self.simulated_time(true),
+            _ => SystemTime::now()
                 .duration_since(SystemTime::UNIX_EPOCH)
                 .ok()
                 .map(|d| d.as_nanos() as u64),
-            ClockType::Monotonic => {
-                // For monotonic, we'd use std::time::Instant in real code
-                // Here we use system time as a placeholder
-                SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .ok()
-                    .map(|d| d.as_nanos() as u64)
-            }
-            ClockType::Fixed(timestamp) => Some(*timestamp),
-            ClockType::None => None,
         }
     }
 }
@@ -172,6 +277,10 @@ impl Capability for ClockCapability {
             return PermissionResult::NotApplicable;
         }
 
+        let Some(clock_action) = action.as_any().downcast_ref::<ClockAction>() else {
+            return PermissionResult::NotApplicable;
+        };
+
         // Check if any clock access is allowed
         if matches!(self.clock_type, ClockType::None) {
             return PermissionResult::Denied(DenialReason::new(
@@ -181,16 +290,21 @@ impl Capability for ClockCapability {
             ));
         }
 
-        PermissionResult::Allowed
+        check_clock_permission(self, clock_action)
     }
 
     fn handled_action_types(&self) -> Vec<&'static str> {
         vec!["clock:time", "clock:resolution"]
     }
+
+    fn to_policy(&self) -> Option<CapabilityPolicy> {
+        Some(CapabilityPolicy::Clock {
+            clock_type: self.clock_type.clone(),
+        })
+    }
 }
 
 /// Helper function to check clock permission with a concrete action.
-#[allow(dead_code)]
 pub fn check_clock_permission(
     capability: &ClockCapability,
     action: &ClockAction,
@@ -227,6 +341,19 @@ mod tests {
         assert!(!cap.allows_realtime());
     }
 
+    #[test]
+    fn test_clock_capability_monotonic_reads() {
+        let cap = ClockCapability::monotonic_only();
+
+        let first = cap.get_monotonic_nanos().unwrap();
+        let second = cap.get_monotonic_nanos().unwrap();
+        assert!(second >= first);
+
+        // Relative to construction, not the Unix epoch
+        assert!(cap.get_time().unwrap() < 60_000_000_000);
+        assert_eq!(cap.get_realtime_nanos(), None);
+    }
+
     #[test]
     fn test_clock_capability_realtime() {
         let cap = ClockCapability::realtime();
@@ -240,6 +367,28 @@ mod tests {
         let cap = ClockCapability::fixed(timestamp);
 
         assert_eq!(cap.get_time(), Some(timestamp));
+        assert_eq!(cap.get_monotonic_nanos(), Some(timestamp));
+        assert_eq!(cap.get_realtime_nanos(), Some(timestamp));
+    }
+
+    #[test]
+    fn test_clock_capability_stepped() {
+        let cap = ClockCapability::stepped(1_000, 250);
+
+        assert_eq!(cap.peek_time(), Some(1_000));
+        assert_eq!(cap.peek_time(), Some(1_000));
+
+        let reads: Vec<_> = (0..5).map(|_| cap.get_time().unwrap()).collect();
+        assert_eq!(reads, vec![1_000, 1_250, 1_500, 1_750, 2_000]);
+        assert_eq!(cap.peek_time(), Some(2_250));
+
+        // Every kind of read advances the same sequence
+        assert_eq!(cap.get_monotonic_nanos(), Some(2_250));
+        assert_eq!(cap.get_realtime_nanos(), Some(2_500));
+
+        let copy = cap.clone();
+        assert_eq!(copy.get_time(), Some(2_750));
+        assert_eq!(cap.get_time(), Some(2_750));
     }
 
     #[test]
@@ -248,6 +397,8 @@ mod tests {
         assert!(!cap.allows_realtime());
         assert!(!cap.allows_monotonic());
         assert_eq!(cap.get_time(), None);
+        assert_eq!(cap.get_monotonic_nanos(), None);
+        assert_eq!(cap.get_realtime_nanos(), None);
     }
 
     #[test]
@@ -264,4 +415,38 @@ mod tests {
         };
         assert!(check_clock_permission(&cap, &denied).is_denied());
     }
+
+    #[test]
+    fn test_permits_concrete_action() {
+        let cap = ClockCapability::monotonic_only();
+
+        let allowed = ClockAction::GetTime {
+            clock_type: "monotonic".to_string(),
+        };
+        assert!(cap.permits(&allowed).is_allowed());
+
+        let denied = ClockAction::GetTime {
+            clock_type: "realtime".to_string(),
+        };
+        assert!(cap.permits(&denied).is_denied());
+    }
+
+    #[test]
+    fn test_permits_foreign_action_not_applicable() {
+        #[derive(Debug)]
+        struct CustomClockAction;
+
+        impl Action for CustomClockAction {
+            fn action_type(&self) -> &str {
+                "clock:time"
+            }
+        }
+
+        for cap in [ClockCapability::realtime(), ClockCapability::none()] {
+            assert_eq!(
+                cap.permits(&CustomClockAction),
+                PermissionResult::NotApplicable
+            );
+        }
+    }
 }
@@ -1,16 +1,20 @@
 //! Filesystem capability for file system access.
 
-use std::path::{Path, PathBuf};
+use std::any::Any;
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
 
+use globset::{GlobBuilder, GlobMatcher};
 use serde::{Deserialize, Serialize};
 
 use crate::capability::{
-    Action, Capability, CapabilityId, DenialReason, PermissionResult, standard_ids,
+    Action, Capability, CapabilityId, CheckContext, DenialReason, PermissionResult, standard_ids,
 };
 use crate::error::CapabilityError;
+use crate::policy::CapabilityPolicy;
 
 /// Actions related to filesystem operations.
-#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum FilesystemAction {
     /// Read from a file.
@@ -49,9 +53,12 @@ impl Action for FilesystemAction {
             FilesystemAction::Stat { path } => format!("Get metadata: {}", path.display()),
         }
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
-#[allow(dead_code)]
 impl FilesystemAction {
     /// Get the path associated with this action.
     pub fn path(&self) -> &Path {
@@ -67,10 +74,26 @@ impl FilesystemAction {
 }
 
 /// Permission for a specific path.
+///
+/// A permission either covers everything under `path` (prefix matching) or,
+/// when `pattern` is set, only the paths matched by that glob.
+///
+/// Paths are normalized lexically by default: `.` and `..` components are
+/// resolved without touching the filesystem, which keeps decisions
+/// deterministic but does not see through symlinks. Use
+/// [`with_canonicalize`](Self::with_canonicalize) to resolve symlinks as well.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PathPermission {
     /// The path (directory or file).
     pub path: PathBuf,
+    /// Optional glob pattern (e.g., "/var/**/*.log").
+    ///
+    /// `*` does not cross directory boundaries; use `**` for that.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Resolve symlinks with `std::fs::canonicalize` before matching.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub canonicalize: bool,
     /// Allow read access.
     pub read: bool,
     /// Allow write access.
@@ -79,29 +102,41 @@ pub struct PathPermission {
     pub create: bool,
     /// Allow deleting files.
     pub delete: bool,
+    /// Matcher compiled from `pattern`.
+    #[serde(skip)]
+    matcher: OnceLock<GlobMatcher>,
 }
 
-#[allow(dead_code)]
 impl PathPermission {
     /// Create a read-only permission for a path.
+    ///
+    /// Uses lexical normalization; see [`with_canonicalize`](Self::with_canonicalize).
     pub fn read_only(path: impl Into<PathBuf>) -> Self {
         Self {
             path: path.into(),
+            pattern: None,
+            canonicalize: false,
             read: true,
             write: false,
             create: false,
             delete: false,
+            matcher: OnceLock::new(),
         }
     }
 
     /// Create a read-write permission for a path.
+    ///
+    /// Uses lexical normalization; see [`with_canonicalize`](Self::with_canonicalize).
     pub fn read_write(path: impl Into<PathBuf>) -> Self {
         Self {
             path: path.into(),
+            pattern: None,
+            canonicalize: false,
             read: true,
             write: true,
             create: true,
             delete: false,
+            matcher: OnceLock::new(),
         }
     }
 
@@ -109,18 +144,97 @@ impl PathPermission {
     pub fn full(path: impl Into<PathBuf>) -> Self {
         Self {
             path: path.into(),
+            pattern: None,
+            canonicalize: false,
             read: true,
             write: true,
             create: true,
             delete: true,
+            matcher: OnceLock::new(),
+        }
+    }
+
+    /// Create a read-only permission for paths matching a glob pattern.
+    ///
+    /// The permission's `path` is set to the literal directory prefix of the
+    /// pattern.
+    pub fn glob(pattern: impl Into<String>) -> Self {
+        Self::read_only(PathBuf::new()).with_pattern(pattern)
+    }
+
+    /// Restrict this permission to paths matching a glob pattern.
+    pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
+        let pattern = pattern.into();
+        self.path = glob_literal_prefix(&pattern);
+        self.matcher = OnceLock::new();
+        if let Ok(matcher) = compile_glob(&pattern) {
+            let _ = self.matcher.set(matcher);
+        }
+        self.pattern = Some(pattern);
+        self
+    }
+
+    /// Resolve symlinks in both the permission root and the action path.
+    ///
+    /// Paths that cannot be resolved (other than a missing final component,
+    /// as when creating a file) are denied.
+    pub fn with_canonicalize(mut self, canonicalize: bool) -> Self {
+        self.canonicalize = canonicalize;
+        self
+    }
+
+    /// Compile the glob pattern, if any.
+    fn compile_pattern(&self) -> Option<Result<GlobMatcher, globset::Error>> {
+        self.pattern.as_deref().map(compile_glob)
+    }
+
+    /// Get the matcher for `pattern`, compiling it on first use.
+    ///
+    /// The compiled matcher is only reused while it was built from
+    /// `pattern`, since the public field may have been changed since.
+    /// Returns `None` for an invalid pattern.
+    fn matcher(&self, pattern: &str) -> Option<Cow<'_, GlobMatcher>> {
+        match self.matcher.get() {
+            Some(matcher) if matcher.glob().glob() == pattern => Some(Cow::Borrowed(matcher)),
+            Some(_) => compile_glob(pattern).ok().map(Cow::Owned),
+            None => {
+                let matcher = compile_glob(pattern).ok()?;
+                Some(Cow::Borrowed(self.matcher.get_or_init(|| matcher)))
+            }
         }
     }
 
-    /// Check if the action's path is under this permission's path.
+    /// Check if the action's path is covered by this permission.
+    ///
+    /// `.` and `..` components are resolved before matching so that a path
+    /// such as `/data/../etc/passwd` cannot escape the permission.
     fn path_matches(&self, action_path: &Path) -> bool {
-        // Canonicalize paths for comparison (handle .. and symlinks)
-        // In production, use proper canonicalization
-        action_path.starts_with(&self.path)
+        let (root, action_path) = if self.canonicalize {
+            match (
+                canonicalize_path(&self.path),
+                canonicalize_path(action_path),
+            ) {
+                (Some(root), Some(path)) => (root, path),
+                _ => return false,
+            }
+        } else {
+            (normalize_path(&self.path), normalize_path(action_path))
+        };
+
+        let Some(pattern) = &self.pattern else {
+            return action_path.starts_with(&root);
+        };
+        match self.matcher(pattern) {
+            Some(matcher) if self.canonicalize => {
+                // Re-root the resolved path under the pattern's literal prefix
+                action_path
+                    .strip_prefix(&root)
+                    .is_ok_and(|rel| matcher.is_match(self.path.join(rel)))
+            }
+            Some(matcher) => matcher.is_match(&action_path),
+            // An invalid pattern never matches
+            None => false,
+        }
     }
 
     /// Check if this permission allows the given action.
@@ -140,6 +254,72 @@ impl PathPermission {
     }
 }
 
+/// Compile a glob pattern in which `*` does not cross directory boundaries.
+fn compile_glob(pattern: &str) -> Result<GlobMatcher, globset::Error> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+}
+
+/// Lexically resolve `.` and `..` components of a path.
+///
+/// This does not touch the filesystem. `..` at the root is discarded, and
+/// a leading `..` of a relative path is kept, so `../data` never resolves
+/// to `data`.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            other => normalized.push(other),
+        }
+    }
+
+    normalized
+}
+
+/// Resolve a path against the filesystem, following symlinks.
+///
+/// If the path does not exist, its parent is resolved and the final
+/// component re-attached, so that not-yet-created files can be checked.
+fn canonicalize_path(path: &Path) -> Option<PathBuf> {
+    if let Ok(resolved) = std::fs::canonicalize(path) {
+        return Some(resolved);
+    }
+
+    let path = normalize_path(path);
+    let file_name = path.file_name()?;
+    let parent = std::fs::canonicalize(path.parent()?).ok()?;
+    Some(parent.join(file_name))
+}
+
+/// Get the directory prefix of a glob pattern before its first wildcard.
+fn glob_literal_prefix(pattern: &str) -> PathBuf {
+    let mut prefix = PathBuf::new();
+
+    for component in Path::new(pattern).components() {
+        let literal = component
+            .as_os_str()
+            .to_str()
+            .is_some_and(|s| !s.contains(['*', '?', '[', '{']));
+        if !literal {
+            break;
+        }
+        prefix.push(component);
+    }
+
+    prefix
+}
+
 /// Capability for filesystem access.
 ///
 /// This capability controls access to the filesystem, including reading,
@@ -160,17 +340,39 @@ impl PathPermission {
 /// let read_write = FilesystemCapability::new(vec![
 ///     PathPermission::read_write("/tmp"),
 /// ]);
+///
+/// // Everything under /data except /data/secrets
+/// let guarded = FilesystemCapability::read_only(&["/data"])
+///     .with_deny(vec![PathPermission::full("/data/secrets")]);
 /// ```
+///
+/// # Precedence
+///
+/// Deny rules are evaluated first. An action matched by any deny rule is
+/// denied even if an allow permission also matches it; otherwise it is
+/// allowed only if some allow permission matches. A deny rule matches the
+/// same way an allow permission does: its path or glob must cover the
+/// action's path and its flags select the operations it denies, so
+/// `PathPermission::full` denies everything and `PathPermission::read_only`
+/// denies only reads, listings and metadata.
 #[derive(Debug, Clone)]
 pub struct FilesystemCapability {
     /// Allowed paths with their permissions.
     permissions: Vec<PathPermission>,
+    /// Denied paths, evaluated before `permissions`.
+    deny: Vec<PathPermission>,
+    /// Content hash of the only module this capability applies to.
+    module_hash: Option<[u8; 32]>,
 }
 
 impl FilesystemCapability {
     /// Create a new filesystem capability with the given permissions.
     pub fn new(permissions: Vec<PathPermission>) -> Self {
-        Self { permissions }
+        Self {
+            permissions,
+            deny: Vec::new(),
+            module_hash: None,
+        }
     }
 
     /// Create a read-only capability for the given paths.
@@ -180,6 +382,8 @@ impl FilesystemCapability {
                 .iter()
                 .map(|p| PathPermission::read_only(p.as_ref()))
                 .collect(),
+            deny: Vec::new(),
+            module_hash: None,
         }
     }
 
@@ -190,9 +394,28 @@ impl FilesystemCapability {
                 .iter()
                 .map(|p| PathPermission::read_write(p.as_ref()))
                 .collect(),
+            deny: Vec::new(),
+            module_hash: None,
+        }
+    }
+
+    /// Create a capability that only grants access to the module whose
+    /// content hash is `hash`.
+    ///
+    /// Filesystem actions checked with a [`CheckContext`] naming any other
+    /// module, or no module at all, are denied.
+    pub fn for_module(hash: [u8; 32], permissions: Vec<PathPermission>) -> Self {
+        Self {
+            module_hash: Some(hash),
+            ..Self::new(permissions)
         }
     }
 
+    /// Get the content hash of the module this capability is scoped to.
+    pub fn module_hash(&self) -> Option<&[u8; 32]> {
+        self.module_hash.as_ref()
+    }
+
     /// Add a permission to this capability.
     pub fn add_permission(&mut self, permission: PathPermission) {
         self.permissions.push(permission);
@@ -202,6 +425,22 @@ impl FilesystemCapability {
     pub fn permissions(&self) -> &[PathPermission] {
         &self.permissions
     }
+
+    /// Set the deny rules, which take precedence over the permissions.
+    pub fn with_deny(mut self, deny: Vec<PathPermission>) -> Self {
+        self.deny = deny;
+        self
+    }
+
+    /// Add a deny rule to this capability.
+    pub fn add_deny(&mut self, rule: PathPermission) {
+        self.deny.push(rule);
+    }
+
+    /// Get the deny rules.
+    pub fn deny_rules(&self) -> &[PathPermission] {
+        &self.deny
+    }
 }
 
 impl Capability for FilesystemCapability {
@@ -218,16 +457,31 @@ impl Capability for FilesystemCapability {
     }
 
     fn permits(&self, action: &dyn Action) -> PermissionResult {
+        self.permits_with_context(action, &CheckContext::default())
+    }
+
+    fn permits_with_context(&self, action: &dyn Action, ctx: &CheckContext) -> PermissionResult {
         // Check if this is a filesystem action
         let action_type = action.action_type();
         if !action_type.starts_with("fs:") {
             return PermissionResult::NotApplicable;
         }
 
-        // We can't actually downcast here without the concrete action type,
-        // so we return NotApplicable. Use check_filesystem_permission() for
-        // concrete FilesystemAction checks.
-        PermissionResult::NotApplicable
+        if let Some(hash) = &self.module_hash {
+            if ctx.module_hash.as_ref() != Some(hash) {
+                return PermissionResult::Denied(DenialReason::new(
+                    self.id(),
+                    action_type,
+                    "Capability is scoped to another module",
+                ));
+            }
+        }
+
+        match action.as_any().downcast_ref::<FilesystemAction>() {
+            Some(fs_action) => check_filesystem_permission(self, fs_action),
+            // Not a concrete FilesystemAction; let another capability decide.
+            None => PermissionResult::NotApplicable,
+        }
     }
 
     fn handled_action_types(&self) -> Vec<&'static str> {
@@ -247,16 +501,51 @@ impl Capability for FilesystemCapability {
                 "Filesystem capability has no permissions configured".to_string(),
             ));
         }
+        for perm in self.permissions.iter().chain(&self.deny) {
+            if let Some(Err(e)) = perm.compile_pattern() {
+                return Err(CapabilityError::InvalidConfig(format!(
+                    "Invalid glob pattern: {}",
+                    e
+                )));
+            }
+        }
         Ok(())
     }
+
+    fn to_policy(&self) -> Option<CapabilityPolicy> {
+        // Policies cannot express the module scope; dropping it would widen
+        // the grant
+        if self.module_hash.is_some() {
+            return None;
+        }
+
+        Some(CapabilityPolicy::Filesystem {
+            permissions: self.permissions.clone(),
+            deny: self.deny.clone(),
+        })
+    }
 }
 
 /// Helper function to check filesystem permission with a concrete action.
-#[allow(dead_code)]
+///
+/// Deny rules are checked before allow permissions; see
+/// [`FilesystemCapability`] for the precedence rules.
 pub fn check_filesystem_permission(
     capability: &FilesystemCapability,
     action: &FilesystemAction,
 ) -> PermissionResult {
+    if capability
+        .deny_rules()
+        .iter()
+        .any(|rule| rule.allows(action))
+    {
+        return PermissionResult::Denied(DenialReason::new(
+            capability.id(),
+            action.action_type(),
+            format!("Path denied: {}", action.path().display()),
+        ));
+    }
+
     for perm in capability.permissions() {
         if perm.allows(action) {
             return PermissionResult::Allowed;
@@ -335,4 +624,263 @@ mod tests {
         };
         assert!(check_filesystem_permission(&cap, &outside_action).is_denied());
     }
+
+    #[test]
+    fn test_permits_concrete_action() {
+        let cap = FilesystemCapability::read_only(&["/data"]);
+
+        let read_action = FilesystemAction::Read {
+            path: PathBuf::from("/data/file.txt"),
+        };
+        assert!(cap.permits(&read_action).is_allowed());
+
+        let write_action = FilesystemAction::Write {
+            path: PathBuf::from("/data/file.txt"),
+        };
+        assert!(cap.permits(&write_action).is_denied());
+    }
+
+    #[test]
+    fn test_permits_foreign_action_not_applicable() {
+        #[derive(Debug)]
+        struct CustomFsAction;
+
+        impl Action for CustomFsAction {
+            fn action_type(&self) -> &str {
+                "fs:read"
+            }
+        }
+
+        let cap = FilesystemCapability::read_only(&["/data"]);
+        assert_eq!(
+            cap.permits(&CustomFsAction),
+            PermissionResult::NotApplicable
+        );
+    }
+
+    #[test]
+    fn test_glob_permission_matches() {
+        let perm = PathPermission::glob("/var/**/*.log");
+        assert_eq!(perm.path, PathBuf::from("/var"));
+
+        let matching = ["/var/app.log", "/var/log/app.log", "/var/log/nested/x.log"];
+        for path in matching {
+            let action = FilesystemAction::Read {
+                path: PathBuf::from(path),
+            };
+            assert!(perm.allows(&action), "expected match for {}", path);
+        }
+
+        let non_matching = ["/var/log/app.txt", "/etc/app.log", "/var"];
+        for path in non_matching {
+            let action = FilesystemAction::Read {
+                path: PathBuf::from(path),
+            };
+            assert!(!perm.allows(&action), "unexpected match for {}", path);
+        }
+    }
+
+    #[test]
+    fn test_glob_matcher_compiled_once() {
+        let log = FilesystemAction::Read {
+            path: PathBuf::from("/var/log/app.log"),
+        };
+        let txt = FilesystemAction::Read {
+            path: PathBuf::from("/var/log/app.txt"),
+        };
+
+        let mut perm = PathPermission::glob("/var/log/*.log");
+        assert!(perm.matcher.get().is_some());
+        assert!(perm.allows(&log));
+
+        // Deserialized permissions compile the pattern on first use
+        let mut restored: PathPermission =
+            serde_json::from_value(serde_json::to_value(&perm).unwrap()).unwrap();
+        assert!(restored.matcher.get().is_none());
+        assert!(restored.allows(&log));
+        assert!(restored.matcher.get().is_some());
+
+        // A changed pattern is not matched with the stale matcher
+        perm.pattern = Some("/var/log/*.txt".to_string());
+        assert!(!perm.allows(&log));
+        assert!(perm.allows(&txt));
+        restored.pattern = Some("/var/log/[".to_string());
+        assert!(!restored.allows(&log));
+    }
+
+    #[test]
+    fn test_glob_single_star_stays_in_directory() {
+        let perm = PathPermission::glob("/var/log/*.log");
+
+        let direct = FilesystemAction::Read {
+            path: PathBuf::from("/var/log/app.log"),
+        };
+        assert!(perm.allows(&direct));
+
+        let nested = FilesystemAction::Read {
+            path: PathBuf::from("/var/log/sub/app.log"),
+        };
+        assert!(!perm.allows(&nested));
+    }
+
+    #[test]
+    fn test_glob_permission_flags() {
+        let perm = PathPermission::read_write("/tmp").with_pattern("/tmp/*.txt");
+        let write = FilesystemAction::Write {
+            path: PathBuf::from("/tmp/out.txt"),
+        };
+        assert!(perm.allows(&write));
+
+        let read_only = PathPermission::glob("/tmp/*.txt");
+        assert!(!read_only.allows(&write));
+    }
+
+    #[test]
+    fn test_traversal_escape_denied() {
+        let glob = PathPermission::glob("/var/**");
+        let prefix = PathPermission::read_only("/var");
+        let action = FilesystemAction::Read {
+            path: PathBuf::from("/var/../etc/passwd"),
+        };
+        assert!(!glob.allows(&action));
+        assert!(!prefix.allows(&action));
+
+        // Traversal that stays inside the permission is fine
+        let inside = FilesystemAction::Read {
+            path: PathBuf::from("/var/log/../app.log"),
+        };
+        assert!(glob.allows(&inside));
+        assert!(prefix.allows(&inside));
+    }
+
+    #[test]
+    fn test_invalid_glob_rejected() {
+        let cap = FilesystemCapability::new(vec![PathPermission::glob("/var/[")]);
+        assert!(cap.validate().is_err());
+
+        let action = FilesystemAction::Read {
+            path: PathBuf::from("/var/["),
+        };
+        assert!(cap.permits(&action).is_denied());
+    }
+
+    #[test]
+    fn test_dot_components_normalized() {
+        let perm = PathPermission::read_only("/data/./files/");
+        let action = FilesystemAction::Read {
+            path: PathBuf::from("/data/files/./a/../b.txt"),
+        };
+        assert!(perm.allows(&action));
+
+        let escape = FilesystemAction::Read {
+            path: PathBuf::from("/data/files/../../etc/passwd"),
+        };
+        assert!(!perm.allows(&escape));
+    }
+
+    #[test]
+    fn test_absolute_relative_mismatch() {
+        let absolute = PathPermission::read_only("/data");
+        let relative_action = FilesystemAction::Read {
+            path: PathBuf::from("data/file.txt"),
+        };
+        assert!(!absolute.allows(&relative_action));
+
+        let relative = PathPermission::read_only("data");
+        let absolute_action = FilesystemAction::Read {
+            path: PathBuf::from("/data/file.txt"),
+        };
+        assert!(!relative.allows(&absolute_action));
+
+        // Leading `..` cannot be resolved away and never matches
+        let parent_action = FilesystemAction::Read {
+            path: PathBuf::from("../data/file.txt"),
+        };
+        assert!(!relative.allows(&parent_action));
+    }
+
+    #[test]
+    fn test_relative_parent_escape_denied() {
+        let glob = PathPermission::glob("data/**");
+        let prefix = PathPermission::read_only("data");
+
+        // A second `..` must not cancel out the first
+        for path in ["../../data/x", "a/../../data/x"] {
+            let action = FilesystemAction::Read {
+                path: PathBuf::from(path),
+            };
+            assert!(!glob.allows(&action), "{path}");
+            assert!(!prefix.allows(&action), "{path}");
+        }
+        assert_eq!(normalize_path(Path::new("a/../../b")), Path::new("../b"));
+        assert_eq!(normalize_path(Path::new("/../../b")), Path::new("/b"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_canonicalize_blocks_symlink_escape() {
+        let base = std::env::temp_dir().join(format!("aegis-fs-{}", std::process::id()));
+        let data = base.join("data");
+        let secret = base.join("secret");
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::create_dir_all(&secret).unwrap();
+        std::fs::write(secret.join("key"), b"hidden").unwrap();
+        let _ = std::fs::remove_file(data.join("link"));
+        std::os::unix::fs::symlink(&secret, data.join("link")).unwrap();
+
+        let action = FilesystemAction::Read {
+            path: data.join("link").join("key"),
+        };
+
+        let lexical = PathPermission::read_only(&data);
+        assert!(lexical.allows(&action));
+
+        let canonical = PathPermission::read_only(&data).with_canonicalize(true);
+        assert!(!canonical.allows(&action));
+
+        // Files that do not exist yet are resolved through their parent
+        let create = FilesystemAction::Create {
+            path: data.join("new.txt"),
+        };
+        assert!(
+            PathPermission::read_write(&data)
+                .with_canonicalize(true)
+                .allows(&create)
+        );
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn test_deny_rules_take_precedence() {
+        let cap = FilesystemCapability::read_only(&["/data"])
+            .with_deny(vec![PathPermission::full("/data/secrets")]);
+        let read = |path: &str| FilesystemAction::Read {
+            path: PathBuf::from(path),
+        };
+
+        assert!(check_filesystem_permission(&cap, &read("/data/report.csv")).is_allowed());
+        assert!(check_filesystem_permission(&cap, &read("/data/secrets/key.pem")).is_denied());
+        assert!(
+            check_filesystem_permission(&cap, &read("/data/public/../secrets/key.pem")).is_denied()
+        );
+
+        // A deny rule that does not match the path passes through to the allows
+        let cap = FilesystemCapability::read_only(&["/data"])
+            .with_deny(vec![PathPermission::full("/etc")]);
+        assert!(check_filesystem_permission(&cap, &read("/data/report.csv")).is_allowed());
+
+        // A deny rule only covers the operations its flags select
+        let cap = FilesystemCapability::read_write(&["/data"])
+            .with_deny(vec![PathPermission::glob("/data/**/*.lock")]);
+        let write = FilesystemAction::Write {
+            path: PathBuf::from("/data/db.lock"),
+        };
+        assert!(check_filesystem_permission(&cap, &read("/data/db.lock")).is_denied());
+        assert!(check_filesystem_permission(&cap, &write).is_allowed());
+
+        // Deny rules survive a round trip through the policy
+        let restored = cap.to_policy().unwrap().to_capability();
+        assert!(restored.permits(&read("/data/db.lock")).is_denied());
+    }
 }
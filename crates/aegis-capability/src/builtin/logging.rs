@@ -1,10 +1,15 @@
 //! Logging capability for log output.
 
+use std::any::Any;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 
 use crate::capability::{
     Action, Capability, CapabilityId, DenialReason, PermissionResult, standard_ids,
 };
+use crate::policy::CapabilityPolicy;
 
 /// Log levels.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Default)]
@@ -36,7 +41,6 @@ impl LogLevel {
 }
 
 /// Actions related to logging.
-#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum LoggingAction {
     /// Write a log message.
@@ -55,11 +59,53 @@ impl Action for LoggingAction {
             }
         }
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Per-second message budget for rate limiting.
+///
+/// The budget refills at the start of each one-second window.
+#[derive(Debug)]
+struct RateWindow {
+    /// Start of the current window.
+    started: Instant,
+    /// Messages permitted in the current window.
+    count: u32,
+}
+
+impl RateWindow {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            count: 0,
+        }
+    }
+
+    /// Try to take one message from the budget.
+    fn try_acquire(&mut self, max_per_second: u32) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.started) >= Duration::from_secs(1) {
+            self.started = now;
+            self.count = 0;
+        }
+
+        if self.count < max_per_second {
+            self.count += 1;
+            true
+        } else {
+            false
+        }
+    }
 }
 
 /// Capability for logging output.
 ///
-/// This capability controls what log messages a guest can emit.
+/// This capability controls what log messages a guest can emit. When a
+/// rate limit is set, each permitted message consumes from a per-second
+/// budget and messages beyond it are denied until the next window.
 ///
 /// # Example
 ///
@@ -69,7 +115,7 @@ impl Action for LoggingAction {
 /// // Allow info level and above
 /// let cap = LoggingCapability::new(LogLevel::Info, 4096);
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct LoggingCapability {
     /// Minimum log level allowed.
     min_level: LogLevel,
@@ -77,6 +123,20 @@ pub struct LoggingCapability {
     max_message_size: usize,
     /// Maximum messages per second (rate limiting).
     max_rate: Option<u32>,
+    /// Current rate limiting window.
+    window: Mutex<RateWindow>,
+}
+
+impl Clone for LoggingCapability {
+    /// Clones the configuration; the clone starts with a fresh rate budget.
+    fn clone(&self) -> Self {
+        Self {
+            min_level: self.min_level,
+            max_message_size: self.max_message_size,
+            max_rate: self.max_rate,
+            window: Mutex::new(RateWindow::new()),
+        }
+    }
 }
 
 impl LoggingCapability {
@@ -86,6 +146,7 @@ impl LoggingCapability {
             min_level,
             max_message_size,
             max_rate: None,
+            window: Mutex::new(RateWindow::new()),
         }
     }
 
@@ -115,6 +176,21 @@ impl LoggingCapability {
         self.max_message_size
     }
 
+    /// Get the maximum messages per second, if rate limited.
+    pub fn max_rate(&self) -> Option<u32> {
+        self.max_rate
+    }
+
+    /// Consume one message from the rate budget.
+    ///
+    /// Always succeeds when no rate limit is set.
+    fn try_consume_rate(&self) -> bool {
+        match self.max_rate {
+            Some(max) => self.window.lock().try_acquire(max),
+            None => true,
+        }
+    }
+
     /// Check if a log level is allowed.
     pub fn is_level_allowed(&self, level: LogLevel) -> bool {
         level >= self.min_level
@@ -138,16 +214,27 @@ impl Capability for LoggingCapability {
         if action.action_type() != "log:write" {
             return PermissionResult::NotApplicable;
         }
-        PermissionResult::NotApplicable
+
+        match action.as_any().downcast_ref::<LoggingAction>() {
+            Some(log_action) => check_logging_permission(self, log_action),
+            None => PermissionResult::NotApplicable,
+        }
     }
 
     fn handled_action_types(&self) -> Vec<&'static str> {
         vec!["log:write"]
     }
+
+    fn to_policy(&self) -> Option<CapabilityPolicy> {
+        Some(CapabilityPolicy::Logging {
+            min_level: self.min_level,
+            max_message_size: self.max_message_size,
+            max_rate: self.max_rate,
+        })
+    }
 }
 
 /// Helper function to check logging permission with a concrete action.
-#[allow(dead_code)]
 pub fn check_logging_permission(
     capability: &LoggingCapability,
     action: &LoggingAction,
@@ -178,6 +265,17 @@ pub fn check_logging_permission(
                 ));
             }
 
+            if !capability.try_consume_rate() {
+                return PermissionResult::Denied(DenialReason::new(
+                    capability.id(),
+                    action.action_type(),
+                    format!(
+                        "Rate limit exceeded: maximum {} messages per second",
+                        capability.max_rate().unwrap_or_default()
+                    ),
+                ));
+            }
+
             PermissionResult::Allowed
         }
     }
@@ -228,4 +326,60 @@ mod tests {
         };
         assert!(check_logging_permission(&cap, &denied_size).is_denied());
     }
+
+    #[test]
+    fn test_permits_concrete_action() {
+        let cap = LoggingCapability::production();
+
+        let allowed = LoggingAction::Log {
+            level: LogLevel::Warn,
+            message_len: 10,
+        };
+        assert!(cap.permits(&allowed).is_allowed());
+
+        let denied = LoggingAction::Log {
+            level: LogLevel::Trace,
+            message_len: 10,
+        };
+        assert!(cap.permits(&denied).is_denied());
+    }
+
+    #[test]
+    fn test_rate_limit_enforced() {
+        let cap = LoggingCapability::allow_all().with_rate_limit(5);
+        let action = LoggingAction::Log {
+            level: LogLevel::Info,
+            message_len: 10,
+        };
+
+        for _ in 0..5 {
+            assert!(cap.permits(&action).is_allowed());
+        }
+
+        match cap.permits(&action) {
+            PermissionResult::Denied(reason) => {
+                assert!(reason.message.contains("Rate limit exceeded"));
+            }
+            other => panic!("Expected denial, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_ignores_rejected_messages() {
+        let cap = LoggingCapability::production().with_rate_limit(1);
+
+        let too_verbose = LoggingAction::Log {
+            level: LogLevel::Debug,
+            message_len: 10,
+        };
+        assert!(cap.permits(&too_verbose).is_denied());
+
+        // The rejected message did not consume the budget
+        let ok = LoggingAction::Log {
+            level: LogLevel::Info,
+            message_len: 10,
+        };
+        assert!(cap.permits(&ok).is_allowed());
+        assert!(cap.permits(&ok).is_denied());
+    }
 }
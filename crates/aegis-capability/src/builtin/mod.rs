@@ -6,13 +6,24 @@
 //! - [`NetworkCapability`]: Network access
 //! - [`LoggingCapability`]: Logging output
 //! - [`ClockCapability`]: Time and clock access
+//! - [`RandomCapability`]: Random number generation
+//! - [`NestingCapability`]: Spawning child sandboxes
 
 mod clock;
 mod filesystem;
 mod logging;
+mod nesting;
 mod network;
+mod random;
 
-pub use clock::{ClockCapability, ClockType};
-pub use filesystem::{FilesystemCapability, PathPermission};
-pub use logging::{LogLevel, LoggingCapability};
-pub use network::{HostPattern, NetworkCapability, ProtocolSet};
+pub use clock::{ClockAction, ClockCapability, ClockType, check_clock_permission};
+pub use filesystem::{
+    FilesystemAction, FilesystemCapability, PathPermission, check_filesystem_permission,
+};
+pub use logging::{LogLevel, LoggingAction, LoggingCapability, check_logging_permission};
+pub use nesting::{NestingAction, NestingCapability, check_nesting_permission};
+pub use network::{
+    HostPattern, NetworkAction, NetworkCapability, NetworkCapabilityBuilder, PortRange,
+    ProtocolSet, check_network_permission,
+};
+pub use random::{RandomAction, RandomCapability, RandomSource, check_random_permission};
@@ -0,0 +1,231 @@
+//! Nesting capability for spawning child sandboxes.
+
+use std::any::Any;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::capability::{
+    Action, Capability, CapabilityId, DenialReason, PermissionResult, standard_ids,
+};
+use crate::error::CapabilityError;
+use crate::policy::CapabilityPolicy;
+
+/// Actions related to nested sandboxes.
+#[derive(Debug, Clone)]
+pub enum NestingAction {
+    /// Spawn a child sandbox at the given depth (a top-level sandbox's
+    /// children are at depth 1).
+    Spawn { depth: u32 },
+}
+
+impl Action for NestingAction {
+    fn action_type(&self) -> &str {
+        match self {
+            NestingAction::Spawn { .. } => "nesting:spawn",
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            NestingAction::Spawn { depth } => format!("Spawn child sandbox at depth {}", depth),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Capability for spawning child sandboxes.
+///
+/// Each permitted spawn counts towards `max_children`, so the limit covers
+/// every sandbox sharing this capability, including children that inherit
+/// it. Children receive `resource_fraction` of their parent's remaining
+/// fuel and memory.
+///
+/// # Example
+///
+/// ```
+/// use aegis_capability::builtin::{NestingAction, NestingCapability};
+/// use aegis_capability::Capability;
+///
+/// // Up to two children, no grandchildren, each with a quarter of the budget
+/// let cap = NestingCapability::new(2, 1, 0.25);
+///
+/// assert!(cap.permits(&NestingAction::Spawn { depth: 1 }).is_allowed());
+/// assert!(cap.permits(&NestingAction::Spawn { depth: 2 }).is_denied());
+/// ```
+#[derive(Debug)]
+pub struct NestingCapability {
+    /// Maximum number of children that may be spawned.
+    max_children: u32,
+    /// Maximum nesting depth.
+    max_depth: u32,
+    /// Fraction of the parent's remaining resources given to a child.
+    resource_fraction: f64,
+    /// Children spawned so far.
+    spawned: AtomicU32,
+}
+
+impl Clone for NestingCapability {
+    /// Clones the configuration; the clone starts with no children spawned.
+    fn clone(&self) -> Self {
+        Self::new(self.max_children, self.max_depth, self.resource_fraction)
+    }
+}
+
+impl NestingCapability {
+    /// Create a new nesting capability.
+    ///
+    /// `resource_fraction` must be in `(0, 1]`; see
+    /// [`validate`](Capability::validate).
+    pub fn new(max_children: u32, max_depth: u32, resource_fraction: f64) -> Self {
+        Self {
+            max_children,
+            max_depth,
+            resource_fraction,
+            spawned: AtomicU32::new(0),
+        }
+    }
+
+    /// Get the maximum number of children.
+    pub fn max_children(&self) -> u32 {
+        self.max_children
+    }
+
+    /// Get the maximum nesting depth.
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
+
+    /// Get the fraction of the parent's remaining resources given to a child.
+    pub fn resource_fraction(&self) -> f64 {
+        self.resource_fraction
+    }
+
+    /// Get the number of children spawned so far.
+    pub fn spawned(&self) -> u32 {
+        self.spawned.load(Ordering::SeqCst)
+    }
+
+    /// Scale a parent's remaining resource to a child's share.
+    pub fn child_share(&self, remaining: u64) -> u64 {
+        (remaining as f64 * self.resource_fraction) as u64
+    }
+
+    /// Take one child from the budget.
+    fn try_reserve_child(&self) -> bool {
+        self.spawned
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < self.max_children).then_some(n + 1)
+            })
+            .is_ok()
+    }
+}
+
+impl Capability for NestingCapability {
+    fn id(&self) -> CapabilityId {
+        standard_ids::NESTING.clone()
+    }
+
+    fn name(&self) -> &str {
+        "Nesting"
+    }
+
+    fn description(&self) -> &str {
+        "Allows spawning child sandboxes"
+    }
+
+    fn permits(&self, action: &dyn Action) -> PermissionResult {
+        if !action.action_type().starts_with("nesting:") {
+            return PermissionResult::NotApplicable;
+        }
+
+        match action.as_any().downcast_ref::<NestingAction>() {
+            Some(nesting_action) => check_nesting_permission(self, nesting_action),
+            None => PermissionResult::NotApplicable,
+        }
+    }
+
+    fn handled_action_types(&self) -> Vec<&'static str> {
+        vec!["nesting:spawn"]
+    }
+
+    fn validate(&self) -> Result<(), CapabilityError> {
+        if !(self.resource_fraction > 0.0 && self.resource_fraction <= 1.0) {
+            return Err(CapabilityError::InvalidConfig(format!(
+                "resource_fraction must be in (0, 1], got {}",
+                self.resource_fraction
+            )));
+        }
+        Ok(())
+    }
+
+    fn to_policy(&self) -> Option<CapabilityPolicy> {
+        Some(CapabilityPolicy::Nesting {
+            max_children: self.max_children,
+            max_depth: self.max_depth,
+            resource_fraction: self.resource_fraction,
+        })
+    }
+}
+
+/// Helper function to check nesting permission with a concrete action.
+///
+/// A permitted spawn is counted towards the capability's `max_children`.
+pub fn check_nesting_permission(
+    capability: &NestingCapability,
+    action: &NestingAction,
+) -> PermissionResult {
+    match action {
+        NestingAction::Spawn { depth } => {
+            if *depth > capability.max_depth() {
+                return PermissionResult::Denied(DenialReason::new(
+                    capability.id(),
+                    action.action_type(),
+                    format!("Depth {} exceeds maximum {}", depth, capability.max_depth()),
+                ));
+            }
+
+            if !capability.try_reserve_child() {
+                return PermissionResult::Denied(DenialReason::new(
+                    capability.id(),
+                    action.action_type(),
+                    format!("Child limit of {} reached", capability.max_children()),
+                ));
+            }
+
+            PermissionResult::Allowed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_limit() {
+        let cap = NestingCapability::new(2, 3, 0.5);
+        let spawn = NestingAction::Spawn { depth: 1 };
+
+        assert!(cap.permits(&spawn).is_allowed());
+        assert!(cap.permits(&spawn).is_allowed());
+        assert!(cap.permits(&spawn).is_denied());
+        assert_eq!(cap.spawned(), 2);
+
+        // A clone starts with a fresh budget
+        assert!(cap.clone().permits(&spawn).is_allowed());
+    }
+
+    #[test]
+    fn test_depth_and_fraction() {
+        let cap = NestingCapability::new(10, 1, 0.25);
+        assert!(cap.permits(&NestingAction::Spawn { depth: 2 }).is_denied());
+        assert_eq!(cap.spawned(), 0);
+        assert_eq!(cap.child_share(1000), 250);
+
+        assert!(cap.validate().is_ok());
+        assert!(NestingCapability::new(1, 1, 0.0).validate().is_err());
+        assert!(NestingCapability::new(1, 1, 1.5).validate().is_err());
+    }
+}
@@ -1,14 +1,17 @@
 //! Network capability for network access.
 
+use std::any::Any;
+use std::net::IpAddr;
+
 use serde::{Deserialize, Serialize};
 
 use crate::capability::{
     Action, Capability, CapabilityId, DenialReason, PermissionResult, standard_ids,
 };
 use crate::error::CapabilityError;
+use crate::policy::CapabilityPolicy;
 
 /// Actions related to network operations.
-#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum NetworkAction {
     /// Connect to a host.
@@ -43,6 +46,10 @@ impl Action for NetworkAction {
             NetworkAction::DnsLookup { hostname } => format!("DNS lookup: {}", hostname),
         }
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /// Pattern for matching hosts.
@@ -50,30 +57,156 @@ impl Action for NetworkAction {
 pub enum HostPattern {
     /// Exact host match.
     Exact(String),
-    /// Wildcard pattern (e.g., "*.example.com").
+    /// Wildcard pattern (e.g., "*.example.com" or "api.*.example.com").
+    ///
+    /// Patterns and hosts are compared label by label, split on `.`:
+    ///
+    /// - A leading `*.` matches any number of labels, including none, so
+    ///   `*.example.com` matches `example.com`, `a.example.com` and
+    ///   `a.b.example.com`.
+    /// - A `*` label anywhere else matches exactly one label, so
+    ///   `api.*.example.com` matches `api.eu.example.com` but not
+    ///   `api.example.com` or `api.eu.west.example.com`.
+    /// - A `*` within a label matches any run of characters in that label,
+    ///   so `*-staging.example.com` matches `web-staging.example.com`.
+    ///   It never matches across a `.`.
+    ///
+    /// All other characters must match exactly.
     Wildcard(String),
+    /// IP network in CIDR notation (e.g., 10.0.0.0/8).
+    ///
+    /// Only hosts that are literal IP addresses can match.
+    Cidr {
+        /// Network address.
+        network: IpAddr,
+        /// Prefix length in bits.
+        prefix: u8,
+    },
     /// Any host.
     Any,
 }
 
 impl HostPattern {
+    /// Parse a CIDR pattern such as `"10.0.0.0/8"` or `"fd00::/8"`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network address or prefix length is invalid.
+    pub fn cidr(cidr: &str) -> Result<Self, CapabilityError> {
+        let invalid = || CapabilityError::InvalidConfig(format!("Invalid CIDR pattern: {}", cidr));
+
+        let (network, prefix) = cidr.split_once('/').ok_or_else(invalid)?;
+        let network: IpAddr = network.parse().map_err(|_| invalid())?;
+        let prefix: u8 = prefix.parse().map_err(|_| invalid())?;
+
+        if prefix > max_prefix(&network) {
+            return Err(invalid());
+        }
+
+        Ok(HostPattern::Cidr { network, prefix })
+    }
+
     /// Check if a host matches this pattern.
     pub fn matches(&self, host: &str) -> bool {
         match self {
             HostPattern::Exact(pattern) => pattern == host,
-            HostPattern::Wildcard(pattern) => {
-                if pattern.starts_with("*.") {
-                    let suffix = &pattern[1..]; // Include the dot
-                    host.ends_with(suffix) || host == &pattern[2..]
-                } else {
-                    pattern == host
-                }
-            }
+            HostPattern::Wildcard(pattern) => wildcard_matches(pattern, host),
+            HostPattern::Cidr { network, prefix } => host
+                .parse::<IpAddr>()
+                .map(|ip| ip_in_network(ip, *network, *prefix))
+                .unwrap_or(false),
             HostPattern::Any => true,
         }
     }
 }
 
+/// Match a host against a wildcard pattern.
+///
+/// See [`HostPattern::Wildcard`] for the rules.
+fn wildcard_matches(pattern: &str, host: &str) -> bool {
+    let Some(rest) = pattern.strip_prefix("*.") else {
+        return labels_match(pattern, host);
+    };
+
+    // A leading `*.` may absorb any number of non-empty labels
+    let mut candidate = host;
+    loop {
+        if labels_match(rest, candidate) {
+            return true;
+        }
+        match candidate.split_once('.') {
+            Some((label, tail)) if !label.is_empty() => candidate = tail,
+            _ => return false,
+        }
+    }
+}
+
+/// Match a host against a pattern with the same number of labels.
+fn labels_match(pattern: &str, host: &str) -> bool {
+    let mut patterns = pattern.split('.');
+    let mut labels = host.split('.');
+
+    loop {
+        match (patterns.next(), labels.next()) {
+            (Some(p), Some(l)) if !l.is_empty() && label_matches(p, l) => continue,
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Match a single label against a glob where `*` matches any characters.
+fn label_matches(pattern: &str, label: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let label = label.as_bytes();
+    let (mut p, mut l) = (0, 0);
+    // Position of the last `*` seen and the label position it resumes from
+    let mut star: Option<(usize, usize)> = None;
+
+    while l < label.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, l));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == label[l] {
+            p += 1;
+            l += 1;
+        } else if let Some((star_p, star_l)) = star {
+            // Let the last `*` absorb one more character
+            p = star_p + 1;
+            l = star_l + 1;
+            star = Some((star_p, l));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+/// Maximum prefix length for an address family.
+fn max_prefix(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// Check whether `ip` falls within `network/prefix`.
+fn ip_in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) if prefix <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) if prefix <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        // Mismatched address families or invalid prefix never match
+        _ => false,
+    }
+}
+
 /// Set of allowed protocols.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProtocolSet {
@@ -125,6 +258,33 @@ impl ProtocolSet {
     }
 }
 
+/// An inclusive range of ports, e.g. `49152..=65535`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortRange {
+    /// First port in the range.
+    pub start: u16,
+    /// Last port in the range.
+    pub end: u16,
+}
+
+impl PortRange {
+    /// Create a port range covering `start` through `end`, inclusive.
+    pub fn new(start: u16, end: u16) -> Self {
+        Self { start, end }
+    }
+
+    /// Check if a port falls within the range.
+    pub fn contains(&self, port: u16) -> bool {
+        self.start <= port && port <= self.end
+    }
+}
+
+impl std::fmt::Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
 /// Capability for network access.
 ///
 /// This capability controls access to network operations, including
@@ -144,14 +304,38 @@ impl ProtocolSet {
 ///     ProtocolSet::https_only(),
 /// );
 /// ```
+///
+/// Use [`NetworkCapability::builder`] to combine allow and deny rules:
+///
+/// ```
+/// use aegis_capability::builtin::{NetworkCapability, ProtocolSet};
+///
+/// let cap = NetworkCapability::builder()
+///     .allow_host("*.example.com")
+///     .allow_cidr("10.0.0.0/8")
+///     .allow_ports(&[443])
+///     .protocols(ProtocolSet::https_only())
+///     .deny_host("evil.example.com")
+///     .build()
+///     .unwrap();
+///
+/// assert!(cap.is_host_allowed("api.example.com"));
+/// assert!(!cap.is_host_allowed("evil.example.com"));
+/// ```
 #[derive(Debug, Clone)]
 pub struct NetworkCapability {
     /// Allowed hosts.
     allowed_hosts: Vec<HostPattern>,
+    /// Denied hosts, which take precedence over `allowed_hosts`.
+    denied_hosts: Vec<HostPattern>,
     /// Allowed protocols.
     protocols: ProtocolSet,
-    /// Allowed ports (empty means all ports).
+    /// Allowed ports.
     allowed_ports: Vec<u16>,
+    /// Allowed port ranges.
+    ///
+    /// If both this and `allowed_ports` are empty, all ports are allowed.
+    allowed_port_ranges: Vec<PortRange>,
 }
 
 impl NetworkCapability {
@@ -159,8 +343,10 @@ impl NetworkCapability {
     pub fn new(allowed_hosts: Vec<HostPattern>, protocols: ProtocolSet) -> Self {
         Self {
             allowed_hosts,
+            denied_hosts: Vec::new(),
             protocols,
             allowed_ports: Vec::new(),
+            allowed_port_ranges: Vec::new(),
         }
     }
 
@@ -168,8 +354,10 @@ impl NetworkCapability {
     pub fn allow_all() -> Self {
         Self {
             allowed_hosts: vec![HostPattern::Any],
+            denied_hosts: Vec::new(),
             protocols: ProtocolSet::all(),
             allowed_ports: Vec::new(),
+            allowed_port_ranges: Vec::new(),
         }
     }
 
@@ -177,25 +365,65 @@ impl NetworkCapability {
     pub fn https_only(hosts: Vec<String>) -> Self {
         Self {
             allowed_hosts: hosts.into_iter().map(HostPattern::Exact).collect(),
+            denied_hosts: Vec::new(),
             protocols: ProtocolSet::https_only(),
             allowed_ports: vec![443],
+            allowed_port_ranges: Vec::new(),
         }
     }
 
+    /// Start building a capability from allow and deny rules.
+    pub fn builder() -> NetworkCapabilityBuilder {
+        NetworkCapabilityBuilder::new()
+    }
+
     /// Set allowed ports.
     pub fn with_ports(mut self, ports: Vec<u16>) -> Self {
         self.allowed_ports = ports;
         self
     }
 
+    /// Set denied hosts.
+    ///
+    /// A host matching any denied pattern is rejected even if it also
+    /// matches an allowed pattern.
+    pub fn with_denied_hosts(mut self, hosts: Vec<HostPattern>) -> Self {
+        self.denied_hosts = hosts;
+        self
+    }
+
+    /// Check if a host matches a denied pattern.
+    pub fn is_host_denied(&self, host: &str) -> bool {
+        self.denied_hosts.iter().any(|p| p.matches(host))
+    }
+
     /// Check if a host is allowed.
+    ///
+    /// Deny rules take precedence: a host is allowed only if it matches an
+    /// allowed pattern and no denied pattern.
     pub fn is_host_allowed(&self, host: &str) -> bool {
-        self.allowed_hosts.iter().any(|p| p.matches(host))
+        !self.is_host_denied(host) && self.allowed_hosts.iter().any(|p| p.matches(host))
+    }
+
+    /// Set allowed port ranges.
+    ///
+    /// Ranges are allowed in addition to any ports set with
+    /// [`with_ports`](Self::with_ports).
+    pub fn with_port_ranges(mut self, ranges: Vec<PortRange>) -> Self {
+        self.allowed_port_ranges = ranges;
+        self
     }
 
     /// Check if a port is allowed.
+    ///
+    /// A port is allowed if it is listed or falls within an allowed range.
+    /// If no ports or ranges are set, all ports are allowed.
     pub fn is_port_allowed(&self, port: u16) -> bool {
-        self.allowed_ports.is_empty() || self.allowed_ports.contains(&port)
+        if self.allowed_ports.is_empty() && self.allowed_port_ranges.is_empty() {
+            return true;
+        }
+        self.allowed_ports.contains(&port)
+            || self.allowed_port_ranges.iter().any(|r| r.contains(port))
     }
 }
 
@@ -218,9 +446,10 @@ impl Capability for NetworkCapability {
             return PermissionResult::NotApplicable;
         }
 
-        // For proper implementation, we'd need to downcast the action
-        // Here we return NotApplicable as a placeholder
-        PermissionResult::NotApplicable
+        match action.as_any().downcast_ref::<NetworkAction>() {
+            Some(net_action) => check_network_permission(self, net_action),
+            None => PermissionResult::NotApplicable,
+        }
     }
 
     fn handled_action_types(&self) -> Vec<&'static str> {
@@ -239,12 +468,152 @@ impl Capability for NetworkCapability {
                 "Network capability has no allowed hosts".to_string(),
             ));
         }
+        for range in &self.allowed_port_ranges {
+            if range.start > range.end {
+                return Err(CapabilityError::InvalidConfig(format!(
+                    "Invalid port range {}: start is after end",
+                    range
+                )));
+            }
+        }
+        for pattern in self.allowed_hosts.iter().chain(&self.denied_hosts) {
+            if let HostPattern::Cidr { network, prefix } = pattern {
+                if *prefix > max_prefix(network) {
+                    return Err(CapabilityError::InvalidConfig(format!(
+                        "Invalid CIDR prefix /{} for {}",
+                        prefix, network
+                    )));
+                }
+            }
+        }
         Ok(())
     }
+
+    fn to_policy(&self) -> Option<CapabilityPolicy> {
+        Some(CapabilityPolicy::Network {
+            allowed_hosts: self.allowed_hosts.clone(),
+            denied_hosts: self.denied_hosts.clone(),
+            protocols: self.protocols.clone(),
+            allowed_ports: self.allowed_ports.clone(),
+            allowed_port_ranges: self.allowed_port_ranges.clone(),
+        })
+    }
+}
+
+/// Builder for a [`NetworkCapability`] with allow and deny rules.
+///
+/// Host strings containing `*` become [`HostPattern::Wildcard`] patterns, a
+/// lone `*` becomes [`HostPattern::Any`], and anything else is matched
+/// exactly. Deny rules take precedence over allow rules. Protocols default
+/// to HTTPS only.
+#[derive(Debug, Default)]
+pub struct NetworkCapabilityBuilder {
+    allowed_hosts: Vec<HostPattern>,
+    denied_hosts: Vec<HostPattern>,
+    protocols: ProtocolSet,
+    allowed_ports: Vec<u16>,
+    allowed_port_ranges: Vec<PortRange>,
+    /// First invalid CIDR pattern, reported by `build`.
+    error: Option<CapabilityError>,
+}
+
+impl NetworkCapabilityBuilder {
+    /// Create a builder with no rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow a host or host pattern such as `"*.example.com"`.
+    pub fn allow_host(mut self, host: &str) -> Self {
+        self.allowed_hosts.push(host_pattern(host));
+        self
+    }
+
+    /// Allow an IP network in CIDR notation, such as `"10.0.0.0/8"`.
+    pub fn allow_cidr(mut self, cidr: &str) -> Self {
+        if let Some(pattern) = self.parse_cidr(cidr) {
+            self.allowed_hosts.push(pattern);
+        }
+        self
+    }
+
+    /// Deny a host or host pattern, overriding any allow rule.
+    pub fn deny_host(mut self, host: &str) -> Self {
+        self.denied_hosts.push(host_pattern(host));
+        self
+    }
+
+    /// Deny an IP network in CIDR notation, overriding any allow rule.
+    pub fn deny_cidr(mut self, cidr: &str) -> Self {
+        if let Some(pattern) = self.parse_cidr(cidr) {
+            self.denied_hosts.push(pattern);
+        }
+        self
+    }
+
+    /// Allow the given ports.
+    ///
+    /// If no ports or port ranges are allowed, all ports are allowed.
+    pub fn allow_ports(mut self, ports: &[u16]) -> Self {
+        self.allowed_ports.extend_from_slice(ports);
+        self
+    }
+
+    /// Allow an inclusive range of ports.
+    pub fn allow_port_range(mut self, start: u16, end: u16) -> Self {
+        self.allowed_port_ranges.push(PortRange::new(start, end));
+        self
+    }
+
+    /// Set the allowed protocols.
+    pub fn protocols(mut self, protocols: ProtocolSet) -> Self {
+        self.protocols = protocols;
+        self
+    }
+
+    /// Build the capability.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a CIDR pattern was invalid or the capability
+    /// fails validation, e.g. because no hosts are allowed.
+    pub fn build(self) -> Result<NetworkCapability, CapabilityError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        let capability = NetworkCapability::new(self.allowed_hosts, self.protocols)
+            .with_denied_hosts(self.denied_hosts)
+            .with_ports(self.allowed_ports)
+            .with_port_ranges(self.allowed_port_ranges);
+        capability.validate()?;
+        Ok(capability)
+    }
+
+    /// Parse a CIDR pattern, recording the first failure.
+    fn parse_cidr(&mut self, cidr: &str) -> Option<HostPattern> {
+        match HostPattern::cidr(cidr) {
+            Ok(pattern) => Some(pattern),
+            Err(err) => {
+                self.error.get_or_insert(err);
+                None
+            }
+        }
+    }
+}
+
+/// Turn a builder host string into a pattern.
+fn host_pattern(host: &str) -> HostPattern {
+    if host == "*" {
+        HostPattern::Any
+    } else if host.contains('*') {
+        HostPattern::Wildcard(host.to_string())
+    } else {
+        HostPattern::Exact(host.to_string())
+    }
 }
 
 /// Helper function to check network permission with a concrete action.
-#[allow(dead_code)]
 pub fn check_network_permission(
     capability: &NetworkCapability,
     action: &NetworkAction,
@@ -326,7 +695,6 @@ pub fn check_network_permission(
     }
 }
 
-#[allow(dead_code)]
 fn extract_host_from_url(url: &str) -> Option<String> {
     let url = url
         .strip_prefix("https://")
@@ -355,6 +723,79 @@ mod tests {
         assert!(pattern.matches("deep.sub.example.com"));
         assert!(pattern.matches("example.com")); // Base domain matches too
         assert!(!pattern.matches("other.com"));
+        assert!(!pattern.matches("badexample.com"));
+    }
+
+    #[test]
+    fn test_host_pattern_wildcard_inner_label() {
+        let pattern = HostPattern::Wildcard("api.*.example.com".to_string());
+        assert!(pattern.matches("api.eu.example.com"));
+        assert!(pattern.matches("api.us-west.example.com"));
+        assert!(!pattern.matches("api.example.com"));
+        assert!(!pattern.matches("api.eu.west.example.com")); // One label only
+        assert!(!pattern.matches("www.eu.example.com"));
+        assert!(!pattern.matches("api..example.com"));
+    }
+
+    #[test]
+    fn test_host_pattern_wildcard_within_label() {
+        let pattern = HostPattern::Wildcard("*-staging.example.com".to_string());
+        assert!(pattern.matches("web-staging.example.com"));
+        assert!(pattern.matches("a-b-staging.example.com"));
+        assert!(!pattern.matches("web.x-staging.example.com")); // `*` stays in its label
+        assert!(!pattern.matches("web-prod.example.com"));
+
+        let pattern = HostPattern::Wildcard("*.cdn-*.example.com".to_string());
+        assert!(pattern.matches("cdn-1.example.com"));
+        assert!(pattern.matches("img.cdn-eu.example.com"));
+        assert!(pattern.matches("a.b.cdn-eu.example.com"));
+        assert!(!pattern.matches("cdn.example.com"));
+
+        let pattern = HostPattern::Wildcard("example.com".to_string());
+        assert!(pattern.matches("example.com"));
+        assert!(!pattern.matches("sub.example.com"));
+    }
+
+    #[test]
+    fn test_host_pattern_cidr_ipv4() {
+        let pattern = HostPattern::cidr("10.0.0.0/8").unwrap();
+        assert!(pattern.matches("10.0.0.1"));
+        assert!(pattern.matches("10.255.255.255"));
+        assert!(!pattern.matches("11.0.0.1"));
+        assert!(!pattern.matches("192.168.1.1"));
+
+        let single = HostPattern::cidr("192.168.1.7/32").unwrap();
+        assert!(single.matches("192.168.1.7"));
+        assert!(!single.matches("192.168.1.8"));
+
+        let all = HostPattern::cidr("0.0.0.0/0").unwrap();
+        assert!(all.matches("8.8.8.8"));
+        assert!(!all.matches("::1"));
+    }
+
+    #[test]
+    fn test_host_pattern_cidr_ipv6() {
+        let pattern = HostPattern::cidr("fd00::/8").unwrap();
+        assert!(pattern.matches("fd12:3456::1"));
+        assert!(!pattern.matches("fe80::1"));
+        assert!(!pattern.matches("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_host_pattern_cidr_rejects_hostnames() {
+        let pattern = HostPattern::cidr("10.0.0.0/8").unwrap();
+        assert!(!pattern.matches("example.com"));
+        // Looks numeric but is not an IP address
+        assert!(!pattern.matches("10.0.0.1.example.com"));
+        assert!(!pattern.matches("10"));
+    }
+
+    #[test]
+    fn test_host_pattern_cidr_invalid() {
+        assert!(HostPattern::cidr("10.0.0.0").is_err());
+        assert!(HostPattern::cidr("10.0.0.0/33").is_err());
+        assert!(HostPattern::cidr("fd00::/129").is_err());
+        assert!(HostPattern::cidr("example.com/8").is_err());
     }
 
     #[test]
@@ -405,4 +846,99 @@ mod tests {
             Some("api.example.com".to_string())
         );
     }
+
+    #[test]
+    fn test_permits_concrete_action() {
+        let cap = NetworkCapability::https_only(vec!["api.example.com".to_string()]);
+
+        let allowed = NetworkAction::Connect {
+            host: "api.example.com".to_string(),
+            port: 443,
+        };
+        assert!(cap.permits(&allowed).is_allowed());
+
+        let denied = NetworkAction::Connect {
+            host: "api.example.com".to_string(),
+            port: 80,
+        };
+        assert!(cap.permits(&denied).is_denied());
+    }
+
+    #[test]
+    fn test_port_range_boundaries() {
+        let cap =
+            NetworkCapability::allow_all().with_port_ranges(vec![PortRange::new(49152, 65535)]);
+        assert!(!cap.is_port_allowed(49151));
+        assert!(cap.is_port_allowed(49152));
+        assert!(cap.is_port_allowed(60000));
+        assert!(cap.is_port_allowed(65535));
+        assert!(!cap.is_port_allowed(443));
+    }
+
+    #[test]
+    fn test_ports_and_ranges_combine() {
+        let cap = NetworkCapability::allow_all()
+            .with_ports(vec![443])
+            .with_port_ranges(vec![PortRange::new(8000, 8080)]);
+        assert!(cap.is_port_allowed(443));
+        assert!(cap.is_port_allowed(8000));
+        assert!(cap.is_port_allowed(8080));
+        assert!(!cap.is_port_allowed(80));
+        assert!(!cap.is_port_allowed(8081));
+
+        // No ports or ranges means all ports
+        assert!(NetworkCapability::allow_all().is_port_allowed(1));
+
+        let inverted = NetworkCapability::allow_all().with_port_ranges(vec![PortRange::new(10, 5)]);
+        assert!(inverted.validate().is_err());
+    }
+
+    #[test]
+    fn test_builder_deny_overrides_wildcard_allow() {
+        let cap = NetworkCapability::builder()
+            .allow_host("*.example.com")
+            .allow_cidr("10.0.0.0/8")
+            .allow_ports(&[443])
+            .protocols(ProtocolSet::https_only())
+            .deny_host("evil.example.com")
+            .deny_cidr("10.0.0.0/24")
+            .build()
+            .unwrap();
+
+        assert!(cap.is_host_allowed("api.example.com"));
+        assert!(cap.is_host_allowed("10.1.2.3"));
+        assert!(!cap.is_host_allowed("evil.example.com"));
+        assert!(!cap.is_host_allowed("10.0.0.7"));
+
+        let denied = NetworkAction::Connect {
+            host: "evil.example.com".to_string(),
+            port: 443,
+        };
+        assert!(check_network_permission(&cap, &denied).is_denied());
+        let denied = NetworkAction::HttpRequest {
+            url: "https://evil.example.com/payload".to_string(),
+            method: "GET".to_string(),
+        };
+        assert!(check_network_permission(&cap, &denied).is_denied());
+        let allowed = NetworkAction::Connect {
+            host: "api.example.com".to_string(),
+            port: 443,
+        };
+        assert!(check_network_permission(&cap, &allowed).is_allowed());
+
+        // Deny rules survive a round trip through the policy
+        let restored = cap.to_policy().unwrap().to_capability();
+        let denied = NetworkAction::DnsLookup {
+            hostname: "evil.example.com".to_string(),
+        };
+        assert!(restored.permits(&denied).is_denied());
+
+        assert!(
+            NetworkCapability::builder()
+                .allow_host("*")
+                .allow_cidr("10.0.0.0/40")
+                .build()
+                .is_err()
+        );
+    }
 }
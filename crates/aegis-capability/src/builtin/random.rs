@@ -0,0 +1,295 @@
+//! Random capability for random number generation.
+
+use std::any::Any;
+
+use parking_lot::Mutex;
+use rand_chacha::ChaCha20Rng;
+use rand_core::{OsRng, RngCore, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::capability::{
+    Action, Capability, CapabilityId, DenialReason, PermissionResult, standard_ids,
+};
+use crate::error::CapabilityError;
+use crate::policy::CapabilityPolicy;
+
+/// Source of random bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum RandomSource {
+    /// The operating system's random number generator.
+    #[default]
+    System,
+    /// A deterministic ChaCha20 stream seeded with the given value.
+    Seeded(u64),
+    /// No random access (requests are denied).
+    Denied,
+}
+
+/// Actions related to random number generation.
+#[derive(Debug, Clone)]
+pub enum RandomAction {
+    /// Get a number of random bytes.
+    GetBytes { len: usize },
+}
+
+impl Action for RandomAction {
+    fn action_type(&self) -> &str {
+        match self {
+            RandomAction::GetBytes { .. } => "random:bytes",
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            RandomAction::GetBytes { len } => format!("Get {} random bytes", len),
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Capability for random number generation.
+///
+/// This capability controls access to random bytes. A seeded capability
+/// produces the same stream for the same seed, which makes it suitable for
+/// deterministic replay testing.
+///
+/// # Example
+///
+/// ```
+/// use aegis_capability::builtin::RandomCapability;
+///
+/// // OS-backed randomness, at most 1KB per call
+/// let cap = RandomCapability::system().with_max_bytes_per_call(1024);
+///
+/// // Reproducible randomness
+/// let a = RandomCapability::seeded(42);
+/// let b = RandomCapability::seeded(42);
+///
+/// let mut buf_a = [0u8; 16];
+/// let mut buf_b = [0u8; 16];
+/// a.fill_bytes(&mut buf_a).unwrap();
+/// b.fill_bytes(&mut buf_b).unwrap();
+/// assert_eq!(buf_a, buf_b);
+/// ```
+#[derive(Debug)]
+pub struct RandomCapability {
+    /// Source of random bytes.
+    source: RandomSource,
+    /// Maximum bytes per call (None means unlimited).
+    max_bytes_per_call: Option<usize>,
+    /// Deterministic generator for the seeded source.
+    rng: Option<Mutex<ChaCha20Rng>>,
+}
+
+impl RandomCapability {
+    /// Create a new random capability with the given source.
+    pub fn new(source: RandomSource) -> Self {
+        let rng = match source {
+            RandomSource::Seeded(seed) => Some(Mutex::new(ChaCha20Rng::seed_from_u64(seed))),
+            RandomSource::System | RandomSource::Denied => None,
+        };
+
+        Self {
+            source,
+            max_bytes_per_call: None,
+            rng,
+        }
+    }
+
+    /// Create a capability backed by the OS random number generator.
+    pub fn system() -> Self {
+        Self::new(RandomSource::System)
+    }
+
+    /// Create a capability with a deterministic, seeded stream.
+    pub fn seeded(seed: u64) -> Self {
+        Self::new(RandomSource::Seeded(seed))
+    }
+
+    /// Create a capability that denies all random access.
+    pub fn denied() -> Self {
+        Self::new(RandomSource::Denied)
+    }
+
+    /// Set the maximum number of bytes per call.
+    pub fn with_max_bytes_per_call(mut self, max_bytes: usize) -> Self {
+        self.max_bytes_per_call = Some(max_bytes);
+        self
+    }
+
+    /// Get the random source.
+    pub fn source(&self) -> RandomSource {
+        self.source
+    }
+
+    /// Get the maximum bytes per call.
+    pub fn max_bytes_per_call(&self) -> Option<usize> {
+        self.max_bytes_per_call
+    }
+
+    /// Check if a request for `len` bytes is allowed.
+    pub fn is_len_allowed(&self, len: usize) -> bool {
+        self.max_bytes_per_call.is_none_or(|max| len <= max)
+    }
+
+    /// Fill a buffer with random bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if random access is denied, the buffer exceeds the
+    /// per-call maximum, or the OS random source fails.
+    pub fn fill_bytes(&self, buf: &mut [u8]) -> Result<(), CapabilityError> {
+        let action = RandomAction::GetBytes { len: buf.len() };
+        check_random_permission(self, &action).to_result()?;
+
+        match &self.rng {
+            Some(rng) => {
+                rng.lock().fill_bytes(buf);
+                Ok(())
+            }
+            None => OsRng
+                .try_fill_bytes(buf)
+                .map_err(|e| CapabilityError::PermissionDenied {
+                    reason: DenialReason::new(
+                        self.id(),
+                        action.action_type(),
+                        format!("OS random source unavailable: {}", e),
+                    ),
+                }),
+        }
+    }
+}
+
+impl Capability for RandomCapability {
+    fn id(&self) -> CapabilityId {
+        standard_ids::RANDOM.clone()
+    }
+
+    fn name(&self) -> &str {
+        "Random"
+    }
+
+    fn description(&self) -> &str {
+        "Allows access to random number generation"
+    }
+
+    fn permits(&self, action: &dyn Action) -> PermissionResult {
+        if !action.action_type().starts_with("random:") {
+            return PermissionResult::NotApplicable;
+        }
+
+        match action.as_any().downcast_ref::<RandomAction>() {
+            Some(random_action) => check_random_permission(self, random_action),
+            None => PermissionResult::NotApplicable,
+        }
+    }
+
+    fn handled_action_types(&self) -> Vec<&'static str> {
+        vec!["random:bytes"]
+    }
+
+    fn to_policy(&self) -> Option<CapabilityPolicy> {
+        Some(CapabilityPolicy::Random {
+            source: self.source,
+            max_bytes_per_call: self.max_bytes_per_call,
+        })
+    }
+}
+
+/// Helper function to check random permission with a concrete action.
+pub fn check_random_permission(
+    capability: &RandomCapability,
+    action: &RandomAction,
+) -> PermissionResult {
+    match action {
+        RandomAction::GetBytes { len } => {
+            if capability.source() == RandomSource::Denied {
+                return PermissionResult::Denied(DenialReason::new(
+                    capability.id(),
+                    action.action_type(),
+                    "Random access is disabled",
+                ));
+            }
+
+            if !capability.is_len_allowed(*len) {
+                return PermissionResult::Denied(DenialReason::new(
+                    capability.id(),
+                    action.action_type(),
+                    format!(
+                        "Requested {} bytes exceeds maximum {}",
+                        len,
+                        capability.max_bytes_per_call().unwrap_or_default()
+                    ),
+                ));
+            }
+
+            PermissionResult::Allowed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_streams_are_identical() {
+        let a = RandomCapability::seeded(1234);
+        let b = RandomCapability::seeded(1234);
+
+        let mut buf_a = [0u8; 64];
+        let mut buf_b = [0u8; 64];
+        a.fill_bytes(&mut buf_a).unwrap();
+        b.fill_bytes(&mut buf_b).unwrap();
+        assert_eq!(buf_a, buf_b);
+
+        // The stream continues rather than restarting
+        let mut next = [0u8; 64];
+        a.fill_bytes(&mut next).unwrap();
+        assert_ne!(buf_a, next);
+    }
+
+    #[test]
+    fn test_different_seeds_differ() {
+        let a = RandomCapability::seeded(1);
+        let b = RandomCapability::seeded(2);
+
+        let mut buf_a = [0u8; 32];
+        let mut buf_b = [0u8; 32];
+        a.fill_bytes(&mut buf_a).unwrap();
+        b.fill_bytes(&mut buf_b).unwrap();
+        assert_ne!(buf_a, buf_b);
+    }
+
+    #[test]
+    fn test_system_fill_bytes() {
+        let cap = RandomCapability::system();
+        let mut buf = [0u8; 32];
+        assert!(cap.fill_bytes(&mut buf).is_ok());
+    }
+
+    #[test]
+    fn test_denied() {
+        let cap = RandomCapability::denied();
+        let mut buf = [0u8; 8];
+        assert!(cap.fill_bytes(&mut buf).is_err());
+        assert!(cap.permits(&RandomAction::GetBytes { len: 8 }).is_denied());
+    }
+
+    #[test]
+    fn test_max_bytes_per_call() {
+        let cap = RandomCapability::seeded(7).with_max_bytes_per_call(16);
+
+        assert!(
+            cap.permits(&RandomAction::GetBytes { len: 16 })
+                .is_allowed()
+        );
+        assert!(cap.permits(&RandomAction::GetBytes { len: 17 }).is_denied());
+
+        let mut buf = [0u8; 32];
+        assert!(cap.fill_bytes(&mut buf).is_err());
+    }
+}
@@ -4,20 +4,28 @@
 //! Capabilities are explicit, opt-in permissions that control what a sandboxed
 //! module can do.
 
+use std::any::Any;
 use std::borrow::Cow;
 use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
+use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 
 use crate::error::CapabilityError;
+use crate::policy::CapabilityPolicy;
 
 /// Unique identifier for a capability type.
 ///
 /// Capability IDs are used to identify and look up capabilities in a set.
 /// They should be unique and descriptive.
 ///
+/// Built-in capabilities live in the root namespace (`"filesystem"`,
+/// `"network"`, ...). Custom capabilities from libraries should use
+/// [`CapabilityId::namespaced`] so that two libraries defining a `"cache"`
+/// capability can be granted to the same set.
+///
 /// # Example
 ///
 /// ```
@@ -27,24 +35,102 @@ use crate::error::CapabilityError;
 /// let net_cap = CapabilityId::new("network");
 ///
 /// assert_ne!(fs_cap, net_cap);
+///
+/// let cache = CapabilityId::namespaced("acme", "cache");
+/// assert_eq!(cache.as_str(), "acme/cache");
+/// assert_eq!(cache.namespace(), Some("acme"));
+/// assert_eq!(cache.local_name(), "cache");
 /// ```
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CapabilityId(Cow<'static, str>);
 
 impl CapabilityId {
+    /// Separator between the namespace and the local name.
+    pub const NAMESPACE_SEPARATOR: char = '/';
+
+    /// Placeholder ID reported when no capability handles an action.
+    ///
+    /// The angle brackets keep it from being mistaken for a granted
+    /// capability. See [`DenialReason::no_capability`].
+    pub const NO_CAPABILITY: CapabilityId = CapabilityId(Cow::Borrowed("<no capability>"));
+
     /// Create a new capability ID.
     pub fn new(id: impl Into<Cow<'static, str>>) -> Self {
         Self(id.into())
     }
 
+    /// Create an ID backed by a process-wide interned string.
+    ///
+    /// The first call for a given string leaks one copy of it; later calls
+    /// return IDs pointing at that copy, so interned IDs clone without
+    /// allocating and compare equal by pointer. Use this for IDs built at
+    /// run time in hot paths, not for unbounded sets of strings.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use aegis_capability::CapabilityId;
+    ///
+    /// let a = CapabilityId::interned(&format!("acme/{}", "cache"));
+    /// let b = CapabilityId::interned("acme/cache");
+    /// assert_eq!(a.as_str().as_ptr(), b.as_str().as_ptr());
+    /// ```
+    pub fn interned(id: &str) -> Self {
+        static INTERNER: OnceLock<DashMap<String, &'static str>> = OnceLock::new();
+        let interner = INTERNER.get_or_init(DashMap::new);
+
+        if let Some(interned) = interner.get(id) {
+            return Self(Cow::Borrowed(*interned));
+        }
+        let interned = *interner
+            .entry(id.to_string())
+            .or_insert_with(|| Box::leak(id.to_string().into_boxed_str()));
+        Self(Cow::Borrowed(interned))
+    }
+
+    /// Create an ID in a namespace, written `"namespace/name"`.
+    pub fn namespaced(namespace: &str, name: &str) -> Self {
+        Self(Cow::Owned(format!(
+            "{}{}{}",
+            namespace,
+            Self::NAMESPACE_SEPARATOR,
+            name
+        )))
+    }
+
     /// Get the ID as a string slice.
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Get the namespace, or `None` for IDs in the root namespace.
+    ///
+    /// The namespace is everything before the last separator, so namespaces
+    /// may themselves be nested (`"acme/storage/cache"`).
+    pub fn namespace(&self) -> Option<&str> {
+        self.0
+            .rsplit_once(Self::NAMESPACE_SEPARATOR)
+            .map(|(namespace, _)| namespace)
+    }
+
+    /// Get the name within the namespace.
+    ///
+    /// For IDs in the root namespace this is the whole ID.
+    pub fn local_name(&self) -> &str {
+        self.0
+            .rsplit_once(Self::NAMESPACE_SEPARATOR)
+            .map_or(&self.0, |(_, name)| name)
+    }
 }
 
 impl PartialEq for CapabilityId {
     fn eq(&self, other: &Self) -> bool {
+        // Interned and constant IDs share their string
+        if let (Cow::Borrowed(a), Cow::Borrowed(b)) = (&self.0, &other.0) {
+            if std::ptr::eq(*a, *b) {
+                return true;
+            }
+        }
         self.0 == other.0
     }
 }
@@ -87,6 +173,15 @@ pub trait Action: fmt::Debug + Send + Sync {
     fn description(&self) -> String {
         format!("{:?}", self)
     }
+
+    /// Get this action as `Any` so capabilities can downcast to a concrete type.
+    ///
+    /// Built-in actions return `self`. The default returns a value that never
+    /// downcasts to a concrete action, so capabilities treat custom actions as
+    /// not applicable unless they override this.
+    fn as_any(&self) -> &dyn Any {
+        &()
+    }
 }
 
 /// Result of a permission check.
@@ -111,10 +206,23 @@ impl PermissionResult {
         matches!(self, PermissionResult::Denied(_))
     }
 
+    /// Check if the action was denied because no capability handles it.
+    pub fn is_no_capability(&self) -> bool {
+        matches!(self, PermissionResult::Denied(reason) if reason.is_no_capability())
+    }
+
     /// Convert to a Result type.
+    ///
+    /// A denial from [`DenialReason::no_capability`] becomes
+    /// [`CapabilityError::NoCapabilityFound`] rather than `PermissionDenied`.
     pub fn to_result(&self) -> Result<(), CapabilityError> {
         match self {
             PermissionResult::Allowed => Ok(()),
+            PermissionResult::Denied(reason) if reason.is_no_capability() => {
+                Err(CapabilityError::NoCapabilityFound {
+                    action: reason.action.clone(),
+                })
+            }
             PermissionResult::Denied(reason) => Err(CapabilityError::PermissionDenied {
                 reason: reason.clone(),
             }),
@@ -149,10 +257,30 @@ impl DenialReason {
             message: message.into(),
         }
     }
+
+    /// Create the denial for an action that no capability handles.
+    ///
+    /// The reason names [`CapabilityId::NO_CAPABILITY`], so audit logs can
+    /// tell it apart from a denial by a granted capability.
+    pub fn no_capability(action: impl Into<String>) -> Self {
+        Self::new(
+            CapabilityId::NO_CAPABILITY,
+            action,
+            "No capability handles this action",
+        )
+    }
+
+    /// Check if this denial is because no capability handles the action.
+    pub fn is_no_capability(&self) -> bool {
+        self.capability == CapabilityId::NO_CAPABILITY
+    }
 }
 
 impl fmt::Display for DenialReason {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_no_capability() {
+            return write!(f, "{} - {}", self.action, self.message);
+        }
         write!(
             f,
             "[{}] {} - {}",
@@ -161,6 +289,56 @@ impl fmt::Display for DenialReason {
     }
 }
 
+/// Information about who is asking for a permission.
+///
+/// Passed alongside the action to
+/// [`Capability::permits_with_context`] by
+/// [`CapabilitySet::check_permission_with_context`](crate::CapabilitySet::check_permission_with_context).
+/// Checks without a context see the default, which identifies no sandbox,
+/// module or principal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckContext {
+    /// ID of the sandbox making the request, as displayed.
+    pub sandbox_id: Option<String>,
+    /// SHA-256 hash of the bytes of the module making the request.
+    pub module_hash: Option<[u8; 32]>,
+    /// Nesting depth of the sandbox; top-level sandboxes are at depth 0.
+    pub depth: u32,
+    /// Tenant, user or other principal the sandbox runs on behalf of.
+    pub principal: Option<String>,
+}
+
+impl CheckContext {
+    /// Create a context that identifies nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the ID of the sandbox making the request.
+    pub fn with_sandbox_id(mut self, id: impl Into<String>) -> Self {
+        self.sandbox_id = Some(id.into());
+        self
+    }
+
+    /// Set the hash of the module making the request.
+    pub fn with_module_hash(mut self, hash: [u8; 32]) -> Self {
+        self.module_hash = Some(hash);
+        self
+    }
+
+    /// Set the nesting depth of the sandbox making the request.
+    pub fn with_depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Set the principal the request is made on behalf of.
+    pub fn with_principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = Some(principal.into());
+        self
+    }
+}
+
 /// Core trait for all capabilities.
 ///
 /// Capabilities define what actions a sandboxed module is permitted to perform.
@@ -221,6 +399,15 @@ pub trait Capability: Send + Sync + fmt::Debug {
     /// - `NotApplicable` if this capability doesn't handle this action type
     fn permits(&self, action: &dyn Action) -> PermissionResult;
 
+    /// Check if this capability permits an action requested in `ctx`.
+    ///
+    /// Override this for capabilities whose decision depends on who is
+    /// asking, such as grants scoped to one module. The default ignores the
+    /// context and calls [`permits`](Self::permits).
+    fn permits_with_context(&self, action: &dyn Action, _ctx: &CheckContext) -> PermissionResult {
+        self.permits(action)
+    }
+
     /// Get a list of action types this capability handles.
     ///
     /// This is used for documentation and validation purposes.
@@ -242,6 +429,25 @@ pub trait Capability: Send + Sync + fmt::Debug {
     fn validate(&self) -> Result<(), CapabilityError> {
         Ok(())
     }
+
+    /// Describe this capability as a serializable policy.
+    ///
+    /// Returns `None` for capabilities that cannot be expressed as a policy,
+    /// which is the default for custom capabilities.
+    fn to_policy(&self) -> Option<CapabilityPolicy> {
+        None
+    }
+
+    /// Check if this capability's decisions depend only on the action type.
+    ///
+    /// A set with a decision cache (see `CapabilitySet::with_decision_cache`)
+    /// calls `permits` once per action type for such capabilities and reuses
+    /// the result. Capabilities that inspect the action's payload, such as
+    /// paths or hosts, whose decisions change over time, or that inspect the
+    /// [`CheckContext`] must return `false`, which is the default.
+    fn is_decision_cacheable(&self) -> bool {
+        false
+    }
 }
 
 /// A boxed capability trait object.
@@ -271,6 +477,9 @@ pub mod standard_ids {
 
     /// Random number generation capability ID.
     pub const RANDOM: CapabilityId = CapabilityId(std::borrow::Cow::Borrowed("random"));
+
+    /// Nested sandbox capability ID.
+    pub const NESTING: CapabilityId = CapabilityId(std::borrow::Cow::Borrowed("nesting"));
 }
 
 #[cfg(test)]
@@ -329,6 +538,37 @@ mod tests {
         assert_ne!(id1, id3);
     }
 
+    #[test]
+    fn test_namespaced_id() {
+        let id = CapabilityId::namespaced("acme", "cache");
+        assert_eq!(id, CapabilityId::new("acme/cache"));
+        assert_eq!(id.namespace(), Some("acme"));
+        assert_eq!(id.local_name(), "cache");
+
+        let nested = CapabilityId::namespaced("acme/storage", "cache");
+        assert_eq!(nested.namespace(), Some("acme/storage"));
+        assert_eq!(nested.local_name(), "cache");
+
+        assert_eq!(standard_ids::FILESYSTEM.namespace(), None);
+        assert_eq!(standard_ids::FILESYSTEM.local_name(), "filesystem");
+    }
+
+    #[test]
+    fn test_interned_id_identity() {
+        let name = String::from("test/interned");
+        let first = CapabilityId::interned(&name);
+
+        for _ in 0..1_000_000 {
+            let id = CapabilityId::interned(&name);
+            assert_eq!(id.as_str().as_ptr(), first.as_str().as_ptr());
+        }
+
+        let copy = first.clone();
+        assert_eq!(copy.as_str().as_ptr(), first.as_str().as_ptr());
+        assert_eq!(copy, CapabilityId::new(name));
+        assert_ne!(first, CapabilityId::interned("test/other"));
+    }
+
     #[test]
     fn test_capability_permits() {
         let cap = TestCapability {
@@ -356,7 +596,18 @@ mod tests {
             "action",
             "reason",
         ));
-        assert!(denied.to_result().is_err());
+        assert!(matches!(
+            denied.to_result(),
+            Err(CapabilityError::PermissionDenied { .. })
+        ));
+
+        let unhandled = PermissionResult::Denied(DenialReason::no_capability("fs_read"));
+        assert!(unhandled.is_no_capability());
+        assert!(!denied.is_no_capability());
+        match unhandled.to_result() {
+            Err(CapabilityError::NoCapabilityFound { action }) => assert_eq!(action, "fs_read"),
+            other => panic!("expected NoCapabilityFound, got {other:?}"),
+        }
     }
 
     #[test]
@@ -0,0 +1,566 @@
+//! Capabilities composed from other capabilities.
+//!
+//! This module provides `AllOf` and `AnyOf`, which combine the decisions of
+//! several child capabilities into one, `ExpiringCapability`, which
+//! limits a capability to a deadline, and `QuotaCapability`, which limits
+//! how many times a capability may be exercised.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::capability::{
+    Action, BoxedCapability, Capability, CapabilityId, CheckContext, DenialReason, PermissionResult,
+};
+use crate::error::CapabilityError;
+
+/// A capability that allows an action only if every child that handles it
+/// allows it.
+///
+/// Children returning `NotApplicable` are ignored. The first denial from a
+/// child is returned as-is. If no child handles the action, the result is
+/// `NotApplicable`.
+///
+/// # Example
+///
+/// ```
+/// use aegis_capability::{AllOf, Capability};
+/// use aegis_capability::builtin::{LogLevel, LoggingCapability};
+///
+/// // Messages must pass both the level filter and the rate limit
+/// let cap = AllOf(vec![
+///     Box::new(LoggingCapability::new(LogLevel::Warn, 4096)),
+///     Box::new(LoggingCapability::new(LogLevel::Trace, 4096).with_rate_limit(10)),
+/// ]);
+/// assert_eq!(cap.id().as_str(), "all_of(logging,logging)");
+/// ```
+#[derive(Debug)]
+pub struct AllOf(pub Vec<BoxedCapability>);
+
+/// A capability that allows an action if any child allows it.
+///
+/// If no child allows the action, the first denial from a child is
+/// returned. If no child handles the action, the result is `NotApplicable`.
+#[derive(Debug)]
+pub struct AnyOf(pub Vec<BoxedCapability>);
+
+/// Build an ID such as `all_of(filesystem,logging)` from the children's IDs.
+fn composite_id(kind: &str, children: &[BoxedCapability]) -> CapabilityId {
+    let ids: Vec<_> = children.iter().map(|c| c.id().to_string()).collect();
+    CapabilityId::new(format!("{}({})", kind, ids.join(",")))
+}
+
+/// Union of the children's handled action types, in first-seen order.
+fn union_action_types(children: &[BoxedCapability]) -> Vec<&'static str> {
+    let mut types = Vec::new();
+    for child in children {
+        for action_type in child.handled_action_types() {
+            if !types.contains(&action_type) {
+                types.push(action_type);
+            }
+        }
+    }
+    types
+}
+
+impl Capability for AllOf {
+    fn id(&self) -> CapabilityId {
+        composite_id("all_of", &self.0)
+    }
+
+    fn name(&self) -> &str {
+        "All Of"
+    }
+
+    fn description(&self) -> &str {
+        "Allows an action only if every applicable child capability allows it"
+    }
+
+    fn permits(&self, action: &dyn Action) -> PermissionResult {
+        self.permits_with_context(action, &CheckContext::default())
+    }
+
+    fn permits_with_context(&self, action: &dyn Action, ctx: &CheckContext) -> PermissionResult {
+        let mut allowed = false;
+
+        for child in &self.0 {
+            match child.permits_with_context(action, ctx) {
+                PermissionResult::Allowed => allowed = true,
+                denied @ PermissionResult::Denied(_) => return denied,
+                PermissionResult::NotApplicable => {}
+            }
+        }
+
+        if allowed {
+            PermissionResult::Allowed
+        } else {
+            PermissionResult::NotApplicable
+        }
+    }
+
+    fn handled_action_types(&self) -> Vec<&'static str> {
+        union_action_types(&self.0)
+    }
+
+    fn on_attach(&self) -> Result<(), CapabilityError> {
+        self.0.iter().try_for_each(|c| c.on_attach())
+    }
+
+    fn on_detach(&self) {
+        self.0.iter().for_each(|c| c.on_detach());
+    }
+
+    fn validate(&self) -> Result<(), CapabilityError> {
+        self.0.iter().try_for_each(|c| c.validate())
+    }
+}
+
+impl Capability for AnyOf {
+    fn id(&self) -> CapabilityId {
+        composite_id("any_of", &self.0)
+    }
+
+    fn name(&self) -> &str {
+        "Any Of"
+    }
+
+    fn description(&self) -> &str {
+        "Allows an action if any child capability allows it"
+    }
+
+    fn permits(&self, action: &dyn Action) -> PermissionResult {
+        self.permits_with_context(action, &CheckContext::default())
+    }
+
+    fn permits_with_context(&self, action: &dyn Action, ctx: &CheckContext) -> PermissionResult {
+        let mut denial = None;
+
+        for child in &self.0 {
+            match child.permits_with_context(action, ctx) {
+                PermissionResult::Allowed => return PermissionResult::Allowed,
+                denied @ PermissionResult::Denied(_) => {
+                    denial.get_or_insert(denied);
+                }
+                PermissionResult::NotApplicable => {}
+            }
+        }
+
+        denial.unwrap_or(PermissionResult::NotApplicable)
+    }
+
+    fn handled_action_types(&self) -> Vec<&'static str> {
+        union_action_types(&self.0)
+    }
+
+    fn on_attach(&self) -> Result<(), CapabilityError> {
+        self.0.iter().try_for_each(|c| c.on_attach())
+    }
+
+    fn on_detach(&self) {
+        self.0.iter().for_each(|c| c.on_detach());
+    }
+
+    fn validate(&self) -> Result<(), CapabilityError> {
+        self.0.iter().try_for_each(|c| c.validate())
+    }
+}
+
+/// A capability that stops permitting actions after a deadline.
+///
+/// Until the deadline the inner capability decides. Afterwards, actions the
+/// inner capability handles are denied with an "expired" reason, and other
+/// actions remain `NotApplicable`. The ID and handled action types are those
+/// of the inner capability, so it takes the inner capability's place in a
+/// `CapabilitySet`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use aegis_capability::ExpiringCapability;
+/// use aegis_capability::builtin::ClockCapability;
+///
+/// let cap = ExpiringCapability::new(
+///     Box::new(ClockCapability::monotonic_only()),
+///     Duration::from_secs(60),
+/// );
+/// assert!(!cap.is_expired());
+/// ```
+#[derive(Debug)]
+pub struct ExpiringCapability {
+    /// The capability that decides until the deadline.
+    inner: BoxedCapability,
+    /// When the capability expires.
+    expires_at: Instant,
+}
+
+impl ExpiringCapability {
+    /// Wrap a capability so that it expires after `ttl`.
+    pub fn new(inner: BoxedCapability, ttl: Duration) -> Self {
+        Self::with_deadline(inner, Instant::now() + ttl)
+    }
+
+    /// Wrap a capability so that it expires at `expires_at`.
+    pub fn with_deadline(inner: BoxedCapability, expires_at: Instant) -> Self {
+        Self { inner, expires_at }
+    }
+
+    /// Get the deadline.
+    pub fn expires_at(&self) -> Instant {
+        self.expires_at
+    }
+
+    /// Check if the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+
+    /// Get the wrapped capability.
+    pub fn inner(&self) -> &dyn Capability {
+        self.inner.as_ref()
+    }
+}
+
+impl Capability for ExpiringCapability {
+    fn id(&self) -> CapabilityId {
+        self.inner.id()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn permits(&self, action: &dyn Action) -> PermissionResult {
+        self.permits_with_context(action, &CheckContext::default())
+    }
+
+    fn permits_with_context(&self, action: &dyn Action, ctx: &CheckContext) -> PermissionResult {
+        let result = self.inner.permits_with_context(action, ctx);
+        if !self.is_expired() || result == PermissionResult::NotApplicable {
+            return result;
+        }
+
+        PermissionResult::Denied(DenialReason::new(
+            self.id(),
+            action.action_type(),
+            "Capability expired",
+        ))
+    }
+
+    fn handled_action_types(&self) -> Vec<&'static str> {
+        self.inner.handled_action_types()
+    }
+
+    fn on_attach(&self) -> Result<(), CapabilityError> {
+        self.inner.on_attach()
+    }
+
+    fn on_detach(&self) {
+        self.inner.on_detach();
+    }
+
+    fn validate(&self) -> Result<(), CapabilityError> {
+        self.inner.validate()
+    }
+}
+
+/// A capability that permits an action at most `max_uses` times.
+///
+/// Each `Allowed` result from the inner capability uses up one unit of the
+/// quota. Once the quota is used up, actions the inner capability would
+/// allow are denied with a "quota exceeded" reason until
+/// [`reset_quota`](Self::reset_quota) is called, e.g. between executions.
+/// Denials and `NotApplicable` results do not count. The ID and handled
+/// action types are those of the inner capability.
+///
+/// # Example
+///
+/// ```
+/// use aegis_capability::QuotaCapability;
+/// use aegis_capability::builtin::NetworkCapability;
+///
+/// // At most 10 connections per execution
+/// let cap = QuotaCapability::new(
+///     Box::new(NetworkCapability::https_only(vec!["api.example.com".into()])),
+///     10,
+/// );
+/// assert_eq!(cap.remaining(), 10);
+/// ```
+#[derive(Debug)]
+pub struct QuotaCapability {
+    /// The capability that decides while quota remains.
+    inner: BoxedCapability,
+    /// Number of allowed actions before further ones are denied.
+    max_uses: u64,
+    /// Number of allowed actions so far.
+    uses: AtomicU64,
+}
+
+impl QuotaCapability {
+    /// Wrap a capability so that it allows at most `max_uses` actions.
+    pub fn new(inner: BoxedCapability, max_uses: u64) -> Self {
+        Self {
+            inner,
+            max_uses,
+            uses: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the quota.
+    pub fn max_uses(&self) -> u64 {
+        self.max_uses
+    }
+
+    /// Get the number of actions allowed since the last reset.
+    pub fn uses(&self) -> u64 {
+        self.uses.load(Ordering::SeqCst)
+    }
+
+    /// Get the number of actions that may still be allowed.
+    pub fn remaining(&self) -> u64 {
+        self.max_uses.saturating_sub(self.uses())
+    }
+
+    /// Restore the full quota.
+    pub fn reset_quota(&self) {
+        self.uses.store(0, Ordering::SeqCst);
+    }
+
+    /// Get the wrapped capability.
+    pub fn inner(&self) -> &dyn Capability {
+        self.inner.as_ref()
+    }
+}
+
+impl Capability for QuotaCapability {
+    fn id(&self) -> CapabilityId {
+        self.inner.id()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn permits(&self, action: &dyn Action) -> PermissionResult {
+        self.permits_with_context(action, &CheckContext::default())
+    }
+
+    fn permits_with_context(&self, action: &dyn Action, ctx: &CheckContext) -> PermissionResult {
+        let result = self.inner.permits_with_context(action, ctx);
+        if result != PermissionResult::Allowed {
+            return result;
+        }
+
+        let counted = self
+            .uses
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |uses| {
+                (uses < self.max_uses).then_some(uses + 1)
+            });
+
+        match counted {
+            Ok(_) => PermissionResult::Allowed,
+            Err(_) => PermissionResult::Denied(DenialReason::new(
+                self.id(),
+                action.action_type(),
+                format!("Quota exceeded: at most {} uses", self.max_uses),
+            )),
+        }
+    }
+
+    fn handled_action_types(&self) -> Vec<&'static str> {
+        self.inner.handled_action_types()
+    }
+
+    fn on_attach(&self) -> Result<(), CapabilityError> {
+        self.inner.on_attach()
+    }
+
+    fn on_detach(&self) {
+        self.inner.on_detach();
+    }
+
+    fn validate(&self) -> Result<(), CapabilityError> {
+        self.inner.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestAction;
+
+    impl Action for TestAction {
+        fn action_type(&self) -> &str {
+            "test:run"
+        }
+    }
+
+    /// A child capability that always returns the same result.
+    #[derive(Debug)]
+    struct Fixed {
+        id: &'static str,
+        result: PermissionResult,
+        handles: Vec<&'static str>,
+    }
+
+    impl Capability for Fixed {
+        fn id(&self) -> CapabilityId {
+            CapabilityId::new(self.id)
+        }
+
+        fn name(&self) -> &str {
+            self.id
+        }
+
+        fn description(&self) -> &str {
+            "Fixed result"
+        }
+
+        fn permits(&self, _action: &dyn Action) -> PermissionResult {
+            self.result.clone()
+        }
+
+        fn handled_action_types(&self) -> Vec<&'static str> {
+            self.handles.clone()
+        }
+    }
+
+    fn allow(id: &'static str) -> BoxedCapability {
+        Box::new(Fixed {
+            id,
+            result: PermissionResult::Allowed,
+            handles: vec!["test:run", "test:stop"],
+        })
+    }
+
+    fn deny(id: &'static str) -> BoxedCapability {
+        Box::new(Fixed {
+            id,
+            result: PermissionResult::Denied(DenialReason::new(
+                CapabilityId::new(id),
+                "test:run",
+                "denied",
+            )),
+            handles: vec!["test:run"],
+        })
+    }
+
+    fn skip(id: &'static str) -> BoxedCapability {
+        Box::new(Fixed {
+            id,
+            result: PermissionResult::NotApplicable,
+            handles: vec!["test:other"],
+        })
+    }
+
+    fn denied_by(result: &PermissionResult) -> Option<&str> {
+        match result {
+            PermissionResult::Denied(reason) => Some(reason.capability.as_str()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_all_of() {
+        let all = AllOf(vec![allow("a"), skip("b"), allow("c")]);
+        assert!(all.permits(&TestAction).is_allowed());
+
+        let all = AllOf(vec![allow("a"), deny("b"), deny("c")]);
+        assert_eq!(denied_by(&all.permits(&TestAction)), Some("b"));
+
+        let all = AllOf(vec![skip("a"), skip("b")]);
+        assert_eq!(all.permits(&TestAction), PermissionResult::NotApplicable);
+
+        let all = AllOf(Vec::new());
+        assert_eq!(all.permits(&TestAction), PermissionResult::NotApplicable);
+    }
+
+    #[test]
+    fn test_any_of() {
+        let any = AnyOf(vec![deny("a"), skip("b"), allow("c")]);
+        assert!(any.permits(&TestAction).is_allowed());
+
+        let any = AnyOf(vec![skip("a"), deny("b"), deny("c")]);
+        assert_eq!(denied_by(&any.permits(&TestAction)), Some("b"));
+
+        let any = AnyOf(vec![skip("a"), skip("b")]);
+        assert_eq!(any.permits(&TestAction), PermissionResult::NotApplicable);
+    }
+
+    #[test]
+    fn test_combinator_metadata() {
+        let all = AllOf(vec![allow("a"), deny("b"), skip("c")]);
+        assert_eq!(all.id().as_str(), "all_of(a,b,c)");
+        assert_eq!(
+            all.handled_action_types(),
+            vec!["test:run", "test:stop", "test:other"]
+        );
+
+        let any = AnyOf(vec![allow("a"), Box::new(AllOf(vec![deny("b")]))]);
+        assert_eq!(any.id().as_str(), "any_of(a,all_of(b))");
+    }
+
+    #[test]
+    fn test_expiring_capability() {
+        let cap = ExpiringCapability::new(allow("clock"), Duration::from_millis(20));
+        assert_eq!(cap.id().as_str(), "clock");
+        assert_eq!(cap.handled_action_types(), vec!["test:run", "test:stop"]);
+        assert!(cap.permits(&TestAction).is_allowed());
+
+        std::thread::sleep(Duration::from_millis(30));
+
+        assert!(cap.is_expired());
+        match cap.permits(&TestAction) {
+            PermissionResult::Denied(reason) => {
+                assert_eq!(reason.capability.as_str(), "clock");
+                assert!(reason.message.contains("expired"));
+            }
+            other => panic!("expected denial, got {other:?}"),
+        }
+
+        let unrelated = ExpiringCapability::with_deadline(skip("other"), Instant::now());
+        assert_eq!(
+            unrelated.permits(&TestAction),
+            PermissionResult::NotApplicable
+        );
+    }
+
+    #[test]
+    fn test_quota_capability() {
+        let cap = QuotaCapability::new(allow("network"), 3);
+        assert_eq!(cap.id().as_str(), "network");
+        assert_eq!(cap.handled_action_types(), vec!["test:run", "test:stop"]);
+
+        for _ in 0..3 {
+            assert!(cap.permits(&TestAction).is_allowed());
+        }
+        assert_eq!(cap.remaining(), 0);
+
+        match cap.permits(&TestAction) {
+            PermissionResult::Denied(reason) => {
+                assert_eq!(reason.capability.as_str(), "network");
+                assert!(reason.message.contains("Quota exceeded"));
+            }
+            other => panic!("expected denial, got {other:?}"),
+        }
+        assert_eq!(cap.uses(), 3);
+
+        cap.reset_quota();
+        assert!(cap.permits(&TestAction).is_allowed());
+        assert_eq!(cap.remaining(), 2);
+
+        // Denials from the inner capability do not use the quota
+        let denied = QuotaCapability::new(deny("network"), 1);
+        assert!(denied.permits(&TestAction).is_denied());
+        assert_eq!(denied.uses(), 0);
+    }
+}
@@ -9,7 +9,8 @@
 //! Aegis uses a capability-based security model where:
 //!
 //! - All permissions must be explicitly granted (no ambient authority)
-//! - Capabilities are immutable once execution begins
+//! - Capability sets can be frozen before execution begins, or have
+//!   capabilities revoked mid-execution with immediate effect
 //! - Capabilities are composable
 //! - Absence of a capability guarantees denial
 //!
@@ -21,6 +22,8 @@
 //! - [`NetworkCapability`]: Network access
 //! - [`LoggingCapability`]: Logging output
 //! - [`ClockCapability`]: Time and clock access
+//! - [`RandomCapability`]: Random number generation
+//! - [`NestingCapability`]: Spawning child sandboxes
 //!
 //! # Custom Capabilities
 //!
@@ -69,21 +72,32 @@
 
 pub mod builtin;
 pub mod capability;
+pub mod combinator;
 pub mod error;
+pub mod policy;
+pub mod schema;
 pub mod set;
 
 // Re-export main types
 pub use capability::{
-    Action, BoxedCapability, Capability, CapabilityId, DenialReason, PermissionResult,
-    SharedCapability, standard_ids,
+    Action, BoxedCapability, Capability, CapabilityId, CheckContext, DenialReason,
+    PermissionResult, SharedCapability, standard_ids,
 };
+pub use combinator::{AllOf, AnyOf, ExpiringCapability, QuotaCapability};
 pub use error::{CapabilityError, CapabilityResult};
-pub use set::{CapabilitySet, CapabilitySetBuilder};
+pub use policy::CapabilityPolicy;
+pub use schema::capability_policy_schema;
+pub use set::{
+    CapabilityDiff, CapabilitySet, CapabilitySetBuilder, CapabilityView, FrozenCapabilitySet,
+    MergeStrategy, RevocationList,
+};
 
 // Re-export built-in capabilities
 pub use builtin::{
-    ClockCapability, ClockType, FilesystemCapability, HostPattern, LogLevel, LoggingCapability,
-    NetworkCapability, PathPermission, ProtocolSet,
+    ClockAction, ClockCapability, ClockType, FilesystemAction, FilesystemCapability, HostPattern,
+    LogLevel, LoggingAction, LoggingCapability, NestingAction, NestingCapability, NetworkAction,
+    NetworkCapability, NetworkCapabilityBuilder, PathPermission, PortRange, ProtocolSet,
+    RandomAction, RandomCapability, RandomSource,
 };
 
 /// Prelude module for convenient imports.
@@ -94,7 +108,8 @@ pub mod prelude {
 
     // Built-in capabilities
     pub use crate::builtin::{
-        ClockCapability, FilesystemCapability, LoggingCapability, NetworkCapability,
+        ClockCapability, FilesystemCapability, LoggingCapability, NestingCapability,
+        NetworkCapability, RandomCapability,
     };
 }
 
@@ -122,4 +137,35 @@ mod tests {
         assert!(set.has(&standard_ids::CLOCK));
         assert!(!set.has(&standard_ids::FILESYSTEM));
     }
+
+    #[test]
+    fn test_require_builtin_actions() {
+        use std::path::PathBuf;
+
+        let set = CapabilitySetBuilder::new()
+            .with(FilesystemCapability::read_only(&["/data"]))
+            .with(LoggingCapability::production())
+            .build()
+            .unwrap();
+
+        assert!(
+            set.require(&FilesystemAction::Read {
+                path: PathBuf::from("/data/input.txt"),
+            })
+            .is_ok()
+        );
+        assert!(
+            set.require(&FilesystemAction::Write {
+                path: PathBuf::from("/data/input.txt"),
+            })
+            .is_err()
+        );
+        assert!(
+            set.require(&LoggingAction::Log {
+                level: LogLevel::Error,
+                message_len: 64,
+            })
+            .is_ok()
+        );
+    }
 }
@@ -0,0 +1,262 @@
+//! Serializable capability policies.
+//!
+//! This module provides the `CapabilityPolicy` type, which describes a
+//! built-in capability's configuration in a form that can be stored in a
+//! JSON or TOML policy file and turned back into a capability.
+
+use serde::{Deserialize, Serialize};
+
+use crate::builtin::{
+    ClockCapability, ClockType, FilesystemCapability, HostPattern, LogLevel, LoggingCapability,
+    NestingCapability, NetworkCapability, PathPermission, PortRange, ProtocolSet, RandomCapability,
+    RandomSource,
+};
+use crate::capability::BoxedCapability;
+
+/// Serializable description of a built-in capability.
+///
+/// # Example
+///
+/// ```
+/// use aegis_capability::{CapabilityPolicy, CapabilitySet, PathPermission};
+///
+/// let policies = vec![CapabilityPolicy::Filesystem {
+///     permissions: vec![PathPermission::read_only("/data")],
+///     deny: vec![],
+/// }];
+///
+/// let set = CapabilitySet::from_policies(&policies).unwrap();
+/// assert_eq!(set.len(), 1);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CapabilityPolicy {
+    /// Filesystem access.
+    Filesystem {
+        /// Allowed paths with their permissions.
+        permissions: Vec<PathPermission>,
+        /// Denied paths, which take precedence over `permissions`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        deny: Vec<PathPermission>,
+    },
+    /// Network access.
+    Network {
+        /// Allowed hosts.
+        allowed_hosts: Vec<HostPattern>,
+        /// Denied hosts, which take precedence over `allowed_hosts`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        denied_hosts: Vec<HostPattern>,
+        /// Allowed protocols.
+        protocols: ProtocolSet,
+        /// Allowed ports.
+        #[serde(default)]
+        allowed_ports: Vec<u16>,
+        /// Allowed port ranges. If both this and `allowed_ports` are empty,
+        /// all ports are allowed.
+        #[serde(default)]
+        allowed_port_ranges: Vec<PortRange>,
+    },
+    /// Logging output.
+    Logging {
+        /// Minimum log level allowed.
+        min_level: LogLevel,
+        /// Maximum message size in bytes.
+        max_message_size: usize,
+        /// Maximum messages per second.
+        #[serde(default)]
+        max_rate: Option<u32>,
+    },
+    /// Clock access.
+    Clock {
+        /// Type of clock to provide.
+        clock_type: ClockType,
+    },
+    /// Random number generation.
+    Random {
+        /// Source of random bytes.
+        source: RandomSource,
+        /// Maximum bytes per call.
+        #[serde(default)]
+        max_bytes_per_call: Option<usize>,
+    },
+    /// Spawning child sandboxes.
+    Nesting {
+        /// Maximum number of children.
+        max_children: u32,
+        /// Maximum nesting depth.
+        max_depth: u32,
+        /// Fraction of the parent's remaining fuel and memory given to a child.
+        resource_fraction: f64,
+    },
+}
+
+impl CapabilityPolicy {
+    /// Get the policy type name.
+    pub fn policy_type(&self) -> &'static str {
+        match self {
+            CapabilityPolicy::Filesystem { .. } => "filesystem",
+            CapabilityPolicy::Network { .. } => "network",
+            CapabilityPolicy::Logging { .. } => "logging",
+            CapabilityPolicy::Clock { .. } => "clock",
+            CapabilityPolicy::Random { .. } => "random",
+            CapabilityPolicy::Nesting { .. } => "nesting",
+        }
+    }
+
+    /// Build the capability described by this policy.
+    pub fn to_capability(&self) -> BoxedCapability {
+        match self {
+            CapabilityPolicy::Filesystem { permissions, deny } => {
+                Box::new(FilesystemCapability::new(permissions.clone()).with_deny(deny.clone()))
+            }
+            CapabilityPolicy::Network {
+                allowed_hosts,
+                denied_hosts,
+                protocols,
+                allowed_ports,
+                allowed_port_ranges,
+            } => Box::new(
+                NetworkCapability::new(allowed_hosts.clone(), protocols.clone())
+                    .with_denied_hosts(denied_hosts.clone())
+                    .with_ports(allowed_ports.clone())
+                    .with_port_ranges(allowed_port_ranges.clone()),
+            ),
+            CapabilityPolicy::Logging {
+                min_level,
+                max_message_size,
+                max_rate,
+            } => {
+                let cap = LoggingCapability::new(*min_level, *max_message_size);
+                match max_rate {
+                    Some(rate) => Box::new(cap.with_rate_limit(*rate)),
+                    None => Box::new(cap),
+                }
+            }
+            CapabilityPolicy::Clock { clock_type } => {
+                Box::new(ClockCapability::new(clock_type.clone()))
+            }
+            CapabilityPolicy::Random {
+                source,
+                max_bytes_per_call,
+            } => {
+                let cap = RandomCapability::new(*source);
+                match max_bytes_per_call {
+                    Some(max) => Box::new(cap.with_max_bytes_per_call(*max)),
+                    None => Box::new(cap),
+                }
+            }
+            CapabilityPolicy::Nesting {
+                max_children,
+                max_depth,
+                resource_fraction,
+            } => Box::new(NestingCapability::new(
+                *max_children,
+                *max_depth,
+                *resource_fraction,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::builtin::{FilesystemAction, LoggingAction, NetworkAction};
+    use crate::set::{CapabilitySet, CapabilitySetBuilder};
+
+    #[test]
+    fn test_policy_json_round_trip() {
+        let set = CapabilitySetBuilder::new()
+            .with(FilesystemCapability::read_only(&["/data"]))
+            .with(NetworkCapability::https_only(vec![
+                "api.example.com".to_string(),
+            ]))
+            .with(LoggingCapability::production().with_rate_limit(10))
+            .with(ClockCapability::fixed(1_000))
+            .build()
+            .unwrap();
+
+        let json = serde_json::to_string(&set.to_policy()).unwrap();
+        let policies: Vec<CapabilityPolicy> = serde_json::from_str(&json).unwrap();
+        let restored = CapabilitySet::from_policies(&policies).unwrap();
+
+        assert_eq!(restored.len(), set.len());
+
+        let actions: Vec<Box<dyn crate::Action>> = vec![
+            Box::new(FilesystemAction::Read {
+                path: PathBuf::from("/data/a.txt"),
+            }),
+            Box::new(FilesystemAction::Write {
+                path: PathBuf::from("/data/a.txt"),
+            }),
+            Box::new(NetworkAction::Connect {
+                host: "api.example.com".to_string(),
+                port: 443,
+            }),
+            Box::new(NetworkAction::Connect {
+                host: "api.example.com".to_string(),
+                port: 80,
+            }),
+            Box::new(LoggingAction::Log {
+                level: LogLevel::Debug,
+                message_len: 1,
+            }),
+        ];
+
+        for action in &actions {
+            assert_eq!(
+                set.check_permission(action.as_ref()).is_allowed(),
+                restored.check_permission(action.as_ref()).is_allowed(),
+                "decision mismatch for {:?}",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn test_policy_tagged_format() {
+        let json = r#"[{"type": "clock", "clock_type": "Monotonic"}]"#;
+        let policies: Vec<CapabilityPolicy> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(policies.len(), 1);
+        assert_eq!(policies[0].policy_type(), "clock");
+    }
+
+    #[test]
+    fn test_custom_capability_skipped() {
+        use crate::capability::{Action, Capability, CapabilityId, PermissionResult};
+
+        #[derive(Debug)]
+        struct CustomCapability;
+
+        impl Capability for CustomCapability {
+            fn id(&self) -> CapabilityId {
+                CapabilityId::new("custom")
+            }
+
+            fn name(&self) -> &str {
+                "Custom"
+            }
+
+            fn description(&self) -> &str {
+                "Not serializable"
+            }
+
+            fn permits(&self, _action: &dyn Action) -> PermissionResult {
+                PermissionResult::NotApplicable
+            }
+        }
+
+        let set = CapabilitySetBuilder::new()
+            .with(CustomCapability)
+            .with(ClockCapability::monotonic_only())
+            .build()
+            .unwrap();
+
+        let policies = set.to_policy();
+        assert_eq!(policies.len(), 1);
+        assert_eq!(policies[0].policy_type(), "clock");
+    }
+}
@@ -0,0 +1,390 @@
+//! JSON Schema for capability policy documents.
+//!
+//! This module provides `capability_policy_schema`, which describes the
+//! policy files read with `CapabilitySet::from_policies`: a JSON array of
+//! [`CapabilityPolicy`](crate::CapabilityPolicy) values in their serde
+//! representation.
+
+use serde_json::{Map, Value, json};
+
+/// JSON Schema dialect of the generated schema.
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Generate the JSON Schema of a capability policy document.
+///
+/// The schema follows the serde representation of `CapabilityPolicy` and
+/// the types it contains. It is stricter than deserialization in two ways:
+/// unknown fields are rejected rather than ignored, and value ranges that
+/// capability validation would reject (e.g. a `resource_fraction` outside
+/// `(0, 1]`) are rejected up front.
+///
+/// # Example
+///
+/// ```
+/// use aegis_capability::capability_policy_schema;
+///
+/// let schema = capability_policy_schema();
+/// assert_eq!(schema["type"], "array");
+/// ```
+pub fn capability_policy_schema() -> Value {
+    let mut defs = Map::new();
+    defs.insert("CapabilityPolicy".to_string(), capability_policy());
+    defs.insert("PathPermission".to_string(), path_permission());
+    defs.insert("HostPattern".to_string(), host_pattern());
+    defs.insert("ProtocolSet".to_string(), protocol_set());
+    defs.insert("PortRange".to_string(), port_range());
+    defs.insert("LogLevel".to_string(), log_level());
+    defs.insert("ClockType".to_string(), clock_type());
+    defs.insert("RandomSource".to_string(), random_source());
+
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "title": "Aegis capability policy",
+        "description": "Capabilities granted to a sandbox.",
+        "type": "array",
+        "items": reference("CapabilityPolicy"),
+        "$defs": defs,
+    })
+}
+
+/// Reference a schema in `$defs`.
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{}", name) })
+}
+
+/// An unsigned integer no larger than `max`.
+fn unsigned(max: Option<u64>) -> Value {
+    match max {
+        Some(max) => json!({ "type": "integer", "minimum": 0, "maximum": max }),
+        None => json!({ "type": "integer", "minimum": 0 }),
+    }
+}
+
+/// An optional unsigned integer (`Option<u32>`, `Option<usize>`).
+fn optional_unsigned(max: Option<u64>) -> Value {
+    let mut schema = unsigned(max);
+    schema["type"] = json!(["integer", "null"]);
+    schema
+}
+
+/// A struct with the given properties, of which `required` must be present.
+fn object(properties: Value, required: &[&str]) -> Value {
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// A non-unit variant of an externally tagged enum: `{"Name": value}`.
+fn external_variant(name: &str, value: Value) -> Value {
+    let mut properties = Map::new();
+    properties.insert(name.to_string(), value);
+    object(Value::Object(properties), &[name])
+}
+
+/// A variant of `CapabilityPolicy`, internally tagged by `type`.
+fn policy_variant(tag: &str, properties: Value, required: &[&str]) -> Value {
+    let mut properties = properties;
+    properties["type"] = json!({ "const": tag });
+
+    let mut required = required.to_vec();
+    required.insert(0, "type");
+    object(properties, &required)
+}
+
+fn capability_policy() -> Value {
+    json!({
+        "description": "Serializable description of a built-in capability.",
+        "oneOf": [
+            policy_variant(
+                "filesystem",
+                json!({
+                    "permissions": { "type": "array", "items": reference("PathPermission") },
+                    "deny": { "type": "array", "items": reference("PathPermission") },
+                }),
+                &["permissions"],
+            ),
+            policy_variant(
+                "network",
+                json!({
+                    "allowed_hosts": { "type": "array", "items": reference("HostPattern") },
+                    "denied_hosts": { "type": "array", "items": reference("HostPattern") },
+                    "protocols": reference("ProtocolSet"),
+                    "allowed_ports": { "type": "array", "items": unsigned(Some(u16::MAX.into())) },
+                    "allowed_port_ranges": { "type": "array", "items": reference("PortRange") },
+                }),
+                &["allowed_hosts", "protocols"],
+            ),
+            policy_variant(
+                "logging",
+                json!({
+                    "min_level": reference("LogLevel"),
+                    "max_message_size": unsigned(None),
+                    "max_rate": optional_unsigned(Some(u32::MAX.into())),
+                }),
+                &["min_level", "max_message_size"],
+            ),
+            policy_variant(
+                "clock",
+                json!({ "clock_type": reference("ClockType") }),
+                &["clock_type"],
+            ),
+            policy_variant(
+                "random",
+                json!({
+                    "source": reference("RandomSource"),
+                    "max_bytes_per_call": optional_unsigned(None),
+                }),
+                &["source"],
+            ),
+            policy_variant(
+                "nesting",
+                json!({
+                    "max_children": unsigned(Some(u32::MAX.into())),
+                    "max_depth": unsigned(Some(u32::MAX.into())),
+                    "resource_fraction": { "type": "number", "exclusiveMinimum": 0, "maximum": 1 },
+                }),
+                &["max_children", "max_depth", "resource_fraction"],
+            ),
+        ],
+    })
+}
+
+fn path_permission() -> Value {
+    let mut schema = object(
+        json!({
+            "path": { "type": "string" },
+            "pattern": { "type": "string" },
+            "canonicalize": { "type": "boolean" },
+            "read": { "type": "boolean" },
+            "write": { "type": "boolean" },
+            "create": { "type": "boolean" },
+            "delete": { "type": "boolean" },
+        }),
+        &["path", "read", "write", "create", "delete"],
+    );
+    schema["description"] = json!("Permission for a path.");
+    schema
+}
+
+fn host_pattern() -> Value {
+    json!({
+        "description": "Pattern for matching hosts.",
+        "oneOf": [
+            external_variant("Exact", json!({ "type": "string" })),
+            external_variant("Wildcard", json!({ "type": "string" })),
+            external_variant(
+                "Cidr",
+                object(
+                    json!({
+                        "network": { "type": "string" },
+                        "prefix": unsigned(Some(128)),
+                    }),
+                    &["network", "prefix"],
+                ),
+            ),
+            { "const": "Any" },
+        ],
+    })
+}
+
+fn protocol_set() -> Value {
+    object(
+        json!({
+            "http": { "type": "boolean" },
+            "https": { "type": "boolean" },
+            "tcp": { "type": "boolean" },
+            "udp": { "type": "boolean" },
+        }),
+        &["http", "https", "tcp", "udp"],
+    )
+}
+
+fn port_range() -> Value {
+    let port = unsigned(Some(u16::MAX.into()));
+    object(json!({ "start": port, "end": port }), &["start", "end"])
+}
+
+fn log_level() -> Value {
+    json!({ "enum": ["Trace", "Debug", "Info", "Warn", "Error"] })
+}
+
+fn clock_type() -> Value {
+    json!({
+        "oneOf": [
+            { "enum": ["RealTime", "Monotonic", "None"] },
+            external_variant("Fixed", unsigned(None)),
+            external_variant(
+                "Stepped",
+                object(
+                    json!({ "start_nanos": unsigned(None), "step_nanos": unsigned(None) }),
+                    &["start_nanos", "step_nanos"],
+                ),
+            ),
+        ],
+    })
+}
+
+fn random_source() -> Value {
+    json!({
+        "oneOf": [
+            { "enum": ["System", "Denied"] },
+            external_variant("Seeded", unsigned(None)),
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{
+        ClockCapability, FilesystemCapability, LoggingCapability, NestingCapability,
+        NetworkCapability, RandomCapability,
+    };
+    use crate::set::CapabilitySetBuilder;
+
+    /// Validate `value` against `schema`, supporting the keywords the
+    /// generated schema uses.
+    fn validate(root: &Value, schema: &Value, value: &Value) -> bool {
+        if let Some(target) = schema.get("$ref").and_then(Value::as_str) {
+            let name = target.trim_start_matches("#/$defs/");
+            return validate(root, &root["$defs"][name], value);
+        }
+
+        if let Some(types) = schema.get("type") {
+            let matches_type = |ty: &Value| match ty.as_str().unwrap() {
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                "string" => value.is_string(),
+                "boolean" => value.is_boolean(),
+                "integer" => value.is_u64() || value.is_i64(),
+                "number" => value.is_number(),
+                "null" => value.is_null(),
+                other => panic!("unsupported type {}", other),
+            };
+            let matched = match types.as_array() {
+                Some(types) => types.iter().any(matches_type),
+                None => matches_type(types),
+            };
+            if !matched {
+                return false;
+            }
+        }
+
+        if schema.get("const").is_some_and(|c| c != value) {
+            return false;
+        }
+        if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+            if !options.contains(value) {
+                return false;
+            }
+        }
+        if let Some(options) = schema.get("oneOf").and_then(Value::as_array) {
+            let matching = options
+                .iter()
+                .filter(|option| validate(root, option, value))
+                .count();
+            if matching != 1 {
+                return false;
+            }
+        }
+
+        if let Some(number) = value.as_f64() {
+            if schema["minimum"].as_f64().is_some_and(|min| number < min)
+                || schema["maximum"].as_f64().is_some_and(|max| number > max)
+                || schema["exclusiveMinimum"]
+                    .as_f64()
+                    .is_some_and(|min| number <= min)
+            {
+                return false;
+            }
+        }
+
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            if !values.iter().all(|item| validate(root, items, item)) {
+                return false;
+            }
+        }
+
+        if let Some(fields) = value.as_object() {
+            let properties = schema["properties"].as_object();
+            if let Some(required) = schema["required"].as_array() {
+                if !required
+                    .iter()
+                    .all(|name| fields.contains_key(name.as_str().unwrap()))
+                {
+                    return false;
+                }
+            }
+            for (name, field) in fields {
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) if !validate(root, property, field) => return false,
+                    None if schema["additionalProperties"] == false => return false,
+                    _ => {}
+                }
+            }
+        }
+
+        true
+    }
+
+    #[test]
+    fn test_schema_matches_serialized_policies() {
+        let set = CapabilitySetBuilder::new()
+            .with(FilesystemCapability::read_only(&["/data"]))
+            .with(NetworkCapability::https_only(vec![
+                "api.example.com".to_string(),
+            ]))
+            .with(LoggingCapability::production().with_rate_limit(10))
+            .with(ClockCapability::stepped(0, 1_000))
+            .with(RandomCapability::seeded(7))
+            .with(NestingCapability::new(2, 1, 0.5))
+            .build()
+            .unwrap();
+        let document = serde_json::to_value(set.to_policy()).unwrap();
+
+        let schema = capability_policy_schema();
+        assert!(validate(&schema, &schema, &document));
+    }
+
+    #[test]
+    fn test_schema_accepts_valid_and_rejects_malformed_documents() {
+        let schema = capability_policy_schema();
+
+        let valid = json!([
+            { "type": "filesystem", "permissions": [
+                { "path": "/data", "read": true, "write": false, "create": false, "delete": false }
+            ], "deny": [
+                { "path": "/data/secrets", "read": true, "write": true, "create": true, "delete": true }
+            ] },
+            { "type": "network",
+              "allowed_hosts": [{ "Wildcard": "*.example.com" }, { "Cidr": { "network": "10.0.0.0", "prefix": 8 } }, "Any"],
+              "denied_hosts": [{ "Exact": "evil.example.com" }],
+              "protocols": { "http": false, "https": true, "tcp": false, "udp": false },
+              "allowed_port_ranges": [{ "start": 8000, "end": 8080 }] },
+            { "type": "clock", "clock_type": { "Fixed": 1000 } },
+            { "type": "random", "source": "System", "max_bytes_per_call": null },
+        ]);
+        assert!(validate(&schema, &schema, &valid));
+
+        let malformed = [
+            json!([{ "type": "teleport" }]),
+            json!([{ "type": "clock" }]),
+            json!([{ "type": "clock", "clock_type": "Sundial" }]),
+            json!([{ "type": "logging", "min_level": "Info", "max_message_size": -1 }]),
+            json!([{ "type": "network", "allowed_hosts": [], "protocols": { "https": true } }]),
+            json!([{ "type": "nesting", "max_children": 1, "max_depth": 1, "resource_fraction": 2.0 }]),
+            json!([{ "type": "filesystem", "permissions": [{ "path": "/data", "read": true }] }]),
+            json!([{ "type": "random", "source": "System", "max_bytes": 16 }]),
+        ];
+        for document in &malformed {
+            assert!(
+                !validate(&schema, &schema, document),
+                "accepted malformed document {}",
+                document
+            );
+        }
+    }
+}
@@ -4,15 +4,114 @@
 //! of capabilities and provides methods for permission checking.
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use tracing::{debug, info, warn};
 
 use crate::capability::{
-    Action, BoxedCapability, Capability, CapabilityId, DenialReason, PermissionResult,
-    SharedCapability,
+    Action, BoxedCapability, Capability, CapabilityId, CheckContext, DenialReason,
+    PermissionResult, SharedCapability,
 };
 use crate::error::{CapabilityError, CapabilityResult};
+use crate::policy::CapabilityPolicy;
+
+/// Strategy for resolving conflicts when merging capability sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// Fail with `CapabilityError::AlreadyExists` if both sets contain the same ID.
+    #[default]
+    Reject,
+    /// Keep the capability from `self` when both sets contain the same ID.
+    PreferSelf,
+    /// Keep the capability from `other` when both sets contain the same ID.
+    PreferOther,
+}
+
+/// Differences between a capability set and a baseline.
+///
+/// Returned by [`CapabilitySet::diff`]. Each list is sorted by ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapabilityDiff {
+    /// Capabilities in the set but not in the baseline.
+    pub added: Vec<CapabilityId>,
+    /// Capabilities in the baseline but not in the set.
+    pub removed: Vec<CapabilityId>,
+    /// Capabilities in both whose policies differ.
+    ///
+    /// Only capabilities that can be expressed as a policy on both sides
+    /// are compared.
+    pub drifted: Vec<CapabilityId>,
+}
+
+impl CapabilityDiff {
+    /// Check if the set matches the baseline.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.drifted.is_empty()
+    }
+
+    /// Check if any shared capability's configuration changed.
+    pub fn has_drift(&self) -> bool {
+        !self.drifted.is_empty()
+    }
+}
+
+/// Capability IDs that have been revoked from a `CapabilitySet`.
+///
+/// The list is consulted before any capability is asked for a decision, and
+/// again before an allow is returned, so a check that is in progress when a
+/// capability is revoked cannot allow an action through it.
+#[derive(Debug, Default)]
+pub struct RevocationList {
+    ids: DashSet<CapabilityId>,
+}
+
+impl RevocationList {
+    /// Create an empty revocation list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark an ID as revoked. Returns `false` if it was already revoked.
+    pub fn revoke(&self, id: CapabilityId) -> bool {
+        self.ids.insert(id)
+    }
+
+    /// Remove an ID from the list. Returns `false` if it was not revoked.
+    pub fn restore(&self, id: &CapabilityId) -> bool {
+        self.ids.remove(id).is_some()
+    }
+
+    /// Check if an ID is revoked.
+    pub fn is_revoked(&self, id: &CapabilityId) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// Get all revoked IDs.
+    pub fn ids(&self) -> Vec<CapabilityId> {
+        self.ids.iter().map(|id| id.key().clone()).collect()
+    }
+
+    /// Get the number of revoked IDs.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Check if no IDs are revoked.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+impl Clone for RevocationList {
+    fn clone(&self) -> Self {
+        let ids = DashSet::new();
+        for id in self.ids.iter() {
+            ids.insert(id.key().clone());
+        }
+        Self { ids }
+    }
+}
 
 /// A set of capabilities granted to a sandbox.
 ///
@@ -35,6 +134,23 @@ use crate::error::{CapabilityError, CapabilityResult};
 pub struct CapabilitySet {
     /// Map of capability ID to capability.
     capabilities: DashMap<CapabilityId, SharedCapability>,
+    /// IDs revoked from the set, denied even to checks already in progress.
+    revoked: RevocationList,
+    /// Cached decisions of cacheable capabilities, if enabled.
+    decision_cache: Option<DecisionCache>,
+}
+
+/// Decisions of cacheable capabilities keyed by capability and action type.
+///
+/// Entries record the generation they were made in; bumping the generation
+/// invalidates them all, including ones inserted by checks that were in
+/// progress during the invalidation.
+#[derive(Default)]
+struct DecisionCache {
+    /// Current generation.
+    generation: AtomicU64,
+    /// Cached decisions with the generation they were made in.
+    decisions: DashMap<(CapabilityId, String), (u64, PermissionResult)>,
 }
 
 impl CapabilitySet {
@@ -42,7 +158,64 @@ impl CapabilitySet {
     pub fn new() -> Self {
         Self {
             capabilities: DashMap::new(),
+            revoked: RevocationList::new(),
+            decision_cache: None,
+        }
+    }
+
+    /// Cache the decisions of capabilities that opt in through
+    /// [`Capability::is_decision_cacheable`].
+    ///
+    /// Such a capability is asked about each action type once, and the
+    /// decision is reused until a capability is granted to or revoked from
+    /// the set. Other capabilities are asked on every check.
+    pub fn with_decision_cache(mut self) -> Self {
+        self.decision_cache = Some(DecisionCache::default());
+        self
+    }
+
+    /// Check if the set caches decisions.
+    pub fn has_decision_cache(&self) -> bool {
+        self.decision_cache.is_some()
+    }
+
+    /// Drop all cached decisions.
+    fn invalidate_decision_cache(&self) {
+        if let Some(cache) = &self.decision_cache {
+            cache.generation.fetch_add(1, Ordering::SeqCst);
+            cache.decisions.clear();
+        }
+    }
+
+    /// Ask a capability about an action, through the decision cache if the
+    /// capability allows it.
+    fn permits(
+        &self,
+        id: &CapabilityId,
+        capability: &SharedCapability,
+        action: &dyn Action,
+        ctx: &CheckContext,
+    ) -> PermissionResult {
+        let Some(cache) = &self.decision_cache else {
+            return capability.permits_with_context(action, ctx);
+        };
+        if !capability.is_decision_cacheable() {
+            return capability.permits_with_context(action, ctx);
+        }
+
+        let generation = cache.generation.load(Ordering::SeqCst);
+        let key = (id.clone(), action.action_type().to_string());
+        if let Some(entry) = cache.decisions.get(&key) {
+            let (cached_generation, result) = entry.value();
+            if *cached_generation == generation {
+                debug!(capability = %id, action_type = action.action_type(), "Using cached decision");
+                return result.clone();
+            }
         }
+
+        let result = capability.permits_with_context(action, ctx);
+        cache.decisions.insert(key, (generation, result.clone()));
+        result
     }
 
     /// Create a capability set with the given capabilities.
@@ -54,6 +227,40 @@ impl CapabilitySet {
         Ok(set)
     }
 
+    /// Create a capability set from serialized policies.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if two policies describe the same capability or a
+    /// policy fails validation.
+    pub fn from_policies(policies: &[CapabilityPolicy]) -> CapabilityResult<Self> {
+        let set = Self::new();
+        for policy in policies {
+            set.grant_boxed(policy.to_capability())?;
+        }
+        Ok(set)
+    }
+
+    /// Describe this set as serializable policies.
+    ///
+    /// Policies are ordered by capability ID. Capabilities that cannot be
+    /// expressed as a policy are skipped with a warning.
+    pub fn to_policy(&self) -> Vec<CapabilityPolicy> {
+        let mut ids = self.ids();
+        ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        ids.iter()
+            .filter_map(|id| {
+                let cap = self.get(id)?;
+                let policy = cap.to_policy();
+                if policy.is_none() {
+                    warn!(capability = %id, "Capability cannot be expressed as a policy; skipping");
+                }
+                policy
+            })
+            .collect()
+    }
+
     /// Grant a capability to this set.
     ///
     /// # Errors
@@ -76,6 +283,8 @@ impl CapabilitySet {
 
         let shared: SharedCapability = capability.into();
         self.capabilities.insert(id.clone(), shared);
+        self.revoked.restore(&id);
+        self.invalidate_decision_cache();
 
         info!(capability = %id, "Capability granted");
         Ok(())
@@ -93,13 +302,21 @@ impl CapabilitySet {
         capability.on_attach()?;
 
         self.capabilities.insert(id.clone(), capability);
+        self.revoked.restore(&id);
+        self.invalidate_decision_cache();
 
         info!(capability = %id, "Capability granted");
         Ok(())
     }
 
     /// Revoke a capability from this set.
+    ///
+    /// The ID is added to the set's [`RevocationList`] before the capability
+    /// is removed, so once this returns no check can be allowed by it, even
+    /// one that started earlier. Granting the ID again lifts the revocation.
     pub fn revoke(&self, id: &CapabilityId) -> Option<SharedCapability> {
+        self.revoked.revoke(id.clone());
+        self.invalidate_decision_cache();
         self.capabilities.remove(id).map(|(_, cap)| {
             cap.on_detach();
             info!(capability = %id, "Capability revoked");
@@ -112,6 +329,16 @@ impl CapabilitySet {
         self.capabilities.contains_key(id)
     }
 
+    /// Check if a capability ID has been revoked and not granted again.
+    pub fn is_revoked(&self, id: &CapabilityId) -> bool {
+        self.revoked.is_revoked(id)
+    }
+
+    /// Get the revocation list.
+    pub fn revocations(&self) -> &RevocationList {
+        &self.revoked
+    }
+
     /// Get a capability by ID.
     pub fn get(&self, id: &CapabilityId) -> Option<SharedCapability> {
         self.capabilities.get(id).map(|r| Arc::clone(r.value()))
@@ -134,35 +361,155 @@ impl CapabilitySet {
 
     /// Check if an action is permitted by any capability in the set.
     ///
-    /// This iterates through all capabilities until one either allows or
-    /// denies the action. If all capabilities return `NotApplicable`,
-    /// the action is denied.
+    /// The action is allowed if any capability allows it. Otherwise, if one or
+    /// more capabilities deny it, the denial from the capability whose ID sorts
+    /// first is returned. If all capabilities return `NotApplicable`, the
+    /// action is denied.
     pub fn check_permission(&self, action: &dyn Action) -> PermissionResult {
+        self.check_permission_detailed(action).1
+    }
+
+    /// Check if an action is permitted, also returning the deciding capability.
+    ///
+    /// The returned ID is the capability that allowed or denied the action,
+    /// or [`CapabilityId::NO_CAPABILITY`] if no capability handled it. See
+    /// [`check_permission`](Self::check_permission) for how the decision is made.
+    pub fn check_permission_detailed(
+        &self,
+        action: &dyn Action,
+    ) -> (CapabilityId, PermissionResult) {
+        self.check_permission_detailed_with_context(action, &CheckContext::default())
+    }
+
+    /// Check if an action requested in `ctx` is permitted.
+    ///
+    /// Capabilities see the context through
+    /// [`Capability::permits_with_context`]; otherwise this is the same as
+    /// [`check_permission`](Self::check_permission).
+    pub fn check_permission_with_context(
+        &self,
+        action: &dyn Action,
+        ctx: &CheckContext,
+    ) -> PermissionResult {
+        self.check_permission_detailed_with_context(action, ctx).1
+    }
+
+    /// Check if an action requested in `ctx` is permitted, also returning
+    /// the deciding capability.
+    pub fn check_permission_detailed_with_context(
+        &self,
+        action: &dyn Action,
+        ctx: &CheckContext,
+    ) -> (CapabilityId, PermissionResult) {
+        let entries = self
+            .capabilities
+            .iter()
+            .map(|entry| (entry.key().clone(), Arc::clone(entry.value())));
+        self.decide(action, ctx, entries)
+    }
+
+    /// Ask every capability about an action, for debugging denials.
+    ///
+    /// Unlike [`check_permission`](Self::check_permission), this does not
+    /// stop at the first allow and bypasses the decision cache, so each
+    /// capability's own verdict is returned, ordered by capability ID.
+    /// Revoked capabilities are left out. Capabilities whose `permits` has
+    /// side effects, such as `NestingCapability` counting spawns, see the
+    /// check as a real one.
+    pub fn explain(&self, action: &dyn Action) -> Vec<(CapabilityId, PermissionResult)> {
+        let mut verdicts: Vec<_> = self
+            .snapshot()
+            .into_iter()
+            .filter(|(id, _)| !self.revoked.is_revoked(id))
+            .map(|(id, capability)| {
+                let result = capability.permits(action);
+                (id, result)
+            })
+            .collect();
+        verdicts.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        verdicts
+    }
+
+    /// Format [`explain`](Self::explain) as text, one capability per line.
+    pub fn explain_text(&self, action: &dyn Action) -> String {
+        let verdicts = self.explain(action);
+
+        let mut output = format!(
+            "Action: {} ({})\n",
+            action.action_type(),
+            action.description()
+        );
+        if verdicts.is_empty() {
+            output.push_str("  (no capabilities)\n");
+        }
+        for (id, result) in &verdicts {
+            let verdict = match result {
+                PermissionResult::Allowed => "allowed".to_string(),
+                PermissionResult::Denied(reason) => format!("denied: {}", reason.message),
+                PermissionResult::NotApplicable => "not applicable".to_string(),
+            };
+            output.push_str(&format!("  {}: {}\n", id, verdict));
+        }
+
+        let decision = if verdicts.iter().any(|(_, result)| result.is_allowed()) {
+            "allowed"
+        } else if verdicts.iter().any(|(_, result)| result.is_denied()) {
+            "denied"
+        } else {
+            "denied (no capability handles this action)"
+        };
+        output.push_str(&format!("Decision: {}\n", decision));
+        output
+    }
+
+    /// Decide whether an action is permitted by the given capabilities.
+    ///
+    /// Revoked capabilities are skipped, including ones revoked while their
+    /// `permits` call was running.
+    fn decide(
+        &self,
+        action: &dyn Action,
+        ctx: &CheckContext,
+        entries: impl Iterator<Item = (CapabilityId, SharedCapability)>,
+    ) -> (CapabilityId, PermissionResult) {
         debug!(action_type = action.action_type(), "Checking permission");
 
         let mut denial: Option<DenialReason> = None;
 
-        for entry in self.capabilities.iter() {
-            let result = entry.value().permits(action);
+        for (id, capability) in entries {
+            if self.revoked.is_revoked(&id) {
+                debug!(capability = %id, "Skipping revoked capability");
+                continue;
+            }
+
+            let result = self.permits(&id, &capability, action, ctx);
 
             match result {
+                PermissionResult::Allowed if self.revoked.is_revoked(&id) => {
+                    debug!(capability = %id, "Capability revoked during check");
+                    continue;
+                }
                 PermissionResult::Allowed => {
                     debug!(
-                        capability = %entry.key(),
+                        capability = %id,
                         action_type = action.action_type(),
                         "Permission allowed"
                     );
-                    return PermissionResult::Allowed;
+                    return (id, PermissionResult::Allowed);
                 }
                 PermissionResult::Denied(reason) => {
                     debug!(
-                        capability = %entry.key(),
+                        capability = %id,
                         action_type = action.action_type(),
                         reason = %reason,
                         "Permission denied"
                     );
-                    // Keep the first denial reason
-                    if denial.is_none() {
+                    // Keep the denial from the lowest capability ID so the
+                    // reported reason does not depend on iteration order
+                    let replace = denial
+                        .as_ref()
+                        .is_none_or(|d| reason.capability.as_str() < d.capability.as_str());
+                    if replace {
                         denial = Some(reason);
                     }
                 }
@@ -175,7 +522,7 @@ impl CapabilitySet {
 
         // If we have an explicit denial, return it
         if let Some(reason) = denial {
-            return PermissionResult::Denied(reason);
+            return (reason.capability.clone(), PermissionResult::Denied(reason));
         }
 
         // No capability handled this action - deny by default
@@ -184,11 +531,11 @@ impl CapabilitySet {
             "No capability found for action"
         );
 
-        PermissionResult::Denied(DenialReason {
-            capability: CapabilityId::new("none"),
-            action: action.action_type().to_string(),
-            message: "No capability grants this permission".to_string(),
-        })
+        let reason = DenialReason::no_capability(action.action_type());
+        (
+            CapabilityId::NO_CAPABILITY,
+            PermissionResult::Denied(reason),
+        )
     }
 
     /// Require that an action is permitted.
@@ -198,6 +545,50 @@ impl CapabilitySet {
         self.check_permission(action).to_result()
     }
 
+    /// Check a batch of actions, pairing each with its result.
+    ///
+    /// The capabilities are read once for the whole batch, so a capability
+    /// granted or revoked concurrently applies to all actions or none.
+    pub fn check_all<'a>(
+        &self,
+        actions: &[&'a dyn Action],
+    ) -> Vec<(&'a dyn Action, PermissionResult)> {
+        let snapshot = self.snapshot();
+        actions
+            .iter()
+            .map(|&action| {
+                let (_, result) =
+                    self.decide(action, &CheckContext::default(), snapshot.iter().cloned());
+                (action, result)
+            })
+            .collect()
+    }
+
+    /// Require that every action in a batch is permitted.
+    ///
+    /// Actions are checked in order and checking stops at the first denial,
+    /// which is returned together with the denied action.
+    pub fn require_all<'a>(
+        &self,
+        actions: &[&'a dyn Action],
+    ) -> Result<(), (&'a dyn Action, CapabilityError)> {
+        let snapshot = self.snapshot();
+        for &action in actions {
+            let (_, result) =
+                self.decide(action, &CheckContext::default(), snapshot.iter().cloned());
+            result.to_result().map_err(|err| (action, err))?;
+        }
+        Ok(())
+    }
+
+    /// Copy the current capabilities.
+    fn snapshot(&self) -> Vec<(CapabilityId, SharedCapability)> {
+        self.capabilities
+            .iter()
+            .map(|entry| (entry.key().clone(), Arc::clone(entry.value())))
+            .collect()
+    }
+
     /// Validate that all capabilities in the set are compatible.
     pub fn validate(&self) -> CapabilityResult<()> {
         for entry in self.capabilities.iter() {
@@ -206,12 +597,100 @@ impl CapabilitySet {
         Ok(())
     }
 
+    /// Merge this set with another, producing a new set.
+    ///
+    /// Capabilities are shared rather than copied, so merging is cheap.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CapabilityError::AlreadyExists` if both sets contain a
+    /// capability with the same ID. Use [`merge_with`](Self::merge_with) to
+    /// choose which side wins instead.
+    pub fn merge(&self, other: &CapabilitySet) -> CapabilityResult<CapabilitySet> {
+        self.merge_with(other, MergeStrategy::Reject)
+    }
+
+    /// Merge this set with another using the given conflict strategy.
+    ///
+    /// Every capability in the result is attached to the new set, so
+    /// `on_attach` runs once for each capability that is kept.
+    pub fn merge_with(
+        &self,
+        other: &CapabilitySet,
+        strategy: MergeStrategy,
+    ) -> CapabilityResult<CapabilitySet> {
+        // Detect conflicts up front so a rejected merge attaches nothing
+        if strategy == MergeStrategy::Reject {
+            if let Some(entry) = self.capabilities.iter().find(|e| other.has(e.key())) {
+                return Err(CapabilityError::AlreadyExists(entry.key().clone()));
+            }
+        }
+
+        let (primary, secondary) = match strategy {
+            MergeStrategy::PreferOther => (other, self),
+            MergeStrategy::Reject | MergeStrategy::PreferSelf => (self, other),
+        };
+
+        let merged = Self::new();
+        for cap in primary.iter() {
+            merged.grant_shared(cap)?;
+        }
+        for cap in secondary.iter() {
+            if !merged.has(&cap.id()) {
+                merged.grant_shared(cap)?;
+            }
+        }
+
+        debug!(
+            capabilities = merged.len(),
+            strategy = ?strategy,
+            "Merged capability sets"
+        );
+        Ok(merged)
+    }
+
+    /// Compare this set against a baseline.
+    ///
+    /// Capabilities are matched by ID. For capabilities present in both
+    /// sets, the serialized policies are compared to detect configuration
+    /// drift, e.g. a changed filesystem permission.
+    pub fn diff(&self, baseline: &CapabilitySet) -> CapabilityDiff {
+        let mut diff = CapabilityDiff::default();
+
+        for id in self.ids() {
+            if !baseline.has(&id) {
+                diff.added.push(id);
+            }
+        }
+        for cap in baseline.iter() {
+            let id = cap.id();
+            let Some(current) = self.get(&id) else {
+                diff.removed.push(id);
+                continue;
+            };
+
+            let policies = (current.to_policy(), cap.to_policy());
+            if let (Some(current), Some(baseline)) = policies {
+                // Policies have no equality; compare their serialized form
+                if serde_json::to_value(&current).ok() != serde_json::to_value(&baseline).ok() {
+                    diff.drifted.push(id);
+                }
+            }
+        }
+
+        for ids in [&mut diff.added, &mut diff.removed, &mut diff.drifted] {
+            ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        }
+        diff
+    }
+
     /// Clear all capabilities from the set.
     pub fn clear(&self) {
         for entry in self.capabilities.iter() {
             entry.value().on_detach();
         }
         self.capabilities.clear();
+        self.invalidate_decision_cache();
         info!("Capability set cleared");
     }
 
@@ -219,16 +698,216 @@ impl CapabilitySet {
     pub fn iter(&self) -> impl Iterator<Item = SharedCapability> + '_ {
         self.capabilities.iter().map(|r| Arc::clone(r.value()))
     }
+
+    /// Freeze the set so that it can no longer be changed.
+    pub fn freeze(self) -> FrozenCapabilitySet {
+        debug!(capabilities = self.len(), "Capability set frozen");
+        FrozenCapabilitySet {
+            view: CapabilityView {
+                inner: Arc::new(self),
+                frozen: true,
+            },
+        }
+    }
+}
+
+/// Read-only access to a capability set.
+///
+/// Only permission checks and lookups are available; there is no way to
+/// grant, revoke or clear capabilities through a view. A view created with
+/// [`new`](Self::new) still sees changes its owner makes through the
+/// shared set, such as revocations; one converted from a
+/// [`FrozenCapabilitySet`] never changes. Clones share the same set.
+///
+/// ```compile_fail
+/// use std::sync::Arc;
+/// use aegis_capability::builtin::ClockCapability;
+/// use aegis_capability::{CapabilitySet, CapabilityView};
+///
+/// let view = CapabilityView::new(Arc::new(CapabilitySet::new()));
+/// view.grant(ClockCapability::monotonic_only()).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct CapabilityView {
+    inner: Arc<CapabilitySet>,
+    /// Whether the set was frozen, so that nothing can change it.
+    frozen: bool,
+}
+
+impl CapabilityView {
+    /// Create a read-only view of a set that its owner may still change.
+    pub fn new(set: Arc<CapabilitySet>) -> Self {
+        Self {
+            inner: set,
+            frozen: false,
+        }
+    }
+
+    /// Check if the viewed set is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Check if an action is permitted by any capability in the set.
+    ///
+    /// See [`CapabilitySet::check_permission`].
+    pub fn check_permission(&self, action: &dyn Action) -> PermissionResult {
+        self.inner.check_permission(action)
+    }
+
+    /// Check if an action is permitted, also returning the deciding capability.
+    pub fn check_permission_detailed(
+        &self,
+        action: &dyn Action,
+    ) -> (CapabilityId, PermissionResult) {
+        self.inner.check_permission_detailed(action)
+    }
+
+    /// Check if an action requested in `ctx` is permitted.
+    ///
+    /// See [`CapabilitySet::check_permission_with_context`].
+    pub fn check_permission_with_context(
+        &self,
+        action: &dyn Action,
+        ctx: &CheckContext,
+    ) -> PermissionResult {
+        self.inner.check_permission_with_context(action, ctx)
+    }
+
+    /// Check if an action requested in `ctx` is permitted, also returning
+    /// the deciding capability.
+    pub fn check_permission_detailed_with_context(
+        &self,
+        action: &dyn Action,
+        ctx: &CheckContext,
+    ) -> (CapabilityId, PermissionResult) {
+        self.inner
+            .check_permission_detailed_with_context(action, ctx)
+    }
+
+    /// Require that an action is permitted.
+    pub fn require(&self, action: &dyn Action) -> CapabilityResult<()> {
+        self.inner.require(action)
+    }
+
+    /// Ask every capability about an action, for debugging denials.
+    ///
+    /// See [`CapabilitySet::explain`].
+    pub fn explain(&self, action: &dyn Action) -> Vec<(CapabilityId, PermissionResult)> {
+        self.inner.explain(action)
+    }
+
+    /// Format [`explain`](Self::explain) as text, one capability per line.
+    pub fn explain_text(&self, action: &dyn Action) -> String {
+        self.inner.explain_text(action)
+    }
+
+    /// Check if a capability is granted.
+    pub fn has(&self, id: &CapabilityId) -> bool {
+        self.inner.has(id)
+    }
+
+    /// Get a capability by ID.
+    pub fn get(&self, id: &CapabilityId) -> Option<SharedCapability> {
+        self.inner.get(id)
+    }
+
+    /// Iterate over all capabilities.
+    pub fn iter(&self) -> impl Iterator<Item = SharedCapability> + '_ {
+        self.inner.iter()
+    }
+
+    /// Get the number of capabilities in the set.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Check if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Get all capability IDs.
+    pub fn ids(&self) -> Vec<CapabilityId> {
+        self.inner.ids()
+    }
+}
+
+impl Default for CapabilityView {
+    /// A view of an empty frozen set, which denies every action.
+    fn default() -> Self {
+        CapabilitySet::new().freeze().into()
+    }
+}
+
+impl From<Arc<CapabilitySet>> for CapabilityView {
+    fn from(set: Arc<CapabilitySet>) -> Self {
+        Self::new(set)
+    }
+}
+
+impl From<FrozenCapabilitySet> for CapabilityView {
+    fn from(set: FrozenCapabilitySet) -> Self {
+        set.view
+    }
+}
+
+impl std::fmt::Debug for CapabilityView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CapabilityView")
+            .field("capabilities", &self.ids())
+            .field("frozen", &self.frozen)
+            .finish()
+    }
+}
+
+/// A capability set that cannot be changed.
+///
+/// Created by [`CapabilitySet::freeze`]. It dereferences to a
+/// [`CapabilityView`], so only permission checks and lookups are
+/// available; nothing holds a handle through which the set could be
+/// changed. Clones share the same set.
+///
+/// ```compile_fail
+/// use aegis_capability::builtin::ClockCapability;
+/// use aegis_capability::CapabilitySet;
+///
+/// let frozen = CapabilitySet::new().freeze();
+/// frozen.grant(ClockCapability::monotonic_only()).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct FrozenCapabilitySet {
+    view: CapabilityView,
+}
+
+impl std::ops::Deref for FrozenCapabilitySet {
+    type Target = CapabilityView;
+
+    fn deref(&self) -> &CapabilityView {
+        &self.view
+    }
+}
+
+impl std::fmt::Debug for FrozenCapabilitySet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrozenCapabilitySet")
+            .field("capabilities", &self.ids())
+            .finish()
+    }
 }
 
 impl Clone for CapabilitySet {
     fn clone(&self) -> Self {
-        let new_set = Self::new();
+        let mut new_set = Self::new();
         for entry in self.capabilities.iter() {
             new_set
                 .capabilities
                 .insert(entry.key().clone(), Arc::clone(entry.value()));
         }
+        new_set.revoked = self.revoked.clone();
+        if self.has_decision_cache() {
+            new_set.decision_cache = Some(DecisionCache::default());
+        }
         new_set
     }
 }
@@ -237,6 +916,7 @@ impl std::fmt::Debug for CapabilitySet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("CapabilitySet")
             .field("capabilities", &self.ids())
+            .field("revoked", &self.revoked.ids())
             .finish()
     }
 }
@@ -348,6 +1028,40 @@ mod tests {
         assert_eq!(set.len(), 1);
     }
 
+    #[test]
+    fn test_namespaced_ids_coexist() {
+        #[derive(Debug)]
+        struct Cache(&'static str);
+
+        impl Capability for Cache {
+            fn id(&self) -> CapabilityId {
+                CapabilityId::namespaced(self.0, "cache")
+            }
+
+            fn name(&self) -> &str {
+                "Cache"
+            }
+
+            fn description(&self) -> &str {
+                "A library-defined cache capability"
+            }
+
+            fn permits(&self, _action: &dyn Action) -> PermissionResult {
+                PermissionResult::NotApplicable
+            }
+        }
+
+        let set = CapabilitySet::new();
+        set.grant(Cache("acme")).unwrap();
+        set.grant(Cache("initech")).unwrap();
+        assert!(set.grant(Cache("acme")).is_err());
+
+        assert_eq!(set.len(), 2);
+        assert!(set.has(&CapabilityId::namespaced("acme", "cache")));
+        assert!(set.has(&CapabilityId::namespaced("initech", "cache")));
+        assert!(!set.has(&CapabilityId::new("cache")));
+    }
+
     #[test]
     fn test_grant_duplicate() {
         let set = CapabilitySet::new();
@@ -400,6 +1114,11 @@ mod tests {
         };
         let result = set.check_permission(&action);
         assert!(result.is_denied());
+        assert!(result.is_no_capability());
+        assert!(matches!(
+            set.require(&action),
+            Err(CapabilityError::NoCapabilityFound { .. })
+        ));
     }
 
     #[test]
@@ -421,4 +1140,590 @@ mod tests {
         assert_eq!(cloned.len(), 1);
         assert!(cloned.has(&CapabilityId::new("allow_all")));
     }
+
+    #[derive(Debug)]
+    struct NamedCapability {
+        id: &'static str,
+        allow: bool,
+        attached: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl NamedCapability {
+        fn new(id: &'static str, allow: bool) -> Self {
+            Self {
+                id,
+                allow,
+                attached: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl Capability for NamedCapability {
+        fn id(&self) -> CapabilityId {
+            CapabilityId::new(self.id)
+        }
+
+        fn name(&self) -> &str {
+            self.id
+        }
+
+        fn description(&self) -> &str {
+            "Named test capability"
+        }
+
+        fn permits(&self, action: &dyn Action) -> PermissionResult {
+            if self.allow {
+                PermissionResult::Allowed
+            } else {
+                PermissionResult::Denied(DenialReason::new(
+                    self.id(),
+                    action.action_type(),
+                    "Denied",
+                ))
+            }
+        }
+
+        fn on_attach(&self) -> Result<(), CapabilityError> {
+            self.attached
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_merge_disjoint() {
+        let a = CapabilitySetBuilder::new()
+            .with(AllowAllCapability)
+            .build()
+            .unwrap();
+        let b = CapabilitySetBuilder::new()
+            .with(DenyAllCapability)
+            .build()
+            .unwrap();
+
+        let merged = a.merge(&b).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert!(merged.has(&CapabilityId::new("allow_all")));
+        assert!(merged.has(&CapabilityId::new("deny_all")));
+
+        // Sources are untouched
+        assert_eq!(a.len(), 1);
+        assert_eq!(b.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_overlap_rejected() {
+        let a = CapabilitySetBuilder::new()
+            .with(AllowAllCapability)
+            .build()
+            .unwrap();
+        let b = CapabilitySetBuilder::new()
+            .with(AllowAllCapability)
+            .build()
+            .unwrap();
+
+        let result = a.merge(&b);
+        assert!(matches!(result, Err(CapabilityError::AlreadyExists(_))));
+    }
+
+    #[test]
+    fn test_merge_overlap_strategies() {
+        let action = TestAction {
+            action_type: "test".to_string(),
+        };
+
+        let base = CapabilitySet::new();
+        base.grant(NamedCapability::new("shared", true)).unwrap();
+        let overlay = CapabilitySet::new();
+        overlay
+            .grant(NamedCapability::new("shared", false))
+            .unwrap();
+
+        let prefer_self = base
+            .merge_with(&overlay, MergeStrategy::PreferSelf)
+            .unwrap();
+        assert_eq!(prefer_self.len(), 1);
+        assert!(prefer_self.check_permission(&action).is_allowed());
+
+        let prefer_other = base
+            .merge_with(&overlay, MergeStrategy::PreferOther)
+            .unwrap();
+        assert_eq!(prefer_other.len(), 1);
+        assert!(prefer_other.check_permission(&action).is_denied());
+    }
+
+    #[test]
+    fn test_merge_calls_on_attach() {
+        let kept = NamedCapability::new("kept", true);
+        let kept_count = Arc::clone(&kept.attached);
+        let dropped = NamedCapability::new("kept", false);
+        let dropped_count = Arc::clone(&dropped.attached);
+
+        let a = CapabilitySet::new();
+        a.grant(kept).unwrap();
+        let b = CapabilitySet::new();
+        b.grant(dropped).unwrap();
+
+        let _merged = a.merge_with(&b, MergeStrategy::PreferSelf).unwrap();
+
+        // Attached once to its original set and once to the merged set
+        assert_eq!(kept_count.load(std::sync::atomic::Ordering::SeqCst), 2);
+        // The losing capability is never attached to the merged set
+        assert_eq!(dropped_count.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_merge_rejected_attaches_nothing() {
+        let cap = NamedCapability::new("other", true);
+        let count = Arc::clone(&cap.attached);
+
+        let a = CapabilitySet::new();
+        a.grant(cap).unwrap();
+        a.grant(AllowAllCapability).unwrap();
+        let b = CapabilitySetBuilder::new()
+            .with(AllowAllCapability)
+            .build()
+            .unwrap();
+
+        assert!(a.merge(&b).is_err());
+        assert_eq!(count.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_denial_reason_is_deterministic() {
+        let action = TestAction {
+            action_type: "test:action".to_string(),
+        };
+        let mut reasons = Vec::new();
+
+        for _ in 0..8 {
+            let set = CapabilitySetBuilder::new()
+                .with(NamedCapability::new("zeta", false))
+                .with(NamedCapability::new("alpha", false))
+                .with(NamedCapability::new("mid", false))
+                .build()
+                .unwrap();
+
+            match set.check_permission(&action) {
+                PermissionResult::Denied(reason) => reasons.push(reason.to_string()),
+                other => panic!("Expected denial, got {:?}", other),
+            }
+        }
+
+        assert!(reasons.iter().all(|r| r == &reasons[0]));
+        assert!(reasons[0].starts_with("[alpha]"));
+    }
+
+    /// Allows `read` actions, denies `write` actions, and counts checks.
+    #[derive(Debug, Default)]
+    struct ReadOnlyCapability {
+        checks: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Capability for ReadOnlyCapability {
+        fn id(&self) -> CapabilityId {
+            CapabilityId::new("read_only")
+        }
+
+        fn name(&self) -> &str {
+            "Read Only"
+        }
+
+        fn description(&self) -> &str {
+            "Allows reads and denies writes"
+        }
+
+        fn permits(&self, action: &dyn Action) -> PermissionResult {
+            self.checks
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            match action.action_type() {
+                "read" => PermissionResult::Allowed,
+                "write" => PermissionResult::Denied(DenialReason {
+                    capability: self.id(),
+                    action: "write".to_string(),
+                    message: "read only".to_string(),
+                }),
+                _ => PermissionResult::NotApplicable,
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_all_and_require_all() {
+        let capability = Arc::new(ReadOnlyCapability::default());
+        let set = CapabilitySet::new();
+        set.grant_shared(capability.clone()).unwrap();
+
+        let read = TestAction {
+            action_type: "read".to_string(),
+        };
+        let write = TestAction {
+            action_type: "write".to_string(),
+        };
+        let exec = TestAction {
+            action_type: "exec".to_string(),
+        };
+        let actions: Vec<&dyn Action> = vec![&read, &write, &exec, &read];
+
+        let results = set.check_all(&actions);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].0.action_type(), "read");
+        assert!(results[0].1.is_allowed());
+        assert!(results[1].1.is_denied());
+        assert!(results[2].1.is_denied());
+        assert!(results[3].1.is_allowed());
+
+        assert!(set.require_all(&[&read, &read]).is_ok());
+
+        let checks = || capability.checks.load(std::sync::atomic::Ordering::Relaxed);
+        let before = checks();
+        let (failed, err) = set.require_all(&actions).unwrap_err();
+        assert_eq!(failed.action_type(), "write");
+        assert!(matches!(err, CapabilityError::PermissionDenied { .. }));
+        // Stops after the denied write without checking the rest
+        assert_eq!(checks() - before, 2);
+    }
+
+    #[test]
+    fn test_frozen_set_checks() {
+        let set = CapabilitySet::new();
+        set.grant(DenyAllCapability).unwrap();
+        set.grant(AllowAllCapability).unwrap();
+
+        let frozen = set.freeze();
+        let action = TestAction {
+            action_type: "test".to_string(),
+        };
+        assert_eq!(frozen.len(), 2);
+        assert!(frozen.has(&CapabilityId::new("deny_all")));
+        assert!(frozen.check_permission(&action).is_allowed());
+        assert!(frozen.require(&action).is_ok());
+
+        let (id, _) = frozen.clone().check_permission_detailed(&action);
+        assert_eq!(id.as_str(), "allow_all");
+        assert!(
+            !CapabilitySet::new()
+                .freeze()
+                .check_permission(&action)
+                .is_allowed()
+        );
+    }
+
+    #[test]
+    fn test_revocation_list() {
+        let set = CapabilitySet::new();
+        let id = CapabilityId::new("allow_all");
+        set.grant(AllowAllCapability).unwrap();
+        assert!(!set.is_revoked(&id));
+
+        set.revoke(&id);
+        assert!(set.is_revoked(&id));
+        assert_eq!(set.revocations().ids(), vec![id.clone()]);
+
+        set.grant(AllowAllCapability).unwrap();
+        assert!(!set.is_revoked(&id));
+        assert!(set.revocations().is_empty());
+    }
+
+    #[test]
+    fn test_revoke_while_checking() {
+        use std::sync::Barrier;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let set = Arc::new(CapabilitySet::new());
+        set.grant(AllowAllCapability).unwrap();
+        let id = CapabilityId::new("allow_all");
+
+        let revoked = Arc::new(AtomicBool::new(false));
+        let barrier = Arc::new(Barrier::new(5));
+
+        let checkers: Vec<_> = (0..4)
+            .map(|_| {
+                let set = Arc::clone(&set);
+                let revoked = Arc::clone(&revoked);
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    let action = TestAction {
+                        action_type: "test".to_string(),
+                    };
+                    barrier.wait();
+                    for _ in 0..10_000 {
+                        // Read the flag first: if revocation had completed,
+                        // this check must not be allowed
+                        let was_revoked = revoked.load(Ordering::SeqCst);
+                        let result = set.check_permission(&action);
+                        assert!(!(was_revoked && result.is_allowed()));
+                    }
+                })
+            })
+            .collect();
+
+        barrier.wait();
+        std::thread::yield_now();
+        set.revoke(&id);
+        revoked.store(true, Ordering::SeqCst);
+
+        for checker in checkers {
+            checker.join().unwrap();
+        }
+        assert!(set.is_revoked(&id));
+        assert!(
+            set.check_permission(&TestAction {
+                action_type: "test".to_string(),
+            })
+            .is_denied()
+        );
+    }
+
+    #[test]
+    fn test_decision_cache() {
+        use std::sync::atomic::AtomicUsize;
+
+        /// Counts how often it is asked for a decision.
+        #[derive(Debug)]
+        struct Counting {
+            id: &'static str,
+            cacheable: bool,
+            calls: Arc<AtomicUsize>,
+        }
+
+        impl Capability for Counting {
+            fn id(&self) -> CapabilityId {
+                CapabilityId::new(self.id)
+            }
+
+            fn name(&self) -> &str {
+                "Counting"
+            }
+
+            fn description(&self) -> &str {
+                "Allows everything and counts checks"
+            }
+
+            fn permits(&self, _action: &dyn Action) -> PermissionResult {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                PermissionResult::Allowed
+            }
+
+            fn is_decision_cacheable(&self) -> bool {
+                self.cacheable
+            }
+        }
+
+        let cached_calls = Arc::new(AtomicUsize::new(0));
+        let set = CapabilitySet::new().with_decision_cache();
+        set.grant(Counting {
+            id: "cached",
+            cacheable: true,
+            calls: Arc::clone(&cached_calls),
+        })
+        .unwrap();
+
+        let action = TestAction {
+            action_type: "test:action".to_string(),
+        };
+        for _ in 0..3 {
+            assert!(set.check_permission(&action).is_allowed());
+        }
+        assert_eq!(cached_calls.load(Ordering::SeqCst), 1);
+
+        // Granting and revoking invalidate the cache
+        set.grant(DenyAllCapability).unwrap();
+        assert!(set.check_permission(&action).is_allowed());
+        assert!(set.check_permission(&action).is_allowed());
+        assert_eq!(cached_calls.load(Ordering::SeqCst), 2);
+
+        set.revoke(&CapabilityId::new("deny_all"));
+        assert!(set.check_permission(&action).is_allowed());
+        assert_eq!(cached_calls.load(Ordering::SeqCst), 3);
+
+        // A revoked capability's decision is not served from the cache
+        set.revoke(&CapabilityId::new("cached"));
+        assert!(set.check_permission(&action).is_denied());
+
+        // Capabilities that do not opt in are asked on every check
+        let uncached_calls = Arc::new(AtomicUsize::new(0));
+        set.grant(Counting {
+            id: "uncached",
+            cacheable: false,
+            calls: Arc::clone(&uncached_calls),
+        })
+        .unwrap();
+        for _ in 0..3 {
+            assert!(set.check_permission(&action).is_allowed());
+        }
+        assert_eq!(uncached_calls.load(Ordering::SeqCst), 3);
+
+        // Without a cache every check asks the capability
+        let plain = CapabilitySet::new();
+        plain
+            .grant(Counting {
+                id: "cached",
+                cacheable: true,
+                calls: Arc::clone(&cached_calls),
+            })
+            .unwrap();
+        plain.check_permission(&action);
+        plain.check_permission(&action);
+        assert_eq!(cached_calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_explain() {
+        #[derive(Debug)]
+        struct Unrelated;
+
+        impl Capability for Unrelated {
+            fn id(&self) -> CapabilityId {
+                CapabilityId::new("unrelated")
+            }
+
+            fn name(&self) -> &str {
+                "Unrelated"
+            }
+
+            fn description(&self) -> &str {
+                "Handles no actions"
+            }
+
+            fn permits(&self, _action: &dyn Action) -> PermissionResult {
+                PermissionResult::NotApplicable
+            }
+        }
+
+        let set = CapabilitySetBuilder::new()
+            .with(AllowAllCapability)
+            .with(DenyAllCapability)
+            .with(Unrelated)
+            .build()
+            .unwrap();
+        let action = TestAction {
+            action_type: "test:action".to_string(),
+        };
+
+        let verdicts = set.explain(&action);
+        let ids: Vec<_> = verdicts.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["allow_all", "deny_all", "unrelated"]);
+        assert!(verdicts[0].1.is_allowed());
+        assert!(verdicts[1].1.is_denied());
+        assert_eq!(verdicts[2].1, PermissionResult::NotApplicable);
+
+        let text = set.explain_text(&action);
+        assert!(text.contains("allow_all: allowed"));
+        assert!(text.contains("deny_all: denied: All actions denied"));
+        assert!(text.contains("unrelated: not applicable"));
+        assert!(text.contains("Decision: allowed"));
+
+        // The regular check is unaffected
+        assert!(set.check_permission(&action).is_allowed());
+    }
+
+    #[test]
+    fn test_diff_against_baseline() {
+        use crate::builtin::{ClockCapability, FilesystemCapability, LoggingCapability};
+        use crate::capability::standard_ids;
+
+        let baseline = CapabilitySetBuilder::new()
+            .with(FilesystemCapability::read_only(&["/data"]))
+            .with(LoggingCapability::production())
+            .build()
+            .unwrap();
+        assert!(baseline.diff(&baseline).is_empty());
+
+        let granted = CapabilitySetBuilder::new()
+            .with(FilesystemCapability::read_write(&["/data"]))
+            .with(ClockCapability::monotonic_only())
+            .build()
+            .unwrap();
+
+        let diff = granted.diff(&baseline);
+        assert_eq!(diff.added, [standard_ids::CLOCK]);
+        assert_eq!(diff.removed, [standard_ids::LOGGING]);
+        assert_eq!(diff.drifted, [standard_ids::FILESYSTEM]);
+        assert!(diff.has_drift());
+
+        let reverse = baseline.diff(&granted);
+        assert_eq!(reverse.added, diff.removed);
+        assert_eq!(reverse.removed, diff.added);
+    }
+
+    #[derive(Debug)]
+    struct TenantCapability {
+        tenant: &'static str,
+    }
+
+    impl Capability for TenantCapability {
+        fn id(&self) -> CapabilityId {
+            CapabilityId::new("tenant")
+        }
+
+        fn name(&self) -> &str {
+            "Tenant"
+        }
+
+        fn description(&self) -> &str {
+            "Allows actions requested on behalf of one tenant"
+        }
+
+        fn permits(&self, action: &dyn Action) -> PermissionResult {
+            self.permits_with_context(action, &CheckContext::default())
+        }
+
+        fn permits_with_context(
+            &self,
+            action: &dyn Action,
+            ctx: &CheckContext,
+        ) -> PermissionResult {
+            if ctx.principal.as_deref() == Some(self.tenant) {
+                return PermissionResult::Allowed;
+            }
+            PermissionResult::Denied(DenialReason::new(
+                self.id(),
+                action.action_type(),
+                "Wrong tenant",
+            ))
+        }
+    }
+
+    #[test]
+    fn test_check_permission_with_context() {
+        let set = CapabilitySet::new();
+        set.grant(TenantCapability { tenant: "acme" }).unwrap();
+
+        let action = TestAction {
+            action_type: "test".to_string(),
+        };
+        let acme = CheckContext::new().with_principal("acme");
+        let globex = CheckContext::new().with_principal("globex");
+
+        assert!(
+            set.check_permission_with_context(&action, &acme)
+                .is_allowed()
+        );
+        assert!(
+            set.check_permission_with_context(&action, &globex)
+                .is_denied()
+        );
+        // Checks without a context name no principal
+        assert!(set.check_permission(&action).is_denied());
+
+        // Combinators pass the context through to their children
+        let wrapped = CapabilitySet::new();
+        wrapped
+            .grant(crate::AllOf(vec![Box::new(TenantCapability {
+                tenant: "acme",
+            })]))
+            .unwrap();
+        assert!(
+            wrapped
+                .check_permission_with_context(&action, &acme)
+                .is_allowed()
+        );
+        assert!(
+            wrapped
+                .freeze()
+                .check_permission_with_context(&action, &globex)
+                .is_denied()
+        );
+    }
 }
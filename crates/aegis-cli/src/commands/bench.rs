@@ -0,0 +1,159 @@
+//! Bench command - Time repeated executions of a function.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::Serialize;
+
+use aegis_observe::{Distribution, IterationSample, MetricsCollector};
+use aegis_wasm::prelude::*;
+
+use crate::OutputFormat;
+use crate::commands::run::{prepare_args, select_function};
+
+/// Arguments for the bench command.
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Path to the WebAssembly module
+    #[arg(required = true)]
+    pub module: PathBuf,
+
+    /// Function to execute (default: _start or main)
+    #[arg(short = 'e', long)]
+    pub function: Option<String>,
+
+    /// Arguments to pass to the function
+    #[arg(last = true)]
+    pub args: Vec<String>,
+
+    /// Number of measured iterations
+    #[arg(short = 'n', long, default_value = "100")]
+    pub iterations: usize,
+
+    /// Iterations run before measuring, excluded from the statistics
+    #[arg(long, default_value = "5")]
+    pub warmup: usize,
+
+    /// Memory limit in bytes (default: 64MB)
+    #[arg(long, default_value = "67108864")]
+    pub memory_limit: usize,
+
+    /// Fuel limit per iteration (default: 1B)
+    #[arg(long, default_value = "1000000000")]
+    pub fuel_limit: u64,
+
+    /// Timeout per iteration in seconds (default: 30)
+    #[arg(long, default_value = "30")]
+    pub timeout: u64,
+}
+
+/// Benchmark result.
+#[derive(Debug, Serialize)]
+struct BenchResult {
+    path: String,
+    function: String,
+    iterations: usize,
+    warmup: usize,
+    wall_time_ns: Option<Distribution>,
+    fuel: Option<Distribution>,
+    samples: Vec<IterationSample>,
+}
+
+/// Execute the bench command.
+pub fn execute(args: BenchArgs, format: OutputFormat) -> Result<()> {
+    let runtime = Aegis::builder()
+        .with_memory_limit(args.memory_limit)
+        .with_fuel_limit(args.fuel_limit)
+        .with_timeout(Duration::from_secs(args.timeout))
+        .build()
+        .context("Failed to create runtime")?;
+
+    let module = runtime
+        .load_file(&args.module)
+        .context("Failed to load module")?;
+    let function = select_function(args.function.as_deref(), &module);
+
+    let mut sandbox = runtime
+        .sandbox()
+        .build()
+        .context("Failed to create sandbox")?;
+    sandbox
+        .load_module(&module)
+        .context("Failed to load module into sandbox")?;
+
+    let wasm_args = prepare_args(sandbox.get_func_type(function), function, &args.args)?;
+
+    // Each iteration gets a fresh instance with the full fuel budget
+    let collector = MetricsCollector::new();
+    for iteration in 0..args.warmup + args.iterations {
+        sandbox.reset();
+        sandbox
+            .load_module(&module)
+            .context("Failed to reload module")?;
+
+        let start = Instant::now();
+        sandbox
+            .call_dynamic(function, wasm_args.clone())
+            .with_context(|| format!("Iteration {} failed", iteration))?;
+        let wall_time = start.elapsed();
+
+        if iteration >= args.warmup {
+            collector.record_iteration(wall_time, sandbox.metrics().fuel_consumed);
+        }
+    }
+
+    let iterations = collector.snapshot().iterations;
+    let result = BenchResult {
+        path: args.module.display().to_string(),
+        function: function.to_string(),
+        iterations: args.iterations,
+        warmup: args.warmup,
+        wall_time_ns: iterations.wall_time(),
+        fuel: iterations.fuel(),
+        samples: iterations.samples,
+    };
+
+    match format {
+        OutputFormat::Human => print_human(&result),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&result)?),
+        OutputFormat::JsonCompact | OutputFormat::JsonLines => {
+            println!("{}", serde_json::to_string(&result)?)
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the benchmark summary in human-readable form.
+fn print_human(result: &BenchResult) {
+    println!(
+        "Benchmark: {} ({} iterations, {} warmup)",
+        result.function, result.iterations, result.warmup
+    );
+
+    let (Some(wall_time), Some(fuel)) = (&result.wall_time_ns, &result.fuel) else {
+        println!("  No iterations measured");
+        return;
+    };
+
+    let time = |nanos: u64| format!("{:?}", Duration::from_nanos(nanos));
+    println!(
+        "\n  {:<10} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "", "min", "mean", "median", "p95", "max"
+    );
+    println!(
+        "  {:<10} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "Wall time",
+        time(wall_time.min),
+        time(wall_time.mean as u64),
+        time(wall_time.median),
+        time(wall_time.p95),
+        time(wall_time.max),
+    );
+    println!(
+        "  {:<10} {:>12} {:>12.0} {:>12} {:>12} {:>12}",
+        "Fuel", fuel.min, fuel.mean, fuel.median, fuel.p95, fuel.max,
+    );
+}
@@ -33,6 +33,14 @@ pub struct InspectArgs {
     /// Show all information
     #[arg(long, short)]
     pub all: bool,
+
+    /// Print the module as WAT text
+    #[arg(long)]
+    pub wat: bool,
+
+    /// Print a single exported function as WAT text
+    #[arg(long, value_name = "FUNCTION", conflicts_with = "wat")]
+    pub disasm: Option<String>,
 }
 
 /// Inspection result.
@@ -46,6 +54,8 @@ struct InspectionResult {
     imports: Option<Vec<ImportDisplay>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     memories: Option<Vec<MemoryDisplay>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wat: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -123,7 +133,8 @@ pub fn execute(args: InspectArgs, format: OutputFormat) -> Result<()> {
         .load_file(&args.module)
         .context("Failed to load module")?;
 
-    let show_all = args.all || (!args.exports && !args.imports && !args.memory);
+    let disassemble = args.wat || args.disasm.is_some();
+    let show_all = args.all || (!args.exports && !args.imports && !args.memory && !disassemble);
 
     let mut result = InspectionResult {
         path: args.module.display().to_string(),
@@ -131,6 +142,7 @@ pub fn execute(args: InspectArgs, format: OutputFormat) -> Result<()> {
         exports: None,
         imports: None,
         memories: None,
+        wat: None,
     };
 
     if show_all || args.exports {
@@ -156,6 +168,16 @@ pub fn execute(args: InspectArgs, format: OutputFormat) -> Result<()> {
         );
     }
 
+    if let Some(function) = &args.disasm {
+        result.wat = Some(
+            module
+                .function_to_wat(function)
+                .context("Failed to disassemble function")?,
+        );
+    } else if args.wat {
+        result.wat = Some(module.to_wat().context("Failed to disassemble module")?);
+    }
+
     // Output results
     match format {
         OutputFormat::Human => {
@@ -203,11 +225,18 @@ pub fn execute(args: InspectArgs, format: OutputFormat) -> Result<()> {
                     println!("  [{}] {} - {} pages ({})", i, memory.min_pages, max, bits);
                 }
             }
+
+            if let Some(wat) = &result.wat {
+                if result.memories.is_some() {
+                    println!();
+                }
+                println!("{}", wat.trim_end());
+            }
         }
         OutputFormat::Json => {
             println!("{}", serde_json::to_string_pretty(&result)?);
         }
-        OutputFormat::JsonCompact => {
+        OutputFormat::JsonCompact | OutputFormat::JsonLines => {
             println!("{}", serde_json::to_string(&result)?);
         }
     }
@@ -1,5 +1,27 @@
 //! CLI command implementations.
 
+pub mod bench;
 pub mod inspect;
 pub mod run;
+pub mod schema;
 pub mod validate;
+
+use std::path::Path;
+
+use aegis_core::ModuleError;
+use aegis_wasm::AegisError;
+
+/// Describe a module load failure.
+///
+/// WAT parse errors are rendered as `path:line:column: message` so editors
+/// and terminals can jump to the offending line.
+fn describe_load_error(path: &Path, err: &AegisError) -> String {
+    match err {
+        AegisError::Module(ModuleError::WatParse {
+            message,
+            line,
+            column,
+        }) => format!("{}:{}:{}: {}", path.display(), line, column, message),
+        err => err.to_string(),
+    }
+}
@@ -1,18 +1,41 @@
 //! Run command - Execute a WebAssembly module.
 
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Context, Result};
 use clap::Args;
-
-use aegis_observe::{ExecutionOutcome, ExecutionReport, ModuleInfo};
+use serde_json::json;
+use thiserror::Error;
+use wasmtime::{FuncType, Val, ValType};
+
+use aegis_core::ExecutionError;
+use aegis_host::{EntropySources, HostError, OutputCapture};
+use aegis_observe::{
+    ExecutionOutcome, ExecutionReport, JsonLinesSubscriber, MetricsCollector, ModuleInfo,
+    ResourceType, SandboxEvent, TrapInfo,
+};
 use aegis_wasm::prelude::*;
 
 use crate::OutputFormat;
+use crate::commands::describe_load_error;
+
+/// Exit codes, shown at the end of `aegis run --help`.
+const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  Success
+  1  Other error (bad arguments, setup failure)
+  2  The guest trapped
+  3  Execution timed out
+  4  A resource limit (fuel, memory) was exhausted
+  5  A capability was denied
+  6  The module failed to load or instantiate";
 
 /// Arguments for the run command.
 #[derive(Args)]
+#[command(after_help = EXIT_CODES_HELP)]
 pub struct RunArgs {
     /// Path to the WebAssembly module
     #[arg(required = true)]
@@ -57,31 +80,324 @@ pub struct RunArgs {
     /// Show execution metrics
     #[arg(long)]
     pub metrics: bool,
+
+    /// Capture guest stdout/stderr written via WASI fd_write
+    /// (default: on for JSON output)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    pub capture_output: Option<bool>,
+
+    /// Run reproducibly: simulated clock, seeded randomness
+    ///
+    /// The guest sees a clock that starts at 2024-01-01 and advances 1ms per
+    /// read, and random bytes from a stream seeded with --seed. Execution is
+    /// bounded by --fuel-limit, which does not depend on the host.
+    ///
+    /// Remaining sources of nondeterminism: NaN bit patterns produced by
+    /// float instructions, hitting the wall-clock --timeout, and the
+    /// execution ID and durations in the report.
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Seed for the random source in deterministic mode (default: 0)
+    #[arg(long, requires = "deterministic")]
+    pub seed: Option<u64>,
+
+    /// List the host functions available to the module and the capabilities
+    /// they require, then exit without running it
+    #[arg(long)]
+    pub list_host_funcs: bool,
 }
 
-/// Parse a CLI argument into a WASM value based on expected type.
-fn parse_wasm_arg(arg: &str, expected_type: wasmtime::ValType) -> Result<wasmtime::Val> {
-    match expected_type {
-        wasmtime::ValType::I32 => {
-            let val: i32 = arg.parse().context("Expected i32 value")?;
-            Ok(wasmtime::Val::I32(val))
+/// Errors from turning command-line arguments into a function call.
+#[derive(Debug, Error)]
+pub enum RunError {
+    /// The number of arguments does not match the function's parameters.
+    #[error("Function expects {expected} arguments, got {got}")]
+    ArityMismatch {
+        /// Number of parameters the function takes.
+        expected: usize,
+        /// Number of arguments given.
+        got: usize,
+    },
+
+    /// An argument could not be parsed as its parameter type.
+    #[error("Argument {index} ('{value}') is not a valid {expected_type}")]
+    ParseArg {
+        /// Zero-based position of the argument.
+        index: usize,
+        /// The parameter type.
+        expected_type: ValType,
+        /// The argument as given.
+        value: String,
+    },
+
+    /// The function takes a parameter type the CLI cannot parse.
+    #[error("Unsupported parameter type: {0}")]
+    UnsupportedParamType(ValType),
+
+    /// The module does not export the function.
+    #[error("Function '{0}' not found")]
+    FunctionNotFound(String),
+
+    /// The module could not be loaded or instantiated.
+    #[error("Failed to load module: {0}")]
+    ModuleLoad(String),
+}
+
+/// How a run ended, reported as the process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    /// The function returned normally.
+    Success,
+    /// Any failure not covered by another status.
+    Failed,
+    /// The guest trapped.
+    Trapped,
+    /// Execution timed out.
+    Timeout,
+    /// A resource limit was exhausted.
+    ResourceExhausted,
+    /// A capability was denied.
+    CapabilityDenied,
+    /// The module failed to load or instantiate.
+    ModuleLoadFailed,
+}
+
+impl RunStatus {
+    /// Get the exit code for this status.
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Success => 0,
+            Self::Failed => 1,
+            Self::Trapped => 2,
+            Self::Timeout => 3,
+            Self::ResourceExhausted => 4,
+            Self::CapabilityDenied => 5,
+            Self::ModuleLoadFailed => 6,
         }
-        wasmtime::ValType::I64 => {
-            let val: i64 = arg.parse().context("Expected i64 value")?;
-            Ok(wasmtime::Val::I64(val))
+    }
+
+    /// Get the status for an execution outcome.
+    pub fn from_outcome(outcome: &ExecutionOutcome) -> Self {
+        match outcome {
+            ExecutionOutcome::Success { .. } => Self::Success,
+            ExecutionOutcome::Trapped { .. } => Self::Trapped,
+            ExecutionOutcome::Timeout { .. } => Self::Timeout,
+            ExecutionOutcome::ResourceExhausted { .. } => Self::ResourceExhausted,
+            ExecutionOutcome::CapabilityDenied { .. } => Self::CapabilityDenied,
+            ExecutionOutcome::Error { .. } => Self::Failed,
+        }
+    }
+}
+
+impl From<RunStatus> for ExitCode {
+    fn from(status: RunStatus) -> Self {
+        ExitCode::from(status.code())
+    }
+}
+
+impl RunError {
+    /// Get a machine-readable code for the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ArityMismatch { .. } => "arity_mismatch",
+            Self::ParseArg { .. } => "parse_arg",
+            Self::UnsupportedParamType(_) => "unsupported_param_type",
+            Self::FunctionNotFound(_) => "function_not_found",
+            Self::ModuleLoad(_) => "module_load_failed",
         }
-        wasmtime::ValType::F32 => {
-            let val: f32 = arg.parse().context("Expected f32 value")?;
-            Ok(wasmtime::Val::F32(val.to_bits()))
+    }
+
+    /// Get the run status for the error.
+    pub fn status(&self) -> RunStatus {
+        match self {
+            Self::ModuleLoad(_) => RunStatus::ModuleLoadFailed,
+            _ => RunStatus::Failed,
         }
-        wasmtime::ValType::F64 => {
-            let val: f64 = arg.parse().context("Expected f64 value")?;
-            Ok(wasmtime::Val::F64(val.to_bits()))
+    }
+
+    /// Convert to a JSON object of the form `{"error": {"code": ..., ...}}`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut error = match self {
+            Self::ArityMismatch { expected, got } => json!({
+                "expected": expected,
+                "got": got,
+            }),
+            Self::ParseArg {
+                index,
+                expected_type,
+                value,
+            } => json!({
+                "index": index,
+                "expected_type": expected_type.to_string(),
+                "value": value,
+            }),
+            Self::UnsupportedParamType(ty) => json!({ "param_type": ty.to_string() }),
+            Self::FunctionNotFound(name) => json!({ "function": name }),
+            Self::ModuleLoad(reason) => json!({ "reason": reason }),
+        };
+        error["code"] = self.code().into();
+        error["message"] = self.to_string().into();
+
+        json!({ "error": error })
+    }
+}
+
+/// Parse a CLI argument into a WASM value based on expected type.
+fn parse_wasm_arg(index: usize, arg: &str, expected_type: &ValType) -> Result<Val, RunError> {
+    let invalid = || RunError::ParseArg {
+        index,
+        expected_type: expected_type.clone(),
+        value: arg.to_string(),
+    };
+
+    match expected_type {
+        ValType::I32 => arg.parse().map(Val::I32).map_err(|_| invalid()),
+        ValType::I64 => arg.parse().map(Val::I64).map_err(|_| invalid()),
+        ValType::F32 => arg
+            .parse::<f32>()
+            .map(|v| Val::F32(v.to_bits()))
+            .map_err(|_| invalid()),
+        ValType::F64 => arg
+            .parse::<f64>()
+            .map(|v| Val::F64(v.to_bits()))
+            .map_err(|_| invalid()),
+        other => Err(RunError::UnsupportedParamType(other.clone())),
+    }
+}
+
+/// Pick the function to call: the requested one, else `_start`, `main`, or
+/// the first export.
+pub(crate) fn select_function<'a>(
+    requested: Option<&'a str>,
+    module: &'a ValidatedModule,
+) -> &'a str {
+    requested.unwrap_or_else(|| {
+        if module.has_export("_start") {
+            "_start"
+        } else if module.has_export("main") {
+            "main"
+        } else {
+            module
+                .exports()
+                .first()
+                .map(|e| e.name.as_str())
+                .unwrap_or("_start")
         }
-        _ => anyhow::bail!("Unsupported parameter type: {:?}", expected_type),
+    })
+}
+
+/// Check the arguments against a function's signature and parse them.
+///
+/// `func_type` is `None` if the module does not export `function`.
+pub(crate) fn prepare_args(
+    func_type: Option<FuncType>,
+    function: &str,
+    args: &[String],
+) -> Result<Vec<Val>, RunError> {
+    let func_type = func_type.ok_or_else(|| RunError::FunctionNotFound(function.to_string()))?;
+    let param_types: Vec<_> = func_type.params().collect();
+
+    if args.len() != param_types.len() {
+        return Err(RunError::ArityMismatch {
+            expected: param_types.len(),
+            got: args.len(),
+        });
+    }
+
+    args.iter()
+        .zip(&param_types)
+        .enumerate()
+        .map(|(index, (arg, ty))| parse_wasm_arg(index, arg, ty))
+        .collect()
+}
+
+/// Get the `HostError` a call failed with, whether reported through
+/// `HostFunctionError` or returned directly with `?`.
+fn host_error(error: &ExecutionError) -> Option<&HostError> {
+    match error {
+        ExecutionError::Wasmtime(err) => err.downcast_ref(),
+        _ => error.host_error(),
+    }
+}
+
+/// Classify a failed call as an execution outcome.
+fn outcome_from_error(error: &ExecutionError, elapsed: Duration) -> ExecutionOutcome {
+    match error {
+        ExecutionError::Trap(trap) => ExecutionOutcome::Trapped {
+            trap: TrapInfo {
+                code: trap.code.clone(),
+                message: trap.message.clone(),
+                backtrace: trap.backtrace.clone(),
+                frames: trap.frames.clone(),
+            },
+        },
+        ExecutionError::Timeout(limit) => ExecutionOutcome::Timeout {
+            elapsed,
+            limit: *limit,
+        },
+        ExecutionError::OutOfFuel { consumed, limit } => ExecutionOutcome::ResourceExhausted {
+            resource: ResourceType::Fuel,
+            used: *consumed,
+            limit: *limit,
+        },
+        ExecutionError::StackOverflow { limit } => ExecutionOutcome::ResourceExhausted {
+            resource: ResourceType::Stack,
+            used: *limit as u64,
+            limit: *limit as u64,
+        },
+        ExecutionError::MemoryExceeded { used, limit } => ExecutionOutcome::ResourceExhausted {
+            resource: ResourceType::Memory,
+            used: *used as u64,
+            limit: *limit as u64,
+        },
+        ExecutionError::Host(_) | ExecutionError::Wasmtime(_) => match host_error(error) {
+            Some(HostError::CapabilityNotGranted(capability)) => {
+                ExecutionOutcome::CapabilityDenied {
+                    capability: capability.clone(),
+                    action: String::new(),
+                }
+            }
+            Some(HostError::PermissionDenied {
+                capability, action, ..
+            }) => ExecutionOutcome::CapabilityDenied {
+                capability: capability.clone(),
+                action: action.clone(),
+            },
+            Some(HostError::NoCapabilityForAction { action }) => {
+                ExecutionOutcome::CapabilityDenied {
+                    capability: CapabilityId::NO_CAPABILITY,
+                    action: action.clone(),
+                }
+            }
+            _ => ExecutionOutcome::Error {
+                message: error.to_string(),
+            },
+        },
+        _ => ExecutionOutcome::Error {
+            message: error.to_string(),
+        },
     }
 }
 
+/// Print a run error as JSON for the machine-readable formats.
+///
+/// Human output is left to the caller, which prints the message.
+fn print_run_error(error: &RunError, format: OutputFormat) -> Result<()> {
+    let value = error.to_json();
+    let json = match format {
+        OutputFormat::Human => return Ok(()),
+        OutputFormat::Json => serde_json::to_string_pretty(&value)?,
+        OutputFormat::JsonCompact => serde_json::to_string(&value)?,
+        OutputFormat::JsonLines => serde_json::to_string(&json!({
+            "type": "error",
+            "error": value["error"],
+        }))?,
+    };
+    println!("{}", json);
+    Ok(())
+}
+
 /// Format a WASM value for display.
 fn format_wasm_val(val: &wasmtime::Val) -> String {
     match val {
@@ -96,8 +412,44 @@ fn format_wasm_val(val: &wasmtime::Val) -> String {
     }
 }
 
+/// Convert a WASM value to a JSON object tagged with its type.
+///
+/// Values that JSON numbers cannot carry exactly are preserved as strings:
+/// `i64` as a decimal string, and floats alongside their raw bits in hex.
+/// Non-finite floats have a `null` value.
+fn wasm_val_to_json(val: &Val) -> serde_json::Value {
+    match val {
+        Val::I32(v) => json!({ "type": "i32", "value": v }),
+        Val::I64(v) => json!({ "type": "i64", "value": v.to_string() }),
+        Val::F32(bits) => {
+            // Go through the shortest decimal form so 0.1f32 stays 0.1
+            let value = f32::from_bits(*bits).to_string().parse::<f64>().ok();
+            json!({
+                "type": "f32",
+                "value": value.and_then(serde_json::Number::from_f64),
+                "bits": format!("{:#010x}", bits),
+            })
+        }
+        Val::F64(bits) => json!({
+            "type": "f64",
+            "value": serde_json::Number::from_f64(f64::from_bits(*bits)),
+            "bits": format!("{:#018x}", bits),
+        }),
+        Val::V128(v) => json!({
+            "type": "v128",
+            "value": format!("{:#034x}", v.as_u128()),
+        }),
+        Val::FuncRef(_) => json!({ "type": "funcref", "value": format_wasm_val(val) }),
+        Val::ExternRef(_) => json!({ "type": "externref", "value": format_wasm_val(val) }),
+        Val::AnyRef(_) => json!({ "type": "anyref", "value": format_wasm_val(val) }),
+    }
+}
+
 /// Execute the run command.
-pub fn execute(args: RunArgs, format: OutputFormat, quiet: bool) -> Result<()> {
+///
+/// Failures before the call are returned as errors. Once the function has
+/// been called, its outcome is reported and returned as a [`RunStatus`].
+pub fn execute(args: RunArgs, format: OutputFormat, quiet: bool) -> Result<RunStatus> {
     // Build the runtime
     let mut builder = Aegis::builder()
         .with_memory_limit(args.memory_limit)
@@ -117,33 +469,45 @@ pub fn execute(args: RunArgs, format: OutputFormat, quiet: bool) -> Result<()> {
         builder = builder.with_logging(LoggingCapability::production());
     }
 
-    if args.allow_clock {
+    // In deterministic mode the guest's clock and randomness come from
+    // simulated sources rather than the host
+    let entropy = args
+        .deterministic
+        .then(|| EntropySources::deterministic(args.seed.unwrap_or_default()));
+
+    if let Some(entropy) = &entropy {
+        builder = builder
+            .with_clock(
+                entropy
+                    .clock()
+                    .cloned()
+                    .unwrap_or_else(ClockCapability::none),
+            )
+            .with_random(RandomCapability::seeded(args.seed.unwrap_or_default()));
+    } else if args.allow_clock {
         builder = builder.with_clock(ClockCapability::monotonic_only());
     }
 
+    // Stream events as they happen
+    if matches!(format, OutputFormat::JsonLines) && !quiet {
+        builder =
+            builder.with_event_subscriber(Arc::new(JsonLinesSubscriber::new(std::io::stdout())));
+    }
+
     let runtime = builder.build().context("Failed to create runtime")?;
 
     // Load the module
-    let module = runtime
-        .load_file(&args.module)
-        .context("Failed to load module")?;
-
-    // Determine the function to call
-    let function = args.function.as_deref().unwrap_or_else(|| {
-        // Try to find _start or main
-        if module.has_export("_start") {
-            "_start"
-        } else if module.has_export("main") {
-            "main"
-        } else {
-            // Default to first exported function
-            module
-                .exports()
-                .first()
-                .map(|e| e.name.as_str())
-                .unwrap_or("_start")
+    let module = match runtime.load_file(&args.module) {
+        Ok(module) => module,
+        Err(e) => {
+            return fail(
+                RunError::ModuleLoad(describe_load_error(&args.module, &e)),
+                format,
+            );
         }
-    });
+    };
+
+    let function = select_function(args.function.as_deref(), &module);
 
     if !quiet {
         tracing::info!(
@@ -154,41 +518,69 @@ pub fn execute(args: RunArgs, format: OutputFormat, quiet: bool) -> Result<()> {
     }
 
     // Create sandbox and execute
+    let collector = Arc::new(MetricsCollector::new());
     let mut sandbox = runtime
         .sandbox()
+        .with_metrics_collector(Arc::clone(&collector))
         .build()
         .context("Failed to create sandbox")?;
 
-    sandbox
-        .load_module(&module)
-        .context("Failed to load module into sandbox")?;
+    let is_json = !matches!(format, OutputFormat::Human);
+    let capture = if args.capture_output.unwrap_or(is_json) {
+        let capture = OutputCapture::new();
+        capture
+            .add_to_linker(sandbox.linker_mut())
+            .context("Failed to install output capture")?;
+        for function in capture.registered_functions() {
+            sandbox.record_host_function(function);
+        }
+        Some(capture)
+    } else {
+        None
+    };
 
-    // Get function signature for argument parsing
-    let func_type = sandbox
-        .get_func_type(function)
-        .context(format!("Function '{}' not found", function))?;
+    if let Some(entropy) = &entropy {
+        entropy
+            .add_to_linker(sandbox.linker_mut())
+            .context("Failed to install deterministic clock and random sources")?;
+        for function in entropy.registered_functions() {
+            sandbox.record_host_function(function);
+        }
+    }
 
-    let param_types: Vec<_> = func_type.params().collect();
+    if args.list_host_funcs {
+        print_host_functions(&sandbox, format)?;
+        return Ok(RunStatus::Success);
+    }
 
-    // Validate argument count
-    if args.args.len() != param_types.len() {
-        anyhow::bail!(
-            "Function '{}' expects {} arguments, got {}",
-            function,
-            param_types.len(),
-            args.args.len()
-        );
+    if let Err(e) = sandbox.load_module(&module) {
+        return fail(RunError::ModuleLoad(e.to_string()), format);
     }
 
-    // Parse arguments
-    let wasm_args: Vec<wasmtime::Val> = args
-        .args
-        .iter()
-        .zip(param_types.iter())
-        .map(|(arg, ty)| parse_wasm_arg(arg, ty.clone()))
-        .collect::<Result<Vec<_>>>()?;
+    let sandbox_id = sandbox.id().as_uuid();
+    runtime.event_dispatcher().emit_for(
+        sandbox_id,
+        SandboxEvent::ModuleLoaded {
+            name: module.name().map(String::from),
+            export_count: module.exports().len(),
+            hash: Some(module.id_string()),
+        },
+    );
+
+    // Check and parse arguments against the function signature
+    let wasm_args = match prepare_args(sandbox.get_func_type(function), function, &args.args) {
+        Ok(wasm_args) => wasm_args,
+        Err(e) => return fail(e, format),
+    };
 
     // Execute the function
+    runtime.event_dispatcher().emit_for(
+        sandbox_id,
+        SandboxEvent::ExecutionStarted {
+            function: function.to_string(),
+        },
+    );
+
     let start = std::time::Instant::now();
     let result = sandbox.call_dynamic(function, wasm_args);
     let duration = start.elapsed();
@@ -198,6 +590,7 @@ pub fn execute(args: RunArgs, format: OutputFormat, quiet: bool) -> Result<()> {
         name: module.name().map(String::from),
         export_count: module.exports().len(),
         import_count: module.imports().len(),
+        hash: Some(module.id_string()),
     };
 
     let outcome = match &result {
@@ -205,27 +598,24 @@ pub fn execute(args: RunArgs, format: OutputFormat, quiet: bool) -> Result<()> {
             let return_value = if results.is_empty() {
                 None
             } else {
-                let formatted = results
-                    .iter()
-                    .map(format_wasm_val)
-                    .collect::<Vec<_>>()
-                    .join(", ");
-                Some(serde_json::Value::String(formatted))
+                Some(results.iter().map(wasm_val_to_json).collect())
             };
             ExecutionOutcome::Success { return_value }
         }
-        Err(e) => ExecutionOutcome::Error {
-            message: e.to_string(),
-        },
+        Err(e) => outcome_from_error(e, duration),
     };
 
-    let metrics = sandbox.metrics().clone();
-    let report = ExecutionReport::new(
-        module_info,
-        outcome.clone(),
-        aegis_observe::MetricsCollector::new().snapshot(),
+    runtime.event_dispatcher().emit_for(
+        sandbox_id,
+        SandboxEvent::ExecutionCompleted {
+            function: function.to_string(),
+            outcome: outcome.clone(),
+            duration,
+        },
     );
 
+    let report = ExecutionReport::new(module_info, outcome.clone(), collector.snapshot());
+
     // Output results
     match format {
         OutputFormat::Human => match &result {
@@ -243,25 +633,228 @@ pub fn execute(args: RunArgs, format: OutputFormat, quiet: bool) -> Result<()> {
                 }
                 if args.metrics {
                     println!("\nMetrics:");
-                    println!("  Duration: {:?}", metrics.duration());
-                    println!("  Fuel consumed: {}", metrics.fuel_consumed);
+                    println!("  Duration: {:?}", report.metrics.timing.execution_time);
+                    println!("  Fuel consumed: {}", report.metrics.fuel.consumed_fuel);
                 }
             }
             Err(_) => {
                 println!("{}", report.to_text());
             }
         },
-        OutputFormat::Json | OutputFormat::JsonCompact => {
-            let json = if matches!(format, OutputFormat::JsonCompact) {
-                serde_json::to_string(&report.to_json())?
-            } else {
-                report.to_json_pretty()
+        OutputFormat::Json | OutputFormat::JsonCompact | OutputFormat::JsonLines => {
+            let mut value = report.to_json();
+            if let (Some(capture), Some(object)) = (&capture, value.as_object_mut()) {
+                object.insert("stdout".to_string(), capture.stdout_lossy().into());
+                object.insert("stderr".to_string(), capture.stderr_lossy().into());
+            }
+
+            let json = match format {
+                OutputFormat::JsonCompact => serde_json::to_string(&value)?,
+                OutputFormat::JsonLines => serde_json::to_string(&serde_json::json!({
+                    "type": "summary",
+                    "report": value,
+                }))?,
+                _ => serde_json::to_string_pretty(&value)?,
             };
             println!("{}", json);
         }
     }
 
-    result
-        .map(|_| ())
-        .map_err(|e| anyhow::anyhow!("Execution failed: {}", e))
+    if let (OutputFormat::Human, Some(capture)) = (format, &capture) {
+        print_captured("stdout", &capture.stdout_lossy());
+        print_captured("stderr", &capture.stderr_lossy());
+    }
+
+    Ok(RunStatus::from_outcome(&outcome))
+}
+
+/// Print the sandbox's host functions and whether their required
+/// capabilities were granted.
+fn print_host_functions(sandbox: &Sandbox, format: OutputFormat) -> Result<()> {
+    let missing = sandbox.missing_capabilities();
+    let functions: Vec<_> = sandbox
+        .host_functions()
+        .iter()
+        .map(|f| {
+            json!({
+                "module": f.module,
+                "name": f.name,
+                "required_capability": f.required_capability.as_ref().map(|c| c.as_str()),
+                "granted": f.required_capability.as_ref().is_none_or(|c| !missing.contains(c)),
+            })
+        })
+        .collect();
+
+    match format {
+        OutputFormat::Human => {
+            if functions.is_empty() {
+                println!("No host functions registered");
+            }
+            for function in sandbox.host_functions() {
+                match &function.required_capability {
+                    Some(capability) => println!(
+                        "{}::{}  requires {}{}",
+                        function.module,
+                        function.name,
+                        capability,
+                        if missing.contains(capability) {
+                            " (not granted)"
+                        } else {
+                            ""
+                        }
+                    ),
+                    None => println!("{}::{}", function.module, function.name),
+                }
+            }
+        }
+        OutputFormat::JsonCompact | OutputFormat::JsonLines => {
+            println!("{}", serde_json::to_string(&functions)?);
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&functions)?),
+    }
+    Ok(())
+}
+
+/// Report a run error in the output format and return it.
+fn fail<T>(error: RunError, format: OutputFormat) -> Result<T> {
+    print_run_error(&error, format)?;
+    Err(error.into())
+}
+
+/// Print a captured guest stream in human-readable form.
+fn print_captured(stream: &str, output: &str) {
+    if output.is_empty() {
+        return;
+    }
+
+    println!("\n--- guest {} ---", stream);
+    print!("{}", output);
+    if !output.ends_with('\n') {
+        println!();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn add_type() -> FuncType {
+        let engine = wasmtime::Engine::default();
+        FuncType::new(&engine, [ValType::I32, ValType::I32], [ValType::I32])
+    }
+
+    #[test]
+    fn test_arity_mismatch() {
+        let err = prepare_args(Some(add_type()), "add", &args(&["1"])).unwrap_err();
+        assert!(matches!(
+            err,
+            RunError::ArityMismatch {
+                expected: 2,
+                got: 1
+            }
+        ));
+
+        let json = err.to_json();
+        assert_eq!(json["error"]["code"], "arity_mismatch");
+        assert_eq!(json["error"]["expected"], 2);
+        assert_eq!(json["error"]["got"], 1);
+    }
+
+    #[test]
+    fn test_unparseable_i32() {
+        let err = prepare_args(Some(add_type()), "add", &args(&["1", "two"])).unwrap_err();
+        match &err {
+            RunError::ParseArg {
+                index,
+                expected_type,
+                value,
+            } => {
+                assert_eq!(*index, 1);
+                assert!(matches!(expected_type, ValType::I32));
+                assert_eq!(value, "two");
+            }
+            other => panic!("expected ParseArg, got {other:?}"),
+        }
+
+        let json = err.to_json();
+        assert_eq!(json["error"]["code"], "parse_arg");
+        assert_eq!(json["error"]["expected_type"], "i32");
+
+        let err = prepare_args(None, "missing", &[]).unwrap_err();
+        assert_eq!(err.code(), "function_not_found");
+    }
+
+    #[test]
+    fn test_typed_result_json() {
+        assert_eq!(
+            wasm_val_to_json(&Val::I64(i64::MAX)),
+            json!({ "type": "i64", "value": "9223372036854775807" })
+        );
+        assert_eq!(
+            wasm_val_to_json(&Val::I32(-7)),
+            json!({ "type": "i32", "value": -7 })
+        );
+
+        let f32_json = wasm_val_to_json(&Val::F32(0.1f32.to_bits()));
+        assert_eq!(f32_json["value"], json!(0.1));
+        assert_eq!(f32_json["bits"], "0x3dcccccd");
+
+        let f64_json = wasm_val_to_json(&Val::F64(f64::NAN.to_bits()));
+        assert!(f64_json["value"].is_null());
+        let bits = f64_json["bits"].as_str().unwrap().trim_start_matches("0x");
+        assert!(f64::from_bits(u64::from_str_radix(bits, 16).unwrap()).is_nan());
+    }
+
+    /// Run a WAT module through the command and return its status.
+    fn run_wat(name: &str, wat: &str, extra: &[&str]) -> Result<RunStatus> {
+        use clap::Parser;
+
+        let path =
+            std::env::temp_dir().join(format!("aegis-run-{}-{}.wat", name, std::process::id()));
+        std::fs::write(&path, wat).unwrap();
+
+        let mut argv = vec!["aegis", "run", path.to_str().unwrap(), "-e", "run"];
+        argv.extend_from_slice(extra);
+        let crate::Commands::Run(args) = crate::Cli::try_parse_from(argv).unwrap().command else {
+            unreachable!();
+        };
+
+        let status = execute(args, OutputFormat::Human, true);
+        std::fs::remove_file(&path).ok();
+        status
+    }
+
+    #[test]
+    fn test_exit_code_for_trap() {
+        let status = run_wat("trap", "(module (func (export \"run\") unreachable))", &[]);
+        assert_eq!(status.unwrap(), RunStatus::Trapped);
+        assert_eq!(RunStatus::Trapped.code(), 2);
+    }
+
+    #[test]
+    fn test_exit_code_for_fuel_exhaustion() {
+        let wat = "(module (func (export \"run\") (loop $l (br $l))))";
+        let status = run_wat("fuel", wat, &["--fuel-limit", "1000"]);
+        assert_eq!(status.unwrap(), RunStatus::ResourceExhausted);
+        assert_eq!(RunStatus::ResourceExhausted.code(), 4);
+    }
+
+    #[test]
+    fn test_exit_code_for_stack_overflow() {
+        let wat = "(module (func $f (export \"run\") (call $f)))";
+        let status = run_wat("stack", wat, &[]);
+        assert_eq!(status.unwrap(), RunStatus::ResourceExhausted);
+    }
+
+    #[test]
+    fn test_exit_code_for_module_load_error() {
+        let err = run_wat("invalid", "(module (func", &[]).unwrap_err();
+        let err = err.downcast_ref::<RunError>().unwrap();
+        assert_eq!(err.status(), RunStatus::ModuleLoadFailed);
+        assert_eq!(err.status().code(), 6);
+    }
 }
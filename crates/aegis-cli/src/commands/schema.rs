@@ -0,0 +1,41 @@
+//! Schema command - Print JSON Schemas for Aegis file formats.
+
+use anyhow::Result;
+use clap::{Args, Subcommand};
+
+use aegis_capability::capability_policy_schema;
+
+use crate::OutputFormat;
+
+/// Arguments for the schema command.
+#[derive(Args)]
+pub struct SchemaArgs {
+    /// Schema to print
+    #[command(subcommand)]
+    pub kind: SchemaKind,
+}
+
+/// Available schemas.
+#[derive(Subcommand)]
+pub enum SchemaKind {
+    /// Capability policy files, as read by `validate --against-caps`
+    Capabilities,
+}
+
+/// Execute the schema command.
+pub fn execute(args: SchemaArgs, format: OutputFormat) -> Result<()> {
+    let schema = match args.kind {
+        SchemaKind::Capabilities => capability_policy_schema(),
+    };
+
+    match format {
+        OutputFormat::JsonCompact | OutputFormat::JsonLines => {
+            println!("{}", serde_json::to_string(&schema)?)
+        }
+        OutputFormat::Human | OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&schema)?)
+        }
+    }
+
+    Ok(())
+}
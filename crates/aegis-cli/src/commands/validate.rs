@@ -1,14 +1,17 @@
 //! Validate command - Validate a WebAssembly module.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use clap::Args;
 use serde::Serialize;
 
+use aegis_capability::{CapabilityId, CapabilityPolicy, CapabilitySet, standard_ids};
+use aegis_observe::{Diagnostic, DiagnosticLevel};
 use aegis_wasm::prelude::*;
 
 use crate::OutputFormat;
+use crate::commands::describe_load_error;
 
 /// Arguments for the validate command.
 #[derive(Args)]
@@ -17,9 +20,14 @@ pub struct ValidateArgs {
     #[arg(required = true)]
     pub module: PathBuf,
 
-    /// Strict validation mode
+    /// Strict validation mode: missing entry points are errors, and unknown
+    /// import modules and missing memories are reported
     #[arg(long)]
     pub strict: bool,
+
+    /// Check that imports are covered by the capabilities in a policy file (JSON)
+    #[arg(long, value_name = "POLICY")]
+    pub against_caps: Option<PathBuf>,
 }
 
 /// Validation result.
@@ -30,8 +38,179 @@ struct ValidationResult {
     module_name: Option<String>,
     exports: usize,
     imports: usize,
-    warnings: Vec<String>,
-    errors: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unsatisfied_imports: Vec<UnsatisfiedImport>,
+}
+
+/// An import that requires a capability the policy does not grant.
+#[derive(Debug, Serialize)]
+struct UnsatisfiedImport {
+    module: String,
+    name: String,
+    missing_capability: CapabilityId,
+}
+
+/// Size of a WebAssembly page in bytes.
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// Load a capability policy file.
+fn load_policy(path: &Path) -> Result<CapabilitySet> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read policy file: {}", path.display()))?;
+    let policies: Vec<CapabilityPolicy> = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse policy file: {}", path.display()))?;
+
+    CapabilitySet::from_policies(&policies).context("Invalid capability policy")
+}
+
+/// Get the capability a WASI import requires, if any.
+///
+/// Imports from other modules are provided by the host and are not mapped.
+fn required_capability(module: &str, name: &str) -> Option<CapabilityId> {
+    if !matches!(module, "wasi_snapshot_preview1" | "wasi_unstable") {
+        return None;
+    }
+
+    if name.starts_with("fd_") || name.starts_with("path_") {
+        Some(standard_ids::FILESYSTEM)
+    } else if name.starts_with("sock_") {
+        Some(standard_ids::NETWORK)
+    } else if name.starts_with("clock_") {
+        Some(standard_ids::CLOCK)
+    } else if name.starts_with("environ_") {
+        Some(standard_ids::ENV)
+    } else if name == "random_get" {
+        Some(standard_ids::RANDOM)
+    } else {
+        None
+    }
+}
+
+/// Build a diagnostic.
+fn diagnostic(level: DiagnosticLevel, message: impl Into<String>) -> Diagnostic {
+    Diagnostic {
+        level,
+        message: message.into(),
+        context: None,
+    }
+}
+
+/// Check a loaded module, returning its diagnostics and any imports the
+/// granted capabilities do not cover.
+///
+/// Missing entry points are errors in strict mode and warnings otherwise.
+fn check_module(
+    module: &ValidatedModule,
+    granted: Option<&CapabilitySet>,
+    strict: bool,
+) -> (Vec<Diagnostic>, Vec<UnsatisfiedImport>) {
+    let mut diagnostics = Vec::new();
+    let mut unsatisfied = Vec::new();
+
+    // Check for common issues
+    if module.exports().is_empty() {
+        diagnostics.push(diagnostic(
+            DiagnosticLevel::Warning,
+            "Module has no exports",
+        ));
+    }
+
+    // Check for required functions
+    if !module.has_export("_start") && !module.has_export("main") {
+        let level = if strict {
+            DiagnosticLevel::Error
+        } else {
+            DiagnosticLevel::Warning
+        };
+        diagnostics.push(diagnostic(
+            level,
+            "Module has no _start or main function - may not be directly executable",
+        ));
+    }
+
+    // Check memory minimums against the default sandbox limit
+    let memory_limit = ResourceLimits::default().max_memory_bytes as u64;
+    for (index, memory) in module.metadata().memories.iter().enumerate() {
+        let min_bytes = memory.min_pages.saturating_mul(WASM_PAGE_SIZE);
+        if min_bytes > memory_limit {
+            diagnostics.push(Diagnostic {
+                level: DiagnosticLevel::Warning,
+                message: format!(
+                    "Memory {} declares a minimum of {} bytes, above the default limit of {} bytes",
+                    index, min_bytes, memory_limit
+                ),
+                context: Some("Raise --memory-limit to instantiate this module".to_string()),
+            });
+        }
+    }
+
+    // Check imports
+    for import in module.imports() {
+        match import.module.as_str() {
+            "wasi_snapshot_preview1" | "wasi" => {
+                diagnostics.push(diagnostic(
+                    DiagnosticLevel::Warning,
+                    format!(
+                        "Module imports from '{}' - WASI support required",
+                        import.module
+                    ),
+                ));
+            }
+            "env" => {
+                // Common import module, usually OK
+            }
+            other => {
+                if strict {
+                    diagnostics.push(diagnostic(
+                        DiagnosticLevel::Warning,
+                        format!("Module imports from unknown module: {}", other),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Strict mode checks
+    if strict && module.metadata().memories.is_empty() {
+        diagnostics.push(diagnostic(DiagnosticLevel::Warning, "Module has no memory"));
+    }
+
+    // Check imports against the granted capabilities
+    if let Some(granted) = granted {
+        for import in module.imports() {
+            let Some(capability) = required_capability(&import.module, &import.name) else {
+                continue;
+            };
+            if granted.has(&capability) {
+                continue;
+            }
+
+            diagnostics.push(diagnostic(
+                DiagnosticLevel::Error,
+                format!(
+                    "Import '{}::{}' requires the '{}' capability, which is not granted",
+                    import.module, import.name, capability
+                ),
+            ));
+            unsatisfied.push(UnsatisfiedImport {
+                module: import.module.clone(),
+                name: import.name.clone(),
+                missing_capability: capability,
+            });
+        }
+    }
+
+    (diagnostics, unsatisfied)
+}
+
+/// Get the label for a diagnostic level in human output.
+fn level_label(level: DiagnosticLevel) -> &'static str {
+    match level {
+        DiagnosticLevel::Info => "INFO",
+        DiagnosticLevel::Warning => "WARN",
+        DiagnosticLevel::Error => "ERROR",
+    }
 }
 
 /// Execute the validate command.
@@ -40,14 +219,16 @@ pub fn execute(args: ValidateArgs, format: OutputFormat) -> Result<()> {
         .build()
         .context("Failed to create runtime")?;
 
+    let granted = args.against_caps.as_deref().map(load_policy).transpose()?;
+
     let mut result = ValidationResult {
         valid: true,
         path: args.module.display().to_string(),
         module_name: None,
         exports: 0,
         imports: 0,
-        warnings: Vec::new(),
-        errors: Vec::new(),
+        diagnostics: Vec::new(),
+        unsatisfied_imports: Vec::new(),
     };
 
     // Attempt to load and validate the module
@@ -57,82 +238,52 @@ pub fn execute(args: ValidateArgs, format: OutputFormat) -> Result<()> {
             result.exports = module.exports().len();
             result.imports = module.imports().len();
 
-            // Check for common issues
-            if module.exports().is_empty() {
-                result.warnings.push("Module has no exports".to_string());
-            }
-
-            // Check for required functions
-            let has_start = module.has_export("_start");
-            let has_main = module.has_export("main");
-            if !has_start && !has_main {
-                result.warnings.push(
-                    "Module has no _start or main function - may not be directly executable"
-                        .to_string(),
-                );
-            }
-
-            // Check imports
-            for import in module.imports() {
-                match import.module.as_str() {
-                    "wasi_snapshot_preview1" | "wasi" => {
-                        result.warnings.push(format!(
-                            "Module imports from '{}' - WASI support required",
-                            import.module
-                        ));
-                    }
-                    "env" => {
-                        // Common import module, usually OK
-                    }
-                    other => {
-                        if args.strict {
-                            result
-                                .warnings
-                                .push(format!("Module imports from unknown module: {}", other));
-                        }
-                    }
-                }
-            }
-
-            // Strict mode checks
-            if args.strict && module.metadata().memories.is_empty() {
-                result.warnings.push("Module has no memory".to_string());
-            }
+            let (diagnostics, unsatisfied) = check_module(&module, granted.as_ref(), args.strict);
+            result.diagnostics = diagnostics;
+            result.unsatisfied_imports = unsatisfied;
         }
         Err(e) => {
-            result.valid = false;
-            result.errors.push(e.to_string());
+            result.diagnostics.push(diagnostic(
+                DiagnosticLevel::Error,
+                describe_load_error(&args.module, &e),
+            ));
         }
     }
 
+    // Only errors fail validation
+    result.valid = !result
+        .diagnostics
+        .iter()
+        .any(|d| d.level == DiagnosticLevel::Error);
+
     // Output results
     match format {
         OutputFormat::Human => {
             if result.valid {
                 println!("Module is valid: {}", args.module.display());
-                if let Some(name) = &result.module_name {
-                    println!("  Name: {}", name);
-                }
-                println!("  Exports: {}", result.exports);
-                println!("  Imports: {}", result.imports);
-
-                if !result.warnings.is_empty() {
-                    println!("\nWarnings:");
-                    for warning in &result.warnings {
-                        println!("  - {}", warning);
-                    }
-                }
             } else {
                 println!("Module is INVALID: {}", args.module.display());
-                for error in &result.errors {
-                    println!("  Error: {}", error);
+            }
+            if let Some(name) = &result.module_name {
+                println!("  Name: {}", name);
+            }
+            println!("  Exports: {}", result.exports);
+            println!("  Imports: {}", result.imports);
+
+            if !result.diagnostics.is_empty() {
+                println!("\nDiagnostics:");
+                for diag in &result.diagnostics {
+                    println!("  [{}] {}", level_label(diag.level), diag.message);
+                    if let Some(context) = &diag.context {
+                        println!("      {}", context);
+                    }
                 }
             }
         }
         OutputFormat::Json => {
             println!("{}", serde_json::to_string_pretty(&result)?);
         }
-        OutputFormat::JsonCompact => {
+        OutputFormat::JsonCompact | OutputFormat::JsonLines => {
             println!("{}", serde_json::to_string(&result)?);
         }
     }
@@ -143,3 +294,52 @@ pub fn execute(args: ValidateArgs, format: OutputFormat) -> Result<()> {
         Err(anyhow::anyhow!("Validation failed"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(wat: &str) -> ValidatedModule {
+        Aegis::builder().build().unwrap().load_wat(wat).unwrap()
+    }
+
+    fn levels(diagnostics: &[Diagnostic]) -> Vec<DiagnosticLevel> {
+        diagnostics.iter().map(|d| d.level).collect()
+    }
+
+    #[test]
+    fn test_large_memory_minimum_warns() {
+        let module = load(r#"(module (memory (export "memory") 32768) (func (export "_start")))"#);
+
+        let (diagnostics, _) = check_module(&module, None, false);
+        assert_eq!(levels(&diagnostics), [DiagnosticLevel::Warning]);
+        assert!(diagnostics[0].message.contains("2147483648 bytes"));
+    }
+
+    #[test]
+    fn test_missing_entrypoint() {
+        let module = load(r#"(module (func (export "helper")))"#);
+
+        let (diagnostics, _) = check_module(&module, None, false);
+        assert_eq!(levels(&diagnostics), [DiagnosticLevel::Warning]);
+
+        let (diagnostics, _) = check_module(&module, None, true);
+        assert!(diagnostics.iter().any(|d| {
+            d.level == DiagnosticLevel::Error && d.message.contains("_start or main")
+        }));
+    }
+
+    #[test]
+    fn test_wat_error_location() {
+        let err = Aegis::builder()
+            .build()
+            .unwrap()
+            .load_wat("(module\n  (func i32.bogus))")
+            .unwrap_err();
+
+        assert_eq!(
+            describe_load_error(Path::new("plugin.wat"), &err),
+            "plugin.wat:2:9: unknown operator or unexpected token"
+        );
+    }
+}
@@ -8,6 +8,8 @@ use tracing_subscriber::EnvFilter;
 
 mod commands;
 
+use commands::run::RunError;
+
 /// Aegis WebAssembly Sandbox Runtime
 #[derive(Parser)]
 #[command(name = "aegis")]
@@ -44,6 +46,8 @@ pub enum OutputFormat {
     Json,
     /// Compact JSON (single line)
     JsonCompact,
+    /// Events streamed as JSON Lines, followed by a summary line
+    JsonLines,
 }
 
 /// Available commands.
@@ -51,14 +55,30 @@ pub enum OutputFormat {
 pub enum Commands {
     /// Execute a WebAssembly module
     Run(commands::run::RunArgs),
+    /// Time repeated executions of a function
+    Bench(commands::bench::BenchArgs),
     /// Validate a WebAssembly module
     Validate(commands::validate::ValidateArgs),
     /// Inspect a WebAssembly module
     Inspect(commands::inspect::InspectArgs),
+    /// Print the JSON Schema of a file format
+    Schema(commands::schema::SchemaArgs),
 }
 
 fn main() -> ExitCode {
-    let cli = Cli::parse();
+    // Usage errors exit 1 rather than clap's default of 2, which `run`
+    // reserves for a trapped guest.
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            return if e.use_stderr() {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
 
     // Initialize logging based on verbosity
     let log_level = match cli.verbose {
@@ -78,18 +98,31 @@ fn main() -> ExitCode {
 
     // Run the command
     let result = match cli.command {
-        Commands::Run(args) => commands::run::execute(args, cli.format, cli.quiet),
-        Commands::Validate(args) => commands::validate::execute(args, cli.format),
-        Commands::Inspect(args) => commands::inspect::execute(args, cli.format),
+        Commands::Run(args) => {
+            commands::run::execute(args, cli.format, cli.quiet).map(ExitCode::from)
+        }
+        Commands::Bench(args) => {
+            commands::bench::execute(args, cli.format).map(|()| ExitCode::SUCCESS)
+        }
+        Commands::Validate(args) => {
+            commands::validate::execute(args, cli.format).map(|()| ExitCode::SUCCESS)
+        }
+        Commands::Inspect(args) => {
+            commands::inspect::execute(args, cli.format).map(|()| ExitCode::SUCCESS)
+        }
+        Commands::Schema(args) => {
+            commands::schema::execute(args, cli.format).map(|()| ExitCode::SUCCESS)
+        }
     };
 
     match result {
-        Ok(()) => ExitCode::SUCCESS,
+        Ok(code) => code,
         Err(e) => {
             if !cli.quiet {
                 eprintln!("Error: {:#}", e);
             }
-            ExitCode::FAILURE
+            e.downcast_ref::<RunError>()
+                .map_or(ExitCode::FAILURE, |e| e.status().into())
         }
     }
 }
@@ -0,0 +1,214 @@
+//! Resource budgets shared across sandboxes.
+//!
+//! This module provides `ResourceAccount`, which caps the total fuel and the
+//! concurrent memory of every sandbox that references it, e.g. all the
+//! sandboxes of one tenant.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use tracing::debug;
+
+/// A resource tracked by a [`ResourceAccount`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountResource {
+    /// Fuel consumed by calls.
+    Fuel,
+    /// Linear memory held by live sandboxes.
+    Memory,
+}
+
+impl fmt::Display for AccountResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AccountResource::Fuel => write!(f, "fuel"),
+            AccountResource::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// A fuel and memory budget shared by several sandboxes.
+///
+/// Attach an account with `SandboxConfig::with_account` or
+/// `SandboxBuilder::with_account`. Each call reserves its fuel from the
+/// account when it starts and returns what it did not use when it ends, so
+/// the fuel ceiling covers every call of every attached sandbox. Memory is
+/// charged as it grows and released when the sandbox is reset or dropped,
+/// so the memory ceiling bounds the memory held at any one time.
+///
+/// A call that runs into either ceiling fails with
+/// `ExecutionError::AccountExhausted`.
+///
+/// # Example
+///
+/// ```ignore
+/// let tenant = Arc::new(ResourceAccount::new(10_000_000, 256 * 1024 * 1024));
+///
+/// let a = SandboxBuilder::new(engine.clone())
+///     .with_account(Arc::clone(&tenant))
+///     .build()?;
+/// let b = SandboxBuilder::new(engine)
+///     .with_account(Arc::clone(&tenant))
+///     .build()?;
+/// ```
+#[derive(Debug)]
+pub struct ResourceAccount {
+    /// Total fuel the account may spend.
+    fuel_limit: u64,
+    /// Memory the account may hold at once, in bytes.
+    memory_limit: usize,
+    /// Fuel spent or reserved by running calls.
+    fuel_used: AtomicU64,
+    /// Memory currently held, in bytes.
+    memory_used: AtomicUsize,
+    /// Largest amount of memory held at once, in bytes.
+    peak_memory: AtomicUsize,
+}
+
+impl ResourceAccount {
+    /// Create an account with a total fuel and a concurrent memory ceiling.
+    pub fn new(fuel_limit: u64, memory_limit: usize) -> Self {
+        Self {
+            fuel_limit,
+            memory_limit,
+            fuel_used: AtomicU64::new(0),
+            memory_used: AtomicUsize::new(0),
+            peak_memory: AtomicUsize::new(0),
+        }
+    }
+
+    /// Get the total fuel the account may spend.
+    pub fn fuel_limit(&self) -> u64 {
+        self.fuel_limit
+    }
+
+    /// Get the memory the account may hold at once, in bytes.
+    pub fn memory_limit(&self) -> usize {
+        self.memory_limit
+    }
+
+    /// Get the fuel spent so far, including fuel reserved by running calls.
+    pub fn fuel_used(&self) -> u64 {
+        self.fuel_used.load(Ordering::SeqCst)
+    }
+
+    /// Get the fuel left to spend.
+    pub fn fuel_remaining(&self) -> u64 {
+        self.fuel_limit.saturating_sub(self.fuel_used())
+    }
+
+    /// Get the memory currently held, in bytes.
+    pub fn memory_used(&self) -> usize {
+        self.memory_used.load(Ordering::SeqCst)
+    }
+
+    /// Get the largest amount of memory held at once, in bytes.
+    pub fn peak_memory(&self) -> usize {
+        self.peak_memory.load(Ordering::SeqCst)
+    }
+
+    /// Get the limit of `resource`.
+    pub fn limit(&self, resource: AccountResource) -> u64 {
+        match resource {
+            AccountResource::Fuel => self.fuel_limit,
+            AccountResource::Memory => self.memory_limit as u64,
+        }
+    }
+
+    /// Reserve up to `amount` fuel, returning the amount reserved.
+    pub(crate) fn reserve_fuel(&self, amount: u64) -> u64 {
+        let mut reserved = 0;
+        let _ = self
+            .fuel_used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                reserved = amount.min(self.fuel_limit.saturating_sub(used));
+                Some(used + reserved)
+            });
+        reserved
+    }
+
+    /// Return reserved fuel that was not spent.
+    pub(crate) fn refund_fuel(&self, amount: u64) {
+        let _ = self
+            .fuel_used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                Some(used.saturating_sub(amount))
+            });
+    }
+
+    /// Charge `bytes` of memory, failing if it would exceed the limit.
+    pub(crate) fn charge_memory(&self, bytes: usize) -> bool {
+        let charged = self
+            .memory_used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                used.checked_add(bytes)
+                    .filter(|&total| total <= self.memory_limit)
+            });
+
+        match charged {
+            Ok(previous) => {
+                self.peak_memory
+                    .fetch_max(previous + bytes, Ordering::SeqCst);
+                true
+            }
+            Err(used) => {
+                debug!(
+                    used,
+                    requested = bytes,
+                    limit = self.memory_limit,
+                    "Account memory exhausted"
+                );
+                false
+            }
+        }
+    }
+
+    /// Release `bytes` of charged memory.
+    pub(crate) fn release_memory(&self, bytes: usize) {
+        let _ = self
+            .memory_used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
+}
+
+/// Error raised inside a call when an account limit is reached.
+#[derive(Debug, thiserror::Error)]
+#[error("Resource account {resource} limit of {limit} exhausted")]
+pub(crate) struct AccountExhausted {
+    /// The exhausted resource.
+    pub(crate) resource: AccountResource,
+    /// The account's limit for it.
+    pub(crate) limit: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuel_reservation() {
+        let account = ResourceAccount::new(1000, 0);
+
+        assert_eq!(account.reserve_fuel(600), 600);
+        assert_eq!(account.reserve_fuel(600), 400);
+        assert_eq!(account.fuel_remaining(), 0);
+
+        account.refund_fuel(100);
+        assert_eq!(account.fuel_used(), 900);
+    }
+
+    #[test]
+    fn test_memory_ceiling() {
+        let account = ResourceAccount::new(0, 1024);
+
+        assert!(account.charge_memory(768));
+        assert!(!account.charge_memory(512));
+        account.release_memory(768);
+        assert!(account.charge_memory(512));
+
+        assert_eq!(account.memory_used(), 512);
+        assert_eq!(account.peak_memory(), 768);
+    }
+}
@@ -0,0 +1,72 @@
+//! Cooperative cancellation of running sandboxes.
+//!
+//! This module provides `CancellationToken`, which lets another thread stop
+//! a call in progress at the sandbox's next epoch check.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::debug;
+
+/// A handle for cancelling calls in the sandboxes it is attached to.
+///
+/// Attach a token with `SandboxBuilder::with_cancellation` or
+/// `Sandbox::set_cancellation`. Calling [`cancel`](Self::cancel) from any
+/// thread makes the running call trap at its next epoch check and fail with
+/// `ExecutionError::Cancelled`; later calls fail the same way until the
+/// sandbox is reset. Clones share the same state.
+///
+/// Cancellation requires epochs to be enabled on the engine and advanced,
+/// e.g. by an epoch manager: a sandbox with a token checks it on every
+/// epoch tick, so a running call stops within one tick of `cancel`. Other
+/// sandboxes on the engine are not affected.
+///
+/// # Example
+///
+/// ```ignore
+/// // With an epoch manager advancing `engine`'s epoch
+/// let token = CancellationToken::new();
+/// let mut sandbox = SandboxBuilder::new(engine)
+///     .with_cancellation(token.clone())
+///     .build()?;
+///
+/// std::thread::spawn(move || token.cancel());
+/// let result = sandbox.call_void("run"); // Err(ExecutionError::Cancelled)
+/// ```
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+#[derive(Default)]
+struct TokenInner {
+    /// Whether the token has been cancelled.
+    cancelled: AtomicBool,
+}
+
+impl CancellationToken {
+    /// Create a token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel running and future calls in every attached sandbox.
+    pub fn cancel(&self) {
+        if !self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            debug!("Cancellation requested");
+        }
+    }
+
+    /// Check if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl std::fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
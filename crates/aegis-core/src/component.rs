@@ -0,0 +1,463 @@
+//! Component-model components.
+//!
+//! This module provides `ValidatedComponent`, loaded with
+//! `ModuleLoader::load_component_bytes`, and `ComponentSandbox`, which runs
+//! a component's exported functions under the limits of a `SandboxConfig`.
+
+use std::time::Instant;
+
+use tracing::{debug, info, warn};
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, Instance, Linker, Val};
+use wasmtime::{Store, Trap, WasmBacktrace};
+
+use crate::account::{AccountExhausted, AccountResource};
+use crate::cancel::CancellationToken;
+use crate::config::SandboxConfig;
+use crate::engine::{AegisEngine, SharedEngine};
+use crate::error::{ExecutionError, ExecutionResult, HostFunctionError, TrapInfo};
+use crate::module::ContentHash;
+use crate::sandbox::{Sandbox, SandboxData, SandboxId};
+
+/// A validated component-model component ready for instantiation.
+///
+/// Components are loaded with [`ModuleLoader::load_component_bytes`] or
+/// [`ModuleLoader::load_component_file`] and run in a [`ComponentSandbox`].
+///
+/// [`ModuleLoader::load_component_bytes`]: crate::ModuleLoader::load_component_bytes
+/// [`ModuleLoader::load_component_file`]: crate::ModuleLoader::load_component_file
+#[derive(Clone)]
+pub struct ValidatedComponent {
+    /// The compiled Wasmtime component.
+    inner: Component,
+    /// Names of the exported functions.
+    exports: Vec<String>,
+    /// SHA-256 hash of the bytes the component was loaded from.
+    hash: ContentHash,
+}
+
+impl ValidatedComponent {
+    /// Wrap a compiled component whose bytes hash to `hash`.
+    pub(crate) fn new(engine: &AegisEngine, inner: Component, hash: ContentHash) -> Self {
+        let exports = inner
+            .component_type()
+            .exports(engine.inner())
+            .filter(|(_, item)| matches!(item, ComponentItem::ComponentFunc(_)))
+            .map(|(name, _)| name.to_string())
+            .collect();
+
+        Self {
+            inner,
+            exports,
+            hash,
+        }
+    }
+
+    /// Get the underlying Wasmtime component.
+    pub fn inner(&self) -> &Component {
+        &self.inner
+    }
+
+    /// Get the names of the exported functions.
+    pub fn exports(&self) -> &[String] {
+        &self.exports
+    }
+
+    /// Check if the component exports a function with this name.
+    pub fn has_export(&self, name: &str) -> bool {
+        self.exports.iter().any(|export| export == name)
+    }
+
+    /// Get the SHA-256 hash of the bytes the component was loaded from.
+    pub fn content_hash(&self) -> ContentHash {
+        self.hash
+    }
+
+    /// Get the component's hash as lowercase hex, for use in logs and
+    /// reports.
+    pub fn id_string(&self) -> String {
+        self.hash.to_string()
+    }
+}
+
+impl std::fmt::Debug for ValidatedComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValidatedComponent")
+            .field("exports", &self.exports)
+            .field("hash", &self.hash)
+            .finish()
+    }
+}
+
+/// A sandbox that runs a component-model component.
+///
+/// Fuel, memory and timeout limits come from the `SandboxConfig` as for a
+/// [`Sandbox`], as does the resource account, if any. A component is made of
+/// several core instances, so its store allows more instances, tables and
+/// memories than a module's, with `max_memory_bytes` bounding the combined
+/// size of its memories (see [`SandboxLimiter::for_component`]). Host functions are defined on the component linker
+/// returned by [`linker_mut`](Self::linker_mut) before the component is
+/// loaded. Only synchronous engines are supported.
+///
+/// [`SandboxLimiter::for_component`]: crate::SandboxLimiter::for_component
+///
+/// # Example
+///
+/// ```ignore
+/// let component = loader.load_component_bytes(&bytes)?;
+///
+/// let mut sandbox = ComponentSandbox::new(engine, (), SandboxConfig::default())?;
+/// sandbox.load_component(&component)?;
+/// let results = sandbox.call("add", &[Val::S32(2), Val::S32(3)])?;
+/// assert_eq!(results, [Val::S32(5)]);
+/// ```
+pub struct ComponentSandbox<S = ()> {
+    /// Reference to the engine.
+    engine: SharedEngine,
+    /// Wasmtime store holding the sandbox data.
+    store: Store<SandboxData<S>>,
+    /// Component linker for host function registration.
+    linker: Linker<SandboxData<S>>,
+    /// Currently loaded instance.
+    instance: Option<Instance>,
+    /// Currently loaded component.
+    component: Option<ValidatedComponent>,
+}
+
+impl<S: Send + 'static> ComponentSandbox<S> {
+    /// Create a new component sandbox with the given engine and user state.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ExecutionError::AsyncRequired` if the engine has async
+    /// support enabled.
+    pub fn new(
+        engine: SharedEngine,
+        user_state: S,
+        config: SandboxConfig,
+    ) -> ExecutionResult<Self> {
+        if engine.async_enabled() {
+            return Err(ExecutionError::AsyncRequired("ComponentSandbox::new"));
+        }
+
+        let data = SandboxData::for_component(user_state, config);
+        let id = data.id;
+        let store = Sandbox::build_store(&engine, data);
+        let linker = Linker::new(engine.inner());
+
+        info!(sandbox_id = %id, "Created new component sandbox");
+
+        Ok(Self {
+            engine,
+            store,
+            linker,
+            instance: None,
+            component: None,
+        })
+    }
+
+    /// Get the sandbox ID.
+    pub fn id(&self) -> SandboxId {
+        self.store.data().id
+    }
+
+    /// Let `token` cancel this sandbox's calls.
+    ///
+    /// Requires epochs to be enabled on the engine. See
+    /// [`CancellationToken`] for details.
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.store.data_mut().cancellation = Some(token);
+
+        if self.engine.epoch_enabled() {
+            Sandbox::install_epoch_callback(&self.engine, &mut self.store);
+        }
+    }
+
+    /// Get the component linker, to define host functions.
+    pub fn linker_mut(&mut self) -> &mut Linker<SandboxData<S>> {
+        &mut self.linker
+    }
+
+    /// Get the user state.
+    pub fn user_state(&self) -> &S {
+        &self.store.data().user_state
+    }
+
+    /// Get the currently loaded component.
+    pub fn component(&self) -> Option<&ValidatedComponent> {
+        self.component.as_ref()
+    }
+
+    /// Get the remaining fuel, if fuel metering is enabled.
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.engine
+            .fuel_enabled()
+            .then(|| self.store.get_fuel().ok())
+            .flatten()
+    }
+
+    /// Instantiate a component in this sandbox.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an import is not defined on the linker or
+    /// instantiation traps.
+    pub fn load_component(&mut self, component: &ValidatedComponent) -> ExecutionResult<()> {
+        let _active = self.engine.enter_execution();
+        Sandbox::arm_store_deadline(&self.engine, &mut self.store);
+        let budget = self.remaining_fuel().unwrap_or(0);
+        Sandbox::reserve_account_fuel(&self.engine, &mut self.store, budget);
+
+        let result = self.linker.instantiate(&mut self.store, component.inner());
+        self.store.data_mut().limits.confirm_growth();
+        let account_withheld = if self.engine.fuel_enabled() {
+            let remaining = self.remaining_fuel().unwrap_or(0);
+            Sandbox::settle_account_fuel(&mut self.store, remaining)
+        } else {
+            0
+        };
+        let instance = result.map_err(|e| self.classify("<instantiate>", e, account_withheld))?;
+        self.instance = Some(instance);
+        self.component = Some(component.clone());
+        self.store.data_mut().module_hash = Some(component.content_hash());
+
+        debug!(
+            sandbox_id = %self.id(),
+            component = %component.id_string(),
+            "Component instantiated"
+        );
+        Ok(())
+    }
+
+    /// Call an exported component function.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ExecutionError::ModuleNotLoaded` if no component is loaded,
+    /// `ExecutionError::FunctionNotFound` if `name` is not exported, or an
+    /// error if the parameters do not match the function's type or the call
+    /// fails.
+    pub fn call(&mut self, name: &str, params: &[Val]) -> ExecutionResult<Vec<Val>> {
+        let instance = self.instance.ok_or(ExecutionError::ModuleNotLoaded)?;
+        let func = instance
+            .get_func(&mut self.store, name)
+            .ok_or_else(|| ExecutionError::FunctionNotFound(name.to_string()))?;
+
+        debug!(sandbox_id = %self.id(), function = name, "Calling component function");
+
+        let _active = self.engine.enter_execution();
+        Sandbox::arm_store_deadline(&self.engine, &mut self.store);
+        let budget = self.remaining_fuel().unwrap_or(0);
+        let initial_fuel = Sandbox::reserve_account_fuel(&self.engine, &mut self.store, budget);
+        self.store.data_mut().metrics.start_time = Some(Instant::now());
+
+        // Results are overwritten by the call; the placeholder is arbitrary
+        let mut results = vec![Val::Bool(false); func.results(&self.store).len()];
+        let result = func
+            .call(&mut self.store, params, &mut results)
+            .and_then(|()| func.post_return(&mut self.store));
+        self.store.data_mut().limits.confirm_growth();
+
+        let remaining_fuel = self.remaining_fuel().unwrap_or(0);
+        let metrics = &mut self.store.data_mut().metrics;
+        metrics.end_time = Some(Instant::now());
+        metrics.fuel_consumed = initial_fuel.saturating_sub(remaining_fuel);
+        let account_withheld = if self.engine.fuel_enabled() {
+            Sandbox::settle_account_fuel(&mut self.store, remaining_fuel)
+        } else {
+            0
+        };
+
+        result.map_err(|e| self.classify(name, e, account_withheld))?;
+        Ok(results)
+    }
+
+    /// Translate a failed instantiation or call into an `ExecutionError`.
+    ///
+    /// `account_withheld` is the fuel the resource account could not cover.
+    fn classify(&self, name: &str, err: wasmtime::Error, account_withheld: u64) -> ExecutionError {
+        let data = self.store.data();
+        let limits = &data.config().limits;
+
+        if let Some(exhausted) = err.downcast_ref::<AccountExhausted>() {
+            warn!(sandbox_id = %self.id(), function = name, "Resource account exhausted");
+            return data.account_exhausted(exhausted.resource);
+        }
+
+        if let Some(trap) = err.downcast_ref::<Trap>() {
+            warn!(sandbox_id = %self.id(), function = name, trap = ?trap, "Component trapped");
+            return match *trap {
+                Trap::Interrupt if data.is_cancelled() => ExecutionError::Cancelled,
+                Trap::OutOfFuel if account_withheld > 0 => {
+                    data.account_exhausted(AccountResource::Fuel)
+                }
+                Trap::OutOfFuel => ExecutionError::OutOfFuel {
+                    consumed: self.store.data().metrics.fuel_consumed,
+                    limit: limits.initial_fuel,
+                },
+                Trap::Interrupt => ExecutionError::Timeout(limits.timeout),
+                Trap::StackOverflow => ExecutionError::StackOverflow {
+                    limit: self.engine.config().max_wasm_stack,
+                },
+                trap => {
+                    let info = TrapInfo::from(trap);
+                    match err.downcast_ref::<WasmBacktrace>() {
+                        Some(backtrace) if self.store.data().config().capture_backtraces => {
+                            ExecutionError::Trap(info.with_backtrace(backtrace))
+                        }
+                        _ => ExecutionError::Trap(info),
+                    }
+                }
+            };
+        }
+
+        match err.downcast::<HostFunctionError>() {
+            Ok(host) => ExecutionError::Host(host),
+            Err(err) => ExecutionError::Wasmtime(err),
+        }
+    }
+}
+
+impl<S> std::fmt::Debug for ComponentSandbox<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentSandbox")
+            .field("id", &self.store.data().id)
+            .field("component", &self.component)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EngineConfig;
+    use crate::engine::IntoShared;
+    use crate::error::ModuleError;
+    use crate::module::ModuleLoader;
+
+    const ADD_COMPONENT: &str = r#"
+        (component
+            (core module $m
+                (func (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add))
+            (core instance $i (instantiate $m))
+            (func (export "add") (param "a" s32) (param "b" s32) (result s32)
+                (canon lift (core func $i "add")))
+        )
+    "#;
+
+    #[test]
+    fn test_component_add() {
+        let engine = AegisEngine::new(EngineConfig::default().with_component_model(true))
+            .unwrap()
+            .into_shared();
+        let component = ModuleLoader::new(engine.clone())
+            .load_component_bytes(ADD_COMPONENT.as_bytes())
+            .unwrap();
+        assert!(component.has_export("add"));
+
+        let mut sandbox = ComponentSandbox::new(engine, (), SandboxConfig::default()).unwrap();
+        assert!(matches!(
+            sandbox.call("add", &[]),
+            Err(ExecutionError::ModuleNotLoaded)
+        ));
+
+        sandbox.load_component(&component).unwrap();
+        let results = sandbox.call("add", &[Val::S32(2), Val::S32(40)]).unwrap();
+        assert_eq!(results, [Val::S32(42)]);
+
+        assert!(matches!(
+            sandbox.call("sub", &[]),
+            Err(ExecutionError::FunctionNotFound(_))
+        ));
+
+        // Components need an engine with the component model enabled
+        let plain = AegisEngine::new(EngineConfig::default().with_component_model(false))
+            .unwrap()
+            .into_shared();
+        assert!(matches!(
+            ModuleLoader::new(plain).load_component_bytes(ADD_COMPONENT.as_bytes()),
+            Err(ModuleError::ComponentModelDisabled)
+        ));
+    }
+
+    const LAYERED_COMPONENT: &str = r#"
+        (component
+            (core module $a
+                (memory (export "memory") 1)
+                (func (export "double") (param i32) (result i32)
+                    local.get 0
+                    local.get 0
+                    i32.add))
+            (core instance $ai (instantiate $a))
+            (core module $b
+                (import "a" "double" (func $double (param i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "quad") (param i32) (result i32)
+                    local.get 0
+                    call $double
+                    call $double)
+                (func (export "spin") (loop $l (br $l))))
+            (core instance $bi (instantiate $b (with "a" (instance $ai))))
+            (func (export "quad") (param "x" s32) (result s32)
+                (canon lift (core func $bi "quad")))
+            (func (export "spin")
+                (canon lift (core func $bi "spin")))
+        )
+    "#;
+
+    #[test]
+    fn test_component_with_several_core_instances() {
+        use std::sync::Arc;
+
+        use crate::account::ResourceAccount;
+        use crate::config::ResourceLimits;
+
+        let engine = AegisEngine::new(EngineConfig::default().with_component_model(true))
+            .unwrap()
+            .into_shared();
+        let component = ModuleLoader::new(engine.clone())
+            .load_component_bytes(LAYERED_COMPONENT.as_bytes())
+            .unwrap();
+
+        let mut sandbox =
+            ComponentSandbox::new(engine.clone(), (), SandboxConfig::default()).unwrap();
+        sandbox.load_component(&component).unwrap();
+        let results = sandbox.call("quad", &[Val::S32(3)]).unwrap();
+        assert_eq!(results, [Val::S32(12)]);
+
+        // The memory limit bounds both memories together
+        let config =
+            SandboxConfig::default().with_limits(ResourceLimits::default().with_max_memory(65536));
+        let mut sandbox = ComponentSandbox::new(engine.clone(), (), config).unwrap();
+        assert!(sandbox.load_component(&component).is_err());
+
+        // Cancellation stops a running call
+        let token = CancellationToken::new();
+        token.cancel();
+        let mut sandbox =
+            ComponentSandbox::new(engine.clone(), (), SandboxConfig::default()).unwrap();
+        sandbox.load_component(&component).unwrap();
+        sandbox.set_cancellation(token);
+        assert!(matches!(
+            sandbox.call("spin", &[]),
+            Err(ExecutionError::Cancelled)
+        ));
+
+        // Calls draw fuel from the resource account
+        let account = Arc::new(ResourceAccount::new(10_000, usize::MAX));
+        let config = SandboxConfig::default().with_account(Arc::clone(&account));
+        let mut sandbox = ComponentSandbox::new(engine, (), config).unwrap();
+        sandbox.load_component(&component).unwrap();
+        assert!(matches!(
+            sandbox.call("spin", &[]),
+            Err(ExecutionError::AccountExhausted {
+                resource: AccountResource::Fuel,
+                ..
+            })
+        ));
+        assert_eq!(account.fuel_remaining(), 0);
+    }
+}
@@ -3,8 +3,15 @@
 //! This module provides configuration structures for customizing the behavior
 //! of the Aegis engine and sandbox execution.
 
+use std::sync::Arc;
 use std::time::Duration;
 
+use aegis_capability::{CapabilityView, FrozenCapabilitySet};
+use aegis_observe::{EventDispatcher, MetricsCollector, ResourceType};
+
+use crate::account::ResourceAccount;
+use crate::module::ValidatedModule;
+
 /// Configuration for the Aegis engine.
 ///
 /// This controls how the underlying Wasmtime engine is configured.
@@ -44,6 +51,17 @@ pub struct EngineConfig {
     /// This increases compilation time and memory usage but provides
     /// better error messages and backtraces.
     pub debug_info: bool,
+
+    /// Expected interval between epoch increments.
+    ///
+    /// Used to convert sandbox timeouts into epoch deadlines. Defaults to
+    /// 10ms; an epoch manager updates the engine with its own interval.
+    pub epoch_tick_interval: Duration,
+
+    /// Configure Wasmtime for reproducible execution and compilation.
+    ///
+    /// See [`EngineConfig::deterministic`].
+    pub deterministic: bool,
 }
 
 impl Default for EngineConfig {
@@ -55,6 +73,8 @@ impl Default for EngineConfig {
             async_support: false,
             component_model: false,
             debug_info: false,
+            epoch_tick_interval: Duration::from_millis(10),
+            deterministic: false,
         }
     }
 }
@@ -101,6 +121,20 @@ impl EngineConfig {
         self
     }
 
+    /// Configure Wasmtime for reproducible execution and compilation.
+    ///
+    /// See [`EngineConfig::deterministic`].
+    pub fn with_deterministic(mut self, enabled: bool) -> Self {
+        self.deterministic = enabled;
+        self
+    }
+
+    /// Set the expected interval between epoch increments.
+    pub fn with_epoch_tick_interval(mut self, interval: Duration) -> Self {
+        self.epoch_tick_interval = interval;
+        self
+    }
+
     /// Create a configuration optimized for security.
     ///
     /// This enables all safety features and uses conservative limits.
@@ -112,6 +146,8 @@ impl EngineConfig {
             async_support: false,
             component_model: false,
             debug_info: false,
+            epoch_tick_interval: Duration::from_millis(10),
+            deterministic: false,
         }
     }
 
@@ -126,6 +162,39 @@ impl EngineConfig {
             async_support: false,
             component_model: false,
             debug_info: false,
+            epoch_tick_interval: Duration::from_millis(10),
+            deterministic: false,
+        }
+    }
+
+    /// Create a configuration for reproducible execution.
+    ///
+    /// On top of the defaults this canonicalizes NaNs produced by float
+    /// operations, disables SIMD and relaxed SIMD (whose NaN bit patterns
+    /// and relaxed results vary by platform), and pins compilation to
+    /// single-threaded Cranelift at a fixed optimization level, so engines
+    /// built from this configuration on the same host produce identical
+    /// compiled code (see [`AegisEngine::verify_reproducible`]).
+    ///
+    /// Engine settings alone do not make a guest deterministic. The
+    /// remaining caveats:
+    ///
+    /// - Clocks and randomness come from host functions; grant a stepped
+    ///   `ClockCapability` and a seeded `RandomCapability` (or use
+    ///   `EntropySources::deterministic` from `aegis-host`).
+    /// - Epoch timeouts depend on wall-clock time; rely on fuel for limits
+    ///   that must reproduce.
+    /// - Compiled code is only identical for the same Wasmtime version,
+    ///   target and host CPU features.
+    /// - Stack overflow depends on native frame sizes, so deep recursion
+    ///   near `max_wasm_stack` may still trap at different depths across
+    ///   targets.
+    ///
+    /// [`AegisEngine::verify_reproducible`]: crate::AegisEngine::verify_reproducible
+    pub fn deterministic() -> Self {
+        Self {
+            deterministic: true,
+            ..Self::default()
         }
     }
 }
@@ -143,6 +212,37 @@ pub struct SandboxConfig {
 
     /// Whether to allow the sandbox to be reused after execution.
     pub reusable: bool,
+
+    /// Capabilities granted to this sandbox.
+    ///
+    /// The sandbox can only read the set. Defaults to an empty set, which
+    /// denies every action.
+    pub capabilities: CapabilityView,
+
+    /// Dispatcher that receives events such as capability checks.
+    pub event_dispatcher: Option<Arc<EventDispatcher>>,
+
+    /// Whether high-frequency events are buffered and dispatched at the end
+    /// of each call instead of as they occur.
+    pub buffer_events: bool,
+
+    /// Collector fed with the sandbox's timing, fuel, memory, capability
+    /// and host call metrics.
+    pub metrics_collector: Option<Arc<MetricsCollector>>,
+
+    /// Account whose fuel and memory budget this sandbox draws from.
+    pub account: Option<Arc<ResourceAccount>>,
+
+    /// Whether traps carry the WASM backtrace.
+    ///
+    /// Frames are symbolized when an error occurs, so this costs nothing
+    /// on successful calls. Function names need the module's name section.
+    pub capture_backtraces: bool,
+
+    /// Tenant, user or other principal the sandbox runs on behalf of.
+    ///
+    /// Passed to capabilities in the `CheckContext` of each check.
+    pub principal: Option<String>,
 }
 
 impl Default for SandboxConfig {
@@ -151,6 +251,13 @@ impl Default for SandboxConfig {
             limits: ResourceLimits::default(),
             collect_metrics: true,
             reusable: false,
+            capabilities: CapabilityView::default(),
+            event_dispatcher: None,
+            buffer_events: false,
+            metrics_collector: None,
+            account: None,
+            capture_backtraces: false,
+            principal: None,
         }
     }
 }
@@ -178,6 +285,57 @@ impl SandboxConfig {
         self.reusable = enabled;
         self
     }
+
+    /// Set the capabilities granted to the sandbox.
+    ///
+    /// Given an `Arc<CapabilitySet>`, changes made through it afterwards,
+    /// such as revocations, apply to the sandbox; a `FrozenCapabilitySet`
+    /// never changes.
+    pub fn with_capabilities(mut self, capabilities: impl Into<CapabilityView>) -> Self {
+        self.capabilities = capabilities.into();
+        self
+    }
+
+    /// Set the capabilities granted to the sandbox from a frozen set.
+    pub fn with_frozen_capabilities(self, capabilities: FrozenCapabilitySet) -> Self {
+        self.with_capabilities(capabilities)
+    }
+
+    /// Set the event dispatcher.
+    pub fn with_event_dispatcher(mut self, dispatcher: Arc<EventDispatcher>) -> Self {
+        self.event_dispatcher = Some(dispatcher);
+        self
+    }
+
+    /// Enable or disable buffering of high-frequency events.
+    pub fn with_event_buffering(mut self, enabled: bool) -> Self {
+        self.buffer_events = enabled;
+        self
+    }
+
+    /// Set the metrics collector.
+    pub fn with_metrics_collector(mut self, collector: Arc<MetricsCollector>) -> Self {
+        self.metrics_collector = Some(collector);
+        self
+    }
+
+    /// Draw fuel and memory from a shared resource account.
+    pub fn with_account(mut self, account: Arc<ResourceAccount>) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    /// Enable or disable backtraces on traps.
+    pub fn with_backtraces(mut self, enabled: bool) -> Self {
+        self.capture_backtraces = enabled;
+        self
+    }
+
+    /// Set the principal the sandbox runs on behalf of.
+    pub fn with_principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = Some(principal.into());
+        self
+    }
 }
 
 /// Resource limits for sandbox execution.
@@ -260,6 +418,35 @@ impl ResourceLimits {
         self
     }
 
+    /// Check, without instantiating it, whether a module's declared
+    /// minimums fit within these limits.
+    ///
+    /// Returns the first resource that does not fit: `Memory` if the module
+    /// defines more memories than `max_memories` or its largest memory
+    /// starts above `max_memory_bytes`, `Table` if its largest table starts
+    /// above `max_table_elements`. Passing this check does not guarantee
+    /// the module runs to completion, only that instantiation is not
+    /// refused for these limits.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// if let Err(resource) = limits.can_host(&module) {
+    ///     println!("module needs more {} than allowed", resource);
+    /// }
+    /// ```
+    pub fn can_host(&self, module: &ValidatedModule) -> Result<(), ResourceType> {
+        if module.defined_memories() > self.max_memories
+            || module.estimated_min_memory_bytes() > self.max_memory_bytes as u64
+        {
+            return Err(ResourceType::Memory);
+        }
+        if module.estimated_min_table_elements() > u64::from(self.max_table_elements) {
+            return Err(ResourceType::Table);
+        }
+        Ok(())
+    }
+
     /// Create minimal resource limits for testing.
     pub fn minimal() -> Self {
         Self {
@@ -325,4 +512,32 @@ mod tests {
         assert!(standard.max_memory_bytes < generous.max_memory_bytes);
         assert!(minimal.initial_fuel < standard.initial_fuel);
     }
+
+    #[test]
+    fn test_can_host_large_memory_minimum() {
+        use crate::engine::AegisEngine;
+        use crate::module::ModuleLoader;
+
+        let engine = Arc::new(AegisEngine::new(EngineConfig::default()).unwrap());
+        let loader = ModuleLoader::new(engine);
+
+        // 2048 pages is 128MB
+        let module = loader.load_wat("(module (memory 2048))").unwrap();
+        assert_eq!(module.estimated_min_memory_bytes(), 128 * 1024 * 1024);
+        assert_eq!(module.estimated_min_table_elements(), 0);
+
+        assert_eq!(
+            ResourceLimits::default().can_host(&module),
+            Err(ResourceType::Memory)
+        );
+        assert_eq!(ResourceLimits::generous().can_host(&module), Ok(()));
+
+        let module = loader.load_wat("(module (table 20000 funcref))").unwrap();
+        assert_eq!(module.estimated_min_table_elements(), 20_000);
+        assert_eq!(
+            ResourceLimits::default().can_host(&module),
+            Err(ResourceType::Table)
+        );
+        assert_eq!(ResourceLimits::generous().can_host(&module), Ok(()));
+    }
 }
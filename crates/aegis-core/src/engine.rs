@@ -4,13 +4,16 @@
 //! with Aegis-specific configuration and functionality.
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::Thread;
+use std::time::Duration;
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use tracing::{debug, info};
-use wasmtime::{Config, Engine};
+use wasmtime::{Config, Engine, Module, OptLevel, Strategy};
 
 use crate::config::EngineConfig;
-use crate::error::EngineResult;
+use crate::error::{EngineError, EngineResult};
 
 /// The core Aegis engine that wraps Wasmtime.
 ///
@@ -34,6 +37,12 @@ pub struct AegisEngine {
     config: EngineConfig,
     /// Current epoch value for timeout management.
     epoch: RwLock<u64>,
+    /// Interval between epoch increments.
+    epoch_tick_interval: RwLock<Duration>,
+    /// Number of sandboxes currently executing guest code.
+    active_executions: AtomicUsize,
+    /// Thread driving the epoch, woken when execution resumes after idling.
+    epoch_ticker: Mutex<Option<Thread>>,
 }
 
 impl AegisEngine {
@@ -68,7 +77,16 @@ impl AegisEngine {
         wasmtime_config.wasm_bulk_memory(true);
         wasmtime_config.wasm_multi_value(true);
         wasmtime_config.wasm_reference_types(true);
-        wasmtime_config.wasm_simd(true);
+        wasmtime_config.wasm_simd(!config.deterministic);
+
+        // Avoid platform-dependent float results and pin code generation
+        if config.deterministic {
+            wasmtime_config.wasm_relaxed_simd(false);
+            wasmtime_config.cranelift_nan_canonicalization(true);
+            wasmtime_config.strategy(Strategy::Cranelift);
+            wasmtime_config.cranelift_opt_level(OptLevel::Speed);
+            wasmtime_config.parallel_compilation(false);
+        }
 
         let inner = Engine::new(&wasmtime_config)?;
 
@@ -76,13 +94,17 @@ impl AegisEngine {
             fuel = config.fuel_enabled,
             epochs = config.epoch_enabled,
             async_support = config.async_support,
+            deterministic = config.deterministic,
             "Created Aegis engine"
         );
 
         Ok(Self {
             inner,
+            epoch_tick_interval: RwLock::new(config.epoch_tick_interval),
             config,
             epoch: RwLock::new(0),
+            active_executions: AtomicUsize::new(0),
+            epoch_ticker: Mutex::new(None),
         })
     }
 
@@ -101,6 +123,31 @@ impl AegisEngine {
         &self.config
     }
 
+    /// Check that this engine's configuration compiles `bytes` reproducibly.
+    ///
+    /// The module is compiled by this engine and by a second engine built
+    /// from the same configuration, and the serialized results are
+    /// compared byte for byte. This is expected to hold for
+    /// [`EngineConfig::deterministic`] engines.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EngineError::NotReproducible` if the serialized modules
+    /// differ, or an error if `bytes` cannot be compiled.
+    pub fn verify_reproducible(&self, bytes: &[u8]) -> EngineResult<()> {
+        let other = Self::new(self.config.clone())?;
+        let first = Module::new(&self.inner, bytes)?.serialize()?;
+        let second = Module::new(&other.inner, bytes)?.serialize()?;
+
+        if first != second {
+            return Err(EngineError::NotReproducible {
+                first: first.len(),
+                second: second.len(),
+            });
+        }
+        Ok(())
+    }
+
     /// Increment the epoch counter.
     ///
     /// This is used for epoch-based timeout management. Each increment
@@ -120,6 +167,61 @@ impl AegisEngine {
         *self.epoch.read()
     }
 
+    /// Get the interval between epoch increments.
+    pub fn epoch_tick_interval(&self) -> Duration {
+        *self.epoch_tick_interval.read()
+    }
+
+    /// Set the interval between epoch increments.
+    ///
+    /// Called by whatever drives [`increment_epoch`](Self::increment_epoch)
+    /// so that timeouts convert to the right number of epochs.
+    pub fn set_epoch_tick_interval(&self, interval: Duration) {
+        *self.epoch_tick_interval.write() = interval;
+    }
+
+    /// Get the number of sandboxes currently executing guest code.
+    ///
+    /// An epoch driver can slow down while this is zero, since no deadline
+    /// is running.
+    pub fn active_executions(&self) -> usize {
+        self.active_executions.load(Ordering::SeqCst)
+    }
+
+    /// Mark the start of guest execution until the returned guard is
+    /// dropped.
+    ///
+    /// When the count of active executions rises from zero, the thread set
+    /// with [`set_epoch_ticker`](Self::set_epoch_ticker) is unparked so
+    /// that it resumes its normal interval before the deadline matters.
+    pub fn enter_execution(self: &Arc<Self>) -> ActiveExecution {
+        if self.active_executions.fetch_add(1, Ordering::SeqCst) == 0 {
+            if let Some(ticker) = &*self.epoch_ticker.lock() {
+                ticker.unpark();
+            }
+        }
+        ActiveExecution {
+            engine: Arc::clone(self),
+        }
+    }
+
+    /// Set the thread to unpark when execution resumes after an idle period.
+    ///
+    /// Called by whatever drives [`increment_epoch`](Self::increment_epoch)
+    /// if it parks while [`active_executions`](Self::active_executions) is
+    /// zero.
+    pub fn set_epoch_ticker(&self, ticker: Option<Thread>) {
+        *self.epoch_ticker.lock() = ticker;
+    }
+
+    /// Calculate the number of epochs that make up a timeout.
+    ///
+    /// Always at least one epoch.
+    pub fn epochs_for_timeout(&self, timeout: Duration) -> u64 {
+        let tick = self.epoch_tick_interval().as_nanos().max(1);
+        (timeout.as_nanos() / tick).max(1) as u64
+    }
+
     /// Check if fuel-based limiting is enabled.
     pub fn fuel_enabled(&self) -> bool {
         self.config.fuel_enabled
@@ -141,6 +243,29 @@ impl std::fmt::Debug for AegisEngine {
         f.debug_struct("AegisEngine")
             .field("config", &self.config)
             .field("epoch", &*self.epoch.read())
+            .field("epoch_tick_interval", &self.epoch_tick_interval())
+            .field("active_executions", &self.active_executions())
+            .finish()
+    }
+}
+
+/// Guard returned by [`AegisEngine::enter_execution`].
+///
+/// Dropping it marks the execution as finished.
+pub struct ActiveExecution {
+    engine: SharedEngine,
+}
+
+impl Drop for ActiveExecution {
+    fn drop(&mut self) {
+        self.engine.active_executions.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl std::fmt::Debug for ActiveExecution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ActiveExecution")
+            .field("active", &self.engine.active_executions())
             .finish()
     }
 }
@@ -185,6 +310,32 @@ mod tests {
         assert_eq!(engine.current_epoch(), 2);
     }
 
+    #[test]
+    fn test_deterministic_engines_compile_identically() {
+        let wat = r#"
+            (module
+                (func (export "mix") (param f64 f64) (result f64)
+                    (f64.div (f64.sub (local.get 0) (local.get 1)) (local.get 1)))
+            )
+        "#;
+        let bytes = wat::parse_str(wat).unwrap();
+
+        let first = AegisEngine::new(EngineConfig::deterministic()).unwrap();
+        let second = AegisEngine::new(EngineConfig::deterministic()).unwrap();
+        let serialize = |engine: &AegisEngine| {
+            Module::new(engine.inner(), &bytes)
+                .unwrap()
+                .serialize()
+                .unwrap()
+        };
+        assert_eq!(serialize(&first), serialize(&second));
+        first.verify_reproducible(&bytes).unwrap();
+
+        // SIMD is disabled
+        let simd = wat::parse_str("(module (func (result v128) (v128.const i64x2 0 0)))").unwrap();
+        assert!(Module::new(first.inner(), &simd).is_err());
+    }
+
     #[test]
     fn test_engine_without_epochs() {
         let config = EngineConfig::default().with_epochs(false);
@@ -210,4 +361,18 @@ mod tests {
         assert_eq!(engine.current_epoch(), 1);
         assert_eq!(engine2.current_epoch(), 1);
     }
+
+    #[test]
+    fn test_epochs_for_timeout() {
+        let engine = AegisEngine::new(
+            EngineConfig::default().with_epoch_tick_interval(Duration::from_millis(5)),
+        )
+        .unwrap();
+
+        assert_eq!(engine.epochs_for_timeout(Duration::from_millis(100)), 20);
+        assert_eq!(engine.epochs_for_timeout(Duration::from_millis(1)), 1);
+
+        engine.set_epoch_tick_interval(Duration::from_millis(50));
+        assert_eq!(engine.epochs_for_timeout(Duration::from_millis(100)), 2);
+    }
 }
@@ -5,7 +5,14 @@
 //! and reporting.
 
 use std::time::Duration;
+
+use aegis_capability::CapabilityError;
+use aegis_observe::TrapFrame;
 use thiserror::Error;
+use wasmtime::WasmBacktrace;
+
+use crate::account::AccountResource;
+use crate::module::{ImportKind, LoaderLimit};
 
 /// Top-level error type for Aegis core operations.
 #[derive(Debug, Error)]
@@ -30,6 +37,18 @@ pub enum EngineError {
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
 
+    /// Two engines with the same configuration compiled a module to
+    /// different code.
+    #[error(
+        "Compilation is not reproducible: serialized modules of {first} and {second} bytes differ"
+    )]
+    NotReproducible {
+        /// Size of the module serialized by the first engine.
+        first: usize,
+        /// Size of the module serialized by the second engine.
+        second: usize,
+    },
+
     /// Underlying Wasmtime error.
     #[error("Wasmtime error: {0}")]
     Wasmtime(#[from] wasmtime::Error),
@@ -42,6 +61,17 @@ pub enum ModuleError {
     #[error("Invalid WASM module: {0}")]
     Invalid(String),
 
+    /// WAT text failed to parse.
+    #[error("Invalid WAT at line {line}, column {column}: {message}")]
+    WatParse {
+        /// Description of the problem.
+        message: String,
+        /// 1-based line of the offending token.
+        line: usize,
+        /// 1-based column of the offending token.
+        column: usize,
+    },
+
     /// Module validation failed.
     #[error("Module validation failed: {0}")]
     ValidationFailed(String),
@@ -59,6 +89,30 @@ pub enum ModuleError {
         name: String,
     },
 
+    /// The module's original bytes were not kept, e.g. because it was
+    /// loaded precompiled.
+    #[error("Module source is not available")]
+    SourceUnavailable,
+
+    /// The module does not export a function with this name.
+    #[error("Function not exported: '{0}'")]
+    FunctionNotFound(String),
+
+    /// The module exceeds one of the loader's [`LoaderLimits`](crate::LoaderLimits).
+    #[error("Module exceeds {kind} limit: {actual} > {limit}")]
+    LimitExceeded {
+        /// The limit that was exceeded.
+        kind: LoaderLimit,
+        /// The configured limit.
+        limit: u64,
+        /// The value found in the module.
+        actual: u64,
+    },
+
+    /// A component was loaded on an engine without component-model support.
+    #[error("Component model is not enabled on the engine")]
+    ComponentModelDisabled,
+
     /// Underlying Wasmtime error.
     #[error("Wasmtime error: {0}")]
     Wasmtime(#[from] wasmtime::Error),
@@ -75,6 +129,10 @@ pub enum ExecutionError {
     #[error("Execution timeout after {0:?}")]
     Timeout(Duration),
 
+    /// The call was cancelled through a `CancellationToken`.
+    #[error("Execution cancelled")]
+    Cancelled,
+
     /// Execution ran out of fuel (CPU limit exceeded).
     #[error("Out of fuel: consumed {consumed}, limit was {limit}")]
     OutOfFuel {
@@ -84,6 +142,15 @@ pub enum ExecutionError {
         limit: u64,
     },
 
+    /// A resource account shared with other sandboxes ran out.
+    #[error("Resource account exhausted: {resource} limit of {limit} reached")]
+    AccountExhausted {
+        /// The exhausted resource.
+        resource: AccountResource,
+        /// The account's limit for it (fuel units or bytes).
+        limit: u64,
+    },
+
     /// Memory limit was exceeded.
     #[error("Memory limit exceeded: used {used} bytes, limit {limit} bytes")]
     MemoryExceeded {
@@ -93,28 +160,158 @@ pub enum ExecutionError {
         limit: usize,
     },
 
+    /// The guest exhausted its stack, e.g. through deep recursion.
+    #[error("Stack overflow: exceeded the {limit} byte WASM stack")]
+    StackOverflow {
+        /// The engine's maximum WASM stack size in bytes.
+        limit: usize,
+    },
+
     /// The requested function was not found in the module.
     #[error("Function not found: '{0}'")]
     FunctionNotFound(String),
 
-    /// Type mismatch when calling a function.
-    #[error("Type mismatch: expected {expected}, got {actual}")]
+    /// A call passed the wrong number of parameters.
+    #[error("Function '{function}' takes {expected} parameters, got {got}")]
+    ArityMismatch {
+        /// The function being called.
+        function: String,
+        /// Number of parameters the function takes.
+        expected: usize,
+        /// Number of parameters passed.
+        got: usize,
+    },
+
+    /// A call passed a parameter of the wrong type.
+    #[error("Type mismatch in parameter {index}: expected {expected}, got {got}")]
     TypeMismatch {
-        /// Expected type signature.
+        /// Zero-based index of the parameter.
+        index: usize,
+        /// Type the function expects.
         expected: String,
-        /// Actual type signature.
-        actual: String,
+        /// Type of the value passed.
+        got: String,
     },
 
     /// The module has not been loaded yet.
     #[error("Module not loaded")]
     ModuleNotLoaded,
 
+    /// The loaded module does not export a memory named `memory`.
+    #[error("Module does not export memory")]
+    MemoryNotFound,
+
+    /// A memory access was outside the guest's linear memory.
+    #[error("Memory access out of bounds: offset {offset}, len {len}, memory size {memory_size}")]
+    MemoryAccessOutOfBounds {
+        /// Offset of the access.
+        offset: usize,
+        /// Length of the access.
+        len: usize,
+        /// Current memory size in bytes.
+        memory_size: usize,
+    },
+
+    /// An async method was called but the engine does not support async.
+    #[error("Async support is not enabled on the engine")]
+    AsyncNotEnabled,
+
+    /// A blocking method was called on an engine with async support enabled.
+    #[error("Engine has async support enabled; use the async variant of '{0}'")]
+    AsyncRequired(&'static str),
+
+    /// The module imports an item the sandbox's linker does not define.
+    #[error("Missing import: {expected_kind} '{module}::{name}' is not defined")]
+    MissingImport {
+        /// The import module name.
+        module: String,
+        /// The import name.
+        name: String,
+        /// The kind of item the module expects.
+        expected_kind: ImportKind,
+    },
+
+    /// The sandbox's capabilities do not permit the operation.
+    #[error("Capability error: {0}")]
+    Capability(#[from] CapabilityError),
+
+    /// A host function failed with a [`HostFunctionError`].
+    #[error("Host function failed: {0}")]
+    Host(HostFunctionError),
+
     /// Underlying Wasmtime error.
     #[error("Wasmtime error: {0}")]
     Wasmtime(#[from] wasmtime::Error),
 }
 
+impl ExecutionError {
+    /// Get the typed error a host function failed with, if it is an `E`.
+    ///
+    /// For example, `error.host_error::<aegis_host::HostError>()` recovers a
+    /// permission denial raised by a host function during a call.
+    pub fn host_error<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        match self {
+            ExecutionError::Host(host) => host.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// A typed error returned by a host function.
+///
+/// Host functions return `wasmtime::Result`, which erases the error type.
+/// Returning this wrapper instead (it converts into `wasmtime::Error` with
+/// `?`) lets `Sandbox::call` report the failure as [`ExecutionError::Host`],
+/// from which the original error can be recovered by type.
+pub struct HostFunctionError(Box<dyn std::error::Error + Send + Sync>);
+
+impl HostFunctionError {
+    /// Wrap a host function's error.
+    pub fn new(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Self(Box::new(error))
+    }
+
+    /// Get the wrapped error if it is an `E`.
+    pub fn downcast_ref<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        self.0.downcast_ref()
+    }
+
+    /// Take the wrapped error if it is an `E`.
+    pub fn downcast<E: std::error::Error + 'static>(self) -> std::result::Result<E, Self> {
+        self.0.downcast().map(|e| *e).map_err(Self)
+    }
+}
+
+impl std::fmt::Display for HostFunctionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::fmt::Debug for HostFunctionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for HostFunctionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Errors from acquiring a sandbox from a `SandboxPool`.
+#[derive(Debug, Error)]
+pub enum PoolError {
+    /// No sandbox became available before the deadline.
+    #[error("No pooled sandbox available within {0:?}")]
+    Timeout(Duration),
+
+    /// A new sandbox could not be created.
+    #[error("Failed to create pooled sandbox: {0}")]
+    Sandbox(#[from] ExecutionError),
+}
+
 /// Information about a WASM trap.
 #[derive(Debug, Clone)]
 pub struct TrapInfo {
@@ -124,6 +321,26 @@ pub struct TrapInfo {
     pub message: String,
     /// Stack backtrace, if available.
     pub backtrace: Option<String>,
+    /// Frames of the backtrace, innermost first.
+    pub frames: Vec<TrapFrame>,
+}
+
+impl TrapInfo {
+    /// Attach the frames of a Wasmtime backtrace, along with its rendering.
+    pub fn with_backtrace(mut self, backtrace: &WasmBacktrace) -> Self {
+        self.frames = backtrace
+            .frames()
+            .iter()
+            .map(|frame| TrapFrame {
+                module: frame.module().name().map(str::to_string),
+                func_index: frame.func_index(),
+                func_name: frame.func_name().map(str::to_string),
+                offset: frame.module_offset(),
+            })
+            .collect();
+        self.backtrace = Some(backtrace.to_string());
+        self
+    }
 }
 
 impl std::fmt::Display for TrapInfo {
@@ -144,6 +361,7 @@ impl From<wasmtime::Trap> for TrapInfo {
             code: None,
             message: trap.to_string(),
             backtrace: None,
+            frames: Vec::new(),
         }
     }
 }
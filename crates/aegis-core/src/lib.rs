@@ -6,6 +6,7 @@
 //! - [`AegisEngine`]: The core engine that wraps Wasmtime
 //! - [`ModuleLoader`]: Loading and validating WASM modules
 //! - [`Sandbox`]: Isolated execution environment
+//! - [`SandboxPool`]: Reuse of warm sandboxes across requests
 //! - Configuration types for customizing behavior
 //!
 //! # Quick Start
@@ -52,21 +53,37 @@
 //! └─────────────────────────────────────────┘
 //! ```
 
+pub mod account;
+pub mod cancel;
+pub mod component;
 pub mod config;
 pub mod engine;
 pub mod error;
+pub mod limiter;
 pub mod module;
+pub mod pool;
 pub mod sandbox;
 
 // Re-export main types at crate root
+pub use account::{AccountResource, ResourceAccount};
+pub use cancel::CancellationToken;
+pub use component::{ComponentSandbox, ValidatedComponent};
 pub use config::{EngineConfig, ResourceLimits, SandboxConfig};
-pub use engine::{AegisEngine, IntoShared, SharedEngine};
-pub use error::{AegisError, EngineError, ExecutionError, ModuleError, Result, TrapInfo};
+pub use engine::{ActiveExecution, AegisEngine, IntoShared, SharedEngine};
+pub use error::{
+    AegisError, EngineError, ExecutionError, HostFunctionError, ModuleError, PoolError, Result,
+    TrapInfo,
+};
+pub use limiter::{BoxedResourceLimiter, MemoryLoan, SandboxLimiter};
 pub use module::{
-    ExportInfo, ExportKind, ImportInfo, ImportKind, MemoryInfo, ModuleLoader, ModuleMetadata,
-    ValidatedModule,
+    ContentHash, ExportInfo, ExportKind, ImportInfo, ImportKind, LoaderLimit, LoaderLimits,
+    MemoryInfo, ModuleLoader, ModuleMetadata, ValidatedModule,
+};
+pub use pool::{PooledSandbox, SandboxPool};
+pub use sandbox::{
+    CallRecord, HostFunctions, InitHook, RefuelPolicy, RegisteredFunction, ResetHook, Resettable,
+    Sandbox, SandboxBuilder, SandboxData, SandboxId, SandboxMetrics,
 };
-pub use sandbox::{Sandbox, SandboxBuilder, SandboxData, SandboxId, SandboxMetrics};
 
 /// Prelude module for convenient imports.
 ///
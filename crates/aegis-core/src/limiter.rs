@@ -0,0 +1,472 @@
+//! Store limiter used by sandboxes.
+//!
+//! This module provides the `SandboxLimiter` type, which enforces a
+//! sandbox's memory and table limits and reports memory growth.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use aegis_observe::{EventDispatcher, MetricsCollector, SandboxEvent};
+use tracing::debug;
+use wasmtime::{ResourceLimiter, StoreLimits, StoreLimitsBuilder};
+
+use crate::account::{AccountExhausted, AccountResource, ResourceAccount};
+use crate::config::ResourceLimits;
+use crate::sandbox::SandboxId;
+
+/// Core instances a component store may create.
+pub const COMPONENT_MAX_INSTANCES: usize = 64;
+
+/// Tables a component store may create.
+pub const COMPONENT_MAX_TABLES: usize = 64;
+
+/// Linear memories a component store may create, unless the configured
+/// `max_memories` is higher.
+pub const COMPONENT_MAX_MEMORIES: usize = 16;
+
+/// A resource limiter that can replace the sandbox's default limits.
+pub type BoxedResourceLimiter = Box<dyn ResourceLimiter + Send + Sync>;
+
+/// Resource limiter installed on every sandbox store.
+///
+/// Limits are enforced by Wasmtime's `StoreLimits` unless a custom limiter
+/// is set. Growth past a memory's declared maximum is always denied. Each
+/// memory growth, including the initial allocation at instantiation,
+/// updates the peak memory and is emitted as a `MemoryGrew` event and
+/// recorded with the metrics collector, if any. Wasmtime can still fail a
+/// growth the limiter permitted, e.g. when allocation fails, so these are
+/// deferred until the growth is confirmed: by the next limiter call, by
+/// [`confirm_growth`](Self::confirm_growth) or when the limiter is dropped.
+///
+/// With a resource account attached, growth is also charged to the account
+/// and traps if the account's memory ceiling would be exceeded. The charge
+/// is released when the limiter is dropped.
+///
+/// Component stores instantiate several core instances, so they use
+/// [`for_component`](Self::for_component), which raises the instance, table
+/// and memory counts and bounds the combined size of all memories instead.
+pub struct SandboxLimiter {
+    /// Limits enforced by Wasmtime.
+    limits: StoreLimits,
+    /// Custom limiter used instead of `limits`.
+    custom: Option<BoxedResourceLimiter>,
+    /// Largest memory size permitted so far, in bytes.
+    peak_memory: usize,
+    /// Dispatcher that receives `MemoryGrew` events.
+    event_dispatcher: Option<Arc<EventDispatcher>>,
+    /// Sandbox the `MemoryGrew` events are attributed to.
+    sandbox_id: Option<SandboxId>,
+    /// Collector that records memory allocations.
+    metrics_collector: Option<Arc<MetricsCollector>>,
+    /// Account charged for memory growth.
+    account: Option<Arc<ResourceAccount>>,
+    /// Memory charged to the account, in bytes.
+    account_charged: usize,
+    /// Bound on the combined size of all memories, in bytes.
+    max_total_memory: Option<usize>,
+    /// Combined size of all memories, in bytes.
+    total_memory: usize,
+    /// Growth permitted by the last `memory_growing` and not yet confirmed.
+    pending_growth: Option<PendingGrowth>,
+    /// Configured size of each memory, in bytes.
+    memory_limit: usize,
+    /// Memory lent to child sandboxes and withheld from `memory_limit`, in
+    /// bytes.
+    lent_memory: Arc<AtomicUsize>,
+}
+
+impl SandboxLimiter {
+    /// Create a limiter enforcing the given resource limits.
+    pub fn new(limits: &ResourceLimits, event_dispatcher: Option<Arc<EventDispatcher>>) -> Self {
+        let memory_limit = limits.max_memory_bytes;
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(limits.max_memory_bytes)
+            .table_elements(limits.max_table_elements as usize)
+            .instances(1)
+            .tables(10)
+            .memories(limits.max_memories as usize)
+            .build();
+
+        Self {
+            limits,
+            custom: None,
+            peak_memory: 0,
+            event_dispatcher,
+            sandbox_id: None,
+            metrics_collector: None,
+            account: None,
+            account_charged: 0,
+            max_total_memory: None,
+            total_memory: 0,
+            pending_growth: None,
+            memory_limit,
+            lent_memory: Arc::default(),
+        }
+    }
+
+    /// Create a limiter for a component store.
+    ///
+    /// Up to [`COMPONENT_MAX_INSTANCES`] core instances and
+    /// [`COMPONENT_MAX_TABLES`] tables are allowed, and `max_memory_bytes`
+    /// bounds the combined size of the component's memories.
+    pub fn for_component(
+        limits: &ResourceLimits,
+        event_dispatcher: Option<Arc<EventDispatcher>>,
+    ) -> Self {
+        let mut limiter = Self::new(limits, event_dispatcher);
+        limiter.limits = StoreLimitsBuilder::new()
+            .memory_size(limits.max_memory_bytes)
+            .table_elements(limits.max_table_elements as usize)
+            .instances(COMPONENT_MAX_INSTANCES)
+            .tables(COMPONENT_MAX_TABLES)
+            .memories(COMPONENT_MAX_MEMORIES.max(limits.max_memories as usize))
+            .build();
+        limiter.max_total_memory = Some(limits.max_memory_bytes);
+        limiter
+    }
+
+    /// Attribute `MemoryGrew` events to the sandbox `id`.
+    pub fn with_sandbox_id(mut self, id: SandboxId) -> Self {
+        self.sandbox_id = Some(id);
+        self
+    }
+
+    /// Charge memory growth to `account`.
+    pub fn with_account(mut self, account: Arc<ResourceAccount>) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    /// Record memory allocations with `collector`.
+    pub fn with_metrics_collector(mut self, collector: Arc<MetricsCollector>) -> Self {
+        self.metrics_collector = Some(collector);
+        self
+    }
+
+    /// Enforce limits with a custom limiter instead of the configured
+    /// resource limits.
+    pub fn set_custom(&mut self, limiter: BoxedResourceLimiter) {
+        self.custom = Some(limiter);
+    }
+
+    /// Get the limiter that enforces limits.
+    fn enforcing(&mut self) -> &mut dyn ResourceLimiter {
+        match &mut self.custom {
+            Some(custom) => custom.as_mut(),
+            None => &mut self.limits,
+        }
+    }
+
+    /// Get the limiter that enforces limits.
+    fn enforcing_ref(&self) -> &dyn ResourceLimiter {
+        match &self.custom {
+            Some(custom) => custom.as_ref(),
+            None => &self.limits,
+        }
+    }
+
+    /// Get the peak memory size in bytes.
+    ///
+    /// A pending growth is counted: a failed growth is reported before
+    /// the guest or host regains control, so by the time the peak can be
+    /// read any growth still pending has happened.
+    pub fn peak_memory(&self) -> usize {
+        self.pending_growth.map_or(self.peak_memory, |pending| {
+            self.peak_memory.max(pending.to_bytes)
+        })
+    }
+
+    /// Record the pending memory growth, if any, as having happened.
+    ///
+    /// This updates the peak memory, emits the `MemoryGrew` event and
+    /// records the allocation with the metrics collector.
+    pub fn confirm_growth(&mut self) {
+        let Some(PendingGrowth {
+            from_bytes,
+            to_bytes,
+            ..
+        }) = self.pending_growth.take()
+        else {
+            return;
+        };
+
+        self.peak_memory = self.peak_memory.max(to_bytes);
+        debug!(from_bytes, to_bytes, "Memory grew");
+
+        if let Some(dispatcher) = &self.event_dispatcher {
+            let event = SandboxEvent::MemoryGrew {
+                from_bytes,
+                to_bytes,
+            };
+            match self.sandbox_id {
+                Some(id) => dispatcher.emit_for(id.as_uuid(), event),
+                None => dispatcher.emit(event),
+            }
+        }
+
+        if let Some(collector) = &self.metrics_collector {
+            // Growth from zero is the initial allocation at instantiation
+            if from_bytes == 0 {
+                collector.record_initial_memory(to_bytes);
+            }
+            collector.record_memory_allocation(to_bytes);
+        }
+    }
+
+    /// Reset the peak memory size.
+    pub fn reset_peak(&mut self) {
+        self.peak_memory = 0;
+    }
+
+    /// Lend `bytes` of the memory limit to a child sandbox.
+    ///
+    /// Each memory is held to the configured limit less the memory on loan,
+    /// even under a custom limiter, until the returned loan is dropped.
+    pub fn lend_memory(&mut self, bytes: usize) -> MemoryLoan {
+        self.lent_memory.fetch_add(bytes, Ordering::Relaxed);
+        MemoryLoan {
+            lent: Arc::clone(&self.lent_memory),
+            bytes,
+        }
+    }
+
+    /// Get the memory on loan to child sandboxes, in bytes.
+    pub fn lent_memory(&self) -> usize {
+        self.lent_memory.load(Ordering::Relaxed)
+    }
+
+    /// Restore the configured memory limit, forgiving outstanding loans.
+    ///
+    /// Loans dropped afterwards no longer affect this limiter.
+    pub fn forgive_loans(&mut self) {
+        self.lent_memory = Arc::default();
+    }
+
+    /// Get the combined size of all memories in bytes.
+    pub fn total_memory(&self) -> usize {
+        self.total_memory
+    }
+
+    /// Release the memory charged to the account and counted toward the
+    /// total memory limit, once the memory it covers has been freed.
+    pub fn release_account_memory(&mut self) {
+        if let Some(account) = &self.account {
+            account.release_memory(self.account_charged);
+        }
+        self.account_charged = 0;
+        self.total_memory = 0;
+    }
+}
+
+impl Drop for SandboxLimiter {
+    fn drop(&mut self) {
+        self.confirm_growth();
+        self.release_account_memory();
+    }
+}
+
+/// A memory growth the limiter permitted but Wasmtime has not yet made.
+#[derive(Debug, Clone, Copy)]
+struct PendingGrowth {
+    /// Size before the growth, in bytes.
+    from_bytes: usize,
+    /// Size after the growth, in bytes.
+    to_bytes: usize,
+    /// Bytes charged to the total memory and the account.
+    growth: usize,
+}
+
+/// Memory a sandbox lent to a child, returned when the loan is dropped.
+///
+/// Created by [`SandboxLimiter::lend_memory`].
+#[derive(Debug)]
+pub struct MemoryLoan {
+    /// Memory on loan from the lending limiter.
+    lent: Arc<AtomicUsize>,
+    /// Size of this loan, in bytes.
+    bytes: usize,
+}
+
+impl Drop for MemoryLoan {
+    fn drop(&mut self) {
+        self.lent.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+impl ResourceLimiter for SandboxLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        // Any earlier growth would have failed before this call
+        self.confirm_growth();
+
+        // Wasmtime fails such growth even if the limiter permits it
+        if maximum.is_some_and(|max| desired > max) {
+            debug!(
+                desired_bytes = desired,
+                maximum, "Memory growth denied by declared maximum"
+            );
+            return Ok(false);
+        }
+
+        let allowed = self.enforcing().memory_growing(current, desired, maximum)?;
+        if !allowed {
+            return Ok(false);
+        }
+
+        let lent = self.lent_memory();
+        if lent > 0 && desired > self.memory_limit.saturating_sub(lent) {
+            debug!(
+                desired_bytes = desired,
+                lent_bytes = lent,
+                "Memory growth denied by memory lent to child sandboxes"
+            );
+            return Ok(false);
+        }
+
+        let growth = desired.saturating_sub(current);
+        if let Some(max) = self.max_total_memory {
+            if self.total_memory + growth > max {
+                debug!(
+                    total_bytes = self.total_memory,
+                    growth, max, "Memory growth denied by total memory limit"
+                );
+                return Ok(false);
+            }
+        }
+
+        if let Some(account) = &self.account {
+            if !account.charge_memory(growth) {
+                return Err(AccountExhausted {
+                    resource: AccountResource::Memory,
+                    limit: account.memory_limit() as u64,
+                }
+                .into());
+            }
+            self.account_charged += growth;
+        }
+        self.total_memory += growth;
+        self.pending_growth = Some(PendingGrowth {
+            from_bytes: current,
+            to_bytes: desired,
+            growth,
+        });
+
+        Ok(true)
+    }
+
+    fn memory_grow_failed(&mut self, error: wasmtime::Error) -> wasmtime::Result<()> {
+        // Undo the accounting of the growth that was permitted but not made
+        if let Some(pending) = self.pending_growth.take() {
+            self.total_memory -= pending.growth;
+            if let Some(account) = &self.account {
+                account.release_memory(pending.growth);
+                self.account_charged -= pending.growth;
+            }
+            debug!(
+                from_bytes = pending.from_bytes,
+                to_bytes = pending.to_bytes,
+                "Memory growth failed"
+            );
+        }
+        self.enforcing().memory_grow_failed(error)
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        self.confirm_growth();
+        self.enforcing().table_growing(current, desired, maximum)
+    }
+
+    fn table_grow_failed(&mut self, error: wasmtime::Error) -> wasmtime::Result<()> {
+        self.enforcing().table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.enforcing_ref().instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.enforcing_ref().tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.enforcing_ref().memories()
+    }
+}
+
+impl std::fmt::Debug for SandboxLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SandboxLimiter")
+            .field("custom", &self.custom.is_some())
+            .field("peak_memory", &self.peak_memory)
+            .field("lent_memory", &self.lent_memory())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aegis_observe::{CollectingSubscriber, EventSubscriber};
+
+    const PAGE: usize = 65536;
+
+    #[test]
+    fn test_failed_growth_is_not_recorded() {
+        let dispatcher = Arc::new(EventDispatcher::new());
+        let events = Arc::new(CollectingSubscriber::new(10));
+        dispatcher.subscribe(Arc::clone(&events) as Arc<dyn EventSubscriber>);
+        let collector = Arc::new(MetricsCollector::new());
+        let account = Arc::new(ResourceAccount::new(0, 8 * PAGE));
+
+        let limits = ResourceLimits {
+            max_memory_bytes: 8 * PAGE,
+            ..ResourceLimits::default()
+        };
+        let mut limiter = SandboxLimiter::new(&limits, Some(dispatcher))
+            .with_metrics_collector(Arc::clone(&collector))
+            .with_account(Arc::clone(&account));
+
+        assert!(limiter.memory_growing(0, PAGE, None).unwrap());
+        limiter.confirm_growth();
+        assert_eq!(events.len(), 1);
+
+        // Growth past the declared maximum is denied up front
+        assert!(
+            !limiter
+                .memory_growing(PAGE, 4 * PAGE, Some(2 * PAGE))
+                .unwrap()
+        );
+
+        // Growth that fails after being permitted leaves no trace
+        assert!(limiter.memory_growing(PAGE, 4 * PAGE, None).unwrap());
+        limiter
+            .memory_grow_failed(wasmtime::Error::msg("allocation failed"))
+            .unwrap();
+        limiter.confirm_growth();
+        assert_eq!(limiter.peak_memory(), PAGE);
+        assert_eq!(limiter.total_memory(), PAGE);
+        assert_eq!(account.memory_used(), PAGE);
+        assert_eq!(events.len(), 1);
+        assert_eq!(collector.snapshot().memory.peak_memory, PAGE);
+        assert_eq!(collector.snapshot().memory.allocation_count, 1);
+
+        // Successful growth is recorded once confirmed by the next request
+        assert!(limiter.memory_growing(PAGE, 2 * PAGE, None).unwrap());
+        assert_eq!(limiter.peak_memory(), 2 * PAGE);
+        assert_eq!(events.len(), 1);
+        assert!(limiter.memory_growing(2 * PAGE, 3 * PAGE, None).unwrap());
+        assert_eq!(events.len(), 2);
+        drop(limiter);
+        assert_eq!(events.len(), 3);
+        assert_eq!(collector.snapshot().memory.peak_memory, 3 * PAGE);
+    }
+}
@@ -3,15 +3,25 @@
 //! This module provides types for loading, validating, and inspecting
 //! WebAssembly modules before execution.
 
-use std::path::Path;
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use tracing::{debug, info};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+use wasmparser::{Payload, TypeRef};
+use wasmtime::component::Component;
 use wasmtime::{ExternType, Module};
 
+use crate::component::ValidatedComponent;
 use crate::engine::AegisEngine;
 use crate::error::{ModuleError, ModuleResult};
 
+/// Size of a WASM memory page in bytes.
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
 /// A validated WebAssembly module ready for instantiation.
 ///
 /// `ValidatedModule` wraps a Wasmtime module with additional metadata
@@ -23,6 +33,8 @@ pub struct ValidatedModule {
     inner: Module,
     /// Metadata extracted from the module.
     metadata: ModuleMetadata,
+    /// The binary the module was compiled from, if known.
+    source: Option<Arc<[u8]>>,
 }
 
 impl ValidatedModule {
@@ -63,6 +75,170 @@ impl ValidatedModule {
             .iter()
             .any(|i| i.module == module && i.name == name)
     }
+
+    /// Estimate the memory limit needed to instantiate the module, in bytes.
+    ///
+    /// This is the declared minimum of the module's largest memory, since
+    /// [`ResourceLimits::max_memory_bytes`](crate::ResourceLimits::max_memory_bytes)
+    /// applies to each memory. Imported memories are not counted, and the
+    /// estimate says nothing about how far the module grows at run time.
+    pub fn estimated_min_memory_bytes(&self) -> u64 {
+        self.inner
+            .resources_required()
+            .max_initial_memory_size
+            .map_or(0, |pages| pages.saturating_mul(WASM_PAGE_SIZE))
+    }
+
+    /// Estimate the table element limit needed to instantiate the module.
+    ///
+    /// This is the declared minimum of the module's largest table; imported
+    /// tables are not counted.
+    pub fn estimated_min_table_elements(&self) -> u64 {
+        self.inner
+            .resources_required()
+            .max_initial_table_size
+            .unwrap_or(0)
+    }
+
+    /// Get the number of memories the module defines.
+    pub fn defined_memories(&self) -> u32 {
+        self.inner.resources_required().num_memories
+    }
+
+    /// Serialize the compiled module.
+    ///
+    /// The result can be loaded with [`ModuleLoader::load_precompiled`] by an
+    /// engine with the same configuration, skipping compilation.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization fails.
+    pub fn serialize(&self) -> ModuleResult<Vec<u8>> {
+        Ok(self.inner.serialize()?)
+    }
+
+    /// Get the binary the module was compiled from.
+    ///
+    /// This is `None` for modules loaded with
+    /// [`ModuleLoader::load_precompiled`].
+    pub fn source(&self) -> Option<&[u8]> {
+        self.source.as_deref()
+    }
+
+    /// Get the SHA-256 hash of the bytes the module was loaded from.
+    ///
+    /// This identifies the module across loads: identical input bytes give
+    /// the same hash. Bytes given as WAT text are hashed as text, and
+    /// [`ModuleLoader::load_file_cached`] hashes the source file rather
+    /// than the cache. For [`ModuleLoader::load_precompiled`] it is the hash
+    /// of the precompiled bytes.
+    pub fn hash(&self) -> &[u8; 32] {
+        &self.metadata.hash
+    }
+
+    /// Get the module's [`hash`](Self::hash) as a [`ContentHash`].
+    pub fn content_hash(&self) -> ContentHash {
+        ContentHash(self.metadata.hash)
+    }
+
+    /// Get the module's [`hash`](Self::hash) as lowercase hex, for use in
+    /// logs and reports.
+    pub fn id_string(&self) -> String {
+        self.content_hash().to_string()
+    }
+
+    /// Disassemble the module to WAT text.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModuleError::SourceUnavailable` if the module's binary was
+    /// not kept.
+    pub fn to_wat(&self) -> ModuleResult<String> {
+        let source = self.source().ok_or(ModuleError::SourceUnavailable)?;
+        wasmprinter::print_bytes(source).map_err(|e| ModuleError::Invalid(e.to_string()))
+    }
+
+    /// Disassemble a single exported function to WAT text.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModuleError::FunctionNotFound` if `name` is not an exported
+    /// function defined by the module, or `ModuleError::SourceUnavailable`
+    /// if the module's binary was not kept.
+    pub fn function_to_wat(&self, name: &str) -> ModuleResult<String> {
+        let source = self.source().ok_or(ModuleError::SourceUnavailable)?;
+        let not_found = || ModuleError::FunctionNotFound(name.to_string());
+        let invalid = |e: wasmparser::BinaryReaderError| ModuleError::Invalid(e.to_string());
+
+        let mut imported = 0;
+        let mut index = None;
+        for payload in wasmparser::Parser::new(0).parse_all(source) {
+            match payload.map_err(invalid)? {
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        if matches!(import.map_err(invalid)?.ty, TypeRef::Func(_)) {
+                            imported += 1;
+                        }
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export.map_err(invalid)?;
+                        if export.name == name && export.kind == wasmparser::ExternalKind::Func {
+                            index = Some(export.index);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Imported functions have no body to print
+        let defined = index
+            .and_then(|i| i.checked_sub(imported))
+            .ok_or_else(not_found)? as usize;
+
+        let wat = self.to_wat()?;
+        top_level_fields(&wat)
+            .filter(|field| field.starts_with("(func"))
+            .nth(defined)
+            .map(|field| {
+                // Undo the module-level indentation
+                let lines: Vec<_> = field
+                    .lines()
+                    .map(|line| line.strip_prefix("  ").unwrap_or(line))
+                    .collect();
+                lines.join("\n")
+            })
+            .ok_or_else(not_found)
+    }
+}
+
+/// Split printed WAT into its top-level module fields.
+///
+/// Relies on `wasmprinter` placing each field on lines indented by exactly
+/// two spaces, with the contents of the field indented further.
+fn top_level_fields(wat: &str) -> impl Iterator<Item = &str> {
+    let mut starts: Vec<usize> = Vec::new();
+    let mut offset = 0;
+    for line in wat.split_inclusive('\n') {
+        if line.starts_with("  (") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+
+    let ends: Vec<usize> = starts.iter().skip(1).copied().chain([wat.len()]).collect();
+    starts.into_iter().zip(ends).map(move |(start, end)| {
+        let field = wat[start..end].trim_end();
+        // The last field is followed by the module's closing paren
+        let field = if end == wat.len() {
+            field.strip_suffix(')').unwrap_or(field)
+        } else {
+            field
+        };
+        field.trim()
+    })
 }
 
 impl std::fmt::Debug for ValidatedModule {
@@ -75,6 +251,38 @@ impl std::fmt::Debug for ValidatedModule {
     }
 }
 
+/// SHA-256 hash of a module's bytes, e.g. for keying compiled code caches.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    /// Hash `bytes`.
+    pub fn of(bytes: &[u8]) -> Self {
+        Self(Sha256::digest(bytes).into())
+    }
+
+    /// Get the raw hash bytes.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ContentHash {
+    /// Formats the hash as lowercase hex.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ContentHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ContentHash({})", self)
+    }
+}
+
 /// Metadata extracted from a WASM module.
 #[derive(Debug, Clone, Default)]
 pub struct ModuleMetadata {
@@ -86,6 +294,8 @@ pub struct ModuleMetadata {
     pub imports: Vec<ImportInfo>,
     /// Memory requirements.
     pub memories: Vec<MemoryInfo>,
+    /// SHA-256 hash of the bytes the module was loaded from.
+    pub hash: [u8; 32],
 }
 
 /// Information about an exported item.
@@ -144,6 +354,36 @@ pub enum ImportKind {
     Table,
 }
 
+impl ImportKind {
+    /// Get the kind name, e.g. `"function"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImportKind::Function { .. } => "function",
+            ImportKind::Memory => "memory",
+            ImportKind::Global => "global",
+            ImportKind::Table => "table",
+        }
+    }
+
+    /// Check if an extern definition has this kind.
+    pub fn matches(&self, item: &wasmtime::Extern) -> bool {
+        matches!(
+            (self, item),
+            (ImportKind::Function { .. }, wasmtime::Extern::Func(_))
+                | (ImportKind::Memory, wasmtime::Extern::Memory(_))
+                | (ImportKind::Memory, wasmtime::Extern::SharedMemory(_))
+                | (ImportKind::Global, wasmtime::Extern::Global(_))
+                | (ImportKind::Table, wasmtime::Extern::Table(_))
+        )
+    }
+}
+
+impl std::fmt::Display for ImportKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 /// Information about a memory definition.
 #[derive(Debug, Clone)]
 pub struct MemoryInfo {
@@ -155,6 +395,207 @@ pub struct MemoryInfo {
     pub memory64: bool,
 }
 
+/// A limit enforced by [`LoaderLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoaderLimit {
+    /// Size of the module in bytes.
+    Bytes,
+    /// Number of functions defined by the module.
+    Functions,
+    /// Number of imports.
+    Imports,
+    /// Number of exports.
+    Exports,
+    /// Minimum size of any memory, in pages.
+    MemoryMinPages,
+}
+
+impl LoaderLimit {
+    /// Get a short name for the limit.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bytes => "bytes",
+            Self::Functions => "functions",
+            Self::Imports => "imports",
+            Self::Exports => "exports",
+            Self::MemoryMinPages => "memory minimum pages",
+        }
+    }
+}
+
+impl std::fmt::Display for LoaderLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Bounds on module size and complexity, checked before compilation.
+///
+/// A small module can still declare thousands of functions or a huge
+/// initial memory, costing compile time or allocations before any
+/// `ResourceLimits` apply. Each limit is disabled when `None`, which is
+/// the default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoaderLimits {
+    /// Maximum module size in bytes.
+    pub max_bytes: Option<usize>,
+    /// Maximum number of functions defined by the module.
+    pub max_functions: Option<u32>,
+    /// Maximum number of imports.
+    pub max_imports: Option<u32>,
+    /// Maximum initial size of any memory, in 64KB pages.
+    pub max_memory_min_pages: Option<u64>,
+    /// Maximum number of exports.
+    pub max_exports: Option<u32>,
+}
+
+impl LoaderLimits {
+    /// Create limits with every check disabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum module size in bytes.
+    pub fn with_max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Set the maximum number of defined functions.
+    pub fn with_max_functions(mut self, count: u32) -> Self {
+        self.max_functions = Some(count);
+        self
+    }
+
+    /// Set the maximum number of imports.
+    pub fn with_max_imports(mut self, count: u32) -> Self {
+        self.max_imports = Some(count);
+        self
+    }
+
+    /// Set the maximum initial memory size in pages.
+    pub fn with_max_memory_min_pages(mut self, pages: u64) -> Self {
+        self.max_memory_min_pages = Some(pages);
+        self
+    }
+
+    /// Set the maximum number of exports.
+    pub fn with_max_exports(mut self, count: u32) -> Self {
+        self.max_exports = Some(count);
+        self
+    }
+
+    /// Check if every limit is disabled.
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check a module against the limits without compiling it.
+    ///
+    /// `bytes` may be a binary module or WAT text; the size limit applies
+    /// to `bytes` as given.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModuleError::LimitExceeded` for the first limit exceeded,
+    /// or `ModuleError::Invalid` if the module cannot be parsed.
+    pub fn check(&self, bytes: &[u8]) -> ModuleResult<()> {
+        if self.is_unlimited() {
+            return Ok(());
+        }
+        check_limit(
+            LoaderLimit::Bytes,
+            self.max_bytes.map(|b| b as u64),
+            bytes.len() as u64,
+        )?;
+
+        let wasm = wasm_binary(bytes)?;
+        let invalid = |e: wasmparser::BinaryReaderError| ModuleError::Invalid(e.to_string());
+
+        for payload in wasmparser::Parser::new(0).parse_all(&wasm) {
+            match payload.map_err(invalid)? {
+                Payload::FunctionSection(reader) => {
+                    check_limit(
+                        LoaderLimit::Functions,
+                        self.max_functions.map(u64::from),
+                        reader.count().into(),
+                    )?;
+                }
+                Payload::ImportSection(reader) => {
+                    check_limit(
+                        LoaderLimit::Imports,
+                        self.max_imports.map(u64::from),
+                        reader.count().into(),
+                    )?;
+                    for import in reader {
+                        if let TypeRef::Memory(memory) = import.map_err(invalid)?.ty {
+                            self.check_memory(&memory)?;
+                        }
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    for memory in reader {
+                        self.check_memory(&memory.map_err(invalid)?)?;
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    check_limit(
+                        LoaderLimit::Exports,
+                        self.max_exports.map(u64::from),
+                        reader.count().into(),
+                    )?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check a memory's initial size against the page limit.
+    fn check_memory(&self, memory: &wasmparser::MemoryType) -> ModuleResult<()> {
+        check_limit(
+            LoaderLimit::MemoryMinPages,
+            self.max_memory_min_pages,
+            memory.initial,
+        )
+    }
+}
+
+/// A `Hasher` over SHA-256, whose result does not depend on the process
+/// or Rust version the way `DefaultHasher`'s may.
+struct Sha256Hasher(Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    /// Get the first 8 bytes of the digest.
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        let mut first = [0u8; 8];
+        first.copy_from_slice(&digest[..8]);
+        u64::from_le_bytes(first)
+    }
+}
+
+/// Fail with `LimitExceeded` if `actual` is above an enabled limit.
+fn check_limit(kind: LoaderLimit, limit: Option<u64>, actual: u64) -> ModuleResult<()> {
+    let Some(limit) = limit else {
+        return Ok(());
+    };
+    if actual > limit {
+        warn!(kind = %kind, limit, actual, "Module exceeds loader limit");
+        return Err(ModuleError::LimitExceeded {
+            kind,
+            limit,
+            actual,
+        });
+    }
+    Ok(())
+}
+
 /// Loader for WASM modules.
 ///
 /// `ModuleLoader` provides methods for loading and validating WASM modules
@@ -162,12 +603,31 @@ pub struct MemoryInfo {
 pub struct ModuleLoader {
     /// Reference to the engine used for compilation.
     engine: Arc<AegisEngine>,
+    /// Size and complexity bounds checked before compilation.
+    limits: LoaderLimits,
 }
 
 impl ModuleLoader {
     /// Create a new module loader with the given engine.
     pub fn new(engine: Arc<AegisEngine>) -> Self {
-        Self { engine }
+        Self {
+            engine,
+            limits: LoaderLimits::default(),
+        }
+    }
+
+    /// Set the size and complexity limits checked before compilation.
+    ///
+    /// Limits do not apply to [`load_precompiled`](Self::load_precompiled),
+    /// whose input is trusted.
+    pub fn with_limits(mut self, limits: LoaderLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Get the loader's limits.
+    pub fn limits(&self) -> &LoaderLimits {
+        &self.limits
     }
 
     /// Load and validate a module from raw bytes.
@@ -177,9 +637,52 @@ impl ModuleLoader {
     /// Returns an error if the bytes are not a valid WASM module.
     pub fn load_bytes(&self, bytes: &[u8]) -> ModuleResult<ValidatedModule> {
         debug!(size = bytes.len(), "Loading WASM module from bytes");
+        self.compile(bytes, ContentHash::of(bytes))
+    }
+
+    /// Load and validate a module read from `reader`.
+    ///
+    /// The module is hashed as it is read, and reading stops with
+    /// `ModuleError::LimitExceeded` as soon as it exceeds
+    /// [`LoaderLimits::max_bytes`]; `actual` is then the number of bytes
+    /// read so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, the module is too large, or it is
+    /// not a valid WASM module.
+    pub fn load_reader(&self, mut reader: impl Read) -> ModuleResult<ValidatedModule> {
+        debug!("Loading WASM module from reader");
+
+        let mut bytes = Vec::new();
+        let mut hasher = Sha256::new();
+        let mut chunk = [0u8; 64 * 1024];
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            hasher.update(&chunk[..read]);
+            bytes.extend_from_slice(&chunk[..read]);
+            check_limit(
+                LoaderLimit::Bytes,
+                self.limits.max_bytes.map(|b| b as u64),
+                bytes.len() as u64,
+            )?;
+        }
 
-        let module = Module::new(self.engine.inner(), bytes)?;
-        let metadata = self.extract_metadata(&module);
+        self.compile(&bytes, ContentHash(hasher.finalize().into()))
+    }
+
+    /// Check, compile and validate a module whose bytes hash to
+    /// `content_hash`.
+    fn compile(&self, bytes: &[u8], content_hash: ContentHash) -> ModuleResult<ValidatedModule> {
+        self.limits.check(bytes)?;
+        let source = binary_source(bytes)?;
+        let module = Module::new(self.engine.inner(), &source)?;
+        let metadata = self.extract_metadata(&module, content_hash);
 
         info!(
             name = ?metadata.name,
@@ -191,6 +694,7 @@ impl ModuleLoader {
         Ok(ValidatedModule {
             inner: module,
             metadata,
+            source: Some(source),
         })
     }
 
@@ -202,21 +706,161 @@ impl ModuleLoader {
     pub fn load_file(&self, path: &Path) -> ModuleResult<ValidatedModule> {
         debug!(path = %path.display(), "Loading WASM module from file");
 
-        let module = Module::from_file(self.engine.inner(), path)?;
-        let metadata = self.extract_metadata(&module);
+        let bytes = std::fs::read(path)?;
+        self.compile(&bytes, ContentHash::of(&bytes))
+    }
 
-        info!(
-            path = %path.display(),
-            name = ?metadata.name,
-            exports = metadata.exports.len(),
-            imports = metadata.imports.len(),
-            "Loaded WASM module from file"
-        );
+    /// Load and validate a component-model component from raw bytes.
+    ///
+    /// `bytes` may be a binary component or WAT text. Run the component
+    /// with a [`ComponentSandbox`](crate::ComponentSandbox).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModuleError::ComponentModelDisabled` if the engine was built
+    /// without component-model support, or an error if the bytes are not a
+    /// valid component.
+    pub fn load_component_bytes(&self, bytes: &[u8]) -> ModuleResult<ValidatedComponent> {
+        debug!(size = bytes.len(), "Loading WASM component from bytes");
+        self.compile_component(bytes)
+    }
 
-        Ok(ValidatedModule {
+    /// Load and validate a component-model component from a file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, the engine was built
+    /// without component-model support, or the file is not a valid component.
+    pub fn load_component_file(&self, path: &Path) -> ModuleResult<ValidatedComponent> {
+        debug!(path = %path.display(), "Loading WASM component from file");
+        let bytes = std::fs::read(path)?;
+        self.compile_component(&bytes)
+    }
+
+    /// Check and compile a component.
+    fn compile_component(&self, bytes: &[u8]) -> ModuleResult<ValidatedComponent> {
+        if !self.engine.config().component_model {
+            return Err(ModuleError::ComponentModelDisabled);
+        }
+        self.limits.check(bytes)?;
+
+        let component = Component::new(self.engine.inner(), bytes)?;
+        let component = ValidatedComponent::new(&self.engine, component, ContentHash::of(bytes));
+
+        info!(exports = component.exports().len(), "Loaded WASM component");
+
+        Ok(component)
+    }
+
+    /// Load a module previously produced by [`ValidatedModule::serialize`].
+    ///
+    /// # Safety
+    ///
+    /// The bytes are loaded as native code without validation, so they must
+    /// come from a trusted source such as this process or a cache directory
+    /// that only the host can write to. Wasmtime rejects bytes produced by an
+    /// incompatible engine, but cannot detect tampering.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes were not produced by a compatible engine.
+    pub unsafe fn load_precompiled(&self, bytes: &[u8]) -> ModuleResult<ValidatedModule> {
+        debug!(size = bytes.len(), "Loading precompiled WASM module");
+
+        // SAFETY: upheld by the caller.
+        let module = unsafe { Module::deserialize(self.engine.inner(), bytes)? };
+        Ok(self.validated(module, None, ContentHash::of(bytes)))
+    }
+
+    /// Load a module from a file, caching the compiled code next to it.
+    ///
+    /// The compiled module is written to a `.cwasm` sidecar whose name
+    /// includes the module's [`ContentHash`] and a hash of the engine's
+    /// configuration, so a change to either produces a new cache entry. Later calls load the
+    /// sidecar instead of recompiling. Failing to write the sidecar is not an
+    /// error.
+    ///
+    /// The directory containing `path` must only be writable by trusted
+    /// users, since sidecars are loaded as native code.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a valid WASM module.
+    pub fn load_file_cached(&self, path: &Path) -> ModuleResult<ValidatedModule> {
+        let bytes = std::fs::read(path)?;
+        self.limits.check(&bytes)?;
+        let content_hash = ContentHash::of(&bytes);
+        let cache_path = self.cache_path(path, &content_hash);
+
+        if cache_path.exists() {
+            // SAFETY: sidecars are only written by this method, and the
+            // directory is trusted per the documented contract.
+            match unsafe { Module::deserialize_file(self.engine.inner(), &cache_path) } {
+                Ok(module) => {
+                    debug!(cache = %cache_path.display(), "Loaded WASM module from cache");
+                    return Ok(self.validated(module, Some(binary_source(&bytes)?), content_hash));
+                }
+                Err(e) => {
+                    warn!(
+                        cache = %cache_path.display(),
+                        error = %e,
+                        "Ignoring unusable module cache"
+                    );
+                }
+            }
+        }
+
+        let module = self.compile(&bytes, content_hash)?;
+        match module.serialize() {
+            Ok(serialized) => {
+                if let Err(e) = std::fs::write(&cache_path, serialized) {
+                    warn!(
+                        cache = %cache_path.display(),
+                        error = %e,
+                        "Failed to write module cache"
+                    );
+                }
+            }
+            Err(e) => warn!(error = %e, "Failed to serialize module for cache"),
+        }
+
+        Ok(module)
+    }
+
+    /// Get the cache sidecar path for a module file with the given content
+    /// hash.
+    fn cache_path(&self, path: &Path, content_hash: &ContentHash) -> PathBuf {
+        let mut hasher = Sha256Hasher(Sha256::new());
+        self.engine
+            .inner()
+            .precompile_compatibility_hash()
+            .hash(&mut hasher);
+
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        path.with_file_name(format!(
+            "{}.{}.{:016x}.cwasm",
+            stem,
+            content_hash,
+            hasher.finish()
+        ))
+    }
+
+    /// Wrap a compiled module with its metadata.
+    fn validated(
+        &self,
+        module: Module,
+        source: Option<Arc<[u8]>>,
+        content_hash: ContentHash,
+    ) -> ValidatedModule {
+        let metadata = self.extract_metadata(&module, content_hash);
+        ValidatedModule {
             inner: module,
             metadata,
-        })
+            source,
+        }
     }
 
     /// Load and validate a module from WAT (WebAssembly Text) format.
@@ -229,12 +873,12 @@ impl ModuleLoader {
     pub fn load_wat(&self, wat: &str) -> ModuleResult<ValidatedModule> {
         debug!(size = wat.len(), "Loading WASM module from WAT");
 
-        let wasm = wat::parse_str(wat).map_err(|e| ModuleError::Invalid(e.to_string()))?;
+        let wasm = parse_wat(wat)?;
         self.load_bytes(&wasm)
     }
 
     /// Extract metadata from a compiled module.
-    fn extract_metadata(&self, module: &Module) -> ModuleMetadata {
+    fn extract_metadata(&self, module: &Module, content_hash: ContentHash) -> ModuleMetadata {
         let name = module.name().map(String::from);
 
         let exports = module
@@ -271,10 +915,43 @@ impl ModuleLoader {
             exports,
             imports,
             memories,
+            hash: content_hash.0,
         }
     }
 }
 
+/// Get the binary form of a module given as binary or WAT text.
+fn binary_source(bytes: &[u8]) -> ModuleResult<Arc<[u8]>> {
+    Ok(Arc::from(wasm_binary(bytes)?.as_ref()))
+}
+
+/// Get the binary form of bytes that are either binary or WAT text.
+fn wasm_binary(bytes: &[u8]) -> ModuleResult<Cow<'_, [u8]>> {
+    if bytes.starts_with(b"\0asm") {
+        return Ok(Cow::Borrowed(bytes));
+    }
+
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| ModuleError::Invalid("input is neither WASM nor UTF-8 WAT text".into()))?;
+    parse_wat(text).map(Cow::Owned)
+}
+
+/// Convert WAT text to binary, keeping the location of any parse error.
+fn parse_wat(text: &str) -> ModuleResult<Vec<u8>> {
+    let located = |e: wast::Error| {
+        let (line, column) = e.span().linecol_in(text);
+        ModuleError::WatParse {
+            message: e.message(),
+            line: line + 1,
+            column: column + 1,
+        }
+    };
+
+    let buffer = wast::parser::ParseBuffer::new(text).map_err(located)?;
+    let mut wat = wast::parser::parse::<wast::Wat>(&buffer).map_err(located)?;
+    wat.encode().map_err(located)
+}
+
 fn extern_type_to_export_kind(ty: ExternType) -> ExportKind {
     match ty {
         ExternType::Func(func) => ExportKind::Function {
@@ -389,4 +1066,247 @@ mod tests {
         let result = loader.load_bytes(&[0, 1, 2, 3]);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_wat_parse_error_location() {
+        let loader = create_loader();
+        let wat = "(module\n  (func (export \"f\")\n    i32.bogus))";
+
+        match loader.load_wat(wat) {
+            Err(ModuleError::WatParse {
+                message,
+                line,
+                column,
+            }) => {
+                assert_eq!((line, column), (3, 5));
+                assert!(message.contains("unknown operator"), "{message}");
+            }
+            other => panic!("expected a WAT parse error, got {other:?}"),
+        }
+
+        // Text passed as bytes is reported the same way
+        assert!(matches!(
+            loader.load_bytes(wat.as_bytes()),
+            Err(ModuleError::WatParse { line: 3, .. })
+        ));
+    }
+
+    const ADD_WAT: &str = r#"
+        (module
+            (func (export "add") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.add
+            )
+        )
+    "#;
+
+    #[test]
+    fn test_precompiled_round_trip() {
+        use crate::config::SandboxConfig;
+        use crate::sandbox::Sandbox;
+
+        let module = create_loader().load_wat(ADD_WAT).unwrap();
+        let bytes = module.serialize().unwrap();
+
+        // A fresh engine with the same configuration can load the code
+        let engine = Arc::new(AegisEngine::new(EngineConfig::default()).unwrap());
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+        let restored = unsafe { loader.load_precompiled(&bytes) }.unwrap();
+        assert!(restored.has_export("add"));
+
+        let mut sandbox = Sandbox::<()>::new(engine, (), SandboxConfig::default()).unwrap();
+        sandbox.load_module(&restored).unwrap();
+        let result: i32 = sandbox.call("add", (2i32, 3i32)).unwrap();
+        assert_eq!(result, 5);
+    }
+
+    #[test]
+    fn test_precompiled_incompatible_engine() {
+        let module = create_loader().load_wat(ADD_WAT).unwrap();
+        let bytes = module.serialize().unwrap();
+
+        let engine = Arc::new(AegisEngine::new(EngineConfig::default().with_fuel(false)).unwrap());
+        let loader = ModuleLoader::new(engine);
+        assert!(unsafe { loader.load_precompiled(&bytes) }.is_err());
+    }
+
+    #[test]
+    fn test_load_file_cached() {
+        let dir = std::env::temp_dir().join(format!("aegis-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("add.wasm");
+        let bytes = wat::parse_str(ADD_WAT).unwrap();
+        std::fs::write(&path, &bytes).unwrap();
+
+        let loader = create_loader();
+        let first = loader.load_file_cached(&path).unwrap();
+        assert!(first.has_export("add"));
+
+        let sidecars: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "cwasm"))
+            .collect();
+        assert_eq!(sidecars.len(), 1);
+
+        // The sidecar is keyed on the module's content hash
+        let name = sidecars[0].file_name().to_string_lossy().into_owned();
+        assert!(name.starts_with(&format!("add.{}.", ContentHash::of(&bytes))));
+
+        let second = loader.load_file_cached(&path).unwrap();
+        assert!(second.has_export("add"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_loader_limits_bytes() {
+        let loader = create_loader().with_limits(LoaderLimits::new().with_max_bytes(64));
+
+        let wasm = wat::parse_str(ADD_WAT).unwrap();
+        assert!(wasm.len() <= 64);
+        loader.load_bytes(&wasm).unwrap();
+
+        let mut oversized = wasm.clone();
+        oversized.resize(1024, 0);
+        match loader.load_bytes(&oversized) {
+            Err(ModuleError::LimitExceeded {
+                kind: LoaderLimit::Bytes,
+                limit: 64,
+                actual: 1024,
+            }) => {}
+            other => panic!("expected bytes limit error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_loader_limits_memory_and_counts() {
+        let limits = LoaderLimits::new()
+            .with_max_memory_min_pages(16)
+            .with_max_functions(1)
+            .with_max_imports(1)
+            .with_max_exports(2);
+        let loader = create_loader().with_limits(limits);
+
+        let exceeded = |wat: &str| match loader.load_wat(wat) {
+            Err(ModuleError::LimitExceeded { kind, actual, .. }) => (kind, actual),
+            other => panic!("expected limit error, got {other:?}"),
+        };
+
+        loader.load_wat(r#"(module (memory 16))"#).unwrap();
+        assert_eq!(
+            exceeded(r#"(module (memory 65536))"#),
+            (LoaderLimit::MemoryMinPages, 65536)
+        );
+        assert_eq!(
+            exceeded(r#"(module (import "env" "memory" (memory 17)))"#),
+            (LoaderLimit::MemoryMinPages, 17)
+        );
+        assert_eq!(
+            exceeded(r#"(module (func) (func))"#),
+            (LoaderLimit::Functions, 2)
+        );
+        assert_eq!(
+            exceeded(r#"(module (import "a" "b" (func)) (import "a" "c" (func)))"#),
+            (LoaderLimit::Imports, 2)
+        );
+        assert_eq!(
+            exceeded(
+                r#"(module (func $f) (export "a" (func $f)) (export "b" (func $f)) (export "c" (func $f)))"#
+            ),
+            (LoaderLimit::Exports, 3)
+        );
+    }
+
+    #[test]
+    fn test_to_wat() {
+        let loader = create_loader();
+        let module = loader
+            .load_wat(
+                r#"
+                (module
+                    (import "env" "log" (func $log (param i32)))
+                    (func $helper (result i32) i32.const 7)
+                    (func $add (export "add") (param i32 i32) (result i32)
+                        local.get 0
+                        local.get 1
+                        i32.add
+                    )
+                    (export "log" (func $log))
+                )
+            "#,
+            )
+            .unwrap();
+
+        let wat = module.to_wat().unwrap();
+        assert!(wat.contains("(func $add"));
+        assert!(wat.contains("i32.add"));
+
+        let add = module.function_to_wat("add").unwrap();
+        assert!(add.starts_with("(func $add"));
+        assert!(add.contains("\n  i32.add"));
+        assert!(add.ends_with("\n)"));
+        assert!(!add.contains("$helper"));
+
+        assert!(matches!(
+            module.function_to_wat("log"),
+            Err(ModuleError::FunctionNotFound(_))
+        ));
+        assert!(matches!(
+            module.function_to_wat("missing"),
+            Err(ModuleError::FunctionNotFound(_))
+        ));
+
+        let serialized = module.serialize().unwrap();
+        // SAFETY: produced by the same engine above.
+        let precompiled = unsafe { loader.load_precompiled(&serialized) }.unwrap();
+        assert!(matches!(
+            precompiled.to_wat(),
+            Err(ModuleError::SourceUnavailable)
+        ));
+    }
+
+    #[test]
+    fn test_load_reader_hashes_content() {
+        let loader = create_loader();
+
+        // The empty module: magic number and version
+        let bytes = b"\0asm\x01\0\0\0";
+        let module = loader.load_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            module.id_string(),
+            "93a44bbb96c751218e4c00d479e4c14358122a389acca16205b1e4d0dc5f9476"
+        );
+        assert_eq!(
+            module.content_hash(),
+            loader.load_bytes(bytes).unwrap().content_hash()
+        );
+
+        let wasm = wat::parse_str(ADD_WAT).unwrap();
+        let limited = create_loader().with_limits(LoaderLimits::new().with_max_bytes(8));
+        assert!(matches!(
+            limited.load_reader(wasm.as_slice()),
+            Err(ModuleError::LimitExceeded {
+                kind: LoaderLimit::Bytes,
+                limit: 8,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_module_hash_identity() {
+        let loader = create_loader();
+        let wasm = wat::parse_str(ADD_WAT).unwrap();
+
+        let first = loader.load_bytes(&wasm).unwrap();
+        let second = loader.load_bytes(&wasm).unwrap();
+        assert_eq!(first.hash(), second.hash());
+        assert_eq!(first.id_string(), second.id_string());
+        assert_eq!(first.id_string().len(), 64);
+
+        let other = loader.load_wat("(module)").unwrap();
+        assert_ne!(first.hash(), other.hash());
+    }
 }
@@ -0,0 +1,453 @@
+//! Pooling of warm sandboxes.
+//!
+//! This module provides the `SandboxPool` type, which keeps idle sandboxes
+//! around so that requests can skip store and linker setup.
+
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+use tracing::debug;
+
+use crate::config::SandboxConfig;
+use crate::engine::SharedEngine;
+use crate::error::{ExecutionResult, PoolError};
+use crate::sandbox::{Resettable, Sandbox};
+
+/// Default maximum number of idle sandboxes kept by a pool.
+const DEFAULT_MAX_IDLE: usize = 16;
+
+/// A pool of reusable sandboxes sharing one engine and configuration.
+///
+/// Sandboxes are handed out by [`acquire`](Self::acquire) and returned to
+/// the pool when the guard is dropped. Returned sandboxes are reset, so each
+/// acquisition starts with no module loaded and full fuel. Registered host
+/// functions survive a reset, and so does user state unless
+/// [`with_resettable_state`](Self::with_resettable_state) is used.
+///
+/// # Example
+///
+/// ```ignore
+/// use aegis_core::{SandboxConfig, SandboxPool};
+///
+/// let pool = SandboxPool::<()>::new(engine, SandboxConfig::default()).with_max_idle(8);
+///
+/// let mut sandbox = pool.acquire()?;
+/// sandbox.load_module(&module)?;
+/// let result: i32 = sandbox.call("add", (2i32, 3i32))?;
+/// // Returned to the pool here
+/// ```
+///
+/// With [`with_max_size`](Self::with_max_size) the pool stops creating
+/// sandboxes once the cap is reached. [`acquire`](Self::acquire) then
+/// waits for a sandbox to be returned, [`try_acquire`](Self::try_acquire)
+/// gives up immediately and [`acquire_timeout`](Self::acquire_timeout)
+/// waits up to a deadline.
+pub struct SandboxPool<S = ()> {
+    /// Shared engine reference.
+    engine: SharedEngine,
+    /// Configuration for new sandboxes.
+    config: SandboxConfig,
+    /// Idle sandboxes and the count of live ones.
+    state: Mutex<PoolState<S>>,
+    /// Signalled when a sandbox is returned or a slot frees up.
+    available: Condvar,
+    /// Maximum number of idle sandboxes to keep.
+    max_idle: usize,
+    /// Maximum number of sandboxes alive at once, idle or in use.
+    max_size: Option<usize>,
+    /// Creates user state for new sandboxes.
+    state_factory: Box<dyn Fn() -> S + Send + Sync>,
+    /// Reset hook installed in new sandboxes.
+    reset_state: Option<fn(&mut S)>,
+}
+
+/// Sandboxes owned by a pool.
+struct PoolState<S> {
+    /// Idle sandboxes ready for reuse.
+    idle: Vec<Sandbox<S>>,
+    /// Sandboxes created by the pool and not yet dropped, idle or in use.
+    live: usize,
+}
+
+impl<S: Send + 'static> SandboxPool<S> {
+    /// Create a new pool whose sandboxes start with default user state.
+    pub fn new(engine: SharedEngine, config: SandboxConfig) -> Self
+    where
+        S: Default,
+    {
+        Self::with_state_factory(engine, config, S::default)
+    }
+
+    /// Create a new pool that builds user state with the given function.
+    pub fn with_state_factory(
+        engine: SharedEngine,
+        config: SandboxConfig,
+        factory: impl Fn() -> S + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            engine,
+            config: config.with_reusable(true),
+            state: Mutex::new(PoolState {
+                idle: Vec::new(),
+                live: 0,
+            }),
+            available: Condvar::new(),
+            max_idle: DEFAULT_MAX_IDLE,
+            max_size: None,
+            state_factory: Box::new(factory),
+            reset_state: None,
+        }
+    }
+
+    /// Reset each sandbox's user state with [`Resettable::reset`] when it is
+    /// returned to the pool.
+    pub fn with_resettable_state(mut self) -> Self
+    where
+        S: Resettable,
+    {
+        self.reset_state = Some(S::reset);
+        self
+    }
+
+    /// Set the maximum number of idle sandboxes to keep.
+    ///
+    /// Sandboxes returned while the pool is full are dropped.
+    pub fn with_max_idle(mut self, max_idle: usize) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+
+    /// Cap the number of sandboxes alive at once, idle or in use.
+    ///
+    /// Without a cap the pool creates a sandbox whenever none are idle.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Get the maximum number of idle sandboxes.
+    pub fn max_idle(&self) -> usize {
+        self.max_idle
+    }
+
+    /// Get the maximum number of sandboxes alive at once, if capped.
+    pub fn max_size(&self) -> Option<usize> {
+        self.max_size
+    }
+
+    /// Get the number of idle sandboxes in the pool.
+    pub fn size(&self) -> usize {
+        self.state.lock().idle.len()
+    }
+
+    /// Take a sandbox from the pool, creating one if none are idle.
+    ///
+    /// If the pool is at its [maximum size](Self::with_max_size), this
+    /// waits until a sandbox is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a new sandbox cannot be created.
+    pub fn acquire(&self) -> ExecutionResult<PooledSandbox<'_, S>> {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(slot) = self.take(&mut state) {
+                drop(state);
+                return self.fill(slot);
+            }
+            self.available.wait(&mut state);
+        }
+    }
+
+    /// Take a sandbox without waiting.
+    ///
+    /// Returns `None` if no sandbox is idle and the pool is at its
+    /// [maximum size](Self::with_max_size).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a new sandbox cannot be created.
+    pub fn try_acquire(&self) -> ExecutionResult<Option<PooledSandbox<'_, S>>> {
+        let slot = self.take(&mut self.state.lock());
+        slot.map(|slot| self.fill(slot)).transpose()
+    }
+
+    /// Take a sandbox, waiting up to `timeout` for one to be returned if
+    /// the pool is at its [maximum size](Self::with_max_size).
+    ///
+    /// # Errors
+    ///
+    /// Returns `PoolError::Timeout` if no sandbox becomes available in
+    /// time, or `PoolError::Sandbox` if a new sandbox cannot be created.
+    pub fn acquire_timeout(&self, timeout: Duration) -> Result<PooledSandbox<'_, S>, PoolError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock();
+        loop {
+            if let Some(slot) = self.take(&mut state) {
+                drop(state);
+                return Ok(self.fill(slot)?);
+            }
+            if self.available.wait_until(&mut state, deadline).timed_out() {
+                // A release may have raced with the timeout
+                return match self.take(&mut state) {
+                    Some(slot) => {
+                        drop(state);
+                        Ok(self.fill(slot)?)
+                    }
+                    None => {
+                        debug!(?timeout, "Timed out waiting for pooled sandbox");
+                        Err(PoolError::Timeout(timeout))
+                    }
+                };
+            }
+        }
+    }
+
+    /// Take an idle sandbox, or reserve room for a new one if the pool is
+    /// below its maximum size.
+    fn take(&self, state: &mut PoolState<S>) -> Option<Slot<S>> {
+        if let Some(sandbox) = state.idle.pop() {
+            return Some(Slot::Idle(Box::new(sandbox)));
+        }
+        if self.max_size.is_some_and(|max| state.live >= max) {
+            return None;
+        }
+        state.live += 1;
+        Some(Slot::New)
+    }
+
+    /// Turn a slot into a guard, creating the sandbox if needed.
+    fn fill(&self, slot: Slot<S>) -> ExecutionResult<PooledSandbox<'_, S>> {
+        let sandbox = match slot {
+            Slot::Idle(sandbox) => {
+                debug!(sandbox_id = %sandbox.id(), "Reusing pooled sandbox");
+                *sandbox
+            }
+            Slot::New => match self.create() {
+                Ok(sandbox) => sandbox,
+                Err(e) => {
+                    self.forget();
+                    return Err(e);
+                }
+            },
+        };
+
+        Ok(PooledSandbox {
+            pool: self,
+            sandbox: Some(sandbox),
+        })
+    }
+
+    /// Create a sandbox for the pool.
+    fn create(&self) -> ExecutionResult<Sandbox<S>> {
+        let mut sandbox = Sandbox::new(
+            self.engine.clone(),
+            (self.state_factory)(),
+            self.config.clone(),
+        )?;
+        if let Some(reset) = self.reset_state {
+            sandbox.set_reset_hook(reset);
+        }
+        Ok(sandbox)
+    }
+
+    /// Give up a live sandbox's slot and wake a waiter to reuse it.
+    fn forget(&self) {
+        self.state.lock().live -= 1;
+        self.available.notify_one();
+    }
+
+    /// Reset a sandbox and return it to the idle list.
+    fn release(&self, mut sandbox: Sandbox<S>) {
+        sandbox.reset();
+
+        let mut state = self.state.lock();
+        if state.idle.len() < self.max_idle {
+            state.idle.push(sandbox);
+        } else {
+            debug!(sandbox_id = %sandbox.id(), "Pool full, dropping sandbox");
+            state.live -= 1;
+        }
+        drop(state);
+        self.available.notify_one();
+    }
+}
+
+/// A sandbox taken from a pool, or room to create one.
+enum Slot<S> {
+    /// An idle sandbox.
+    Idle(Box<Sandbox<S>>),
+    /// A reserved slot for a new sandbox.
+    New,
+}
+
+impl<S> std::fmt::Debug for SandboxPool<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("SandboxPool")
+            .field("idle", &state.idle.len())
+            .field("live", &state.live)
+            .field("max_idle", &self.max_idle)
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+/// A sandbox borrowed from a [`SandboxPool`].
+///
+/// Dereferences to [`Sandbox`]. The sandbox is reset and returned to the
+/// pool when the guard is dropped.
+pub struct PooledSandbox<'a, S: Send + 'static = ()> {
+    pool: &'a SandboxPool<S>,
+    sandbox: Option<Sandbox<S>>,
+}
+
+impl<S: Send + 'static> Deref for PooledSandbox<'_, S> {
+    type Target = Sandbox<S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.sandbox.as_ref().expect("pooled sandbox is present")
+    }
+}
+
+impl<S: Send + 'static> DerefMut for PooledSandbox<'_, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.sandbox.as_mut().expect("pooled sandbox is present")
+    }
+}
+
+impl<S: Send + 'static> Drop for PooledSandbox<'_, S> {
+    fn drop(&mut self) {
+        if let Some(sandbox) = self.sandbox.take() {
+            self.pool.release(sandbox);
+        }
+    }
+}
+
+impl<S: Send + 'static> std::fmt::Debug for PooledSandbox<'_, S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PooledSandbox").field(&**self).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::EngineConfig;
+    use crate::engine::AegisEngine;
+    use crate::module::{ModuleLoader, ValidatedModule};
+
+    fn create_engine() -> SharedEngine {
+        Arc::new(AegisEngine::new(EngineConfig::default()).unwrap())
+    }
+
+    fn counter_module(engine: &SharedEngine) -> ValidatedModule {
+        ModuleLoader::new(Arc::clone(engine))
+            .load_wat(
+                r#"
+            (module
+                (global $count (mut i32) (i32.const 0))
+                (func (export "next") (result i32)
+                    (global.set $count (i32.add (global.get $count) (i32.const 1)))
+                    (global.get $count)
+                )
+            )
+        "#,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pool_reuses_sandbox() {
+        let engine = create_engine();
+        let module = counter_module(&engine);
+        let pool = SandboxPool::<()>::new(engine, SandboxConfig::default());
+
+        let first_id = {
+            let mut sandbox = pool.acquire().unwrap();
+            sandbox.load_module(&module).unwrap();
+            let value: i32 = sandbox.call("next", ()).unwrap();
+            assert_eq!(value, 1);
+            sandbox.id()
+        };
+        assert_eq!(pool.size(), 1);
+
+        let mut sandbox = pool.acquire().unwrap();
+        assert_eq!(sandbox.id(), first_id);
+        assert_eq!(pool.size(), 0);
+
+        // The previous instance was cleared and fuel restored
+        assert!(!sandbox.is_loaded());
+        let initial_fuel = SandboxConfig::default().limits.initial_fuel;
+        assert_eq!(sandbox.remaining_fuel(), Some(initial_fuel));
+
+        sandbox.load_module(&module).unwrap();
+        let value: i32 = sandbox.call("next", ()).unwrap();
+        assert_eq!(value, 1);
+    }
+
+    #[test]
+    fn test_pool_max_idle() {
+        let pool =
+            SandboxPool::<()>::new(create_engine(), SandboxConfig::default()).with_max_idle(1);
+
+        let a = pool.acquire().unwrap();
+        let b = pool.acquire().unwrap();
+        assert_ne!(a.id(), b.id());
+
+        drop(a);
+        drop(b);
+        assert_eq!(pool.size(), 1);
+    }
+
+    #[test]
+    fn test_pool_state_factory() {
+        let pool =
+            SandboxPool::with_state_factory(create_engine(), SandboxConfig::default(), || 7u32);
+
+        let sandbox = pool.acquire().unwrap();
+        assert_eq!(*sandbox.state(), 7);
+    }
+
+    #[test]
+    fn test_acquire_timeout_when_exhausted() {
+        let pool =
+            SandboxPool::<()>::new(create_engine(), SandboxConfig::default()).with_max_size(1);
+
+        let held = pool.try_acquire().unwrap().unwrap();
+        assert!(pool.try_acquire().unwrap().is_none());
+
+        let start = Instant::now();
+        let result = pool.acquire_timeout(Duration::from_millis(50));
+        assert!(matches!(result, Err(PoolError::Timeout(_))));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // Returning the sandbox makes it available again
+        let id = held.id();
+        drop(held);
+        assert_eq!(pool.try_acquire().unwrap().unwrap().id(), id);
+    }
+
+    #[test]
+    fn test_waiter_woken_by_release() {
+        let pool =
+            SandboxPool::<()>::new(create_engine(), SandboxConfig::default()).with_max_size(1);
+        let held = pool.acquire().unwrap();
+        let id = held.id();
+
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                pool.acquire_timeout(Duration::from_secs(10))
+                    .map(|sandbox| sandbox.id())
+            });
+
+            std::thread::sleep(Duration::from_millis(50));
+            drop(held);
+
+            assert_eq!(waiter.join().unwrap().unwrap(), id);
+        });
+    }
+}
@@ -3,16 +3,28 @@
 //! This module provides the `Sandbox` type, which represents an isolated
 //! execution environment for running WebAssembly modules.
 
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use aegis_capability::builtin::NestingAction;
+use aegis_capability::{
+    Action, CapabilityId, CapabilityPolicy, CapabilityView, CheckContext, FrozenCapabilitySet,
+    PermissionResult, standard_ids,
+};
+use aegis_observe::{EventDispatcher, MetricsCollector, SandboxEvent};
 use tracing::{debug, info, warn};
 use uuid::Uuid;
-use wasmtime::{Instance, Linker, Store, StoreLimits, StoreLimitsBuilder};
+use wasmtime::{
+    AsContextMut, CallHook, Instance, Linker, Memory, Store, Trap, UpdateDeadline, WasmBacktrace,
+};
 
+use crate::account::{AccountExhausted, AccountResource, ResourceAccount};
+use crate::cancel::CancellationToken;
 use crate::config::{ResourceLimits, SandboxConfig};
 use crate::engine::SharedEngine;
-use crate::error::{ExecutionError, ExecutionResult, TrapInfo};
-use crate::module::ValidatedModule;
+use crate::error::{ExecutionError, ExecutionResult, HostFunctionError, TrapInfo};
+use crate::limiter::{MemoryLoan, SandboxLimiter};
+use crate::module::{ContentHash, ImportInfo, ValidatedModule};
 
 /// Unique identifier for a sandbox instance.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -23,6 +35,11 @@ impl SandboxId {
     pub fn new() -> Self {
         Self(Uuid::new_v4())
     }
+
+    /// Get the UUID behind this ID.
+    pub fn as_uuid(&self) -> Uuid {
+        self.0
+    }
 }
 
 impl Default for SandboxId {
@@ -43,15 +60,330 @@ pub struct SandboxData<S = ()> {
     pub id: SandboxId,
     /// User-provided state.
     pub user_state: S,
-    /// Resource limits.
-    pub limits: StoreLimits,
+    /// Resource limiter, which also tracks peak memory.
+    pub limits: SandboxLimiter,
     /// Execution metrics.
     pub metrics: SandboxMetrics,
+    /// Capabilities granted to this sandbox.
+    pub capabilities: CapabilityView,
     /// Configuration.
     config: SandboxConfig,
+    /// Engine epoch at which the current call times out.
+    epoch_deadline: u64,
+    /// Low-fuel observer sampled on each epoch tick.
+    fuel_observer: Option<FuelObserver>,
+    /// Token checked on each epoch tick to cancel the current call.
+    pub(crate) cancellation: Option<CancellationToken>,
+    /// High-frequency events waiting to be dispatched at the end of the call.
+    event_buffer: Vec<SandboxEvent>,
+    /// The sandbox this one was spawned from, if any.
+    parent: Option<SandboxId>,
+    /// Nesting depth; top-level sandboxes are at depth 0.
+    depth: u32,
+    /// Fuel held back from the current call because the resource account
+    /// could not cover it.
+    account_withheld: u64,
+    /// Policy consulted when a host function returns, to top up a call
+    /// running low on fuel.
+    refuel_policy: Option<Arc<dyn RefuelPolicy>>,
+    /// Maximum number of refuels per call.
+    max_refuel_rounds: u32,
+    /// Fuel available at the start of the current call.
+    call_budget: u64,
+    /// Content hash of the loaded module, passed to capability checks.
+    pub(crate) module_hash: Option<ContentHash>,
+    /// Memory limit lent by the parent, returned to it when this sandbox
+    /// is dropped.
+    memory_loan: Option<MemoryLoan>,
 }
 
 impl<S> SandboxData<S> {
+    /// Create the store data of a new top-level sandbox.
+    pub(crate) fn new(user_state: S, config: SandboxConfig) -> Self {
+        let limits = SandboxLimiter::new(&config.limits, config.event_dispatcher.clone());
+        Self::with_limiter(user_state, config, limits)
+    }
+
+    /// Create the store data of a component sandbox, whose limiter allows
+    /// the several core instances a component is made of.
+    pub(crate) fn for_component(user_state: S, config: SandboxConfig) -> Self {
+        let limits = SandboxLimiter::for_component(&config.limits, config.event_dispatcher.clone());
+        Self::with_limiter(user_state, config, limits)
+    }
+
+    /// Create the store data around `limits`, attaching the configured
+    /// metrics collector and resource account to it.
+    fn with_limiter(user_state: S, config: SandboxConfig, mut limits: SandboxLimiter) -> Self {
+        let id = SandboxId::new();
+        limits = limits.with_sandbox_id(id);
+
+        if let Some(collector) = &config.metrics_collector {
+            limits = limits.with_metrics_collector(Arc::clone(collector));
+        }
+        if let Some(account) = &config.account {
+            limits = limits.with_account(Arc::clone(account));
+        }
+
+        Self {
+            id,
+            user_state,
+            limits,
+            metrics: SandboxMetrics::default(),
+            capabilities: config.capabilities.clone(),
+            config,
+            epoch_deadline: 0,
+            fuel_observer: None,
+            cancellation: None,
+            event_buffer: Vec::new(),
+            parent: None,
+            depth: 0,
+            account_withheld: 0,
+            refuel_policy: None,
+            max_refuel_rounds: DEFAULT_MAX_REFUEL_ROUNDS,
+            call_budget: 0,
+            module_hash: None,
+            memory_loan: None,
+        }
+    }
+
+    /// Get the sandbox's configuration.
+    pub(crate) fn config(&self) -> &SandboxConfig {
+        &self.config
+    }
+
+    /// Get the context capability checks are made in.
+    ///
+    /// It names this sandbox, its depth and configured principal, and the
+    /// content hash of the loaded module, if any.
+    pub fn check_context(&self) -> CheckContext {
+        let mut ctx = CheckContext::new()
+            .with_sandbox_id(self.id.to_string())
+            .with_depth(self.depth);
+        if let Some(hash) = &self.module_hash {
+            ctx = ctx.with_module_hash(*hash.as_bytes());
+        }
+        if let Some(principal) = &self.config.principal {
+            ctx = ctx.with_principal(principal.clone());
+        }
+        ctx
+    }
+
+    /// Check whether an action is permitted by the sandbox's capabilities.
+    ///
+    /// The check is made in the [`check_context`](Self::check_context) and
+    /// recorded as a `CapabilityChecked` event if an event
+    /// dispatcher is configured, and as capability usage or a denial if a
+    /// metrics collector is. Host functions can call this through
+    /// `Caller::data()`.
+    pub fn check(&self, action: &dyn Action) -> PermissionResult {
+        let (id, result) = self
+            .capabilities
+            .check_permission_detailed_with_context(action, &self.check_context());
+
+        if let Some(collector) = &self.config.metrics_collector {
+            match &result {
+                PermissionResult::Allowed => collector.record_capability_usage(&id),
+                PermissionResult::Denied(reason) => collector.record_capability_denied(
+                    &reason.capability,
+                    reason.action.clone(),
+                    reason.message.clone(),
+                ),
+                PermissionResult::NotApplicable => {}
+            }
+        }
+
+        if let Some(dispatcher) = &self.config.event_dispatcher {
+            dispatcher.emit_for(
+                self.id.as_uuid(),
+                SandboxEvent::CapabilityChecked {
+                    id,
+                    action: action.action_type().to_string(),
+                    permitted: result.is_allowed(),
+                },
+            );
+        }
+
+        result
+    }
+
+    /// Emit an event to the sandbox's event dispatcher, if any.
+    ///
+    /// When event buffering is enabled, `FuelConsumed` and
+    /// `HostFunctionCalled` events are held and dispatched as one batch when
+    /// the current call finishes. Other events are dispatched immediately.
+    pub fn emit(&mut self, event: SandboxEvent) {
+        let Some(dispatcher) = &self.config.event_dispatcher else {
+            return;
+        };
+
+        let high_frequency = matches!(
+            event,
+            SandboxEvent::FuelConsumed { .. } | SandboxEvent::HostFunctionCalled { .. }
+        );
+        if self.config.buffer_events && high_frequency {
+            self.event_buffer.push(event);
+        } else {
+            dispatcher.emit_for(self.id.as_uuid(), event);
+        }
+    }
+
+    /// Dispatch buffered events.
+    fn flush_events(&mut self) {
+        if self.event_buffer.is_empty() {
+            return;
+        }
+
+        let events = std::mem::take(&mut self.event_buffer);
+        if let Some(dispatcher) = &self.config.event_dispatcher {
+            dispatcher.emit_batch_for(self.id.as_uuid(), events);
+        }
+    }
+
+    /// Sample the remaining fuel, firing the low-fuel hook on the first
+    /// sample below the threshold in a call.
+    fn observe_fuel(&mut self, remaining: u64) {
+        let Some(observer) = &mut self.fuel_observer else {
+            return;
+        };
+        if observer.fired || remaining >= observer.threshold {
+            return;
+        }
+
+        observer.fired = true;
+        let consumed = observer.call_budget.saturating_sub(remaining);
+        debug!(sandbox_id = %self.id, remaining, consumed, "Fuel below threshold");
+
+        let callback = Arc::clone(&observer.callback);
+        self.emit(SandboxEvent::FuelConsumed {
+            amount: consumed,
+            remaining,
+        });
+        callback(remaining);
+    }
+
+    /// Ask the refuel policy for more fuel once less than a quarter of the
+    /// call's starting fuel remains, and return the fuel granted.
+    fn grant_refuel(&mut self, remaining: u64) -> Option<u64> {
+        let policy = self.refuel_policy.clone()?;
+        if remaining >= self.call_budget / 4
+            || self.metrics.refuels >= u64::from(self.max_refuel_rounds)
+        {
+            return None;
+        }
+
+        let consumed = (self.call_budget + self.metrics.fuel_refueled).saturating_sub(remaining);
+        let granted = policy
+            .refuel(consumed)
+            .map(|granted| match &self.config.account {
+                Some(account) => account.reserve_fuel(granted),
+                None => granted,
+            })
+            .filter(|&granted| granted > 0)?;
+
+        if let Some(collector) = &self.config.metrics_collector {
+            collector.record_refuel(granted);
+        }
+        if let Some(observer) = &mut self.fuel_observer {
+            observer.call_budget += granted;
+        }
+        self.metrics.refuels += 1;
+        self.metrics.fuel_refueled += granted;
+
+        info!(sandbox_id = %self.id, granted, remaining, "Refueled call running low on fuel");
+        Some(granted)
+    }
+
+    /// Get the sandbox this one was spawned from, if any.
+    pub fn parent(&self) -> Option<SandboxId> {
+        self.parent
+    }
+
+    /// Get the nesting depth; top-level sandboxes are at depth 0.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Derive the configuration of a child sandbox.
+    ///
+    /// The child shares this sandbox's capabilities. Its initial fuel and
+    /// memory limit are the nesting capability's `resource_fraction` of
+    /// `remaining_fuel` and of this sandbox's unused memory; without a
+    /// `NestingCapability` (e.g. under an allow-all capability) the full
+    /// remainder is given.
+    fn child_config(&self, remaining_fuel: Option<u64>) -> SandboxConfig {
+        let fraction = match self
+            .capabilities
+            .get(&standard_ids::NESTING)
+            .and_then(|capability| capability.to_policy())
+        {
+            Some(CapabilityPolicy::Nesting {
+                resource_fraction, ..
+            }) => resource_fraction,
+            _ => 1.0,
+        };
+        let share = |remaining: u64| (remaining as f64 * fraction) as u64;
+
+        // Children report to their own collector, if any
+        let mut config = self.config.clone();
+        config.metrics_collector = None;
+        if let Some(remaining) = remaining_fuel {
+            config.limits.initial_fuel = share(remaining);
+        }
+        let unused_memory = config
+            .limits
+            .max_memory_bytes
+            .saturating_sub(self.limits.lent_memory())
+            .saturating_sub(self.limits.peak_memory());
+        config.limits.max_memory_bytes = share(unused_memory as u64) as usize;
+        config
+    }
+
+    /// Authorize a child sandbox, counting it towards the nesting
+    /// capability's child limit.
+    fn authorize_child(&self) -> ExecutionResult<()> {
+        self.check(&NestingAction::Spawn {
+            depth: self.depth + 1,
+        })
+        .to_result()?;
+        Ok(())
+    }
+
+    /// Record a call of the host function `function` that took `duration`.
+    ///
+    /// Host functions call this to contribute to
+    /// [`SandboxMetrics::host_calls`] and [`SandboxMetrics::host_call_time`],
+    /// and to the metrics collector's host call metrics.
+    pub fn record_host_call(&mut self, function: &str, duration: Duration) {
+        self.metrics.host_calls += 1;
+        self.metrics.host_call_time += duration;
+
+        if let Some(collector) = &self.config.metrics_collector {
+            collector.record_host_call(function, duration);
+        }
+    }
+
+    /// Build the error for an exhausted resource account.
+    pub(crate) fn account_exhausted(&self, resource: AccountResource) -> ExecutionError {
+        let limit = self
+            .config
+            .account
+            .as_ref()
+            .map_or(0, |account| account.limit(resource));
+        ExecutionError::AccountExhausted { resource, limit }
+    }
+
+    /// Get the metrics collector, if any.
+    pub fn metrics_collector(&self) -> Option<&Arc<MetricsCollector>> {
+        self.config.metrics_collector.as_ref()
+    }
+
+    /// Check if the sandbox's cancellation token has been cancelled.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
     /// Access the user state.
     pub fn state(&self) -> &S {
         &self.user_state
@@ -63,6 +395,21 @@ impl<S> SandboxData<S> {
     }
 }
 
+/// Callback fired with the remaining fuel when it drops below a threshold.
+pub type LowFuelHook = Arc<dyn Fn(u64) + Send + Sync>;
+
+/// State for the low-fuel hook.
+struct FuelObserver {
+    /// Remaining fuel below which the hook fires.
+    threshold: u64,
+    /// The hook.
+    callback: LowFuelHook,
+    /// Fuel available at the start of the current call.
+    call_budget: u64,
+    /// Whether the hook has fired during the current call.
+    fired: bool,
+}
+
 /// Metrics collected during sandbox execution.
 #[derive(Debug, Clone, Default)]
 pub struct SandboxMetrics {
@@ -76,6 +423,29 @@ pub struct SandboxMetrics {
     pub peak_memory: usize,
     /// Number of host function calls.
     pub host_calls: u64,
+    /// Total time spent in host function calls.
+    pub host_call_time: Duration,
+    /// Number of refuels granted during the last call.
+    pub refuels: u64,
+    /// Total fuel granted by refuels during the last call.
+    pub fuel_refueled: u64,
+    /// Fuel consumed instantiating the loaded module, including its start
+    /// function and segment initialization.
+    pub instantiation_fuel: u64,
+    /// One record per exported function call since the sandbox was created
+    /// or last reset, in call order.
+    pub per_call: Vec<CallRecord>,
+}
+
+/// Fuel and time attributed to one call of an exported function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallRecord {
+    /// Name of the called export.
+    pub function: String,
+    /// Fuel consumed by the call, including fuel granted by refuels.
+    pub fuel_consumed: u64,
+    /// Wall time spent in the call.
+    pub duration: Duration,
 }
 
 impl SandboxMetrics {
@@ -88,6 +458,97 @@ impl SandboxMetrics {
     }
 }
 
+/// Decides whether a call that is running low on fuel may be given more.
+///
+/// The policy is only consulted when a host function returns to the guest,
+/// so it cannot extend a guest loop that makes no host calls; see
+/// [`Sandbox::set_refuel_policy`].
+///
+/// Implemented by `aegis_resource::FuelManager`.
+pub trait RefuelPolicy: Send + Sync {
+    /// Return the fuel to grant, or `None` to let the call run out.
+    ///
+    /// `consumed` is the fuel the call has used so far.
+    fn refuel(&self, consumed: u64) -> Option<u64>;
+}
+
+impl<T: RefuelPolicy + ?Sized> RefuelPolicy for Arc<T> {
+    fn refuel(&self, consumed: u64) -> Option<u64> {
+        (**self).refuel(consumed)
+    }
+}
+
+/// Information about a registered host function.
+#[derive(Debug, Clone)]
+pub struct RegisteredFunction {
+    /// The import module name.
+    pub module: String,
+    /// The function name.
+    pub name: String,
+    /// Required capability, if any.
+    pub required_capability: Option<CapabilityId>,
+    /// Human-readable description.
+    pub description: Option<String>,
+}
+
+/// A set of host functions that can be installed into a sandbox's linker.
+///
+/// Implemented by `aegis_host::HostFunctionRegistry`.
+pub trait HostFunctions<S>: Send + Sync {
+    /// Define the functions in `linker`.
+    ///
+    /// `capabilities` is the set granted to the sandbox, so implementations
+    /// can refuse to install functions whose capabilities are missing.
+    fn install(
+        &self,
+        linker: &mut Linker<SandboxData<S>>,
+        capabilities: &CapabilityView,
+    ) -> wasmtime::Result<()>;
+
+    /// Describe the functions `install` defines.
+    ///
+    /// These are recorded in [`Sandbox::host_functions`]. The default
+    /// describes none.
+    fn registered_functions(&self) -> Vec<RegisteredFunction> {
+        Vec::new()
+    }
+}
+
+impl<S, T: HostFunctions<S> + ?Sized> HostFunctions<S> for Arc<T> {
+    fn install(
+        &self,
+        linker: &mut Linker<SandboxData<S>>,
+        capabilities: &CapabilityView,
+    ) -> wasmtime::Result<()> {
+        (**self).install(linker, capabilities)
+    }
+
+    fn registered_functions(&self) -> Vec<RegisteredFunction> {
+        (**self).registered_functions()
+    }
+}
+
+/// User state that can clear itself between runs.
+///
+/// Register it with [`SandboxBuilder::with_resettable_state`] so that
+/// [`Sandbox::reset`] also resets the state, e.g. to drop connections or
+/// buffers left over from the previous run.
+pub trait Resettable {
+    /// Return the state to a clean condition for the next run.
+    fn reset(&mut self);
+}
+
+/// Callback run on the user state by [`Sandbox::reset`].
+pub type ResetHook<S> = Box<dyn FnMut(&mut S) + Send>;
+
+/// Callback run on the sandbox after a module is instantiated.
+///
+/// See [`Sandbox::on_instantiate`].
+pub type InitHook<S> = Box<dyn FnOnce(&mut Sandbox<S>) -> ExecutionResult<()> + Send>;
+
+/// Default maximum number of refuels per call.
+const DEFAULT_MAX_REFUEL_ROUNDS: u32 = 4;
+
 /// A sandboxed execution environment for WebAssembly modules.
 ///
 /// The `Sandbox` provides isolation guarantees by:
@@ -116,13 +577,23 @@ pub struct Sandbox<S = ()> {
     /// Shared engine reference.
     engine: SharedEngine,
     /// Wasmtime store with sandbox data.
-    store: Store<SandboxData<S>>,
+    ///
+    /// Always present; it is only taken briefly while `reset` rebuilds it.
+    store: Option<Store<SandboxData<S>>>,
     /// Wasmtime linker for host function registration.
     linker: Linker<SandboxData<S>>,
+    /// Host functions defined in the linker, in registration order.
+    host_functions: Vec<RegisteredFunction>,
     /// Currently loaded instance.
     instance: Option<Instance>,
+    /// Memory exported as `memory` by the loaded instance.
+    memory: Option<Memory>,
     /// Currently loaded module.
     module: Option<ValidatedModule>,
+    /// Hook run on the user state when the sandbox is reset.
+    reset_hook: Option<ResetHook<S>>,
+    /// Hook run once after the next module is instantiated.
+    init_hook: Option<InitHook<S>>,
 }
 
 impl<S: Send + 'static> Sandbox<S> {
@@ -132,25 +603,102 @@ impl<S: Send + 'static> Sandbox<S> {
         user_state: S,
         config: SandboxConfig,
     ) -> ExecutionResult<Self> {
-        let id = SandboxId::new();
+        let data = SandboxData::new(user_state, config);
+        let id = data.id;
+        let store = Self::build_store(&engine, data);
+        let linker = Linker::new(engine.inner());
 
-        // Build store limits from resource limits
-        let limits = StoreLimitsBuilder::new()
-            .memory_size(config.limits.max_memory_bytes)
-            .table_elements(config.limits.max_table_elements as usize)
-            .instances(1)
-            .tables(10)
-            .memories(config.limits.max_memories as usize)
-            .build();
+        info!(sandbox_id = %id, "Created new sandbox");
 
-        let data = SandboxData {
-            id,
-            user_state,
-            limits,
-            metrics: SandboxMetrics::default(),
-            config: config.clone(),
-        };
+        Ok(Self {
+            engine,
+            store: Some(store),
+            linker,
+            host_functions: Vec::new(),
+            instance: None,
+            memory: None,
+            module: None,
+            reset_hook: None,
+            init_hook: None,
+        })
+    }
+
+    /// Create a child of the sandbox whose data is `parent`.
+    ///
+    /// Spawning requires a capability that permits
+    /// `NestingAction::Spawn` at the child's depth, usually a
+    /// `NestingCapability`, and fails with [`ExecutionError::Capability`]
+    /// otherwise. The child shares the parent's engine, configuration and
+    /// capabilities, with its fuel and memory limits derived from the
+    /// parent's remaining resources. Host functions are not inherited.
+    ///
+    /// The child's fuel is taken from the parent's remaining fuel and its
+    /// memory limit lent from the parent's, so a parent and its children
+    /// together stay within the parent's limits. The memory is returned
+    /// when the child is dropped, or when the parent is reset. A spawn that
+    /// fails leaves the parent's resources and child count untouched.
+    ///
+    /// Host functions can pass their `Caller`; otherwise use
+    /// [`spawn_child`](Self::spawn_child).
+    pub fn new_child<P>(
+        engine: SharedEngine,
+        mut parent: impl AsContextMut<Data = SandboxData<P>>,
+        user_state: S,
+    ) -> ExecutionResult<Self> {
+        let mut parent = parent.as_context_mut();
+        let remaining_fuel = parent.get_fuel().ok();
+        let config = parent.data().child_config(remaining_fuel);
+        let (child_fuel, child_memory) =
+            (config.limits.initial_fuel, config.limits.max_memory_bytes);
+
+        // Authorize only once the child exists, so a failed spawn does not
+        // use up one of the capability's children
+        let mut child = Self::new(engine, user_state, config)?;
+        parent.data().authorize_child()?;
+
+        if let Some(remaining) = remaining_fuel {
+            parent.set_fuel(remaining.saturating_sub(child_fuel))?;
+        }
+        let parent = parent.data_mut();
+        let loan = parent.limits.lend_memory(child_memory);
+
+        let data = child.store_mut().data_mut();
+        data.memory_loan = Some(loan);
+        data.parent = Some(parent.id);
+        data.depth = parent.depth + 1;
+        info!(
+            sandbox_id = %data.id,
+            parent_id = %parent.id,
+            depth = data.depth,
+            "Spawned child sandbox"
+        );
+
+        Ok(child)
+    }
+
+    /// Spawn a child of this sandbox.
+    ///
+    /// See [`new_child`](Self::new_child).
+    pub fn spawn_child(&mut self, user_state: S) -> ExecutionResult<Self> {
+        Self::new_child(Arc::clone(&self.engine), self.store_mut(), user_state)
+    }
+
+    /// Get the sandbox this one was spawned from, if any.
+    pub fn parent(&self) -> Option<SandboxId> {
+        self.store().data().parent
+    }
+
+    /// Get the nesting depth; top-level sandboxes are at depth 0.
+    pub fn depth(&self) -> u32 {
+        self.store().data().depth
+    }
 
+    /// Create a store for the given data, applying its configured limits.
+    pub(crate) fn build_store(
+        engine: &SharedEngine,
+        data: SandboxData<S>,
+    ) -> Store<SandboxData<S>> {
+        let limits = data.config.limits.clone();
         let mut store = Store::new(engine.inner(), data);
 
         // Configure store limits
@@ -158,34 +706,177 @@ impl<S: Send + 'static> Sandbox<S> {
 
         // Configure fuel if enabled
         if engine.fuel_enabled() {
-            store.set_fuel(config.limits.initial_fuel)?;
+            store
+                .set_fuel(limits.initial_fuel)
+                .expect("fuel is enabled on the engine");
+        }
+
+        if engine.fuel_enabled() && store.data().refuel_policy.is_some() {
+            Self::install_refuel_hook(&mut store);
         }
 
-        // Configure epoch deadline if enabled
+        // Configure epoch deadline if enabled; it is re-armed before each
+        // load and call so idle time does not count against the timeout
         if engine.epoch_enabled() {
-            // Calculate epochs based on timeout
-            // Assuming 10ms per epoch tick
-            let deadline_epochs = (config.limits.timeout.as_millis() / 10) as u64;
-            store.epoch_deadline_trap();
-            store.set_epoch_deadline(deadline_epochs.max(1));
+            if Self::uses_epoch_callback(engine, store.data()) {
+                Self::install_epoch_callback(engine, &mut store);
+            } else {
+                store.epoch_deadline_trap();
+            }
+            store.set_epoch_deadline(engine.epochs_for_timeout(limits.timeout));
         }
 
-        let linker = Linker::new(engine.inner());
+        store
+    }
 
-        info!(sandbox_id = %id, "Created new sandbox");
+    /// Check if the store must wake on every epoch tick, either to sample
+    /// fuel or to check for cancellation.
+    pub(crate) fn uses_epoch_callback(engine: &SharedEngine, data: &SandboxData<S>) -> bool {
+        (data.fuel_observer.is_some() && engine.fuel_enabled()) || data.cancellation.is_some()
+    }
 
-        Ok(Self {
-            engine,
-            store,
-            linker,
-            instance: None,
-            module: None,
-        })
+    /// Wake on every epoch tick to check for cancellation and sample fuel,
+    /// trapping once the real deadline in `SandboxData::epoch_deadline` has
+    /// passed.
+    pub(crate) fn install_epoch_callback(engine: &SharedEngine, store: &mut Store<SandboxData<S>>) {
+        let engine = Arc::clone(engine);
+        store.epoch_deadline_callback(move |mut ctx| {
+            if ctx.data().is_cancelled() {
+                return Err(Trap::Interrupt.into());
+            }
+
+            if engine.fuel_enabled() {
+                let remaining = ctx.get_fuel().unwrap_or(0);
+                ctx.data_mut().observe_fuel(remaining);
+            }
+
+            let data = ctx.data();
+            if engine.current_epoch() >= data.epoch_deadline {
+                return Err(Trap::Interrupt.into());
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
+    }
+
+    /// Top up fuel from the refuel policy each time a host function returns
+    /// to the guest.
+    ///
+    /// Compiled code keeps its fuel counter in a register and only reloads
+    /// it after calls, so fuel set from an epoch callback would be
+    /// overwritten; a host return is where a top-up takes effect.
+    fn install_refuel_hook(store: &mut Store<SandboxData<S>>) {
+        store.call_hook(|mut ctx, hook| {
+            if matches!(hook, CallHook::ReturningFromHost) {
+                let remaining = ctx.get_fuel()?;
+                if let Some(granted) = ctx.data_mut().grant_refuel(remaining) {
+                    ctx.set_fuel(remaining.saturating_add(granted))?;
+                }
+            }
+            Ok(())
+        });
+    }
+
+    /// Set the epoch deadline to the configured timeout from now.
+    fn arm_epoch_deadline(&mut self) {
+        let engine = Arc::clone(&self.engine);
+        Self::arm_store_deadline(&engine, self.store_mut());
+    }
+
+    /// Set the store's epoch deadline to its configured timeout from now.
+    pub(crate) fn arm_store_deadline(engine: &SharedEngine, store: &mut Store<SandboxData<S>>) {
+        if engine.epoch_enabled() {
+            let timeout = store.data().config.limits.timeout;
+            let epochs = engine.epochs_for_timeout(timeout);
+            let callback = Self::uses_epoch_callback(engine, store.data());
+
+            // A cancelled token traps at the first epoch check
+            let delta = if store.data().is_cancelled() {
+                0
+            } else if callback {
+                1
+            } else {
+                epochs
+            };
+
+            store.data_mut().epoch_deadline = engine.current_epoch() + epochs;
+            store.set_epoch_deadline(delta);
+        }
+    }
+
+    /// Enforce memory and table limits with a custom Wasmtime limiter.
+    ///
+    /// This replaces the limits from the sandbox's `ResourceLimits`; peak
+    /// memory and `MemoryGrew` events are still recorded. It should be set
+    /// before a module is loaded.
+    pub fn set_resource_limiter(
+        &mut self,
+        limiter: impl wasmtime::ResourceLimiter + Send + Sync + 'static,
+    ) {
+        self.store_mut()
+            .data_mut()
+            .limits
+            .set_custom(Box::new(limiter));
+    }
+
+    /// Call `hook` with the remaining fuel when it drops below `threshold`
+    /// during a call.
+    ///
+    /// Fuel is sampled on each epoch tick, so the hook fires at most once per
+    /// call and up to one tick interval (see
+    /// [`AegisEngine::epoch_tick_interval`](crate::AegisEngine::epoch_tick_interval))
+    /// after the threshold is crossed. Wasmtime only writes the guest's fuel
+    /// counter back at calls and returns, so a loop that makes no calls is
+    /// sampled as it was on loop entry. If an event dispatcher is configured
+    /// a `FuelConsumed` event is emitted as well.
+    ///
+    /// Requires fuel and epochs to be enabled on the engine, and the epoch
+    /// counter must be advanced (e.g. by an epoch manager) for samples to be
+    /// taken.
+    pub fn set_low_fuel_hook(
+        &mut self,
+        threshold: u64,
+        hook: impl Fn(u64) + Send + Sync + 'static,
+    ) {
+        self.store_mut().data_mut().fuel_observer = Some(FuelObserver {
+            threshold,
+            callback: Arc::new(hook),
+            call_budget: 0,
+            fired: false,
+        });
+
+        if self.engine.epoch_enabled() && self.engine.fuel_enabled() {
+            let engine = Arc::clone(&self.engine);
+            Self::install_epoch_callback(&engine, self.store_mut());
+        }
+    }
+
+    /// Let `token` cancel this sandbox's calls.
+    ///
+    /// Requires epochs to be enabled on the engine. A token that has been
+    /// cancelled is detached when the sandbox is reset. See
+    /// [`CancellationToken`] for details.
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.store_mut().data_mut().cancellation = Some(token);
+
+        if self.engine.epoch_enabled() {
+            let engine = Arc::clone(&self.engine);
+            Self::install_epoch_callback(&engine, self.store_mut());
+        }
+    }
+
+    /// Get the store.
+    fn store(&self) -> &Store<SandboxData<S>> {
+        self.store.as_ref().expect("sandbox store is present")
+    }
+
+    /// Get the store mutably.
+    fn store_mut(&mut self) -> &mut Store<SandboxData<S>> {
+        self.store.as_mut().expect("sandbox store is present")
     }
 
     /// Get the sandbox ID.
     pub fn id(&self) -> SandboxId {
-        self.store.data().id
+        self.store().data().id
     }
 
     /// Get a reference to the engine.
@@ -195,24 +886,106 @@ impl<S: Send + 'static> Sandbox<S> {
 
     /// Access the user state.
     pub fn state(&self) -> &S {
-        &self.store.data().user_state
+        &self.store().data().user_state
     }
 
     /// Access the user state mutably.
     pub fn state_mut(&mut self) -> &mut S {
-        &mut self.store.data_mut().user_state
+        &mut self.store_mut().data_mut().user_state
+    }
+
+    /// Take the user state, leaving the default in its place.
+    ///
+    /// A call that fails, whether by timing out, running out of fuel or
+    /// trapping, leaves the user state as the guest's host calls last left
+    /// it. For a guest that streams output into the state, this returns
+    /// what it produced before the failure and clears the state for the
+    /// next call.
+    pub fn take_partial(&mut self) -> S
+    where
+        S: Default,
+    {
+        std::mem::take(self.state_mut())
     }
 
     /// Get the execution metrics.
     pub fn metrics(&self) -> &SandboxMetrics {
-        &self.store.data().metrics
+        &self.store().data().metrics
+    }
+
+    /// Get the capabilities granted to this sandbox.
+    pub fn capabilities(&self) -> &CapabilityView {
+        &self.store().data().capabilities
+    }
+
+    /// Check whether an action is permitted by the sandbox's capabilities.
+    ///
+    /// See [`SandboxData::check`].
+    pub fn check(&self, action: &dyn Action) -> PermissionResult {
+        self.store().data().check(action)
     }
 
     /// Get a mutable reference to the linker for registering host functions.
+    ///
+    /// Functions defined directly on the linker are not listed by
+    /// [`host_functions`](Self::host_functions) unless they are recorded
+    /// with [`record_host_function`](Self::record_host_function).
     pub fn linker_mut(&mut self) -> &mut Linker<SandboxData<S>> {
         &mut self.linker
     }
 
+    /// Get the host functions registered with this sandbox, in registration
+    /// order.
+    ///
+    /// This covers functions added with [`register_func`](Self::register_func),
+    /// [`register_func_with_capability`](Self::register_func_with_capability),
+    /// the registries of a [`SandboxBuilder`], and any recorded with
+    /// [`record_host_function`](Self::record_host_function).
+    pub fn host_functions(&self) -> &[RegisteredFunction] {
+        &self.host_functions
+    }
+
+    /// Install a set of host functions into the linker and record them.
+    ///
+    /// The functions are installed against the capabilities granted to the
+    /// sandbox.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the functions cannot be defined.
+    pub fn install_host_functions(
+        &mut self,
+        functions: &dyn HostFunctions<S>,
+    ) -> ExecutionResult<()> {
+        let capabilities = self.store().data().config().capabilities.clone();
+        functions.install(&mut self.linker, &capabilities)?;
+        self.host_functions.extend(functions.registered_functions());
+        Ok(())
+    }
+
+    /// Record a host function defined directly on the linker, so that it is
+    /// listed by [`host_functions`](Self::host_functions).
+    pub fn record_host_function(&mut self, function: RegisteredFunction) {
+        self.host_functions.push(function);
+    }
+
+    /// Get the capabilities required by registered host functions that the
+    /// sandbox was not granted.
+    pub fn missing_capabilities(&self) -> Vec<CapabilityId> {
+        let capabilities = &self.store().data().config().capabilities;
+        let mut missing: Vec<CapabilityId> = Vec::new();
+        for required in self
+            .host_functions
+            .iter()
+            .filter_map(|f| f.required_capability.as_ref())
+        {
+            if !capabilities.has(required) && !missing.contains(required) {
+                missing.push(required.clone());
+            }
+        }
+        missing
+    }
+
     /// Register a host function.
     ///
     /// # Example
@@ -227,8 +1000,29 @@ impl<S: Send + 'static> Sandbox<S> {
         module: &str,
         name: &str,
         func: impl wasmtime::IntoFunc<SandboxData<S>, Params, Results>,
+    ) -> ExecutionResult<()> {
+        self.register_func_with_capability(module, name, None, func)
+    }
+
+    /// Register a host function that requires a capability.
+    ///
+    /// The requirement is listed by [`host_functions`](Self::host_functions)
+    /// and checked by [`missing_capabilities`](Self::missing_capabilities);
+    /// the function itself is still responsible for checking actions.
+    pub fn register_func_with_capability<Params, Results>(
+        &mut self,
+        module: &str,
+        name: &str,
+        required_capability: Option<CapabilityId>,
+        func: impl wasmtime::IntoFunc<SandboxData<S>, Params, Results>,
     ) -> ExecutionResult<()> {
         self.linker.func_wrap(module, name, func)?;
+        self.host_functions.push(RegisteredFunction {
+            module: module.to_string(),
+            name: name.to_string(),
+            required_capability,
+            description: None,
+        });
         debug!(module, name, "Registered host function");
         Ok(())
     }
@@ -238,33 +1032,208 @@ impl<S: Send + 'static> Sandbox<S> {
     /// This compiles and instantiates the module, linking it with any
     /// registered host functions.
     pub fn load_module(&mut self, module: &ValidatedModule) -> ExecutionResult<()> {
+        if self.engine.async_enabled() {
+            return Err(ExecutionError::AsyncRequired("load_module"));
+        }
+
         debug!(
             sandbox_id = %self.id(),
             module_name = ?module.name(),
             "Loading module into sandbox"
         );
 
-        let instance = self.linker.instantiate(&mut self.store, module.inner())?;
-
-        self.instance = Some(instance);
-        self.module = Some(module.clone());
+        self.can_instantiate(module)?;
 
-        info!(
-            sandbox_id = %self.id(),
-            module_name = ?module.name(),
-            "Module loaded successfully"
-        );
+        let _active = self.engine.enter_execution();
+        self.arm_epoch_deadline();
+        let fuel_before = self.reserve_instantiation_fuel();
+        let start = Instant::now();
+        let store = self.store.as_mut().expect("sandbox store is present");
+        let result = self.linker.instantiate(store, module.inner());
+        let instance = self.finish_instantiate(fuel_before, start, result)?;
+        self.finish_load(instance, module);
 
-        Ok(())
+        self.run_init_hook()
     }
 
-    /// Check if a module is currently loaded.
-    pub fn is_loaded(&self) -> bool {
-        self.instance.is_some()
+    /// Get the imports of a module that the linker does not define.
+    ///
+    /// An import counts as missing if nothing is defined under its module
+    /// and name, or the definition is of a different kind (e.g. a memory
+    /// where a function is expected). Signature mismatches are left to
+    /// instantiation.
+    pub fn missing_imports(&mut self, module: &ValidatedModule) -> Vec<ImportInfo> {
+        let store = self.store.as_mut().expect("sandbox store is present");
+
+        module
+            .imports()
+            .iter()
+            .filter(|import| {
+                self.linker
+                    .get(&mut *store, &import.module, &import.name)
+                    .is_none_or(|item| !import.kind.matches(&item))
+            })
+            .cloned()
+            .collect()
     }
 
-    /// Get information about the loaded module.
-    pub fn loaded_module(&self) -> Option<&ValidatedModule> {
+    /// Check that every import of a module is defined in the linker.
+    ///
+    /// Returns [`ExecutionError::MissingImport`] naming the first missing
+    /// import; all of them are logged. Use
+    /// [`missing_imports`](Self::missing_imports) to get the full list.
+    pub fn can_instantiate(&mut self, module: &ValidatedModule) -> ExecutionResult<()> {
+        let missing = self.missing_imports(module);
+
+        for import in &missing {
+            warn!(
+                sandbox_id = %self.id(),
+                module = %import.module,
+                name = %import.name,
+                kind = %import.kind,
+                "Unsatisfied import"
+            );
+        }
+
+        match missing.into_iter().next() {
+            Some(import) => Err(ExecutionError::MissingImport {
+                module: import.module,
+                name: import.name,
+                expected_kind: import.kind,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Load a validated module into the sandbox asynchronously.
+    ///
+    /// This must be used instead of [`load_module`](Self::load_module) when
+    /// the engine has async support enabled.
+    pub async fn load_module_async(&mut self, module: &ValidatedModule) -> ExecutionResult<()> {
+        if !self.engine.async_enabled() {
+            return Err(ExecutionError::AsyncNotEnabled);
+        }
+
+        debug!(
+            sandbox_id = %self.id(),
+            module_name = ?module.name(),
+            "Loading module into sandbox (async)"
+        );
+
+        self.can_instantiate(module)?;
+
+        let _active = self.engine.enter_execution();
+        self.arm_epoch_deadline();
+        let fuel_before = self.reserve_instantiation_fuel();
+        let start = Instant::now();
+        let store = self.store.as_mut().expect("sandbox store is present");
+        let result = self.linker.instantiate_async(store, module.inner()).await;
+        let instance = self.finish_instantiate(fuel_before, start, result)?;
+        self.finish_load(instance, module);
+
+        self.run_init_hook()
+    }
+
+    /// Get the store's remaining fuel, or 0 if fuel is disabled.
+    fn fuel_level(&self) -> u64 {
+        if self.engine.fuel_enabled() {
+            self.store().get_fuel().unwrap_or(0)
+        } else {
+            0
+        }
+    }
+
+    /// Reserve instantiation's fuel from the resource account, if any, and
+    /// return the fuel available to it.
+    ///
+    /// Start functions and segment initializers are charged to the account
+    /// like calls are.
+    fn reserve_instantiation_fuel(&mut self) -> u64 {
+        let engine = Arc::clone(&self.engine);
+        let budget = self.fuel_level();
+        Self::reserve_account_fuel(&engine, self.store_mut(), budget)
+    }
+
+    /// Record the fuel and time used by instantiation, settle its fuel with
+    /// the resource account and classify its failure.
+    fn finish_instantiate(
+        &mut self,
+        fuel_before: u64,
+        start: Instant,
+        result: wasmtime::Result<Instance>,
+    ) -> ExecutionResult<Instance> {
+        let remaining = self.fuel_level();
+        let consumed = fuel_before.saturating_sub(remaining);
+        let account_withheld = if self.engine.fuel_enabled() {
+            Self::settle_account_fuel(self.store_mut(), remaining)
+        } else {
+            0
+        };
+        let data = self.store_mut().data_mut();
+        data.metrics.instantiation_fuel = consumed;
+        if let Some(collector) = &data.config.metrics_collector {
+            collector.record_instantiation_time(start.elapsed());
+        }
+
+        match result {
+            Ok(instance) => Ok(instance),
+            Err(err)
+                if err
+                    .downcast_ref::<Trap>()
+                    .is_some_and(|trap| *trap == Trap::OutOfFuel) =>
+            {
+                if account_withheld > 0 {
+                    warn!(sandbox_id = %self.id(), "Resource account fuel exhausted during instantiation");
+                    return Err(self.account_exhausted(AccountResource::Fuel));
+                }
+                warn!(sandbox_id = %self.id(), "Out of fuel during instantiation");
+                Err(ExecutionError::OutOfFuel {
+                    consumed,
+                    limit: self.store().data().config.limits.initial_fuel,
+                })
+            }
+            Err(err) => match err.downcast_ref::<AccountExhausted>() {
+                Some(exhausted) => {
+                    warn!(sandbox_id = %self.id(), "Resource account exhausted during instantiation");
+                    Err(self.account_exhausted(exhausted.resource))
+                }
+                None => Err(err.into()),
+            },
+        }
+    }
+
+    /// Record a freshly instantiated module.
+    fn finish_load(&mut self, instance: Instance, module: &ValidatedModule) {
+        self.memory = instance.get_memory(self.store_mut(), "memory");
+        self.instance = Some(instance);
+        self.module = Some(module.clone());
+        self.store_mut().data_mut().module_hash = Some(module.content_hash());
+        self.record_peak_memory();
+
+        info!(
+            sandbox_id = %self.id(),
+            module_name = ?module.name(),
+            "Module loaded successfully"
+        );
+    }
+
+    /// Run the init hook, if one is pending.
+    fn run_init_hook(&mut self) -> ExecutionResult<()> {
+        let Some(hook) = self.init_hook.take() else {
+            return Ok(());
+        };
+
+        debug!(sandbox_id = %self.id(), "Running init hook");
+        hook(self)
+    }
+
+    /// Check if a module is currently loaded.
+    pub fn is_loaded(&self) -> bool {
+        self.instance.is_some()
+    }
+
+    /// Get information about the loaded module.
+    pub fn loaded_module(&self) -> Option<&ValidatedModule> {
         self.module.as_ref()
     }
 
@@ -280,6 +1249,9 @@ impl<S: Send + 'static> Sandbox<S> {
     /// - `P`: Parameter type (must implement `WasmParams`)
     /// - `R`: Return type (must implement `WasmResults`)
     ///
+    /// If the call fails, anything host functions wrote to the user state
+    /// before the failure is kept; see [`take_partial`](Self::take_partial).
+    ///
     /// # Example
     ///
     /// ```ignore
@@ -290,39 +1262,251 @@ impl<S: Send + 'static> Sandbox<S> {
         P: wasmtime::WasmParams,
         R: wasmtime::WasmResults,
     {
-        let instance = self
-            .instance
-            .as_ref()
-            .ok_or(ExecutionError::ModuleNotLoaded)?;
+        if self.engine.async_enabled() {
+            return Err(ExecutionError::AsyncRequired("call"));
+        }
 
-        let func = instance
-            .get_typed_func::<P, R>(&mut self.store, name)
-            .map_err(|_| ExecutionError::FunctionNotFound(name.to_string()))?;
+        let func = self.typed_func::<P, R>(name)?;
+        let _active = self.engine.enter_execution();
+        let budget = self.begin_call();
+
+        debug!(sandbox_id = %self.id(), function = name, "Calling function");
+
+        let result = func.call(self.store_mut(), params);
+
+        self.finish_call(name, budget, result)
+    }
+
+    /// Call an exported function asynchronously.
+    ///
+    /// This must be used instead of [`call`](Self::call) when the engine has
+    /// async support enabled, and allows async host functions to be awaited.
+    /// Fuel, timeout, and trap handling are the same as for `call`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let result: i32 = sandbox.call_async("add", (2i32, 3i32)).await?;
+    /// ```
+    pub async fn call_async<P, R>(&mut self, name: &str, params: P) -> ExecutionResult<R>
+    where
+        P: wasmtime::WasmParams,
+        R: wasmtime::WasmResults,
+    {
+        if !self.engine.async_enabled() {
+            return Err(ExecutionError::AsyncNotEnabled);
+        }
+
+        let func = self.typed_func::<P, R>(name)?;
+        let _active = self.engine.enter_execution();
+        let initial_fuel = self.begin_call();
+
+        debug!(sandbox_id = %self.id(), function = name, "Calling function (async)");
+
+        let result = func.call_async(self.store_mut(), params).await;
+
+        self.finish_call(name, initial_fuel, result)
+    }
+
+    /// Look up a typed export of the loaded instance.
+    fn typed_func<P, R>(&mut self, name: &str) -> ExecutionResult<wasmtime::TypedFunc<P, R>>
+    where
+        P: wasmtime::WasmParams,
+        R: wasmtime::WasmResults,
+    {
+        let instance = self.instance.ok_or(ExecutionError::ModuleNotLoaded)?;
+
+        instance
+            .get_typed_func::<P, R>(self.store_mut(), name)
+            .map_err(|_| ExecutionError::FunctionNotFound(name.to_string()))
+    }
+
+    /// Set the policy consulted when a call is running low on fuel.
+    ///
+    /// Fuel is checked each time a host function returns to the guest, and
+    /// once less than a quarter of the call's starting fuel remains the
+    /// policy may grant more, which is added to the running call. Applies
+    /// to [`call`](Self::call) and [`call_async`](Self::call_async) alike.
+    ///
+    /// Only host returns can top up a call: compiled code holds its fuel
+    /// counter in a register between calls, and Wasmtime cannot resume a
+    /// call once it traps. A guest loop that makes no host calls therefore
+    /// still fails with `ExecutionError::OutOfFuel` when the fuel it
+    /// started the loop with runs out, however much the policy would grant.
+    ///
+    /// Requires fuel to be enabled on the engine.
+    pub fn set_refuel_policy(&mut self, policy: impl RefuelPolicy + 'static) {
+        self.store_mut().data_mut().refuel_policy = Some(Arc::new(policy));
+
+        if self.engine.fuel_enabled() {
+            Self::install_refuel_hook(self.store_mut());
+        }
+    }
+
+    /// Remove the refuel policy.
+    pub fn clear_refuel_policy(&mut self) {
+        self.store_mut().data_mut().refuel_policy = None;
+    }
+
+    /// Run `hook` on the user state each time the sandbox is reset.
+    ///
+    /// Replaces any previously set hook.
+    pub fn set_reset_hook(&mut self, hook: impl FnMut(&mut S) + Send + 'static) {
+        self.reset_hook = Some(Box::new(hook));
+    }
+
+    /// Run `hook` once, right after the next module is instantiated.
+    ///
+    /// The hook gets the sandbox with the module loaded, so it can
+    /// initialize guest state before the first call, e.g. with
+    /// [`write_memory`](Self::write_memory) or by calling exported setters.
+    /// An error from the hook is returned by `load_module`, with the module
+    /// left loaded. With an async engine the hook can access memory but not
+    /// call exports. Replaces any pending hook.
+    pub fn on_instantiate(
+        &mut self,
+        hook: impl FnOnce(&mut Sandbox<S>) -> ExecutionResult<()> + Send + 'static,
+    ) {
+        self.init_hook = Some(Box::new(hook));
+    }
+
+    /// Set the maximum number of refuels per call.
+    pub fn set_max_refuel_rounds(&mut self, rounds: u32) {
+        self.store_mut().data_mut().max_refuel_rounds = rounds;
+    }
 
-        // Record start time
-        self.store.data_mut().metrics.start_time = Some(Instant::now());
+    /// Record the start of a call and return the fuel available to it.
+    ///
+    /// This also arms the epoch deadline relative to the current epoch.
+    fn begin_call(&mut self) -> u64 {
+        self.arm_epoch_deadline();
 
-        // Get initial fuel
-        let initial_fuel = if self.engine.fuel_enabled() {
-            self.store.get_fuel().unwrap_or(0)
+        let data = self.store_mut().data_mut();
+        if let Some(collector) = &data.config.metrics_collector {
+            collector.record_start();
+        }
+        let metrics = &mut data.metrics;
+        metrics.start_time = Some(Instant::now());
+        metrics.fuel_consumed = 0;
+        metrics.refuels = 0;
+        metrics.fuel_refueled = 0;
+
+        let budget = if self.engine.fuel_enabled() {
+            self.store().get_fuel().unwrap_or(0)
         } else {
             0
         };
+        let engine = Arc::clone(&self.engine);
+        let budget = Self::reserve_account_fuel(&engine, self.store_mut(), budget);
+        self.store_mut().data_mut().call_budget = budget;
 
-        debug!(sandbox_id = %self.id(), function = name, "Calling function");
+        if let Some(observer) = &mut self.store_mut().data_mut().fuel_observer {
+            observer.call_budget = budget;
+            observer.fired = false;
+        }
+
+        budget
+    }
+
+    /// Reserve a call's fuel from the resource account, if any, and return
+    /// the fuel available to the call.
+    ///
+    /// If the account cannot cover `budget`, the store is limited to what it
+    /// can cover and the rest is restored when the call finishes.
+    pub(crate) fn reserve_account_fuel(
+        engine: &SharedEngine,
+        store: &mut Store<SandboxData<S>>,
+        budget: u64,
+    ) -> u64 {
+        let Some(account) = store.data().config.account.clone() else {
+            return budget;
+        };
+        if !engine.fuel_enabled() {
+            return budget;
+        }
+
+        let reserved = account.reserve_fuel(budget);
+        if reserved < budget && store.set_fuel(reserved).is_ok() {
+            debug!(
+                sandbox_id = %store.data().id,
+                budget,
+                reserved,
+                "Call fuel limited by resource account"
+            );
+            store.data_mut().account_withheld = budget - reserved;
+        }
 
-        // Execute the function
-        let result = func.call(&mut self.store, params);
+        reserved
+    }
 
-        // Record end time
-        self.store.data_mut().metrics.end_time = Some(Instant::now());
+    /// Return a call's unspent fuel to the resource account, if any, and
+    /// restore fuel withheld by it. Returns the fuel that was withheld.
+    pub(crate) fn settle_account_fuel(store: &mut Store<SandboxData<S>>, remaining: u64) -> u64 {
+        let data = store.data_mut();
+        let withheld = std::mem::take(&mut data.account_withheld);
+        let Some(account) = data.config.account.clone() else {
+            return withheld;
+        };
 
-        // Calculate fuel consumed
-        if self.engine.fuel_enabled() {
-            let remaining_fuel = self.store.get_fuel().unwrap_or(0);
-            self.store.data_mut().metrics.fuel_consumed =
+        account.refund_fuel(remaining);
+        if withheld > 0 {
+            let _ = store.set_fuel(remaining + withheld);
+        }
+        withheld
+    }
+
+    /// Build the error for an exhausted resource account.
+    fn account_exhausted(&self, resource: AccountResource) -> ExecutionError {
+        self.store().data().account_exhausted(resource)
+    }
+
+    /// Confirm the limiter's pending memory growth and copy its peak memory
+    /// into the metrics.
+    fn record_peak_memory(&mut self) {
+        let data = self.store_mut().data_mut();
+        data.limits.confirm_growth();
+        data.metrics.peak_memory = data.limits.peak_memory();
+    }
+
+    /// Record the end of a call and translate its result.
+    fn finish_call<T>(
+        &mut self,
+        name: &str,
+        initial_fuel: u64,
+        result: wasmtime::Result<T>,
+    ) -> ExecutionResult<T> {
+        // Record end time and peak memory
+        self.store_mut().data_mut().metrics.end_time = Some(Instant::now());
+        self.record_peak_memory();
+        self.store_mut().data_mut().flush_events();
+
+        // Calculate fuel consumed, counting fuel granted during the call
+        let initial_fuel = initial_fuel + self.store().data().metrics.fuel_refueled;
+        let remaining_fuel = self
+            .engine
+            .fuel_enabled()
+            .then(|| self.store().get_fuel().unwrap_or(0));
+        let mut account_withheld = 0;
+        if let Some(remaining_fuel) = remaining_fuel {
+            self.store_mut().data_mut().metrics.fuel_consumed +=
                 initial_fuel.saturating_sub(remaining_fuel);
+            account_withheld = Self::settle_account_fuel(self.store_mut(), remaining_fuel);
+        }
+
+        let data = self.store_mut().data_mut();
+        let record = CallRecord {
+            function: name.to_string(),
+            fuel_consumed: data.metrics.fuel_consumed,
+            duration: data.metrics.duration().unwrap_or_default(),
+        };
+        if let Some(collector) = &data.config.metrics_collector {
+            collector.record_end();
+            if let Some(remaining_fuel) = remaining_fuel {
+                collector.record_fuel_consumed(initial_fuel, remaining_fuel);
+            }
+            collector.record_call_fuel(&record.function, record.fuel_consumed, record.duration);
         }
+        data.metrics.per_call.push(record);
 
         // Handle the result
         match result {
@@ -330,39 +1514,60 @@ impl<S: Send + 'static> Sandbox<S> {
                 info!(
                     sandbox_id = %self.id(),
                     function = name,
-                    duration = ?self.store.data().metrics.duration(),
+                    duration = ?self.store().data().metrics.duration(),
                     "Function call completed successfully"
                 );
                 Ok(value)
             }
             Err(err) => {
-                // Check if it's a trap first, then inspect the trap message
+                // Check if it's a trap first, then inspect the trap code
                 if let Some(trap) = err.downcast_ref::<wasmtime::Trap>() {
-                    let trap_msg = trap.to_string();
+                    if *trap == Trap::Interrupt && self.store().data().is_cancelled() {
+                        warn!(sandbox_id = %self.id(), function = name, "Call cancelled");
+                        return Err(ExecutionError::Cancelled);
+                    }
+
+                    if *trap == Trap::StackOverflow {
+                        warn!(sandbox_id = %self.id(), function = name, "Stack overflow");
+                        return Err(ExecutionError::StackOverflow {
+                            limit: self.engine.config().max_wasm_stack,
+                        });
+                    }
+
+                    // The call ran out of the fuel the account could cover
+                    if *trap == Trap::OutOfFuel && account_withheld > 0 {
+                        warn!(
+                            sandbox_id = %self.id(),
+                            function = name,
+                            "Resource account fuel exhausted"
+                        );
+                        return Err(self.account_exhausted(AccountResource::Fuel));
+                    }
 
-                    // Check for out of fuel
-                    if trap_msg.contains("fuel") {
-                        let limit = self.store.data().config.limits.initial_fuel;
+                    // Classify by trap code rather than message text, which
+                    // varies across wasmtime versions
+                    if *trap == Trap::OutOfFuel {
+                        let limit = self.store().data().config.limits.initial_fuel;
                         warn!(
                             sandbox_id = %self.id(),
                             function = name,
                             "Out of fuel"
                         );
                         return Err(ExecutionError::OutOfFuel {
-                            consumed: self.store.data().metrics.fuel_consumed,
+                            consumed: self.store().data().metrics.fuel_consumed,
                             limit,
                         });
                     }
 
-                    // Check for epoch deadline
-                    if trap_msg.contains("epoch") {
+                    // An epoch interrupt without cancellation is the deadline
+                    if *trap == Trap::Interrupt {
                         warn!(
                             sandbox_id = %self.id(),
                             function = name,
                             "Execution timeout"
                         );
                         return Err(ExecutionError::Timeout(
-                            self.store.data().config.limits.timeout,
+                            self.store().data().config.limits.timeout,
                         ));
                     }
 
@@ -373,19 +1578,94 @@ impl<S: Send + 'static> Sandbox<S> {
                         trap = ?trap,
                         "Function trapped"
                     );
-                    return Err(ExecutionError::Trap(TrapInfo::from(*trap)));
+                    return Err(ExecutionError::Trap(self.trap_info(*trap, &err)));
+                }
+
+                if let Some(exhausted) = err.downcast_ref::<AccountExhausted>() {
+                    warn!(
+                        sandbox_id = %self.id(),
+                        function = name,
+                        resource = %exhausted.resource,
+                        "Resource account exhausted"
+                    );
+                    return Err(self.account_exhausted(exhausted.resource));
+                }
+
+                // Typed failure reported by a host function
+                match err.downcast::<HostFunctionError>() {
+                    Ok(host) => {
+                        warn!(
+                            sandbox_id = %self.id(),
+                            function = name,
+                            error = %host,
+                            "Host function failed"
+                        );
+                        Err(ExecutionError::Host(host))
+                    }
+                    // Generic wasmtime error
+                    Err(err) => Err(ExecutionError::Wasmtime(err)),
                 }
+            }
+        }
+    }
 
-                // Generic wasmtime error
-                Err(ExecutionError::Wasmtime(err))
+    /// Describe a trap, with its backtrace if backtraces are enabled.
+    fn trap_info(&self, trap: Trap, err: &wasmtime::Error) -> TrapInfo {
+        let info = TrapInfo::from(trap);
+        match err.downcast_ref::<WasmBacktrace>() {
+            Some(backtrace) if self.store().data().config.capture_backtraces => {
+                info.with_backtrace(backtrace)
             }
+            _ => info,
+        }
+    }
+
+    /// Get the size in bytes of the exported `memory`, if any.
+    pub fn memory_size(&self) -> Option<usize> {
+        self.memory.map(|memory| memory.data_size(self.store()))
+    }
+
+    /// Read bytes from the exported `memory`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no module is loaded, the module exports no memory,
+    /// or the range is out of bounds.
+    pub fn read_memory(&mut self, offset: usize, len: usize) -> ExecutionResult<Vec<u8>> {
+        let memory = self.exported_memory()?;
+        let data = memory.data(self.store());
+        let range = memory_range(offset, len, data.len())?;
+
+        Ok(data[range].to_vec())
+    }
+
+    /// Write bytes to the exported `memory`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no module is loaded, the module exports no memory,
+    /// or the range is out of bounds.
+    pub fn write_memory(&mut self, offset: usize, bytes: &[u8]) -> ExecutionResult<()> {
+        let memory = self.exported_memory()?;
+        let data = memory.data_mut(self.store_mut());
+        let range = memory_range(offset, bytes.len(), data.len())?;
+
+        data[range].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Get the exported `memory` of the loaded instance.
+    fn exported_memory(&self) -> ExecutionResult<Memory> {
+        if self.instance.is_none() {
+            return Err(ExecutionError::ModuleNotLoaded);
         }
+        self.memory.ok_or(ExecutionError::MemoryNotFound)
     }
 
     /// Get the remaining fuel.
     pub fn remaining_fuel(&self) -> Option<u64> {
         if self.engine.fuel_enabled() {
-            self.store.get_fuel().ok()
+            self.store().get_fuel().ok()
         } else {
             None
         }
@@ -394,8 +1674,8 @@ impl<S: Send + 'static> Sandbox<S> {
     /// Add more fuel to the sandbox.
     pub fn add_fuel(&mut self, fuel: u64) -> ExecutionResult<()> {
         if self.engine.fuel_enabled() {
-            let current = self.store.get_fuel()?;
-            self.store.set_fuel(current + fuel)?;
+            let current = self.store().get_fuel()?;
+            self.store_mut().set_fuel(current + fuel)?;
             debug!(sandbox_id = %self.id(), added = fuel, total = current + fuel, "Added fuel");
         }
         Ok(())
@@ -405,15 +1685,60 @@ impl<S: Send + 'static> Sandbox<S> {
     ///
     /// Returns the function type if the function exists, or None otherwise.
     pub fn get_func_type(&mut self, name: &str) -> Option<wasmtime::FuncType> {
-        let instance = self.instance.as_ref()?;
-        let func = instance.get_func(&mut self.store, name)?;
-        Some(func.ty(&self.store))
+        let instance = self.instance?;
+        let func = instance.get_func(self.store_mut(), name)?;
+        Some(func.ty(self.store()))
+    }
+
+    /// Check that `params` match an exported function's signature without
+    /// calling it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ExecutionError::ModuleNotLoaded` or
+    /// `ExecutionError::FunctionNotFound` if there is no such function,
+    /// `ExecutionError::ArityMismatch` if the number of parameters differs,
+    /// or `ExecutionError::TypeMismatch` for the first parameter whose type
+    /// differs.
+    pub fn check_call_signature(
+        &mut self,
+        name: &str,
+        params: &[wasmtime::Val],
+    ) -> ExecutionResult<()> {
+        if self.instance.is_none() {
+            return Err(ExecutionError::ModuleNotLoaded);
+        }
+        let func_type = self
+            .get_func_type(name)
+            .ok_or_else(|| ExecutionError::FunctionNotFound(name.to_string()))?;
+
+        if func_type.params().len() != params.len() {
+            return Err(ExecutionError::ArityMismatch {
+                function: name.to_string(),
+                expected: func_type.params().len(),
+                got: params.len(),
+            });
+        }
+
+        for (index, (expected, param)) in func_type.params().zip(params).enumerate() {
+            if !param.matches_ty(self.store(), &expected)? {
+                let got = param.ty(self.store())?;
+                return Err(ExecutionError::TypeMismatch {
+                    index,
+                    expected: expected.to_string(),
+                    got: got.to_string(),
+                });
+            }
+        }
+
+        Ok(())
     }
 
     /// Call an exported function with dynamic typing.
     ///
     /// This is useful for CLI tools or scenarios where function signatures
-    /// aren't known at compile time.
+    /// aren't known at compile time. Parameters are checked with
+    /// [`check_call_signature`](Self::check_call_signature) first.
     ///
     /// # Arguments
     ///
@@ -428,102 +1753,86 @@ impl<S: Send + 'static> Sandbox<S> {
         name: &str,
         params: Vec<wasmtime::Val>,
     ) -> ExecutionResult<Vec<wasmtime::Val>> {
-        let instance = self
-            .instance
-            .as_ref()
-            .ok_or(ExecutionError::ModuleNotLoaded)?;
+        if self.engine.async_enabled() {
+            return Err(ExecutionError::AsyncRequired("call_dynamic"));
+        }
+
+        self.check_call_signature(name, &params)?;
+        let instance = self.instance.ok_or(ExecutionError::ModuleNotLoaded)?;
 
         let func = instance
-            .get_func(&mut self.store, name)
+            .get_func(self.store_mut(), name)
             .ok_or_else(|| ExecutionError::FunctionNotFound(name.to_string()))?;
 
         // Get function type to determine result count
-        let func_type = func.ty(&self.store);
+        let func_type = func.ty(self.store());
         let result_count = func_type.results().len();
         let mut results = vec![wasmtime::Val::I32(0); result_count];
 
-        // Record start time
-        self.store.data_mut().metrics.start_time = Some(Instant::now());
-
-        // Get initial fuel
-        let initial_fuel = if self.engine.fuel_enabled() {
-            self.store.get_fuel().unwrap_or(0)
-        } else {
-            0
-        };
+        let _active = self.engine.enter_execution();
+        let budget = self.begin_call();
 
         debug!(sandbox_id = %self.id(), function = name, "Calling function (dynamic)");
 
-        // Execute the function
-        let call_result = func.call(&mut self.store, &params, &mut results);
-
-        // Record end time
-        self.store.data_mut().metrics.end_time = Some(Instant::now());
-
-        // Record fuel consumption
-        if self.engine.fuel_enabled() {
-            let remaining = self.store.get_fuel().unwrap_or(0);
-            self.store.data_mut().metrics.fuel_consumed = initial_fuel.saturating_sub(remaining);
-        }
-
-        match call_result {
-            Ok(()) => {
-                info!(
-                    sandbox_id = %self.id(),
-                    function = name,
-                    "Function call completed successfully"
-                );
-                Ok(results)
-            }
-            Err(err) => {
-                // Check if it's a trap first, then inspect the trap message
-                if let Some(trap) = err.downcast_ref::<wasmtime::Trap>() {
-                    let trap_msg = trap.to_string();
-
-                    if trap_msg.contains("fuel") {
-                        let limit = self.store.data().config.limits.initial_fuel;
-                        warn!(sandbox_id = %self.id(), function = name, "Out of fuel");
-                        return Err(ExecutionError::OutOfFuel {
-                            consumed: self.store.data().metrics.fuel_consumed,
-                            limit,
-                        });
-                    }
-
-                    if trap_msg.contains("epoch") {
-                        warn!(sandbox_id = %self.id(), function = name, "Execution timeout");
-                        return Err(ExecutionError::Timeout(
-                            self.store.data().config.limits.timeout,
-                        ));
-                    }
-
-                    warn!(sandbox_id = %self.id(), function = name, trap = ?trap, "Function trapped");
-                    return Err(ExecutionError::Trap(TrapInfo::from(*trap)));
-                }
+        let call_result = func.call(self.store_mut(), &params, &mut results);
 
-                Err(ExecutionError::Wasmtime(err))
-            }
-        }
+        self.finish_call(name, budget, call_result)?;
+        Ok(results)
     }
 
     /// Reset the sandbox for reuse.
     ///
-    /// This clears the current instance and resets metrics, but preserves
-    /// registered host functions.
+    /// This drops the current instance and its memory, resets metrics, and
+    /// restores the initial fuel, epoch deadline and configured memory
+    /// limit, even while children it lent memory to are alive. A
+    /// cancellation token that has been cancelled is detached, so later
+    /// calls run. Capabilities and registered host functions are preserved.
+    /// User state is kept as well, after being passed to the reset hook if
+    /// one is set (see [`set_reset_hook`](Self::set_reset_hook)).
     pub fn reset(&mut self) {
         self.instance = None;
+        self.memory = None;
         self.module = None;
-        self.store.data_mut().metrics = SandboxMetrics::default();
 
-        // Reset fuel if enabled
-        if self.engine.fuel_enabled() {
-            let initial = self.store.data().config.limits.initial_fuel;
-            let _ = self.store.set_fuel(initial);
+        // Wasmtime never frees instances from a live store, so rebuild it
+        let mut data = self
+            .store
+            .take()
+            .expect("sandbox store is present")
+            .into_data();
+        data.metrics = SandboxMetrics::default();
+        data.module_hash = None;
+        data.limits.reset_peak();
+        data.limits.release_account_memory();
+        data.limits.forgive_loans();
+        if data.is_cancelled() {
+            data.cancellation = None;
         }
+        if let Some(hook) = &mut self.reset_hook {
+            hook(&mut data.user_state);
+        }
+        self.store = Some(Self::build_store(&self.engine, data));
 
         debug!(sandbox_id = %self.id(), "Sandbox reset");
     }
 }
 
+/// Bounds-check a memory access, returning the byte range it covers.
+fn memory_range(
+    offset: usize,
+    len: usize,
+    memory_size: usize,
+) -> ExecutionResult<std::ops::Range<usize>> {
+    match offset.checked_add(len) {
+        Some(end) if end <= memory_size => Ok(offset..end),
+        _ => Err(ExecutionError::MemoryAccessOutOfBounds {
+            offset,
+            len,
+            memory_size,
+        }),
+    }
+}
+
 impl<S: Send + 'static> std::fmt::Debug for Sandbox<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Sandbox")
@@ -539,6 +1848,10 @@ pub struct SandboxBuilder<S = ()> {
     engine: SharedEngine,
     user_state: Option<S>,
     config: SandboxConfig,
+    registries: Vec<Arc<dyn HostFunctions<S>>>,
+    cancellation: Option<CancellationToken>,
+    reset_hook: Option<ResetHook<S>>,
+    init_hook: Option<InitHook<S>>,
 }
 
 impl<S: Send + 'static> SandboxBuilder<S> {
@@ -548,6 +1861,10 @@ impl<S: Send + 'static> SandboxBuilder<S> {
             engine,
             user_state: None,
             config: SandboxConfig::default(),
+            registries: Vec::new(),
+            cancellation: None,
+            reset_hook: None,
+            init_hook: None,
         }
     }
 
@@ -587,33 +1904,188 @@ impl<S: Send + 'static> SandboxBuilder<S> {
         self
     }
 
-    /// Build the sandbox.
-    pub fn build(self) -> ExecutionResult<Sandbox<S>>
-    where
-        S: Default,
-    {
-        let state = self.user_state.unwrap_or_default();
-        Sandbox::new(self.engine, state, self.config)
+    /// Set the capabilities granted to the sandbox.
+    ///
+    /// See [`SandboxConfig::with_capabilities`].
+    pub fn with_capabilities(mut self, capabilities: impl Into<CapabilityView>) -> Self {
+        self.config = self.config.with_capabilities(capabilities);
+        self
     }
 
-    /// Build the sandbox with the provided state.
-    pub fn build_with_state(self, state: S) -> ExecutionResult<Sandbox<S>> {
-        Sandbox::new(self.engine, state, self.config)
+    /// Set the capabilities granted to the sandbox from a frozen set.
+    ///
+    /// Unlike [`with_capabilities`](Self::with_capabilities), the caller
+    /// keeps no handle through which the set could be changed.
+    pub fn with_frozen_capabilities(mut self, capabilities: FrozenCapabilitySet) -> Self {
+        self.config = self.config.with_frozen_capabilities(capabilities);
+        self
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::EngineConfig;
-    use crate::engine::AegisEngine;
-    use crate::module::ModuleLoader;
-    use std::sync::Arc;
+    /// Set the event dispatcher.
+    pub fn with_event_dispatcher(mut self, dispatcher: Arc<EventDispatcher>) -> Self {
+        self.config.event_dispatcher = Some(dispatcher);
+        self
+    }
 
-    fn create_engine() -> SharedEngine {
+    /// Feed the sandbox's metrics to `collector`.
+    ///
+    /// The collector is shared rather than reset, so it accumulates across
+    /// calls and resets; its timing and fuel totals describe the latest call.
+    pub fn with_metrics_collector(mut self, collector: Arc<MetricsCollector>) -> Self {
+        self.config.metrics_collector = Some(collector);
+        self
+    }
+
+    /// Draw fuel and memory from a resource account shared with other
+    /// sandboxes.
+    pub fn with_account(mut self, account: Arc<ResourceAccount>) -> Self {
+        self.config.account = Some(account);
+        self
+    }
+
+    /// Let `token` cancel the sandbox's calls.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Enable or disable backtraces on traps.
+    ///
+    /// See [`SandboxConfig::capture_backtraces`].
+    pub fn with_backtraces(mut self, enabled: bool) -> Self {
+        self.config.capture_backtraces = enabled;
+        self
+    }
+
+    /// Set the principal the sandbox runs on behalf of.
+    ///
+    /// See [`SandboxConfig::principal`].
+    pub fn with_principal(mut self, principal: impl Into<String>) -> Self {
+        self.config.principal = Some(principal.into());
+        self
+    }
+
+    /// Enable or disable buffering of high-frequency events.
+    ///
+    /// See [`SandboxData::emit`].
+    pub fn with_event_buffering(mut self, enabled: bool) -> Self {
+        self.config.buffer_events = enabled;
+        self
+    }
+
+    /// Run `hook` on the user state each time the sandbox is reset.
+    ///
+    /// See [`Sandbox::set_reset_hook`].
+    pub fn on_reset(mut self, hook: impl FnMut(&mut S) + Send + 'static) -> Self {
+        self.reset_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Run `hook` once, right after the first module is instantiated.
+    ///
+    /// See [`Sandbox::on_instantiate`].
+    pub fn with_init(
+        mut self,
+        hook: impl FnOnce(&mut Sandbox<S>) -> ExecutionResult<()> + Send + 'static,
+    ) -> Self {
+        self.init_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// Reset the user state with [`Resettable::reset`] each time the sandbox
+    /// is reset.
+    pub fn with_resettable_state(self) -> Self
+    where
+        S: Resettable,
+    {
+        self.on_reset(S::reset)
+    }
+
+    /// Preload host functions from a registry.
+    ///
+    /// The functions are installed into the sandbox's linker when it is
+    /// built. A registry can be shared by any number of builders.
+    pub fn with_registry(mut self, registry: Arc<dyn HostFunctions<S>>) -> Self {
+        self.registries.push(registry);
+        self
+    }
+
+    /// Build the sandbox.
+    pub fn build(mut self) -> ExecutionResult<Sandbox<S>>
+    where
+        S: Default,
+    {
+        let state = self.user_state.take().unwrap_or_default();
+        self.build_with_state(state)
+    }
+
+    /// Build the sandbox with the provided state.
+    pub fn build_with_state(self, state: S) -> ExecutionResult<Sandbox<S>> {
+        let mut sandbox = Sandbox::new(self.engine, state, self.config)?;
+        if let Some(token) = self.cancellation {
+            sandbox.set_cancellation(token);
+        }
+        sandbox.reset_hook = self.reset_hook;
+        sandbox.init_hook = self.init_hook;
+
+        for registry in &self.registries {
+            sandbox.install_host_functions(registry.as_ref())?;
+        }
+
+        Ok(sandbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EngineConfig;
+    use crate::engine::AegisEngine;
+    use crate::module::{ImportKind, ModuleLoader};
+    use aegis_capability::CapabilitySet;
+    use std::sync::Arc;
+
+    fn create_engine() -> SharedEngine {
         Arc::new(AegisEngine::new(EngineConfig::default()).unwrap())
     }
 
+    /// Background thread that advances an engine's epoch, as an epoch
+    /// manager would, until dropped.
+    struct EpochTicker {
+        stop: Arc<std::sync::atomic::AtomicBool>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl EpochTicker {
+        /// Increment `engine`'s epoch every `interval`.
+        fn start(engine: &SharedEngine, interval: Duration) -> Self {
+            let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            let thread = {
+                let engine = Arc::clone(engine);
+                let stop = Arc::clone(&stop);
+                std::thread::spawn(move || {
+                    while !stop.load(std::sync::atomic::Ordering::Relaxed) {
+                        std::thread::sleep(interval);
+                        engine.increment_epoch();
+                    }
+                })
+            };
+            Self {
+                stop,
+                thread: Some(thread),
+            }
+        }
+    }
+
+    impl Drop for EpochTicker {
+        fn drop(&mut self) {
+            self.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
     #[test]
     fn test_sandbox_creation() {
         let engine = create_engine();
@@ -635,6 +2107,111 @@ mod tests {
         assert!(!sandbox.is_loaded());
     }
 
+    #[test]
+    fn test_missing_import_is_named() {
+        let engine = create_engine();
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (import "env" "log" (func (param i32)))
+                (import "env" "notify" (func))
+                (import "env" "log_level" (global i32))
+            )
+        "#,
+            )
+            .unwrap();
+
+        let mut sandbox = Sandbox::<()>::new(engine, (), SandboxConfig::default()).unwrap();
+        sandbox
+            .linker_mut()
+            .func_wrap("env", "log", |_: i32| {})
+            .unwrap();
+        sandbox
+            .linker_mut()
+            .func_wrap("env", "log_level", || {})
+            .unwrap();
+
+        let missing: Vec<_> = sandbox
+            .missing_imports(&module)
+            .into_iter()
+            .map(|i| format!("{}::{} ({})", i.module, i.name, i.kind))
+            .collect();
+        assert_eq!(
+            missing,
+            vec!["env::notify (function)", "env::log_level (global)"]
+        );
+
+        match sandbox.load_module(&module) {
+            Err(ExecutionError::MissingImport {
+                module,
+                name,
+                expected_kind,
+            }) => {
+                assert_eq!(module, "env");
+                assert_eq!(name, "notify");
+                assert!(matches!(expected_kind, ImportKind::Function { .. }));
+            }
+            other => panic!("expected MissingImport, got {other:?}"),
+        }
+        assert!(!sandbox.is_loaded());
+    }
+
+    #[test]
+    fn test_instantiation_fuel() {
+        let engine = create_engine();
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (global $n (mut i32) (i32.const 0))
+                (func $init
+                    (block $done
+                        (loop $loop
+                            (br_if $done (i32.ge_u (global.get $n) (i32.const 10000)))
+                            (global.set $n (i32.add (global.get $n) (i32.const 1)))
+                            (br $loop)
+                        )
+                    )
+                )
+                (start $init)
+                (func (export "count") (result i32) (global.get $n))
+            )
+        "#,
+            )
+            .unwrap();
+
+        let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+            .with_fuel_limit(1_000)
+            .build()
+            .unwrap();
+        match sandbox.load_module(&module) {
+            Err(ExecutionError::OutOfFuel { consumed, limit }) => {
+                assert_eq!(limit, 1_000);
+                assert_eq!(consumed, 1_000);
+            }
+            other => panic!("expected OutOfFuel, got {other:?}"),
+        }
+        assert!(!sandbox.is_loaded());
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_fuel_limit(1_000_000)
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+        let instantiation_fuel = sandbox.metrics().instantiation_fuel;
+        assert!(instantiation_fuel > 10_000);
+        assert_eq!(
+            sandbox.remaining_fuel().unwrap(),
+            1_000_000 - instantiation_fuel
+        );
+        assert_eq!(sandbox.call::<(), i32>("count", ()).unwrap(), 10_000);
+    }
+
     #[test]
     fn test_load_and_call() {
         let engine = create_engine();
@@ -704,6 +2281,247 @@ mod tests {
         assert!(sandbox.metrics().fuel_consumed > 0);
     }
 
+    #[test]
+    fn test_per_call_fuel_records() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (func (export "cheap") (result i32) (i32.const 1))
+                (func (export "spin") (param i32)
+                    (loop $loop
+                        (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                        (br_if $loop (i32.gt_s (local.get 0) (i32.const 0)))
+                    )
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_fuel_limit(1_000_000)
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let _: i32 = sandbox.call("cheap", ()).unwrap();
+        sandbox
+            .call_dynamic("spin", vec![wasmtime::Val::I32(1000)])
+            .unwrap();
+
+        let records = &sandbox.metrics().per_call;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].function, "cheap");
+        assert_eq!(records[1].function, "spin");
+        assert!(records[0].fuel_consumed > 0);
+        assert!(records[1].fuel_consumed > 1000);
+        assert!(records[1].fuel_consumed > records[0].fuel_consumed * 100);
+        assert_eq!(records[1].fuel_consumed, sandbox.metrics().fuel_consumed);
+
+        sandbox.reset();
+        assert!(sandbox.metrics().per_call.is_empty());
+    }
+
+    #[test]
+    fn test_metrics_collector() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "count") (param i32) (result i32)
+                    (local $i i32)
+                    (loop $loop
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $loop (i32.lt_u (local.get $i) (local.get 0))))
+                    (local.get $i))
+            )
+        "#,
+            )
+            .unwrap();
+
+        let collector = Arc::new(MetricsCollector::new());
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_metrics_collector(Arc::clone(&collector))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+        let result: i32 = sandbox.call("count", 1000).unwrap();
+        assert_eq!(result, 1000);
+
+        let snapshot = collector.snapshot();
+        assert!(snapshot.fuel.consumed_fuel > 0);
+        assert_eq!(snapshot.fuel.per_call.len(), 1);
+        assert!(snapshot.timing.execution_time > Duration::ZERO);
+        assert!(snapshot.timing.instantiation_time > Duration::ZERO);
+        assert_eq!(snapshot.memory.initial_memory, 65536);
+    }
+
+    #[test]
+    fn test_shared_resource_account() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "count") (param i32) (result i32)
+                    (local $i i32)
+                    (loop $loop
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $loop (i32.lt_u (local.get $i) (local.get 0))))
+                    (local.get $i))
+            )
+        "#,
+            )
+            .unwrap();
+
+        let account = Arc::new(ResourceAccount::new(50_000, 3 * 65536 / 2));
+        let build = || {
+            SandboxBuilder::<()>::new(Arc::clone(&engine))
+                .with_account(Arc::clone(&account))
+                .build()
+                .unwrap()
+        };
+
+        // Each sandbox fits on its own, but not both together
+        let mut first = build();
+        first.load_module(&module).unwrap();
+        let count: i32 = first.call("count", 1000).unwrap();
+        assert_eq!(count, 1000);
+        let used = account.fuel_used();
+        assert!(used > 0 && used < 50_000);
+
+        let mut second = build();
+        assert!(matches!(
+            second.load_module(&module),
+            Err(ExecutionError::AccountExhausted {
+                resource: AccountResource::Memory,
+                ..
+            })
+        ));
+
+        // Memory is released once the first sandbox is dropped
+        drop(first);
+        assert_eq!(account.memory_used(), 0);
+        second.reset();
+        second.load_module(&module).unwrap();
+
+        assert!(matches!(
+            second.call::<i32, i32>("count", 1_000_000),
+            Err(ExecutionError::AccountExhausted {
+                resource: AccountResource::Fuel,
+                limit: 50_000,
+            })
+        ));
+        assert_eq!(account.fuel_remaining(), 0);
+        assert_eq!(account.peak_memory(), 65536);
+    }
+
+    #[test]
+    fn test_instantiation_charged_to_account() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (func $spin
+                    (local $i i32)
+                    (loop $loop
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $loop (i32.lt_u (local.get $i) (i32.const 1000)))))
+                (start $spin))
+        "#,
+            )
+            .unwrap();
+
+        let account = Arc::new(ResourceAccount::new(10_000, 1 << 20));
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_account(Arc::clone(&account))
+            .build()
+            .unwrap();
+
+        // Each load spends fuel from the account until it runs out
+        let mut loads = 0;
+        let err = loop {
+            match sandbox.load_module(&module) {
+                Ok(()) => {
+                    loads += 1;
+                    sandbox.reset();
+                }
+                Err(err) => break err,
+            }
+            assert!(loads < 100, "instantiation is not charged to the account");
+        };
+        assert!(loads > 0);
+        assert!(matches!(
+            err,
+            ExecutionError::AccountExhausted {
+                resource: AccountResource::Fuel,
+                limit: 10_000,
+            }
+        ));
+        assert_eq!(account.fuel_remaining(), 0);
+    }
+
+    #[test]
+    fn test_failed_growth_refunds_account() {
+        /// Permits any growth, leaving the memory maximum to the sandbox.
+        struct Unlimited;
+
+        impl wasmtime::ResourceLimiter for Unlimited {
+            fn memory_growing(
+                &mut self,
+                _: usize,
+                _: usize,
+                _: Option<usize>,
+            ) -> wasmtime::Result<bool> {
+                Ok(true)
+            }
+
+            fn table_growing(
+                &mut self,
+                _: usize,
+                _: usize,
+                _: Option<usize>,
+            ) -> wasmtime::Result<bool> {
+                Ok(true)
+            }
+        }
+
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (memory 1 2)
+                (func (export "grow") (param i32) (result i32)
+                    (memory.grow (local.get 0))))
+        "#,
+            )
+            .unwrap();
+
+        let account = Arc::new(ResourceAccount::new(1_000_000, 4 * 65536));
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_account(Arc::clone(&account))
+            .build()
+            .unwrap();
+        sandbox.set_resource_limiter(Unlimited);
+        sandbox.load_module(&module).unwrap();
+
+        // Growing past the declared maximum fails even under a permissive limiter
+        assert_eq!(sandbox.call::<i32, i32>("grow", 2).unwrap(), -1);
+        assert_eq!(account.memory_used(), 65536);
+        assert_eq!(sandbox.call::<i32, i32>("grow", 1).unwrap(), 1);
+        assert_eq!(account.memory_used(), 2 * 65536);
+
+        drop(sandbox);
+        assert_eq!(account.memory_used(), 0);
+    }
+
     #[test]
     fn test_out_of_fuel() {
         let engine = create_engine();
@@ -731,7 +2549,10 @@ mod tests {
         sandbox.load_module(&module).unwrap();
 
         let result = sandbox.call::<(), ()>("infinite", ());
-        assert!(matches!(result, Err(ExecutionError::OutOfFuel { .. })));
+        assert!(matches!(
+            result,
+            Err(ExecutionError::OutOfFuel { limit: 1000, .. })
+        ));
     }
 
     #[test]
@@ -786,4 +2607,977 @@ mod tests {
         assert!(!sandbox.is_loaded());
         assert!(sandbox.remaining_fuel().unwrap() > fuel_after_call);
     }
+
+    #[test]
+    fn test_sandbox_check_uses_capabilities() {
+        use aegis_capability::builtin::{LogLevel, LoggingAction, LoggingCapability};
+        use aegis_observe::{CollectingSubscriber, EventSubscriber};
+
+        let capabilities = CapabilitySet::new();
+        capabilities.grant(LoggingCapability::production()).unwrap();
+
+        let dispatcher = Arc::new(EventDispatcher::new());
+        let collector = Arc::new(CollectingSubscriber::new(10));
+        dispatcher.subscribe(Arc::clone(&collector) as Arc<dyn EventSubscriber>);
+
+        let sandbox = SandboxBuilder::<()>::new(create_engine())
+            .with_frozen_capabilities(capabilities.freeze())
+            .with_event_dispatcher(dispatcher)
+            .build()
+            .unwrap();
+
+        let info = LoggingAction::Log {
+            level: LogLevel::Info,
+            message_len: 10,
+        };
+        let debug = LoggingAction::Log {
+            level: LogLevel::Debug,
+            message_len: 10,
+        };
+
+        assert!(sandbox.capabilities().is_frozen());
+        assert!(sandbox.check(&info).is_allowed());
+        assert!(sandbox.check(&debug).is_denied());
+
+        let permitted: Vec<bool> = collector
+            .events()
+            .iter()
+            .map(|(_, event)| match event {
+                SandboxEvent::CapabilityChecked { id, permitted, .. } => {
+                    assert_eq!(id.as_str(), "logging");
+                    *permitted
+                }
+                other => panic!("Unexpected event: {:?}", other),
+            })
+            .collect();
+        assert_eq!(permitted, [true, false]);
+    }
+
+    #[test]
+    fn test_capability_scoped_to_module() {
+        use aegis_capability::builtin::{FilesystemAction, FilesystemCapability, PathPermission};
+        use std::path::PathBuf;
+
+        let engine = create_engine();
+        let loader = ModuleLoader::new(engine.clone());
+        let a = loader.load_wat(r#"(module (func (export "a")))"#).unwrap();
+        let b = loader.load_wat(r#"(module (func (export "b")))"#).unwrap();
+
+        let capabilities = CapabilitySet::new();
+        capabilities
+            .grant(FilesystemCapability::for_module(
+                *a.content_hash().as_bytes(),
+                vec![PathPermission::read_only("/data")],
+            ))
+            .unwrap();
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_capabilities(Arc::new(capabilities))
+            .build()
+            .unwrap();
+        let read = FilesystemAction::Read {
+            path: PathBuf::from("/data/input.txt"),
+        };
+
+        // No module is loaded yet
+        assert!(sandbox.check(&read).is_denied());
+
+        sandbox.load_module(&a).unwrap();
+        assert!(sandbox.check(&read).is_allowed());
+
+        sandbox.reset();
+        sandbox.load_module(&b).unwrap();
+        assert!(sandbox.check(&read).is_denied());
+    }
+
+    #[test]
+    fn test_check_context_names_sandbox() {
+        let sandbox = SandboxBuilder::<()>::new(create_engine())
+            .with_principal("acme")
+            .build()
+            .unwrap();
+
+        let ctx = sandbox.store().data().check_context();
+        assert_eq!(ctx.sandbox_id, Some(sandbox.id().to_string()));
+        assert_eq!(ctx.principal.as_deref(), Some("acme"));
+        assert_eq!(ctx.depth, 0);
+        assert_eq!(ctx.module_hash, None);
+    }
+
+    #[test]
+    fn test_shared_capabilities_can_be_revoked() {
+        use aegis_capability::builtin::{LogLevel, LoggingAction, LoggingCapability};
+
+        let capabilities = Arc::new(CapabilitySet::new());
+        capabilities.grant(LoggingCapability::allow_all()).unwrap();
+
+        let sandbox = SandboxBuilder::<()>::new(create_engine())
+            .with_capabilities(Arc::clone(&capabilities))
+            .build()
+            .unwrap();
+        let action = LoggingAction::Log {
+            level: LogLevel::Info,
+            message_len: 10,
+        };
+
+        // The sandbox only reads the set; its owner can still revoke
+        assert!(!sandbox.capabilities().is_frozen());
+        assert!(sandbox.check(&action).is_allowed());
+        capabilities.revoke(&standard_ids::LOGGING);
+        assert!(sandbox.check(&action).is_denied());
+    }
+
+    #[test]
+    fn test_sandbox_default_denies() {
+        use aegis_capability::builtin::ClockAction;
+
+        let sandbox = Sandbox::<()>::new(create_engine(), (), SandboxConfig::default()).unwrap();
+        let action = ClockAction::GetTime {
+            clock_type: "monotonic".to_string(),
+        };
+        assert!(sandbox.check(&action).is_denied());
+    }
+
+    #[tokio::test]
+    async fn test_call_async() {
+        let engine = Arc::new(AegisEngine::new(EngineConfig::default().with_async(true)).unwrap());
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (func (export "add") (param i32 i32) (result i32)
+                    local.get 0
+                    local.get 1
+                    i32.add
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let mut sandbox = Sandbox::<()>::new(engine, (), SandboxConfig::default()).unwrap();
+
+        assert!(matches!(
+            sandbox.load_module(&module),
+            Err(ExecutionError::AsyncRequired(_))
+        ));
+
+        sandbox.load_module_async(&module).await.unwrap();
+        let result: i32 = sandbox.call_async("add", (2i32, 3i32)).await.unwrap();
+        assert_eq!(result, 5);
+        assert!(sandbox.metrics().duration().is_some());
+
+        assert!(matches!(
+            sandbox.call::<(i32, i32), i32>("add", (1, 2)),
+            Err(ExecutionError::AsyncRequired(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_call_async_refuels() {
+        struct Grant(u64);
+
+        impl RefuelPolicy for Grant {
+            fn refuel(&self, _consumed: u64) -> Option<u64> {
+                Some(self.0)
+            }
+        }
+
+        let engine = Arc::new(AegisEngine::new(EngineConfig::default().with_async(true)).unwrap());
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (import "env" "step" (func $step (param i32) (result i32)))
+                (func (export "spin") (param $n i32) (result i32)
+                    (local $i i32)
+                    (block $done
+                        (loop $loop
+                            (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                            (local.set $i (call $step (local.get $i)))
+                            (br $loop)))
+                    (local.get $i))
+            )
+        "#,
+            )
+            .unwrap();
+
+        let spin_sandbox = |fuel: u64| {
+            let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+                .with_fuel_limit(fuel)
+                .build()
+                .unwrap();
+            sandbox
+                .register_func("env", "step", |i: i32| i + 1)
+                .unwrap();
+            sandbox
+        };
+
+        let mut sandbox = spin_sandbox(10_000_000);
+        sandbox.load_module_async(&module).await.unwrap();
+        sandbox.call_async::<i32, i32>("spin", 1000).await.unwrap();
+        let cost = sandbox.metrics().fuel_consumed;
+
+        // The call keeps running on the granted fuel instead of restarting
+        let mut sandbox = spin_sandbox(cost / 2);
+        sandbox.set_refuel_policy(Grant(cost / 3));
+        sandbox.load_module_async(&module).await.unwrap();
+        let result: i32 = sandbox.call_async("spin", 1000).await.unwrap();
+        assert_eq!(result, 1000);
+        assert_eq!(sandbox.metrics().refuels, 2);
+        assert_eq!(sandbox.metrics().fuel_consumed, cost);
+    }
+
+    #[tokio::test]
+    async fn test_call_async_requires_async_engine() {
+        let mut sandbox =
+            Sandbox::<()>::new(create_engine(), (), SandboxConfig::default()).unwrap();
+
+        let result = sandbox.call_async::<(), ()>("main", ()).await;
+        assert!(matches!(result, Err(ExecutionError::AsyncNotEnabled)));
+    }
+
+    #[test]
+    fn test_memory_read_write() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "sum") (param $ptr i32) (param $len i32) (result i32)
+                    (local $total i32)
+                    (block $done
+                        (loop $loop
+                            (br_if $done (i32.eqz (local.get $len)))
+                            (local.set $total
+                                (i32.add (local.get $total) (i32.load8_u (local.get $ptr))))
+                            (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+                            (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+                            (br $loop)
+                        )
+                    )
+                    (local.get $total)
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let mut sandbox = Sandbox::<()>::new(engine, (), SandboxConfig::default()).unwrap();
+        assert!(matches!(
+            sandbox.read_memory(0, 1),
+            Err(ExecutionError::ModuleNotLoaded)
+        ));
+
+        sandbox.load_module(&module).unwrap();
+        assert_eq!(sandbox.memory_size(), Some(64 * 1024));
+
+        sandbox.write_memory(100, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(sandbox.read_memory(100, 4).unwrap(), [1, 2, 3, 4]);
+
+        let sum: i32 = sandbox.call("sum", (100i32, 4i32)).unwrap();
+        assert_eq!(sum, 10);
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(r#"(module (memory (export "memory") 1))"#)
+            .unwrap();
+
+        let mut sandbox = Sandbox::<()>::new(engine, (), SandboxConfig::default()).unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let size = 64 * 1024;
+        assert!(matches!(
+            sandbox.read_memory(size - 2, 4),
+            Err(ExecutionError::MemoryAccessOutOfBounds { memory_size, .. }) if memory_size == size
+        ));
+        assert!(matches!(
+            sandbox.write_memory(usize::MAX, &[1]),
+            Err(ExecutionError::MemoryAccessOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_memory_not_exported() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(r#"(module (memory 1))"#)
+            .unwrap();
+
+        let mut sandbox = Sandbox::<()>::new(engine, (), SandboxConfig::default()).unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        assert_eq!(sandbox.memory_size(), None);
+        assert!(matches!(
+            sandbox.read_memory(0, 1),
+            Err(ExecutionError::MemoryNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let engine = Arc::new(
+            AegisEngine::new(EngineConfig::default().with_max_wasm_stack(64 * 1024)).unwrap(),
+        );
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (func $recurse (export "recurse") (param i32) (result i32)
+                    (i32.add (call $recurse (i32.add (local.get 0) (i32.const 1))) (i32.const 1))
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine).build().unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let result: ExecutionResult<i32> = sandbox.call("recurse", 0);
+        match result {
+            Err(ExecutionError::StackOverflow { limit }) => assert_eq!(limit, 64 * 1024),
+            other => panic!("expected stack overflow, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_cancellation_token() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+                (module
+                    (func (export "spin") (loop $l (br $l)))
+                    (func (export "noop")))
+            "#,
+            )
+            .unwrap();
+
+        // Cancelling leaves the shared engine's epoch alone
+        let epoch = engine.current_epoch();
+        CancellationToken::new().cancel();
+        assert_eq!(engine.current_epoch(), epoch);
+
+        let token = CancellationToken::new();
+        let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+            .with_fuel_limit(u64::MAX / 2)
+            .with_timeout(Duration::from_secs(60))
+            .with_cancellation(token.clone())
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+        let ticker = EpochTicker::start(&engine, engine.epoch_tick_interval());
+
+        let canceller = {
+            let token = token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                token.cancel();
+            })
+        };
+
+        let start = Instant::now();
+        let result = sandbox.call_void("spin");
+        canceller.join().unwrap();
+
+        assert!(matches!(result, Err(ExecutionError::Cancelled)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(token.is_cancelled());
+
+        // The token stays cancelled, so later calls stop immediately
+        assert!(matches!(
+            sandbox.call_void("spin"),
+            Err(ExecutionError::Cancelled)
+        ));
+        drop(ticker);
+
+        // until a reset detaches it
+        sandbox.reset();
+        sandbox.load_module(&module).unwrap();
+        sandbox.call_void("noop").unwrap();
+    }
+
+    #[test]
+    fn test_epoch_deadline_starts_at_call() {
+        let engine = create_engine();
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (func (export "count") (param $n i32) (result i32)
+                    (local $i i32)
+                    (block $done
+                        (loop $loop
+                            (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                            (br $loop)
+                        )
+                    )
+                    (local.get $i)
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let ticker = EpochTicker::start(&engine, engine.epoch_tick_interval());
+
+        let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+            .with_timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        // Sit idle for longer than the timeout before the first call
+        std::thread::sleep(Duration::from_millis(250));
+        let first: ExecutionResult<i32> = sandbox.call("count", 1000);
+
+        sandbox.reset();
+        sandbox.load_module(&module).unwrap();
+        std::thread::sleep(Duration::from_millis(250));
+        let after_reset: ExecutionResult<i32> = sandbox.call("count", 1000);
+
+        drop(ticker);
+
+        assert_eq!(first.unwrap(), 1000);
+        assert_eq!(after_reset.unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_partial_output_survives_timeout() {
+        let engine = create_engine();
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (import "env" "emit" (func $emit (param i32)))
+                (func (export "stream")
+                    (local $i i32)
+                    (loop $loop
+                        (call $emit (local.get $i))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $loop)
+                    )
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let ticker = EpochTicker::start(&engine, engine.epoch_tick_interval());
+
+        let mut sandbox = SandboxBuilder::<Vec<i32>>::new(engine)
+            .with_fuel_limit(u64::MAX)
+            .with_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        sandbox
+            .register_func(
+                "env",
+                "emit",
+                |mut caller: wasmtime::Caller<'_, SandboxData<Vec<i32>>>, value: i32| {
+                    caller.data_mut().state_mut().push(value);
+                },
+            )
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let result = sandbox.call::<(), ()>("stream", ());
+
+        drop(ticker);
+
+        assert!(matches!(result, Err(ExecutionError::Timeout(_))));
+        let partial = sandbox.take_partial();
+        assert!(!partial.is_empty());
+        assert!(partial.iter().copied().eq(0..partial.len() as i32));
+        assert!(sandbox.state().is_empty());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let engine = create_engine();
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (func (export "infinite")
+                    (loop $loop
+                        (br $loop)
+                    )
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let ticker = EpochTicker::start(&engine, engine.epoch_tick_interval());
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_fuel_limit(u64::MAX)
+            .with_timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let result = sandbox.call::<(), ()>("infinite", ());
+
+        drop(ticker);
+
+        match result {
+            Err(ExecutionError::Timeout(limit)) => {
+                assert_eq!(limit, Duration::from_millis(50));
+            }
+            other => panic!("expected Timeout, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_low_fuel_hook_fires_during_call() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let engine = create_engine();
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (func $step)
+                (func (export "spin")
+                    (loop $loop
+                        (call $step)
+                        (br $loop)
+                    )
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let ticker = EpochTicker::start(&engine, Duration::from_millis(1));
+
+        let dispatcher = Arc::new(EventDispatcher::new());
+        let collector = Arc::new(aegis_observe::CollectingSubscriber::new(100));
+        dispatcher.subscribe(collector.clone());
+
+        let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+            .with_fuel_limit(50_000_000)
+            .with_event_dispatcher(dispatcher)
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let fired = Arc::new(AtomicU64::new(0));
+        let observed = Arc::new(AtomicU64::new(u64::MAX));
+        {
+            let fired = Arc::clone(&fired);
+            let observed = Arc::clone(&observed);
+            sandbox.set_low_fuel_hook(40_000_000, move |remaining| {
+                fired.fetch_add(1, Ordering::Relaxed);
+                observed.store(remaining, Ordering::Relaxed);
+            });
+        }
+
+        let result = sandbox.call::<(), ()>("spin", ());
+
+        drop(ticker);
+
+        assert!(matches!(result, Err(ExecutionError::OutOfFuel { .. })));
+        assert_eq!(fired.load(Ordering::Relaxed), 1);
+        assert!(observed.load(Ordering::Relaxed) < 40_000_000);
+
+        let events = collector.events();
+        assert!(events.iter().any(|(_, event)| matches!(
+            event,
+            SandboxEvent::FuelConsumed { remaining, .. } if *remaining < 40_000_000
+        )));
+    }
+
+    #[test]
+    fn test_memory_growth_is_reported() {
+        let engine = create_engine();
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "grow") (result i32)
+                    (memory.grow (i32.const 2))
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let dispatcher = Arc::new(EventDispatcher::new());
+        let collector = Arc::new(aegis_observe::CollectingSubscriber::new(100));
+        dispatcher.subscribe(collector.clone());
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_event_dispatcher(dispatcher)
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+        assert_eq!(sandbox.metrics().peak_memory, 65536);
+
+        let previous_pages: i32 = sandbox.call("grow", ()).unwrap();
+        assert_eq!(previous_pages, 1);
+        assert_eq!(sandbox.metrics().peak_memory, 3 * 65536);
+
+        let grew: Vec<_> = collector
+            .events()
+            .into_iter()
+            .filter_map(|(_, event)| match event {
+                SandboxEvent::MemoryGrew {
+                    from_bytes,
+                    to_bytes,
+                } => Some((from_bytes, to_bytes)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(grew, vec![(0, 65536), (65536, 3 * 65536)]);
+
+        sandbox.reset();
+        assert_eq!(sandbox.metrics().peak_memory, 0);
+    }
+
+    #[test]
+    fn test_buffered_events_flush_at_call_end() {
+        let engine = create_engine();
+        let loader = ModuleLoader::new(Arc::clone(&engine));
+        let module = loader
+            .load_wat(
+                r#"
+            (module
+                (import "env" "tick" (func $tick))
+                (func (export "run")
+                    (call $tick)
+                    (call $tick)
+                    (call $tick)
+                )
+            )
+        "#,
+            )
+            .unwrap();
+
+        let dispatcher = Arc::new(EventDispatcher::new());
+        let collector = Arc::new(aegis_observe::CollectingSubscriber::new(100));
+        dispatcher.subscribe(collector.clone());
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_event_dispatcher(dispatcher)
+            .with_event_buffering(true)
+            .build()
+            .unwrap();
+
+        let seen_during_call = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&seen_during_call);
+        let observer = Arc::clone(&collector);
+        sandbox
+            .register_func(
+                "env",
+                "tick",
+                move |mut caller: wasmtime::Caller<'_, SandboxData>| {
+                    seen.lock().unwrap().push(observer.len());
+                    caller.data_mut().emit(SandboxEvent::HostFunctionCalled {
+                        module: "env".to_string(),
+                        name: "tick".to_string(),
+                        duration: Duration::from_micros(1),
+                    });
+                },
+            )
+            .unwrap();
+
+        sandbox.load_module(&module).unwrap();
+        let before = collector.len();
+        sandbox.call_void("run").unwrap();
+
+        assert_eq!(*seen_during_call.lock().unwrap(), vec![before; 3]);
+        let host_calls = collector
+            .events()
+            .iter()
+            .filter(|(_, e)| matches!(e, SandboxEvent::HostFunctionCalled { .. }))
+            .count();
+        assert_eq!(host_calls, 3);
+    }
+
+    #[test]
+    fn test_resettable_state() {
+        #[derive(Default)]
+        struct Counter {
+            resets: u32,
+            buffer: Vec<u8>,
+        }
+
+        impl Resettable for Counter {
+            fn reset(&mut self) {
+                self.resets += 1;
+                self.buffer.clear();
+            }
+        }
+
+        let engine = create_engine();
+        let mut sandbox = SandboxBuilder::<Counter>::new(engine)
+            .with_resettable_state()
+            .build()
+            .unwrap();
+
+        sandbox.state_mut().buffer.push(1);
+        sandbox.reset();
+        assert_eq!(sandbox.state().resets, 1);
+        assert!(sandbox.state().buffer.is_empty());
+
+        sandbox.reset();
+        assert_eq!(sandbox.state().resets, 2);
+    }
+
+    #[test]
+    fn test_init_hook_sets_guest_global() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"(module
+                    (global $config (mut i32) (i32.const 0))
+                    (func (export "set_config") (param i32) (global.set $config (local.get 0)))
+                    (func (export "get_config") (result i32) (global.get $config)))"#,
+            )
+            .unwrap();
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_init(|sandbox| sandbox.call::<i32, ()>("set_config", 42))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+        assert_eq!(sandbox.call::<(), i32>("get_config", ()).unwrap(), 42);
+
+        // The hook runs once; a failing hook fails the load
+        sandbox.reset();
+        sandbox.load_module(&module).unwrap();
+        assert_eq!(sandbox.call::<(), i32>("get_config", ()).unwrap(), 0);
+
+        sandbox.reset();
+        sandbox.on_instantiate(|sandbox| sandbox.call_void("missing"));
+        assert!(matches!(
+            sandbox.load_module(&module),
+            Err(ExecutionError::FunctionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_call_signature() {
+        use wasmtime::Val;
+
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"(module
+                    (func (export "scale") (param i32 f64) (result f64)
+                        (f64.mul (f64.convert_i32_s (local.get 0)) (local.get 1))))"#,
+            )
+            .unwrap();
+        let mut sandbox = Sandbox::<()>::new(engine, (), SandboxConfig::default()).unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        sandbox
+            .check_call_signature("scale", &[Val::I32(2), Val::F64(1.5f64.to_bits())])
+            .unwrap();
+
+        match sandbox.check_call_signature("scale", &[Val::I32(2)]) {
+            Err(ExecutionError::ArityMismatch { expected, got, .. }) => {
+                assert_eq!((expected, got), (2, 1));
+            }
+            other => panic!("expected an arity mismatch, got {other:?}"),
+        }
+
+        match sandbox.check_call_signature("scale", &[Val::I32(2), Val::I64(3)]) {
+            Err(ExecutionError::TypeMismatch {
+                index,
+                expected,
+                got,
+            }) => {
+                assert_eq!(index, 1);
+                assert_eq!(expected, "f64");
+                assert_eq!(got, "i64");
+            }
+            other => panic!("expected a type mismatch, got {other:?}"),
+        }
+
+        // call_dynamic reports the mismatch without running the function
+        let result = sandbox.call_dynamic("scale", vec![Val::F32(0), Val::F64(0)]);
+        assert!(matches!(
+            result,
+            Err(ExecutionError::TypeMismatch { index: 0, .. })
+        ));
+        assert!(sandbox.metrics().per_call.is_empty());
+    }
+
+    #[test]
+    fn test_spawn_child_sandboxes() {
+        use aegis_capability::CapabilityError;
+        use aegis_capability::builtin::NestingCapability;
+
+        let engine = create_engine();
+        let capabilities = CapabilitySet::new();
+        capabilities
+            .grant(NestingCapability::new(2, 1, 0.5))
+            .unwrap();
+
+        let mut parent = SandboxBuilder::<()>::new(engine)
+            .with_capabilities(Arc::new(capabilities))
+            .with_fuel_limit(1000)
+            .with_memory_limit(1 << 20)
+            .build()
+            .unwrap();
+
+        let mut child = parent.spawn_child(()).unwrap();
+        assert_eq!(child.parent(), Some(parent.id()));
+        assert_eq!(child.depth(), 1);
+        assert_eq!(child.remaining_fuel(), Some(500));
+        assert_eq!(child.store().data().config.limits.max_memory_bytes, 1 << 19);
+
+        // The child's share is taken from the parent
+        assert_eq!(parent.remaining_fuel(), Some(500));
+        assert_eq!(parent.store().data().limits.lent_memory(), 1 << 19);
+
+        // Grandchildren exceed the maximum depth
+        assert!(matches!(
+            child.spawn_child(()),
+            Err(ExecutionError::Capability(
+                CapabilityError::PermissionDenied { .. }
+            ))
+        ));
+        assert_eq!(child.remaining_fuel(), Some(500));
+
+        let second = parent.spawn_child(()).unwrap();
+        assert_eq!(second.remaining_fuel(), Some(250));
+        assert_eq!(parent.remaining_fuel(), Some(250));
+        assert!(matches!(
+            parent.spawn_child(()),
+            Err(ExecutionError::Capability(
+                CapabilityError::PermissionDenied { .. }
+            ))
+        ));
+
+        assert_eq!(parent.remaining_fuel(), Some(250));
+
+        // Memory lent to the children is withheld from the parent
+        let module = ModuleLoader::new(Arc::clone(&parent.engine))
+            .load_wat(
+                r#"
+                (module
+                    (memory 1)
+                    (func (export "grow") (param i32) (result i32)
+                        (memory.grow (local.get 0))))
+            "#,
+            )
+            .unwrap();
+        parent.load_module(&module).unwrap();
+        assert_eq!(parent.call::<i32, i32>("grow", 4).unwrap(), -1);
+        assert_eq!(parent.call::<i32, i32>("grow", 3).unwrap(), 1);
+
+        // and returned once a child is dropped
+        drop(second);
+        assert_eq!(parent.store().data().limits.lent_memory(), 1 << 19);
+        assert_eq!(parent.call::<i32, i32>("grow", 4).unwrap(), 4);
+        assert_eq!(parent.call::<i32, i32>("grow", 1).unwrap(), -1);
+
+        // Resetting restores the configured limit while a child is alive
+        parent.reset();
+        assert_eq!(parent.store().data().limits.lent_memory(), 0);
+        parent.load_module(&module).unwrap();
+        assert_eq!(parent.call::<i32, i32>("grow", 15).unwrap(), 1);
+        drop(child);
+        assert_eq!(parent.store().data().limits.lent_memory(), 0);
+
+        // Without the capability nothing handles the spawn
+        let mut orphan = SandboxBuilder::<()>::new(create_engine()).build().unwrap();
+        assert!(matches!(
+            orphan.spawn_child(()),
+            Err(ExecutionError::Capability(
+                CapabilityError::NoCapabilityFound { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_trap_backtrace() {
+        let engine = create_engine();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+                (module $app
+                    (func $boom unreachable)
+                    (func (export "run") (call $boom)))
+                "#,
+            )
+            .unwrap();
+
+        let trap = |backtraces: bool| {
+            let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+                .with_backtraces(backtraces)
+                .build()
+                .unwrap();
+            sandbox.load_module(&module).unwrap();
+            match sandbox.call::<(), ()>("run", ()) {
+                Err(ExecutionError::Trap(info)) => info,
+                other => panic!("expected a trap, got {:?}", other),
+            }
+        };
+
+        let info = trap(true);
+        assert_eq!(info.frames.len(), 2);
+        let frame = &info.frames[0];
+        assert_eq!(frame.module.as_deref(), Some("app"));
+        assert_eq!(frame.func_name.as_deref(), Some("boom"));
+        assert_eq!(frame.func_index, 0);
+        assert!(frame.offset.is_some());
+        assert!(info.backtrace.unwrap().contains("boom"));
+
+        let info = trap(false);
+        assert!(info.frames.is_empty());
+        assert!(info.backtrace.is_none());
+    }
+
+    #[test]
+    fn test_host_functions_listed_with_capabilities() {
+        use aegis_capability::builtin::{LogLevel, LoggingCapability};
+
+        let capabilities = CapabilitySet::new();
+        capabilities
+            .grant(LoggingCapability::new(LogLevel::Info, 1024))
+            .unwrap();
+        let config = SandboxConfig::default().with_capabilities(Arc::new(capabilities));
+        let mut sandbox = Sandbox::<()>::new(create_engine(), (), config).unwrap();
+
+        sandbox
+            .register_func_with_capability("env", "log", Some(standard_ids::LOGGING), |_: i32| {})
+            .unwrap();
+        sandbox
+            .register_func_with_capability("env", "now", Some(standard_ids::CLOCK), || 0i64)
+            .unwrap();
+
+        let functions = sandbox.host_functions();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(
+            (functions[0].module.as_str(), functions[0].name.as_str()),
+            ("env", "log")
+        );
+        assert_eq!(
+            functions[0].required_capability,
+            Some(standard_ids::LOGGING)
+        );
+        assert_eq!(functions[1].name, "now");
+        assert_eq!(functions[1].required_capability, Some(standard_ids::CLOCK));
+
+        assert_eq!(sandbox.missing_capabilities(), [standard_ids::CLOCK]);
+    }
 }
@@ -3,13 +3,15 @@
 //! This module provides the `HostContext` type which is available to host
 //! function implementations for accessing sandbox state and capabilities.
 
-use std::sync::Arc;
-
-use aegis_capability::{Action, CapabilityId, CapabilitySet, PermissionResult};
+use aegis_capability::{Action, CapabilityId, CapabilityView, PermissionResult};
+use bytemuck::Pod;
 use wasmtime::Caller;
 
 use crate::error::{HostError, HostResult};
 
+/// Exports searched for by [`HostContext::call_guest_alloc`], in order.
+pub const DEFAULT_ALLOCATORS: [&str; 2] = ["aegis_alloc", "malloc"];
+
 /// Context available to host function implementations.
 ///
 /// `HostContext` provides safe access to sandbox state, capability checking,
@@ -18,7 +20,9 @@ pub struct HostContext<'a, T> {
     /// The Wasmtime caller.
     caller: Caller<'a, T>,
     /// Reference to the capability set.
-    capabilities: Option<Arc<CapabilitySet>>,
+    capabilities: Option<CapabilityView>,
+    /// Allocator export to use instead of the defaults.
+    allocator: Option<String>,
 }
 
 impl<'a, T> HostContext<'a, T> {
@@ -27,17 +31,27 @@ impl<'a, T> HostContext<'a, T> {
         Self {
             caller,
             capabilities: None,
+            allocator: None,
         }
     }
 
     /// Create a host context with capabilities.
-    pub fn with_capabilities(caller: Caller<'a, T>, capabilities: Arc<CapabilitySet>) -> Self {
+    pub fn with_capabilities(caller: Caller<'a, T>, capabilities: CapabilityView) -> Self {
         Self {
             caller,
             capabilities: Some(capabilities),
+            allocator: None,
         }
     }
 
+    /// Use the named export as the guest allocator.
+    ///
+    /// By default [`DEFAULT_ALLOCATORS`] are tried in order.
+    pub fn with_allocator(mut self, name: impl Into<String>) -> Self {
+        self.allocator = Some(name.into());
+        self
+    }
+
     /// Get a reference to the underlying Wasmtime caller.
     pub fn caller(&self) -> &Caller<'a, T> {
         &self.caller
@@ -87,7 +101,13 @@ impl<'a, T> HostContext<'a, T> {
     pub fn require_permission(&self, action: &dyn Action) -> HostResult<()> {
         match self.check_permission(action) {
             PermissionResult::Allowed => Ok(()),
+            PermissionResult::Denied(reason) if reason.is_no_capability() => {
+                Err(HostError::NoCapabilityForAction {
+                    action: action.action_type().to_string(),
+                })
+            }
             PermissionResult::Denied(reason) => Err(HostError::PermissionDenied {
+                capability: reason.capability,
                 action: action.action_type().to_string(),
                 reason: reason.message,
             }),
@@ -98,27 +118,60 @@ impl<'a, T> HostContext<'a, T> {
     }
 
     /// Get the default memory export.
+    ///
+    /// The memory and string helpers of this context operate on it.
     pub fn get_memory(&mut self) -> HostResult<wasmtime::Memory> {
+        self.get_memory_named("memory")
+    }
+
+    /// Get a memory export by name, e.g. a second memory of a multi-memory
+    /// module.
+    pub fn get_memory_named(&mut self, name: &str) -> HostResult<wasmtime::Memory> {
         self.caller
-            .get_export("memory")
+            .get_export(name)
             .and_then(|e| e.into_memory())
-            .ok_or(HostError::MemoryNotFound)
+            .ok_or_else(|| HostError::MemoryNotFound(name.to_string()))
     }
 
     /// Read bytes from guest memory.
+    ///
+    /// Returns an owned copy; use [`with_memory_slice`](Self::with_memory_slice)
+    /// to inspect a region without copying it.
     pub fn read_memory(&mut self, offset: usize, len: usize) -> HostResult<Vec<u8>> {
-        let memory = self.get_memory()?;
-        let data = memory.data(&self.caller);
+        self.with_memory_slice(offset, len, <[u8]>::to_vec)
+    }
+
+    /// Borrow a region of guest memory in place and run `f` on it.
+    pub fn with_memory_slice<R>(
+        &mut self,
+        offset: usize,
+        len: usize,
+        f: impl FnOnce(&[u8]) -> R,
+    ) -> HostResult<R> {
+        self.guest_bytes(offset, len).map(f)
+    }
 
-        if offset + len > data.len() {
+    /// Find the first occurrence of `needle` in guest memory.
+    ///
+    /// At most `max` bytes starting at `offset` are searched, stopping early
+    /// at the end of memory. Returns the position relative to `offset`.
+    pub fn find_byte(
+        &mut self,
+        offset: usize,
+        max: usize,
+        needle: u8,
+    ) -> HostResult<Option<usize>> {
+        let memory_size = self.get_memory()?.data_size(&self.caller);
+        if offset >= memory_size {
             return Err(HostError::MemoryAccessOutOfBounds {
                 offset,
-                len,
-                memory_size: data.len(),
+                len: 1,
+                memory_size,
             });
         }
 
-        Ok(data[offset..offset + len].to_vec())
+        let len = max.min(memory_size - offset);
+        self.with_memory_slice(offset, len, |bytes| bytes.iter().position(|&b| b == needle))
     }
 
     /// Write bytes to guest memory.
@@ -138,6 +191,120 @@ impl<'a, T> HostContext<'a, T> {
         Ok(())
     }
 
+    /// Borrow a bounds-checked range of guest memory.
+    fn guest_bytes(&mut self, offset: usize, len: usize) -> HostResult<&[u8]> {
+        let memory = self.get_memory()?;
+        let data = memory.data(&self.caller);
+
+        match offset.checked_add(len) {
+            Some(end) if end <= data.len() => Ok(&data[offset..end]),
+            _ => Err(HostError::MemoryAccessOutOfBounds {
+                offset,
+                len,
+                memory_size: data.len(),
+            }),
+        }
+    }
+
+    /// Read a plain-old-data value from guest memory.
+    ///
+    /// The bytes are copied as-is and `offset` need not be aligned. Fields
+    /// are therefore in host byte order, which matches the little-endian
+    /// WASM layout on little-endian hosts; use [`read_u32`](Self::read_u32)
+    /// and friends for scalars that must be portable.
+    pub fn read_pod<P: Pod>(&mut self, offset: usize) -> HostResult<P> {
+        let bytes = self.guest_bytes(offset, size_of::<P>())?;
+        Ok(bytemuck::pod_read_unaligned(bytes))
+    }
+
+    /// Read `count` consecutive plain-old-data values from guest memory.
+    ///
+    /// See [`read_pod`](Self::read_pod) for layout.
+    pub fn read_slice<P: Pod>(&mut self, offset: usize, count: usize) -> HostResult<Vec<P>> {
+        let size = size_of::<P>();
+        // An overflowing length can never be in bounds
+        let bytes = self.guest_bytes(offset, count.saturating_mul(size))?;
+
+        if size == 0 {
+            return Ok(vec![P::zeroed(); count]);
+        }
+        Ok(bytes
+            .chunks_exact(size)
+            .map(bytemuck::pod_read_unaligned)
+            .collect())
+    }
+
+    /// Write a plain-old-data value to guest memory.
+    ///
+    /// See [`read_pod`](Self::read_pod) for layout.
+    pub fn write_pod<P: Pod>(&mut self, offset: usize, value: &P) -> HostResult<()> {
+        self.write_memory(offset, bytemuck::bytes_of(value))
+    }
+
+    /// Read a little-endian `u32` from guest memory.
+    pub fn read_u32(&mut self, offset: usize) -> HostResult<u32> {
+        self.read_pod(offset).map(u32::from_le_bytes)
+    }
+
+    /// Read a little-endian `i32` from guest memory.
+    pub fn read_i32(&mut self, offset: usize) -> HostResult<i32> {
+        self.read_pod(offset).map(i32::from_le_bytes)
+    }
+
+    /// Read a little-endian `u64` from guest memory.
+    pub fn read_u64(&mut self, offset: usize) -> HostResult<u64> {
+        self.read_pod(offset).map(u64::from_le_bytes)
+    }
+
+    /// Allocate `size` bytes of guest memory by calling the guest's
+    /// allocator, returning the offset of the new region.
+    ///
+    /// The guest must export a function `(param i32) (result i32)` that
+    /// returns the offset of at least `size` writable bytes, or 0 on
+    /// failure. The export is the one set with
+    /// [`with_allocator`](Self::with_allocator), else the first of
+    /// [`DEFAULT_ALLOCATORS`] that exists. The host never frees the region;
+    /// ownership passes to the guest.
+    pub fn call_guest_alloc(&mut self, size: usize) -> HostResult<usize> {
+        let candidates: Vec<String> = match &self.allocator {
+            Some(name) => vec![name.clone()],
+            None => DEFAULT_ALLOCATORS.iter().map(|n| n.to_string()).collect(),
+        };
+
+        let Some((name, func)) = candidates.iter().find_map(|name| {
+            self.caller
+                .get_export(name)
+                .and_then(|e| e.into_func())
+                .map(|func| (name, func))
+        }) else {
+            return Err(HostError::AllocatorNotFound(candidates));
+        };
+
+        let failed = || HostError::AllocationFailed {
+            name: name.clone(),
+            size,
+        };
+        let request = i32::try_from(size).map_err(|_| failed())?;
+        let alloc = func.typed::<i32, i32>(&self.caller)?;
+        let ptr = alloc.call(&mut self.caller, request)?;
+
+        if ptr == 0 && size != 0 {
+            return Err(failed());
+        }
+        Ok(ptr as u32 as usize)
+    }
+
+    /// Copy bytes into a newly allocated region of guest memory, returning
+    /// its offset.
+    ///
+    /// See [`call_guest_alloc`](Self::call_guest_alloc) for the allocator
+    /// convention. Fails if the returned region is out of bounds.
+    pub fn write_bytes_to_new_region(&mut self, data: &[u8]) -> HostResult<usize> {
+        let offset = self.call_guest_alloc(data.len())?;
+        self.write_memory(offset, data)?;
+        Ok(offset)
+    }
+
     /// Read a null-terminated string from guest memory.
     pub fn read_string(&mut self, offset: usize, max_len: usize) -> HostResult<String> {
         let memory = self.get_memory()?;
@@ -181,7 +348,7 @@ pub trait IntoHostContext<'a, T> {
     fn into_context(self) -> HostContext<'a, T>;
 
     /// Convert into a host context with capabilities.
-    fn into_context_with_caps(self, capabilities: Arc<CapabilitySet>) -> HostContext<'a, T>;
+    fn into_context_with_caps(self, capabilities: CapabilityView) -> HostContext<'a, T>;
 }
 
 impl<'a, T> IntoHostContext<'a, T> for Caller<'a, T> {
@@ -189,7 +356,268 @@ impl<'a, T> IntoHostContext<'a, T> for Caller<'a, T> {
         HostContext::new(self)
     }
 
-    fn into_context_with_caps(self, capabilities: Arc<CapabilitySet>) -> HostContext<'a, T> {
+    fn into_context_with_caps(self, capabilities: CapabilityView) -> HostContext<'a, T> {
         HostContext::with_capabilities(self, capabilities)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+    use wasmtime::{Engine, Linker, Module, Store};
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Point {
+        x: i32,
+        y: u32,
+        z: u64,
+    }
+
+    // SAFETY: `Point` is `repr(C)`, has no padding, and every bit pattern
+    // of its fields is valid.
+    unsafe impl Zeroable for Point {}
+    unsafe impl Pod for Point {}
+
+    const STRUCT_WAT: &str = r#"
+        (module
+            (import "env" "transform" (func $transform (param i32)))
+            (memory (export "memory") 1)
+            ;; Point { x: -1, y: 2, z: 3 } at offset 1 (unaligned)
+            (data (i32.const 1) "\ff\ff\ff\ff\02\00\00\00\03\00\00\00\00\00\00\00")
+            (func (export "run") (call $transform (i32.const 1)))
+        )
+    "#;
+
+    #[test]
+    fn test_pod_round_trip() {
+        let engine = Engine::default();
+        let module = Module::new(&engine, STRUCT_WAT).unwrap();
+        let mut linker = Linker::new(&engine);
+        let mut store = Store::new(&engine, ());
+
+        linker
+            .func_wrap("env", "transform", |caller: Caller<'_, ()>, ptr: i32| {
+                let mut ctx = HostContext::new(caller);
+                let ptr = ptr as usize;
+
+                let point: Point = ctx.read_pod(ptr).unwrap();
+                assert_eq!(point, Point { x: -1, y: 2, z: 3 });
+                assert_eq!(ctx.read_i32(ptr).unwrap(), -1);
+                assert_eq!(ctx.read_u32(ptr + 4).unwrap(), 2);
+                assert_eq!(ctx.read_u64(ptr + 8).unwrap(), 3);
+
+                let moved = Point {
+                    x: point.x * 10,
+                    y: point.y * 10,
+                    z: point.z * 10,
+                };
+                ctx.write_pod(ptr + 16, &moved).unwrap();
+
+                let both: Vec<Point> = ctx.read_slice(ptr, 2).unwrap();
+                assert_eq!(both, vec![point, moved]);
+
+                let end = ctx.get_memory().unwrap().data_size(&ctx.caller);
+                assert!(matches!(
+                    ctx.read_pod::<Point>(end - 8),
+                    Err(HostError::MemoryAccessOutOfBounds { .. })
+                ));
+                assert!(ctx.read_slice::<Point>(ptr, usize::MAX).is_err());
+            })
+            .unwrap();
+
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let run = instance
+            .get_typed_func::<(), ()>(&mut store, "run")
+            .unwrap();
+        run.call(&mut store, ()).unwrap();
+
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        let written = &memory.data(&store)[17..33];
+        assert_eq!(
+            bytemuck::pod_read_unaligned::<Point>(written),
+            Point {
+                x: -10,
+                y: 20,
+                z: 30
+            }
+        );
+    }
+
+    const ALLOC_WAT: &str = r#"
+        (module
+            (import "env" "greet" (func $greet (result i32)))
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 1024))
+            ;; Trivial bump allocator
+            (func (export "aegis_alloc") (param $size i32) (result i32)
+                (global.get $next)
+                (global.set $next (i32.add (global.get $next) (local.get $size)))
+            )
+            (func (export "run") (result i32) (call $greet))
+        )
+    "#;
+
+    /// Instantiate a module whose `greet` import runs `greet` and return
+    /// the result of its `run` export with the guest memory.
+    fn call_greet(
+        wat: &str,
+        greet: impl Fn(HostContext<'_, ()>) -> HostResult<usize> + Send + Sync + 'static,
+    ) -> (i32, Vec<u8>) {
+        let engine = Engine::default();
+        let module = Module::new(&engine, wat).unwrap();
+        let mut linker = Linker::new(&engine);
+        let mut store = Store::new(&engine, ());
+
+        linker
+            .func_wrap("env", "greet", move |caller: Caller<'_, ()>| {
+                Ok(greet(HostContext::new(caller))? as i32)
+            })
+            .unwrap();
+
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let run = instance
+            .get_typed_func::<(), i32>(&mut store, "run")
+            .unwrap();
+        let ptr = run.call(&mut store, ()).unwrap();
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        (ptr, memory.data(&store).to_vec())
+    }
+
+    #[test]
+    fn test_guest_alloc() {
+        let (ptr, memory) = call_greet(ALLOC_WAT, |mut ctx| {
+            let first = ctx.write_bytes_to_new_region(b"hello")?;
+            let second = ctx.write_bytes_to_new_region(b"world")?;
+            assert_eq!(second, first + 5);
+            Ok(first)
+        });
+        assert_eq!(ptr, 1024);
+        assert_eq!(&memory[1024..1034], b"helloworld");
+
+        // A configured name replaces the defaults
+        call_greet(ALLOC_WAT, |ctx| {
+            let mut ctx = ctx.with_allocator("malloc");
+            match ctx.call_guest_alloc(8) {
+                Err(HostError::AllocatorNotFound(tried)) => assert_eq!(tried, ["malloc"]),
+                other => panic!("expected AllocatorNotFound, got {other:?}"),
+            }
+            Ok(0)
+        });
+    }
+
+    #[test]
+    fn test_guest_alloc_not_exported() {
+        let wat = r#"
+            (module
+                (import "env" "greet" (func $greet (result i32)))
+                (memory (export "memory") 1)
+                (func (export "run") (result i32) (call $greet))
+            )
+        "#;
+
+        call_greet(wat, |mut ctx| {
+            match ctx.call_guest_alloc(8) {
+                Err(HostError::AllocatorNotFound(tried)) => {
+                    assert_eq!(tried, DEFAULT_ALLOCATORS);
+                }
+                other => panic!("expected AllocatorNotFound, got {other:?}"),
+            }
+            Ok(0)
+        });
+    }
+
+    const TEXT_WAT: &str = r#"
+        (module
+            (import "env" "greet" (func $greet (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "key=value\00")
+            (func (export "run") (result i32) (call $greet))
+        )
+    "#;
+
+    #[test]
+    fn test_memory_slice_borrows_in_place() {
+        call_greet(TEXT_WAT, |mut ctx| {
+            let base = ctx.get_memory()?.data_ptr(&ctx.caller) as usize;
+
+            // The closure sees guest memory itself, not a copy
+            let (ptr, equals) = ctx.with_memory_slice(16, 9, |bytes| {
+                (
+                    bytes.as_ptr() as usize,
+                    bytes.iter().position(|&b| b == b'='),
+                )
+            })?;
+            assert_eq!(ptr, base + 16);
+            assert_eq!(equals, Some(3));
+
+            assert_eq!(ctx.find_byte(16, 64, b'=')?, Some(3));
+            assert_eq!(ctx.find_byte(16, 64, 0)?, Some(9));
+            assert_eq!(ctx.find_byte(16, 3, b'=')?, None);
+            assert_eq!(ctx.read_memory(16, 3)?, b"key");
+            Ok(0)
+        });
+    }
+
+    #[test]
+    fn test_memory_slice_bounds() {
+        call_greet(TEXT_WAT, |mut ctx| {
+            let size = ctx.get_memory()?.data_size(&ctx.caller);
+
+            assert_eq!(ctx.with_memory_slice(size, 0, |b| b.len())?, 0);
+            assert!(matches!(
+                ctx.with_memory_slice(size - 4, 8, |b| b.len()),
+                Err(HostError::MemoryAccessOutOfBounds { offset, len: 8, .. }) if offset == size - 4
+            ));
+            assert!(ctx.with_memory_slice(1, usize::MAX, |b| b.len()).is_err());
+            assert!(ctx.read_memory(1, usize::MAX).is_err());
+
+            // The search stops at the end of memory rather than failing
+            assert_eq!(ctx.find_byte(size - 4, usize::MAX, b'x')?, None);
+            assert!(ctx.find_byte(size, 8, 0).is_err());
+            Ok(0)
+        });
+    }
+
+    #[test]
+    fn test_named_memory() {
+        let engine = Engine::default();
+        let module = Module::new(
+            &engine,
+            r#"(module
+                (import "env" "stamp" (func $stamp))
+                (memory (export "memory") 1)
+                (memory $scratch (export "scratch") 1)
+                (func (export "run") (result i32)
+                    (call $stamp)
+                    (i32.load8_u $scratch (i32.const 0))))"#,
+        )
+        .unwrap();
+        let mut linker = Linker::new(&engine);
+        let mut store = Store::new(&engine, ());
+
+        linker
+            .func_wrap("env", "stamp", |caller: Caller<'_, ()>| {
+                let mut ctx = HostContext::new(caller);
+                let scratch = ctx.get_memory_named("scratch").unwrap();
+                scratch.data_mut(ctx.caller_mut())[0] = 7;
+
+                assert!(matches!(
+                    ctx.get_memory_named("missing"),
+                    Err(HostError::MemoryNotFound(name)) if name == "missing"
+                ));
+            })
+            .unwrap();
+
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let run = instance
+            .get_typed_func::<(), i32>(&mut store, "run")
+            .unwrap();
+        assert_eq!(run.call(&mut store, ()).unwrap(), 7);
+
+        // The default memory was not touched
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        assert_eq!(memory.data(&store)[0], 0);
+    }
+}
@@ -0,0 +1,302 @@
+//! Guest access to time and randomness.
+//!
+//! This module provides `EntropySources`, which implements the WASI
+//! `clock_time_get`, `clock_res_get` and `random_get` imports on top of a `ClockCapability`
+//! and a `RandomCapability`. With a simulated clock and a seeded random
+//! source, repeated runs of a guest see the same values.
+
+use std::sync::Arc;
+
+use aegis_capability::builtin::{ClockCapability, ClockType, RandomCapability};
+use aegis_capability::standard_ids;
+use aegis_core::RegisteredFunction;
+use tracing::debug;
+use wasmtime::{Caller, Linker};
+
+use crate::context::HostContext;
+use crate::error::{HostError, HostResult};
+use crate::output::{ERRNO_FAULT, ERRNO_SUCCESS, WASI_MODULE, wasi_function};
+
+/// WASI errno for an invalid argument.
+pub(crate) const ERRNO_INVAL: i32 = 28;
+/// WASI errno for a missing capability.
+pub(crate) const ERRNO_NOTCAPABLE: i32 = 76;
+
+/// WASI clock ID for real time.
+pub(crate) const CLOCK_REALTIME: i32 = 0;
+/// WASI clock ID for the monotonic clock.
+pub(crate) const CLOCK_MONOTONIC: i32 = 1;
+
+/// Start of the simulated clock in deterministic mode (2024-01-01 00:00:00 UTC).
+pub const DETERMINISTIC_START_NANOS: u64 = 1_704_067_200_000_000_000;
+
+/// Amount the simulated clock advances per read in deterministic mode (1ms).
+pub const DETERMINISTIC_STEP_NANOS: u64 = 1_000_000;
+
+/// Clock and random sources exposed to the guest through WASI.
+///
+/// A source that is not configured makes the corresponding import return
+/// `ENOTCAPABLE`. Clones share the same sources, so a seeded stream or a
+/// stepped clock continues across them.
+///
+/// # Example
+///
+/// ```ignore
+/// use aegis_host::EntropySources;
+///
+/// let sources = EntropySources::deterministic(42);
+/// sources.add_to_linker(sandbox.linker_mut())?;
+/// ```
+#[derive(Clone, Default)]
+pub struct EntropySources {
+    /// Source for `clock_time_get`.
+    clock: Option<Arc<ClockCapability>>,
+    /// Source for `random_get`.
+    random: Option<Arc<RandomCapability>>,
+}
+
+impl EntropySources {
+    /// Create sources with neither a clock nor randomness.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create reproducible sources.
+    ///
+    /// The clock starts at [`DETERMINISTIC_START_NANOS`] and advances by
+    /// [`DETERMINISTIC_STEP_NANOS`] on every read; random bytes come from a
+    /// stream seeded with `seed`.
+    pub fn deterministic(seed: u64) -> Self {
+        Self::new()
+            .with_clock(ClockCapability::stepped(
+                DETERMINISTIC_START_NANOS,
+                DETERMINISTIC_STEP_NANOS,
+            ))
+            .with_random(RandomCapability::seeded(seed))
+    }
+
+    /// Set the clock source.
+    pub fn with_clock(mut self, clock: ClockCapability) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Set the random source.
+    pub fn with_random(mut self, random: RandomCapability) -> Self {
+        self.random = Some(Arc::new(random));
+        self
+    }
+
+    /// Get the clock source.
+    pub fn clock(&self) -> Option<&ClockCapability> {
+        self.clock.as_deref()
+    }
+
+    /// Get the random source.
+    pub fn random(&self) -> Option<&RandomCapability> {
+        self.random.as_deref()
+    }
+
+    /// Register `clock_time_get`, `clock_res_get` and `random_get` with the
+    /// linker.
+    pub fn add_to_linker<T: 'static>(&self, linker: &mut Linker<T>) -> HostResult<()> {
+        let sources = self.clone();
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "clock_time_get",
+                move |caller: Caller<'_, T>, id: i32, _precision: i64, time: i32| {
+                    sources.clock_time_get(caller, id, time)
+                },
+            )
+            .map_err(|e| registration_failed("clock_time_get", e))?;
+
+        let sources = self.clone();
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "clock_res_get",
+                move |caller: Caller<'_, T>, id: i32, resolution: i32| {
+                    sources.clock_res_get(caller, id, resolution)
+                },
+            )
+            .map_err(|e| registration_failed("clock_res_get", e))?;
+
+        let sources = self.clone();
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "random_get",
+                move |caller: Caller<'_, T>, buf: i32, len: i32| {
+                    sources.random_get(caller, buf, len)
+                },
+            )
+            .map_err(|e| registration_failed("random_get", e))?;
+
+        Ok(())
+    }
+
+    /// Describe the functions registered by [`add_to_linker`](Self::add_to_linker).
+    pub fn registered_functions(&self) -> Vec<RegisteredFunction> {
+        vec![
+            wasi_function("clock_time_get", Some(standard_ids::CLOCK)),
+            wasi_function("clock_res_get", Some(standard_ids::CLOCK)),
+            wasi_function("random_get", Some(standard_ids::RANDOM)),
+        ]
+    }
+
+    /// Implementation of WASI `clock_time_get`, returning a WASI errno.
+    fn clock_time_get<T>(&self, caller: Caller<'_, T>, id: i32, time: i32) -> i32 {
+        let Some(clock) = &self.clock else {
+            return ERRNO_NOTCAPABLE;
+        };
+
+        let nanos = match id {
+            CLOCK_REALTIME => clock.get_realtime_nanos(),
+            CLOCK_MONOTONIC => clock.get_monotonic_nanos(),
+            _ => return ERRNO_INVAL,
+        };
+        let Some(nanos) = nanos else {
+            return ERRNO_NOTCAPABLE;
+        };
+
+        let mut ctx = HostContext::new(caller);
+        match ctx.write_memory(time as u32 as usize, &nanos.to_le_bytes()) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `clock_res_get`, returning a WASI errno.
+    ///
+    /// A stepped clock reports its step; the others report a nanosecond.
+    fn clock_res_get<T>(&self, caller: Caller<'_, T>, id: i32, resolution: i32) -> i32 {
+        let Some(clock) = &self.clock else {
+            return ERRNO_NOTCAPABLE;
+        };
+
+        let allowed = match id {
+            CLOCK_REALTIME => clock.allows_realtime(),
+            CLOCK_MONOTONIC => clock.allows_monotonic(),
+            _ => return ERRNO_INVAL,
+        };
+        if !allowed {
+            return ERRNO_NOTCAPABLE;
+        }
+        let nanos = match clock.clock_type() {
+            ClockType::Stepped { step_nanos, .. } => (*step_nanos).max(1),
+            _ => 1u64,
+        };
+
+        let mut ctx = HostContext::new(caller);
+        match ctx.write_memory(resolution as u32 as usize, &nanos.to_le_bytes()) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `random_get`, returning a WASI errno.
+    fn random_get<T>(&self, caller: Caller<'_, T>, buf: i32, len: i32) -> i32 {
+        let Some(random) = &self.random else {
+            return ERRNO_NOTCAPABLE;
+        };
+
+        let mut bytes = vec![0u8; len as u32 as usize];
+        if random.fill_bytes(&mut bytes).is_err() {
+            return ERRNO_NOTCAPABLE;
+        }
+
+        let mut ctx = HostContext::new(caller);
+        match ctx.write_memory(buf as u32 as usize, &bytes) {
+            Ok(()) => {
+                debug!(len = bytes.len(), "Provided guest random bytes");
+                ERRNO_SUCCESS
+            }
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+}
+
+/// Build the error for a failed WASI registration.
+pub(crate) fn registration_failed(name: &str, error: wasmtime::Error) -> HostError {
+    HostError::RegistrationFailed {
+        module: WASI_MODULE.to_string(),
+        name: name.to_string(),
+        reason: error.to_string(),
+    }
+}
+
+impl std::fmt::Debug for EntropySources {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntropySources")
+            .field("clock", &self.clock.as_ref().map(|c| c.clock_type()))
+            .field("random", &self.random.as_ref().map(|r| r.source()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime::{Engine, Module, Store};
+
+    const READ_TWICE_WAT: &str = r#"
+        (module
+            (import "wasi_snapshot_preview1" "clock_time_get"
+                (func $clock_time_get (param i32 i64 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "random_get"
+                (func $random_get (param i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "run") (result i32)
+                (i32.or
+                    (i32.or
+                        (call $clock_time_get (i32.const 0) (i64.const 1) (i32.const 0))
+                        (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 8)))
+                    (i32.or
+                        (call $random_get (i32.const 16) (i32.const 16))
+                        (call $random_get (i32.const 32) (i32.const 16))))
+            )
+        )
+    "#;
+
+    /// Run the module once with fresh sources and return the bytes it wrote.
+    fn run_once(sources: EntropySources) -> (i32, Vec<u8>) {
+        let engine = Engine::default();
+        let module = Module::new(&engine, READ_TWICE_WAT).unwrap();
+        let mut linker = Linker::new(&engine);
+        let mut store = Store::new(&engine, ());
+
+        sources.add_to_linker(&mut linker).unwrap();
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let run = instance
+            .get_typed_func::<(), i32>(&mut store, "run")
+            .unwrap();
+        let errno = run.call(&mut store, ()).unwrap();
+
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        (errno, memory.data(&store)[..48].to_vec())
+    }
+
+    #[test]
+    fn test_deterministic_runs_are_identical() {
+        let (errno, first) = run_once(EntropySources::deterministic(42));
+        let (_, second) = run_once(EntropySources::deterministic(42));
+        assert_eq!(errno, ERRNO_SUCCESS);
+        assert_eq!(first, second);
+
+        let time = u64::from_le_bytes(first[..8].try_into().unwrap());
+        let next = u64::from_le_bytes(first[8..16].try_into().unwrap());
+        assert_eq!(time, DETERMINISTIC_START_NANOS);
+        assert_eq!(next, time + DETERMINISTIC_STEP_NANOS);
+        assert_ne!(first[16..32], first[32..48]);
+
+        let (_, other_seed) = run_once(EntropySources::deterministic(7));
+        assert_ne!(first[16..], other_seed[16..]);
+    }
+
+    #[test]
+    fn test_missing_sources() {
+        let (errno, _) = run_once(EntropySources::new());
+        assert_eq!(errno, ERRNO_NOTCAPABLE);
+    }
+}
@@ -1,18 +1,32 @@
 //! Error types for the host function system.
 
+use std::time::Duration;
+
 use aegis_capability::CapabilityId;
+use aegis_core::HostFunctionError;
 use thiserror::Error;
 
 /// Errors related to host functions.
+///
+/// A host function can fail a call with one of these by returning
+/// `HostFunctionError::from(error)`; the caller then sees
+/// `ExecutionError::Host` and can recover it with
+/// `ExecutionError::host_error::<HostError>()`.
 #[derive(Debug, Error)]
 pub enum HostError {
     /// A required capability was not granted.
     #[error("Capability not granted: {0}")]
     CapabilityNotGranted(CapabilityId),
 
+    /// A module imports host functions whose capabilities were not granted.
+    #[error("Missing capabilities: {}", join_ids(.0))]
+    MissingCapabilities(Vec<CapabilityId>),
+
     /// Permission was denied for an action.
     #[error("Permission denied for action '{action}': {reason}")]
     PermissionDenied {
+        /// The capability that denied the action.
+        capability: CapabilityId,
         /// The action that was denied.
         action: String,
         /// The reason for denial.
@@ -27,8 +41,8 @@ pub enum HostError {
     },
 
     /// Memory export not found.
-    #[error("Memory export 'memory' not found")]
-    MemoryNotFound,
+    #[error("Memory export '{0}' not found")]
+    MemoryNotFound(String),
 
     /// Memory access out of bounds.
     #[error("Memory access out of bounds: offset={offset}, len={len}, memory_size={memory_size}")]
@@ -65,6 +79,57 @@ pub enum HostError {
         name: String,
     },
 
+    /// The guest does not export an allocator.
+    #[error("No guest allocator exported (looked for: {})", .0.join(", "))]
+    AllocatorNotFound(Vec<String>),
+
+    /// The guest allocator could not provide the requested memory.
+    #[error("Guest allocator '{name}' failed to allocate {size} bytes")]
+    AllocationFailed {
+        /// The allocator export name.
+        name: String,
+        /// The requested size in bytes.
+        size: usize,
+    },
+
+    /// A host function did not finish within its per-call timeout.
+    #[error("Host function '{module}::{name}' timed out after {timeout:?}")]
+    Timeout {
+        /// The module name.
+        module: String,
+        /// The function name.
+        name: String,
+        /// The per-call timeout.
+        timeout: Duration,
+    },
+
+    /// A host function with a timeout already has its maximum number of
+    /// calls running, counting calls that timed out but have not finished.
+    #[error("Host function '{module}::{name}' already has {limit} calls running")]
+    WorkersExhausted {
+        /// The module name.
+        module: String,
+        /// The function name.
+        name: String,
+        /// The maximum number of calls running at once.
+        limit: usize,
+    },
+
+    /// The worker thread for a host function call could not be started.
+    #[error("Failed to start worker for '{module}::{name}': {reason}")]
+    WorkerSpawnFailed {
+        /// The module name.
+        module: String,
+        /// The function name.
+        name: String,
+        /// The reason for failure.
+        reason: String,
+    },
+
+    /// The guest called WASI `proc_exit`.
+    #[error("Guest exited with code {0}")]
+    Exit(i32),
+
     /// Underlying Wasmtime error.
     #[error("Wasmtime error: {0}")]
     Wasmtime(#[from] wasmtime::Error),
@@ -74,5 +139,19 @@ pub enum HostError {
     Other(String),
 }
 
+impl From<HostError> for HostFunctionError {
+    fn from(error: HostError) -> Self {
+        HostFunctionError::new(error)
+    }
+}
+
+/// Join capability IDs for display.
+fn join_ids(ids: &[CapabilityId]) -> String {
+    ids.iter()
+        .map(CapabilityId::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// Result type for host operations.
 pub type HostResult<T> = std::result::Result<T, HostError>;
@@ -5,6 +5,12 @@
 //!
 //! - [`AegisLinker`]: Safe wrapper around Wasmtime's Linker
 //! - [`HostContext`]: Context available to host function implementations
+//! - [`OutputCapture`]: Capture of guest stdout and stderr
+//! - [`EntropySources`]: Guest access to time and randomness
+//! - [`LogSink`]: Destinations for guest log messages, fed by [`register_logging`]
+//! - [`HostFunctionRegistry`]: Host functions shared across sandboxes
+//! - [`ChildSandboxes`]: Child sandboxes spawned by guests
+//! - [`WasiCapability`]: WASI preview1 imports gated by capabilities
 //! - Capability-aware function registration
 //!
 //! # Host Functions
@@ -37,13 +43,25 @@
 //! ```
 
 pub mod context;
+pub mod entropy;
 pub mod error;
 pub mod linker;
+pub mod logging;
+pub mod nesting;
+pub mod output;
+pub mod registry;
+pub mod wasi;
 
 // Re-export main types
 pub use context::{HostContext, IntoHostContext};
+pub use entropy::EntropySources;
 pub use error::{HostError, HostResult};
-pub use linker::{AegisLinker, AegisLinkerBuilder, RegisteredFunction};
+pub use linker::{AegisLinker, AegisLinkerBuilder, MAX_TIMEOUT_WORKERS, RegisteredFunction};
+pub use logging::{CollectingLogSink, LogSink, TracingLogSink, register_logging};
+pub use nesting::ChildSandboxes;
+pub use output::OutputCapture;
+pub use registry::HostFunctionRegistry;
+pub use wasi::WasiCapability;
 
 /// Prelude module for convenient imports.
 pub mod prelude {
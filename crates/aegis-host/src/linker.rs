@@ -3,23 +3,43 @@
 //! This module provides the `AegisLinker` type which wraps Wasmtime's `Linker`
 //! with capability-aware host function registration.
 
-use aegis_capability::{CapabilityId, CapabilitySet};
-use tracing::{debug, info};
-use wasmtime::{Engine, Linker};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use aegis_capability::{CapabilityId, CapabilityView};
+pub use aegis_core::RegisteredFunction;
+use aegis_core::{HostFunctionError, ImportKind, SandboxData, ValidatedModule};
+use tracing::{debug, info, warn};
+use wasmtime::{Caller, Engine, FuncType, Linker, Val};
 
 use crate::error::{HostError, HostResult};
 
-/// Information about a registered host function.
-#[derive(Debug, Clone)]
-pub struct RegisteredFunction {
-    /// The import module name.
-    pub module: String,
-    /// The function name.
-    pub name: String,
-    /// Required capability, if any.
-    pub required_capability: Option<CapabilityId>,
-    /// Human-readable description.
-    pub description: Option<String>,
+/// Maximum number of calls of one host function with a timeout that may run
+/// at once, including calls that timed out but have not finished.
+pub const MAX_TIMEOUT_WORKERS: usize = 8;
+
+/// A call slot of a host function with a timeout, released when the worker
+/// finishes.
+struct WorkerSlot(Arc<AtomicUsize>);
+
+impl WorkerSlot {
+    /// Take a slot if fewer than [`MAX_TIMEOUT_WORKERS`] are in use.
+    fn acquire(in_flight: &Arc<AtomicUsize>) -> Option<Self> {
+        in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < MAX_TIMEOUT_WORKERS).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(in_flight)))
+    }
+}
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
 }
 
 /// A safe wrapper around Wasmtime's `Linker` with capability enforcement.
@@ -42,6 +62,16 @@ impl<T> AegisLinker<T> {
         }
     }
 
+    /// Wrap an existing Wasmtime linker.
+    ///
+    /// Functions already defined in `linker` are not tracked.
+    pub fn from_linker(linker: Linker<T>) -> Self {
+        Self {
+            inner: linker,
+            registered: Vec::new(),
+        }
+    }
+
     /// Get a reference to the underlying Wasmtime linker.
     pub fn inner(&self) -> &Linker<T> {
         &self.inner
@@ -142,7 +172,7 @@ impl<T> AegisLinker<T> {
     }
 
     /// Validate that all required capabilities are present in the given set.
-    pub fn validate_capabilities(&self, capabilities: &CapabilitySet) -> HostResult<()> {
+    pub fn validate_capabilities(&self, capabilities: &CapabilityView) -> HostResult<()> {
         for func in &self.registered {
             if let Some(ref required) = func.required_capability {
                 if !capabilities.has(required) {
@@ -162,7 +192,7 @@ impl<T> AegisLinker<T> {
     }
 
     /// Get functions that require capabilities not in the given set.
-    pub fn missing_capabilities(&self, capabilities: &CapabilitySet) -> Vec<CapabilityId> {
+    pub fn missing_capabilities(&self, capabilities: &CapabilityView) -> Vec<CapabilityId> {
         let mut missing = Vec::new();
 
         for func in &self.registered {
@@ -175,6 +205,199 @@ impl<T> AegisLinker<T> {
 
         missing
     }
+
+    /// Get the capabilities required by the host functions a module imports.
+    ///
+    /// Only function imports that match a registered function by module and
+    /// name are considered. Each capability is listed once, in import order.
+    pub fn required_capabilities_for(&self, module: &ValidatedModule) -> Vec<CapabilityId> {
+        let mut required = Vec::new();
+
+        let function_imports = module
+            .imports()
+            .iter()
+            .filter(|import| matches!(import.kind, ImportKind::Function { .. }));
+
+        for import in function_imports {
+            let capability = self
+                .registered
+                .iter()
+                .find(|f| f.module == import.module && f.name == import.name)
+                .and_then(|f| f.required_capability.as_ref());
+
+            if let Some(capability) = capability {
+                if !required.contains(capability) {
+                    required.push(capability.clone());
+                }
+            }
+        }
+
+        required
+    }
+
+    /// Check, without instantiating, that a module's imports are covered by
+    /// the given capabilities.
+    ///
+    /// Returns [`HostError::MissingCapabilities`] listing every capability
+    /// the module needs but the set does not grant.
+    pub fn preflight(
+        &self,
+        module: &ValidatedModule,
+        capabilities: &CapabilityView,
+    ) -> HostResult<()> {
+        let missing: Vec<CapabilityId> = self
+            .required_capabilities_for(module)
+            .into_iter()
+            .filter(|id| !capabilities.has(id))
+            .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            debug!(module_name = ?module.name(), ?missing, "Preflight found missing capabilities");
+            Err(HostError::MissingCapabilities(missing))
+        }
+    }
+}
+
+impl<S: Send + 'static> AegisLinker<SandboxData<S>> {
+    /// Register a host function that fails with [`HostError::Timeout`] if a
+    /// call takes longer than `timeout`.
+    ///
+    /// Epoch interruption only stops guest code, so a blocking host function
+    /// (e.g. a slow network read) would otherwise stall the call forever.
+    /// `func` runs on a worker thread and receives the call's parameters,
+    /// which must match `ty`. On timeout the worker is abandoned rather than
+    /// stopped, so `func` should not hold resources that must be released
+    /// promptly. Abandoned workers still count toward
+    /// [`MAX_TIMEOUT_WORKERS`]; a call made while that many are running
+    /// fails with [`HostError::WorkersExhausted`]. Every call is recorded
+    /// with [`SandboxData::record_host_call`], whether or not it times out.
+    ///
+    /// Sandboxes usually get these functions from a
+    /// [`HostFunctionRegistry`](crate::HostFunctionRegistry) with
+    /// `register_with_timeout`.
+    pub fn func_wrap_with_timeout<F>(
+        &mut self,
+        module: &str,
+        name: &str,
+        ty: FuncType,
+        timeout: Duration,
+        func: F,
+    ) -> HostResult<&mut Self>
+    where
+        F: Fn(&[Val]) -> HostResult<Vec<Val>> + Send + Sync + 'static,
+    {
+        if self.is_registered(module, name) {
+            return Err(HostError::AlreadyRegistered {
+                module: module.to_string(),
+                name: name.to_string(),
+            });
+        }
+
+        let func = Arc::new(func);
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let (target_module, target_name) = (module.to_string(), name.to_string());
+        let qualified_name = format!("{}::{}", module, name);
+        let wrapper = move |mut caller: Caller<'_, SandboxData<S>>,
+                            params: &[Val],
+                            results: &mut [Val]|
+              -> wasmtime::Result<()> {
+            let Some(slot) = WorkerSlot::acquire(&in_flight) else {
+                warn!(
+                    module = %target_module,
+                    name = %target_name,
+                    limit = MAX_TIMEOUT_WORKERS,
+                    "Host function has too many calls running"
+                );
+                let error = HostError::WorkersExhausted {
+                    module: target_module.clone(),
+                    name: target_name.clone(),
+                    limit: MAX_TIMEOUT_WORKERS,
+                };
+                return Err(HostFunctionError::from(error).into());
+            };
+
+            let start = Instant::now();
+            let (sender, receiver) = mpsc::channel();
+            let worker = Arc::clone(&func);
+            let params = params.to_vec();
+            thread::Builder::new()
+                .name(format!("aegis-host-{}", qualified_name))
+                .spawn(move || {
+                    let _slot = slot;
+                    // The receiver is gone if the call already timed out
+                    let _ = sender.send(worker(&params));
+                })
+                .map_err(|e| {
+                    HostFunctionError::from(HostError::WorkerSpawnFailed {
+                        module: target_module.clone(),
+                        name: target_name.clone(),
+                        reason: e.to_string(),
+                    })
+                })?;
+
+            let outcome = receiver.recv_timeout(timeout);
+            caller
+                .data_mut()
+                .record_host_call(&qualified_name, start.elapsed());
+
+            let error = match outcome {
+                Ok(Ok(values)) if values.len() == results.len() => {
+                    results.clone_from_slice(&values);
+                    return Ok(());
+                }
+                Ok(Ok(values)) => HostError::Other(format!(
+                    "'{}::{}' returned {} values, expected {}",
+                    target_module,
+                    target_name,
+                    values.len(),
+                    results.len()
+                )),
+                Ok(Err(error)) => error,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    warn!(
+                        module = %target_module,
+                        name = %target_name,
+                        ?timeout,
+                        "Host function timed out"
+                    );
+                    HostError::Timeout {
+                        module: target_module.clone(),
+                        name: target_name.clone(),
+                        timeout,
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    HostError::Other(format!("'{}::{}' panicked", target_module, target_name))
+                }
+            };
+            Err(HostFunctionError::from(error).into())
+        };
+
+        self.inner
+            .func_new(module, name, ty, wrapper)
+            .map_err(|e| HostError::RegistrationFailed {
+                module: module.to_string(),
+                name: name.to_string(),
+                reason: e.to_string(),
+            })?;
+
+        self.registered.push(RegisteredFunction {
+            module: module.to_string(),
+            name: name.to_string(),
+            required_capability: None,
+            description: None,
+        });
+
+        debug!(
+            module,
+            name,
+            ?timeout,
+            "Registered host function with timeout"
+        );
+        Ok(self)
+    }
 }
 
 impl<T> std::fmt::Debug for AegisLinker<T> {
@@ -267,7 +490,7 @@ mod tests {
             .unwrap();
 
         // Empty capability set should fail validation
-        let empty_caps = CapabilitySet::new();
+        let empty_caps = CapabilitySet::new().freeze();
         assert!(linker.validate_capabilities(&empty_caps).is_err());
     }
 
@@ -286,11 +509,56 @@ mod tests {
             .func_wrap_with_capability("env", "func2", Some(cap2.clone()), || {})
             .unwrap();
 
-        let empty_caps = CapabilitySet::new();
+        let empty_caps = CapabilitySet::new().freeze();
         let missing = linker.missing_capabilities(&empty_caps);
 
         assert_eq!(missing.len(), 2);
         assert!(missing.contains(&cap1));
         assert!(missing.contains(&cap2));
     }
+
+    #[test]
+    fn test_required_capabilities_for_module() {
+        use aegis_capability::builtin::LoggingCapability;
+        use aegis_core::{AegisEngine, EngineConfig, IntoShared, ModuleLoader};
+
+        let engine = AegisEngine::new(EngineConfig::default())
+            .unwrap()
+            .into_shared();
+        let mut linker = AegisLinker::<()>::new(engine.inner());
+
+        let logging = aegis_capability::standard_ids::LOGGING.clone();
+        let network = aegis_capability::standard_ids::NETWORK.clone();
+        linker
+            .func_wrap_with_capability("env", "log", Some(logging.clone()), |_: i32| {})
+            .unwrap();
+        linker
+            .func_wrap_with_capability("env", "fetch", Some(network.clone()), |_: i32| {})
+            .unwrap();
+        linker.func_wrap("env", "now", || -> i64 { 0 }).unwrap();
+
+        // Imports `log` and `now`, but not `fetch`
+        let module = ModuleLoader::new(engine)
+            .load_wat(
+                r#"
+            (module
+                (import "env" "log" (func (param i32)))
+                (import "env" "now" (func (result i64)))
+            )
+        "#,
+            )
+            .unwrap();
+
+        assert_eq!(linker.required_capabilities_for(&module), vec![logging]);
+
+        let err = linker
+            .preflight(&module, &CapabilitySet::new().freeze())
+            .unwrap_err();
+        assert!(matches!(&err, HostError::MissingCapabilities(ids) if ids.len() == 1));
+        assert_eq!(err.to_string(), "Missing capabilities: logging");
+
+        let granted = CapabilitySet::new();
+        granted.grant(LoggingCapability::production()).unwrap();
+        assert!(linker.preflight(&module, &granted.freeze()).is_ok());
+    }
 }
@@ -0,0 +1,207 @@
+//! Routing of guest log messages.
+//!
+//! This module provides `register_logging`, which defines an `env.log`
+//! import that checks each message against a `LoggingCapability` and
+//! forwards it to a `LogSink`, along with `TracingLogSink` and
+//! `CollectingLogSink`.
+
+use std::sync::Arc;
+
+use aegis_capability::builtin::{
+    LogLevel, LoggingAction, LoggingCapability, check_logging_permission,
+};
+use parking_lot::Mutex;
+use tracing::{debug, error, info, trace, warn};
+use wasmtime::{Caller, Linker};
+
+use crate::context::HostContext;
+use crate::entropy::{ERRNO_INVAL, ERRNO_NOTCAPABLE};
+use crate::error::{HostError, HostResult};
+use crate::output::{ERRNO_FAULT, ERRNO_SUCCESS};
+
+/// Import module that the log function is registered under.
+pub const LOG_MODULE: &str = "env";
+
+/// Name of the log function.
+pub const LOG_FUNCTION: &str = "log";
+
+/// Destination for guest log messages.
+pub trait LogSink: Send + Sync {
+    /// Write a message the logging capability permitted.
+    fn write(&self, level: LogLevel, message: &str);
+}
+
+/// A sink that emits guest messages as `tracing` events.
+///
+/// Events use the `aegis::guest` target at the message's level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingLogSink;
+
+impl LogSink for TracingLogSink {
+    fn write(&self, level: LogLevel, message: &str) {
+        match level {
+            LogLevel::Trace => trace!(target: "aegis::guest", "{}", message),
+            LogLevel::Debug => debug!(target: "aegis::guest", "{}", message),
+            LogLevel::Info => info!(target: "aegis::guest", "{}", message),
+            LogLevel::Warn => warn!(target: "aegis::guest", "{}", message),
+            LogLevel::Error => error!(target: "aegis::guest", "{}", message),
+        }
+    }
+}
+
+/// A sink that keeps guest messages in memory.
+///
+/// Clones share the same buffer, so one clone can be registered and another
+/// read after execution.
+#[derive(Debug, Clone, Default)]
+pub struct CollectingLogSink {
+    /// Messages in the order they were written.
+    entries: Arc<Mutex<Vec<(LogLevel, String)>>>,
+}
+
+impl CollectingLogSink {
+    /// Create an empty sink.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the messages written so far, oldest first.
+    pub fn entries(&self) -> Vec<(LogLevel, String)> {
+        self.entries.lock().clone()
+    }
+
+    /// Discard the collected messages.
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+
+impl LogSink for CollectingLogSink {
+    fn write(&self, level: LogLevel, message: &str) {
+        self.entries.lock().push((level, message.to_string()));
+    }
+}
+
+/// Register `env.log` with the linker.
+///
+/// The guest calls `log(level: i32, ptr: i32, len: i32) -> i32` with a
+/// level from 0 (trace) to 4 (error) and a UTF-8 message in its memory.
+/// Messages the capability permits are forwarded to `sink`. The result is
+/// a WASI-style errno: success, `EINVAL` for an unknown level, `EFAULT` for
+/// an unreadable message, or `ENOTCAPABLE` if the capability denies it.
+///
+/// # Example
+///
+/// ```ignore
+/// use aegis_host::logging::{register_logging, CollectingLogSink};
+///
+/// let sink = CollectingLogSink::new();
+/// register_logging(sandbox.linker_mut(), LoggingCapability::allow_all(), Arc::new(sink.clone()))?;
+/// ```
+pub fn register_logging<T: 'static>(
+    linker: &mut Linker<T>,
+    capability: LoggingCapability,
+    sink: Arc<dyn LogSink>,
+) -> HostResult<()> {
+    let capability = Arc::new(capability);
+    linker
+        .func_wrap(
+            LOG_MODULE,
+            LOG_FUNCTION,
+            move |caller: Caller<'_, T>, level: i32, ptr: i32, len: i32| {
+                guest_log(caller, &capability, sink.as_ref(), level, ptr, len)
+            },
+        )
+        .map_err(|e| HostError::RegistrationFailed {
+            module: LOG_MODULE.to_string(),
+            name: LOG_FUNCTION.to_string(),
+            reason: e.to_string(),
+        })?;
+
+    Ok(())
+}
+
+/// Implementation of `env.log`, returning a WASI errno.
+fn guest_log<T>(
+    caller: Caller<'_, T>,
+    capability: &LoggingCapability,
+    sink: &dyn LogSink,
+    level: i32,
+    ptr: i32,
+    len: i32,
+) -> i32 {
+    let level = match level {
+        0 => LogLevel::Trace,
+        1 => LogLevel::Debug,
+        2 => LogLevel::Info,
+        3 => LogLevel::Warn,
+        4 => LogLevel::Error,
+        _ => return ERRNO_INVAL,
+    };
+
+    let message_len = len as u32 as usize;
+    let action = LoggingAction::Log { level, message_len };
+    if !check_logging_permission(capability, &action).is_allowed() {
+        debug!(
+            level = level.as_str(),
+            message_len, "Guest log message denied"
+        );
+        return ERRNO_NOTCAPABLE;
+    }
+
+    let mut ctx = HostContext::new(caller);
+    match ctx.read_string_with_len(ptr as u32 as usize, message_len) {
+        Ok(message) => {
+            sink.write(level, &message);
+            ERRNO_SUCCESS
+        }
+        Err(_) => ERRNO_FAULT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime::{Engine, Module, Store};
+
+    const LOG_WAT: &str = r#"
+        (module
+            (import "env" "log" (func $log (param i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "disk almost full")
+            (data (i32.const 32) "verbose detail")
+            (func (export "run") (result i32)
+                (drop (call $log (i32.const 3) (i32.const 0) (i32.const 16)))
+                (call $log (i32.const 0) (i32.const 32) (i32.const 14))
+            )
+        )
+    "#;
+
+    #[test]
+    fn test_guest_log_reaches_sink() {
+        let engine = Engine::default();
+        let module = Module::new(&engine, LOG_WAT).unwrap();
+        let mut linker = Linker::new(&engine);
+        let mut store = Store::new(&engine, ());
+
+        let sink = CollectingLogSink::new();
+        register_logging(
+            &mut linker,
+            LoggingCapability::new(LogLevel::Info, 1024),
+            Arc::new(sink.clone()),
+        )
+        .unwrap();
+
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let run = instance
+            .get_typed_func::<(), i32>(&mut store, "run")
+            .unwrap();
+
+        // The trace message is below the capability's minimum level
+        assert_eq!(run.call(&mut store, ()).unwrap(), ERRNO_NOTCAPABLE);
+        assert_eq!(
+            sink.entries(),
+            [(LogLevel::Warn, "disk almost full".to_string())]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Guest-initiated child sandboxes.
+//!
+//! This module provides `ChildSandboxes`, which implements the `spawn_child`
+//! import on top of `Sandbox::new_child` and keeps the children it creates.
+
+use std::sync::Arc;
+
+use aegis_capability::{CapabilityView, standard_ids};
+use aegis_core::{HostFunctions, Sandbox, SandboxData, SharedEngine};
+use parking_lot::Mutex;
+use tracing::warn;
+use wasmtime::{Caller, Linker};
+
+use crate::error::{HostError, HostResult};
+
+/// Import module of the `spawn_child` function.
+pub const NESTING_MODULE: &str = "env";
+
+/// Children spawned by guests through the `spawn_child` import.
+///
+/// `spawn_child` takes no arguments and returns the new child's index in
+/// [`children`](Self::with_children), or -1 if the spawn was denied or
+/// failed. Children start with default user state and no module loaded;
+/// the host decides what to run in them.
+///
+/// The import is only defined when the sandbox holds the nesting
+/// capability, so a guest that imports it without the capability fails to
+/// instantiate. Clones share the same children.
+///
+/// # Example
+///
+/// ```ignore
+/// use aegis_host::ChildSandboxes;
+///
+/// let children = ChildSandboxes::<()>::new(engine.clone());
+/// let sandbox = SandboxBuilder::new(engine)
+///     .with_capabilities(capabilities)
+///     .with_registry(Arc::new(children.clone()))
+///     .build()?;
+/// ```
+pub struct ChildSandboxes<S> {
+    /// Engine the children run on.
+    engine: SharedEngine,
+    /// Spawned children, in spawn order.
+    children: Arc<Mutex<Vec<Sandbox<S>>>>,
+}
+
+impl<S: Default + Send + 'static> ChildSandboxes<S> {
+    /// Create an empty set of children that run on `engine`.
+    pub fn new(engine: SharedEngine) -> Self {
+        Self {
+            engine,
+            children: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Get the number of spawned children.
+    pub fn len(&self) -> usize {
+        self.children.lock().len()
+    }
+
+    /// Check if no children have been spawned.
+    pub fn is_empty(&self) -> bool {
+        self.children.lock().is_empty()
+    }
+
+    /// Access the spawned children.
+    pub fn with_children<R>(&self, f: impl FnOnce(&mut [Sandbox<S>]) -> R) -> R {
+        f(&mut self.children.lock())
+    }
+
+    /// Remove and return the spawned children.
+    pub fn take(&self) -> Vec<Sandbox<S>> {
+        std::mem::take(&mut self.children.lock())
+    }
+
+    /// Register `spawn_child` with the linker.
+    ///
+    /// Nothing is registered unless `capabilities` holds the nesting
+    /// capability.
+    pub fn add_to_linker(
+        &self,
+        linker: &mut Linker<SandboxData<S>>,
+        capabilities: &CapabilityView,
+    ) -> HostResult<()> {
+        if !capabilities.has(&standard_ids::NESTING) {
+            return Ok(());
+        }
+
+        let spawner = self.clone();
+        linker
+            .func_wrap(
+                NESTING_MODULE,
+                "spawn_child",
+                move |caller: Caller<'_, SandboxData<S>>| spawner.spawn_child(caller),
+            )
+            .map_err(|e| HostError::RegistrationFailed {
+                module: NESTING_MODULE.to_string(),
+                name: "spawn_child".to_string(),
+                reason: e.to_string(),
+            })?;
+
+        Ok(())
+    }
+
+    /// Implementation of `spawn_child`, returning the child index or -1.
+    fn spawn_child(&self, mut caller: Caller<'_, SandboxData<S>>) -> i32 {
+        match Sandbox::new_child(Arc::clone(&self.engine), &mut caller, S::default()) {
+            Ok(child) => {
+                let mut children = self.children.lock();
+                children.push(child);
+                (children.len() - 1) as i32
+            }
+            Err(e) => {
+                warn!(sandbox_id = %caller.data().id, error = %e, "Child spawn refused");
+                -1
+            }
+        }
+    }
+}
+
+impl<S> Clone for ChildSandboxes<S> {
+    fn clone(&self) -> Self {
+        Self {
+            engine: Arc::clone(&self.engine),
+            children: Arc::clone(&self.children),
+        }
+    }
+}
+
+impl<S: Default + Send + 'static> HostFunctions<S> for ChildSandboxes<S> {
+    fn install(
+        &self,
+        linker: &mut Linker<SandboxData<S>>,
+        capabilities: &CapabilityView,
+    ) -> wasmtime::Result<()> {
+        self.add_to_linker(linker, capabilities)?;
+        Ok(())
+    }
+}
+
+impl<S> std::fmt::Debug for ChildSandboxes<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChildSandboxes")
+            .field("children", &self.children.lock().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aegis_capability::CapabilitySet;
+    use aegis_capability::builtin::NestingCapability;
+    use aegis_core::{AegisEngine, ExecutionError, IntoShared, ModuleLoader, SandboxBuilder};
+
+    const SPAWN_WAT: &str = r#"
+        (module
+            (import "env" "spawn_child" (func $spawn_child (result i32)))
+            (func (export "spawn") (result i32)
+                (call $spawn_child))
+        )
+    "#;
+
+    #[test]
+    fn test_spawn_up_to_limit() {
+        let engine = AegisEngine::default_engine().unwrap().into_shared();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(SPAWN_WAT)
+            .unwrap();
+
+        let capabilities = CapabilitySet::new();
+        capabilities
+            .grant(NestingCapability::new(2, 1, 0.5))
+            .unwrap();
+
+        let children = ChildSandboxes::<()>::new(Arc::clone(&engine));
+        let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+            .with_capabilities(Arc::new(capabilities))
+            .with_registry(Arc::new(children.clone()))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let spawned: Vec<i32> = (0..3).map(|_| sandbox.call("spawn", ()).unwrap()).collect();
+        assert_eq!(spawned, [0, 1, -1]);
+        assert_eq!(children.len(), 2);
+        children.with_children(|children| {
+            assert!(children.iter().all(|c| c.parent() == Some(sandbox.id())));
+        });
+
+        // Without the capability the import is not defined
+        let mut denied = SandboxBuilder::<()>::new(Arc::clone(&engine))
+            .with_registry(Arc::new(ChildSandboxes::<()>::new(engine)))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            denied.load_module(&module),
+            Err(ExecutionError::MissingImport { .. })
+        ));
+    }
+}
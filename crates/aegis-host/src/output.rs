@@ -0,0 +1,326 @@
+//! Capture of guest stdout and stderr.
+//!
+//! This module provides `OutputCapture`, which implements the WASI
+//! `fd_write` import for file descriptors 1 and 2 and collects what the
+//! guest writes into in-memory buffers.
+
+use std::sync::Arc;
+
+use aegis_capability::CapabilityId;
+use aegis_core::RegisteredFunction;
+use parking_lot::Mutex;
+use tracing::debug;
+use wasmtime::{Caller, Linker};
+
+use crate::context::HostContext;
+use crate::error::{HostError, HostResult};
+
+/// WASI import module that `fd_write` is registered under.
+pub const WASI_MODULE: &str = "wasi_snapshot_preview1";
+
+/// WASI errno for success.
+pub(crate) const ERRNO_SUCCESS: i32 = 0;
+/// WASI errno for a bad file descriptor.
+pub(crate) const ERRNO_BADF: i32 = 8;
+/// WASI errno for a memory access fault.
+pub(crate) const ERRNO_FAULT: i32 = 21;
+
+/// Most bytes a single read or write call transfers; larger requests are
+/// completed short, as WASI permits.
+pub(crate) const MAX_IO_BYTES: usize = 1 << 20;
+/// Most iovecs a single read or write call looks at, as POSIX `IOV_MAX`.
+pub(crate) const MAX_IOVS: usize = 1024;
+
+/// Describe a function registered under [`WASI_MODULE`].
+pub(crate) fn wasi_function(
+    name: &str,
+    required_capability: Option<CapabilityId>,
+) -> RegisteredFunction {
+    RegisteredFunction {
+        module: WASI_MODULE.to_string(),
+        name: name.to_string(),
+        required_capability,
+        description: None,
+    }
+}
+
+/// Buffers that collect guest stdout and stderr.
+///
+/// Clones share the same buffers, so one clone can be registered with a
+/// linker and another read after execution.
+///
+/// # Example
+///
+/// ```ignore
+/// use aegis_host::OutputCapture;
+///
+/// let capture = OutputCapture::new();
+/// capture.add_to_linker(sandbox.linker_mut())?;
+///
+/// sandbox.load_module(&module)?;
+/// sandbox.call_void("_start")?;
+///
+/// println!("{}", capture.stdout_lossy());
+/// ```
+#[derive(Clone, Default)]
+pub struct OutputCapture {
+    /// Bytes written to file descriptor 1.
+    stdout: Arc<Mutex<Vec<u8>>>,
+    /// Bytes written to file descriptor 2.
+    stderr: Arc<Mutex<Vec<u8>>>,
+}
+
+impl OutputCapture {
+    /// Create empty capture buffers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `fd_write` with the linker.
+    pub fn add_to_linker<T: 'static>(&self, linker: &mut Linker<T>) -> HostResult<()> {
+        let capture = self.clone();
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_write",
+                move |caller: Caller<'_, T>, fd: i32, iovs: i32, iovs_len: i32, nwritten: i32| {
+                    capture.fd_write(caller, fd, iovs, iovs_len, nwritten)
+                },
+            )
+            .map_err(|e| HostError::RegistrationFailed {
+                module: WASI_MODULE.to_string(),
+                name: "fd_write".to_string(),
+                reason: e.to_string(),
+            })?;
+
+        Ok(())
+    }
+
+    /// Describe the functions registered by [`add_to_linker`](Self::add_to_linker).
+    pub fn registered_functions(&self) -> Vec<RegisteredFunction> {
+        vec![wasi_function("fd_write", None)]
+    }
+
+    /// Append bytes to the buffer for a file descriptor.
+    ///
+    /// Returns `false` if the descriptor is neither stdout nor stderr.
+    pub fn write(&self, fd: i32, bytes: &[u8]) -> bool {
+        match fd {
+            1 => self.stdout.lock().extend_from_slice(bytes),
+            2 => self.stderr.lock().extend_from_slice(bytes),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Get the bytes written to stdout.
+    pub fn stdout(&self) -> Vec<u8> {
+        self.stdout.lock().clone()
+    }
+
+    /// Get the bytes written to stderr.
+    pub fn stderr(&self) -> Vec<u8> {
+        self.stderr.lock().clone()
+    }
+
+    /// Get stdout as a string, replacing invalid UTF-8.
+    pub fn stdout_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stdout.lock()).into_owned()
+    }
+
+    /// Get stderr as a string, replacing invalid UTF-8.
+    pub fn stderr_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.stderr.lock()).into_owned()
+    }
+
+    /// Clear both buffers.
+    pub fn clear(&self) {
+        self.stdout.lock().clear();
+        self.stderr.lock().clear();
+    }
+
+    /// Implementation of WASI `fd_write`, returning a WASI errno.
+    fn fd_write<T>(
+        &self,
+        caller: Caller<'_, T>,
+        fd: i32,
+        iovs: i32,
+        iovs_len: i32,
+        nwritten: i32,
+    ) -> i32 {
+        if fd != 1 && fd != 2 {
+            return ERRNO_BADF;
+        }
+
+        let mut ctx = HostContext::new(caller);
+        match gather_iovs(&mut ctx, iovs as u32 as usize, iovs_len as u32 as usize) {
+            Ok(bytes) => {
+                self.write(fd, &bytes);
+                let written = (bytes.len() as u32).to_le_bytes();
+                if ctx
+                    .write_memory(nwritten as u32 as usize, &written)
+                    .is_err()
+                {
+                    return ERRNO_FAULT;
+                }
+
+                debug!(fd, bytes = bytes.len(), "Captured guest output");
+                ERRNO_SUCCESS
+            }
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+}
+
+/// Read the bytes described by a WASI iovec array.
+///
+/// At most [`MAX_IOVS`] iovecs and [`MAX_IO_BYTES`] bytes in total are
+/// gathered, so repeating a region across many iovecs cannot multiply the
+/// host allocation.
+pub(crate) fn gather_iovs<T>(
+    ctx: &mut HostContext<'_, T>,
+    iovs: usize,
+    count: usize,
+) -> HostResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    for (ptr, len) in read_iovs(ctx, iovs, count)? {
+        let len = len.min(MAX_IO_BYTES - bytes.len());
+        ctx.with_memory_slice(ptr, len, |data| bytes.extend_from_slice(data))?;
+        if bytes.len() == MAX_IO_BYTES {
+            break;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Read the `(ptr, len)` pairs of a WASI iovec array, up to [`MAX_IOVS`].
+pub(crate) fn read_iovs<T>(
+    ctx: &mut HostContext<'_, T>,
+    iovs: usize,
+    count: usize,
+) -> HostResult<Vec<(usize, usize)>> {
+    let table = ctx.read_memory(iovs, count.min(MAX_IOVS) * 8)?;
+
+    Ok(table
+        .chunks_exact(8)
+        .map(|iov| {
+            let ptr = u32::from_le_bytes([iov[0], iov[1], iov[2], iov[3]]) as usize;
+            let len = u32::from_le_bytes([iov[4], iov[5], iov[6], iov[7]]) as usize;
+            (ptr, len)
+        })
+        .collect())
+}
+
+impl std::fmt::Debug for OutputCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputCapture")
+            .field("stdout_bytes", &self.stdout.lock().len())
+            .field("stderr_bytes", &self.stderr.lock().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wasmtime::{Engine, Module, Store};
+
+    const HELLO_WAT: &str = r#"
+        (module
+            (import "wasi_snapshot_preview1" "fd_write"
+                (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "hello ")
+            (data (i32.const 32) "world\n")
+            (data (i32.const 48) "oops")
+            (func (export "run") (result i32)
+                ;; two iovecs for stdout at offset 0
+                (i32.store (i32.const 0) (i32.const 16))
+                (i32.store (i32.const 4) (i32.const 6))
+                (i32.store (i32.const 8) (i32.const 32))
+                (i32.store (i32.const 12) (i32.const 6))
+                (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 2) (i32.const 64)))
+                ;; one iovec for stderr at offset 80
+                (i32.store (i32.const 80) (i32.const 48))
+                (i32.store (i32.const 84) (i32.const 4))
+                (drop (call $fd_write (i32.const 2) (i32.const 80) (i32.const 1) (i32.const 68)))
+                ;; unsupported descriptor
+                (call $fd_write (i32.const 5) (i32.const 80) (i32.const 1) (i32.const 72))
+            )
+        )
+    "#;
+
+    #[test]
+    fn test_captures_stdout_and_stderr() {
+        let engine = Engine::default();
+        let module = Module::new(&engine, HELLO_WAT).unwrap();
+        let mut linker = Linker::new(&engine);
+        let mut store = Store::new(&engine, ());
+
+        let capture = OutputCapture::new();
+        capture.add_to_linker(&mut linker).unwrap();
+
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let run = instance
+            .get_typed_func::<(), i32>(&mut store, "run")
+            .unwrap();
+
+        assert_eq!(run.call(&mut store, ()).unwrap(), ERRNO_BADF);
+        assert_eq!(capture.stdout_lossy(), "hello world\n");
+        assert_eq!(capture.stderr_lossy(), "oops");
+
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        let nwritten = &memory.data(&store)[64..68];
+        assert_eq!(u32::from_le_bytes(nwritten.try_into().unwrap()), 12);
+
+        capture.clear();
+        assert!(capture.stdout().is_empty());
+    }
+
+    #[test]
+    fn test_gathered_bytes_capped() {
+        // 2048 iovecs that all cover the same 32 KiB of memory
+        let wat = r#"
+            (module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "run") (result i32)
+                    (local $i i32)
+                    (loop $fill
+                        (i32.store (i32.mul (local.get $i) (i32.const 8)) (i32.const 0))
+                        (i32.store
+                            (i32.add (i32.mul (local.get $i) (i32.const 8)) (i32.const 4))
+                            (i32.const 32768))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br_if $fill (i32.lt_u (local.get $i) (i32.const 2048))))
+                    (call $fd_write (i32.const 1) (i32.const 0) (i32.const 2048) (i32.const 65532))
+                )
+            )
+        "#;
+        let engine = Engine::default();
+        let module = Module::new(&engine, wat).unwrap();
+        let mut linker = Linker::new(&engine);
+        let mut store = Store::new(&engine, ());
+
+        let capture = OutputCapture::new();
+        capture.add_to_linker(&mut linker).unwrap();
+
+        let instance = linker.instantiate(&mut store, &module).unwrap();
+        let run = instance
+            .get_typed_func::<(), i32>(&mut store, "run")
+            .unwrap();
+
+        assert_eq!(run.call(&mut store, ()).unwrap(), ERRNO_SUCCESS);
+        assert_eq!(capture.stdout().len(), MAX_IO_BYTES);
+
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        let nwritten = &memory.data(&store)[65532..65536];
+        assert_eq!(
+            u32::from_le_bytes(nwritten.try_into().unwrap()) as usize,
+            MAX_IO_BYTES
+        );
+    }
+}
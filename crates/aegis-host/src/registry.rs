@@ -0,0 +1,448 @@
+//! Reusable collections of host functions.
+//!
+//! This module provides `HostFunctionRegistry`, which holds host function
+//! definitions that can be installed into any number of sandboxes via
+//! `SandboxBuilder::with_registry`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use aegis_capability::{CapabilityId, CapabilityView};
+use aegis_core::{HostFunctions, SandboxData};
+use tracing::debug;
+use wasmtime::{FuncType, IntoFunc, Linker, Val, ValType};
+
+use crate::error::{HostError, HostResult};
+use crate::linker::{AegisLinker, RegisteredFunction};
+
+/// Installs one function into a linker.
+type Installer<S> = Arc<dyn Fn(&mut AegisLinker<SandboxData<S>>) -> HostResult<()> + Send + Sync>;
+
+/// A registered function and the closure that defines it.
+struct Entry<S> {
+    info: RegisteredFunction,
+    install: Installer<S>,
+}
+
+/// A collection of host functions keyed by import module and name.
+///
+/// Each function is stored with its required capability. When installed
+/// into a sandbox, the functions are defined in its linker and then checked
+/// with [`AegisLinker::validate_capabilities`], so a sandbox that lacks a
+/// required capability fails to build rather than failing at call time.
+///
+/// # Example
+///
+/// ```ignore
+/// use std::sync::Arc;
+/// use aegis_host::HostFunctionRegistry;
+///
+/// let mut registry = HostFunctionRegistry::<()>::new();
+/// registry.register_with_capability(
+///     "env",
+///     "log",
+///     Some(CapabilityId::new("logging")),
+///     |value: i32| println!("guest: {value}"),
+/// )?;
+/// let registry = Arc::new(registry);
+///
+/// let sandbox = SandboxBuilder::new(engine)
+///     .with_capabilities(capabilities)
+///     .with_registry(registry.clone())
+///     .build()?;
+/// ```
+pub struct HostFunctionRegistry<S> {
+    entries: Vec<Entry<S>>,
+}
+
+impl<S: Send + 'static> HostFunctionRegistry<S> {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Register a host function.
+    pub fn register<Params, Results, F>(
+        &mut self,
+        module: &str,
+        name: &str,
+        func: F,
+    ) -> HostResult<&mut Self>
+    where
+        F: IntoFunc<SandboxData<S>, Params, Results> + Clone + Send + Sync,
+    {
+        self.register_with_capability(module, name, None, func)
+    }
+
+    /// Register a host function with a required capability.
+    ///
+    /// The closure is cloned into every linker the registry is installed in.
+    pub fn register_with_capability<Params, Results, F>(
+        &mut self,
+        module: &str,
+        name: &str,
+        required_capability: Option<CapabilityId>,
+        func: F,
+    ) -> HostResult<&mut Self>
+    where
+        F: IntoFunc<SandboxData<S>, Params, Results> + Clone + Send + Sync,
+    {
+        if self.is_registered(module, name) {
+            return Err(HostError::AlreadyRegistered {
+                module: module.to_string(),
+                name: name.to_string(),
+            });
+        }
+
+        let info = RegisteredFunction {
+            module: module.to_string(),
+            name: name.to_string(),
+            required_capability,
+            description: None,
+        };
+
+        let target = info.clone();
+        let install: Installer<S> = Arc::new(move |linker| {
+            linker.func_wrap_with_capability(
+                &target.module,
+                &target.name,
+                target.required_capability.clone(),
+                func.clone(),
+            )?;
+            Ok(())
+        });
+
+        self.entries.push(Entry { info, install });
+        debug!(module, name, "Added host function to registry");
+        Ok(self)
+    }
+
+    /// Register a host function that fails with [`HostError::Timeout`] if a
+    /// call takes longer than `timeout`.
+    ///
+    /// `func` takes and returns values of the given `params` and `results`
+    /// types. See [`AegisLinker::func_wrap_with_timeout`] for how calls run.
+    pub fn register_with_timeout<F>(
+        &mut self,
+        module: &str,
+        name: &str,
+        params: impl IntoIterator<Item = ValType>,
+        results: impl IntoIterator<Item = ValType>,
+        timeout: Duration,
+        func: F,
+    ) -> HostResult<&mut Self>
+    where
+        F: Fn(&[Val]) -> HostResult<Vec<Val>> + Send + Sync + 'static,
+    {
+        if self.is_registered(module, name) {
+            return Err(HostError::AlreadyRegistered {
+                module: module.to_string(),
+                name: name.to_string(),
+            });
+        }
+
+        let info = RegisteredFunction {
+            module: module.to_string(),
+            name: name.to_string(),
+            required_capability: None,
+            description: None,
+        };
+
+        let target = info.clone();
+        let params: Vec<ValType> = params.into_iter().collect();
+        let results: Vec<ValType> = results.into_iter().collect();
+        let func = Arc::new(func);
+        let install: Installer<S> = Arc::new(move |linker| {
+            let ty = FuncType::new(
+                linker.inner().engine(),
+                params.iter().cloned(),
+                results.iter().cloned(),
+            );
+            let func = Arc::clone(&func);
+            linker.func_wrap_with_timeout(
+                &target.module,
+                &target.name,
+                ty,
+                timeout,
+                move |values: &[Val]| func(values),
+            )?;
+            Ok(())
+        });
+
+        self.entries.push(Entry { info, install });
+        debug!(
+            module,
+            name,
+            ?timeout,
+            "Added host function with timeout to registry"
+        );
+        Ok(self)
+    }
+
+    /// Check if a function is already registered.
+    pub fn is_registered(&self, module: &str, name: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.info.module == module && e.info.name == name)
+    }
+
+    /// Get the registered functions, in registration order.
+    pub fn functions(&self) -> impl Iterator<Item = &RegisteredFunction> {
+        self.entries.iter().map(|e| &e.info)
+    }
+
+    /// Get the number of registered functions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the registry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Define every function in `linker`, then validate the capability
+    /// requirements against `capabilities`.
+    ///
+    /// Functions already defined in `linker` are left in place.
+    pub fn apply(
+        &self,
+        linker: &mut Linker<SandboxData<S>>,
+        capabilities: &CapabilityView,
+    ) -> HostResult<()> {
+        let engine = linker.engine().clone();
+        let mut aegis = AegisLinker::from_linker(std::mem::replace(linker, Linker::new(&engine)));
+
+        let result = self
+            .entries
+            .iter()
+            .try_for_each(|entry| (entry.install)(&mut aegis))
+            .and_then(|()| aegis.validate_capabilities(capabilities));
+
+        *linker = aegis.into_inner();
+        result
+    }
+}
+
+impl<S: Send + 'static> Default for HostFunctionRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: Send + 'static> HostFunctions<S> for HostFunctionRegistry<S> {
+    fn install(
+        &self,
+        linker: &mut Linker<SandboxData<S>>,
+        capabilities: &CapabilityView,
+    ) -> wasmtime::Result<()> {
+        self.apply(linker, capabilities)?;
+        Ok(())
+    }
+
+    fn registered_functions(&self) -> Vec<RegisteredFunction> {
+        self.functions().cloned().collect()
+    }
+}
+
+impl<S> std::fmt::Debug for HostFunctionRegistry<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HostFunctionRegistry")
+            .field("functions", &self.entries.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aegis_capability::CapabilitySet;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use aegis_capability::builtin::{LogLevel, LoggingAction, LoggingCapability};
+    use aegis_core::{
+        AegisEngine, ExecutionError, HostFunctionError, IntoShared, ModuleLoader, SandboxBuilder,
+    };
+    use wasmtime::Caller;
+
+    use crate::context::HostContext;
+
+    const LOG_WAT: &str = r#"
+        (module
+            (import "env" "log" (func $log (param i32)))
+            (func (export "run") (call $log (i32.const 7)))
+        )
+    "#;
+
+    #[test]
+    fn test_registry_shared_across_sandboxes() {
+        let engine = AegisEngine::default_engine().unwrap().into_shared();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(LOG_WAT)
+            .unwrap();
+
+        let total = Arc::new(AtomicI32::new(0));
+        let counter = Arc::clone(&total);
+
+        let mut registry = HostFunctionRegistry::<()>::new();
+        registry
+            .register_with_capability(
+                "env",
+                "log",
+                Some(CapabilityId::new("logging")),
+                move |value: i32| {
+                    counter.fetch_add(value, Ordering::SeqCst);
+                },
+            )
+            .unwrap();
+        assert!(matches!(
+            registry.register("env", "log", |_: i32| {}),
+            Err(HostError::AlreadyRegistered { .. })
+        ));
+        let registry = Arc::new(registry);
+
+        let capabilities = CapabilitySet::new();
+        capabilities
+            .grant(LoggingCapability::new(LogLevel::Info, 1024))
+            .unwrap();
+        let capabilities = Arc::new(capabilities);
+
+        for _ in 0..2 {
+            let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+                .with_capabilities(Arc::clone(&capabilities))
+                .with_registry(registry.clone())
+                .build()
+                .unwrap();
+            sandbox.load_module(&module).unwrap();
+            sandbox.call_void("run").unwrap();
+        }
+        assert_eq!(total.load(Ordering::SeqCst), 14);
+
+        let result = SandboxBuilder::<()>::new(engine)
+            .with_registry(registry)
+            .build();
+        assert!(matches!(result, Err(ExecutionError::Wasmtime(_))));
+    }
+
+    #[test]
+    fn test_host_error_propagates_to_caller() {
+        let engine = AegisEngine::default_engine().unwrap().into_shared();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(LOG_WAT)
+            .unwrap();
+
+        let mut registry = HostFunctionRegistry::<()>::new();
+        registry
+            .register(
+                "env",
+                "log",
+                |caller: Caller<'_, SandboxData<()>>, _value: i32| -> wasmtime::Result<()> {
+                    let capabilities = caller.data().capabilities.clone();
+                    let ctx = HostContext::with_capabilities(caller, capabilities);
+                    ctx.require_permission(&LoggingAction::Log {
+                        level: LogLevel::Info,
+                        message_len: 4096,
+                    })
+                    .map_err(HostFunctionError::from)?;
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        let capabilities = CapabilitySet::new();
+        capabilities
+            .grant(LoggingCapability::new(LogLevel::Info, 1024))
+            .unwrap();
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_capabilities(Arc::new(capabilities))
+            .with_registry(Arc::new(registry))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let error = sandbox.call_void("run").unwrap_err();
+        match error.host_error::<HostError>() {
+            Some(HostError::PermissionDenied {
+                capability, action, ..
+            }) => {
+                assert_eq!(capability.as_str(), "logging");
+                assert_eq!(action, "log:write");
+            }
+            other => panic!("expected PermissionDenied, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_register_with_timeout() {
+        use std::time::Instant;
+
+        use wasmtime::ValType;
+
+        use crate::linker::MAX_TIMEOUT_WORKERS;
+
+        let engine = AegisEngine::default_engine().unwrap().into_shared();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"
+            (module
+                (import "env" "fetch" (func $fetch (param i32) (result i32)))
+                (func (export "run") (param i32) (result i32)
+                    (call $fetch (local.get 0)))
+            )
+        "#,
+            )
+            .unwrap();
+
+        let mut registry = HostFunctionRegistry::<()>::new();
+        registry
+            .register_with_timeout(
+                "env",
+                "fetch",
+                [ValType::I32],
+                [ValType::I32],
+                Duration::from_millis(50),
+                |params| {
+                    let delay = params[0].unwrap_i32();
+                    std::thread::sleep(Duration::from_millis(delay as u64));
+                    Ok(vec![Val::I32(delay * 2)])
+                },
+            )
+            .unwrap();
+
+        let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+            .with_registry(Arc::new(registry))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        let fast: i32 = sandbox.call("run", 1).unwrap();
+        assert_eq!(fast, 2);
+
+        let started = Instant::now();
+        let error = sandbox.call::<i32, i32>("run", 1000).unwrap_err();
+        assert!(started.elapsed() < Duration::from_millis(900));
+        assert!(matches!(
+            error.host_error::<HostError>(),
+            Some(HostError::Timeout { timeout, .. }) if *timeout == Duration::from_millis(50)
+        ));
+        assert_eq!(sandbox.metrics().host_calls, 2);
+        assert!(sandbox.metrics().host_call_time >= Duration::from_millis(50));
+
+        // Timed-out workers keep their slot until they finish
+        for _ in 1..MAX_TIMEOUT_WORKERS {
+            let error = sandbox.call::<i32, i32>("run", 1000).unwrap_err();
+            assert!(matches!(
+                error.host_error::<HostError>(),
+                Some(HostError::Timeout { .. })
+            ));
+        }
+        let error = sandbox.call::<i32, i32>("run", 1).unwrap_err();
+        assert!(matches!(
+            error.host_error::<HostError>(),
+            Some(HostError::WorkersExhausted { limit, .. }) if *limit == MAX_TIMEOUT_WORKERS
+        ));
+    }
+}
@@ -0,0 +1,1493 @@
+//! WASI preview1 imports gated by capabilities.
+//!
+//! This module provides `WasiCapability`, which defines the
+//! `wasi_snapshot_preview1` imports most modules need and checks each call
+//! against the sandbox's capabilities: stdio goes through the logging
+//! capability, `path_open` through the filesystem capability, and the
+//! socket calls through the network capability.
+
+use std::collections::HashMap;
+use std::fs::{File, Metadata, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use aegis_capability::builtin::{
+    ClockCapability, ClockType, FilesystemAction, LogLevel, LoggingAction, RandomCapability,
+};
+use aegis_capability::{CapabilityPolicy, CapabilityView, standard_ids};
+use aegis_core::{HostFunctionError, HostFunctions, RegisteredFunction, SandboxData};
+use parking_lot::Mutex;
+use tracing::debug;
+use wasmtime::{Caller, Linker};
+
+use crate::context::HostContext;
+use crate::entropy::{
+    CLOCK_MONOTONIC, CLOCK_REALTIME, ERRNO_INVAL, ERRNO_NOTCAPABLE, EntropySources,
+    registration_failed,
+};
+use crate::error::{HostError, HostResult};
+use crate::output::{
+    ERRNO_BADF, ERRNO_FAULT, ERRNO_SUCCESS, MAX_IO_BYTES, OutputCapture, WASI_MODULE, gather_iovs,
+    read_iovs, wasi_function,
+};
+
+/// WASI errno for permission denied by the host filesystem.
+const ERRNO_ACCES: i32 = 2;
+/// WASI errno for an existing file.
+const ERRNO_EXIST: i32 = 20;
+/// WASI errno for an I/O error.
+const ERRNO_IO: i32 = 29;
+/// WASI errno for too many levels of symlinks.
+#[cfg(unix)]
+const ERRNO_LOOP: i32 = 32;
+/// WASI errno for a missing file.
+const ERRNO_NOENT: i32 = 44;
+/// WASI errno for an unsupported operation.
+const ERRNO_NOTSUP: i32 = 58;
+/// WASI errno for seeking on a stream.
+const ERRNO_SPIPE: i32 = 70;
+
+/// `path_open` flag: create the file if it does not exist.
+const OFLAGS_CREAT: i32 = 1 << 0;
+/// `path_open` flag: fail if the path is not a directory.
+const OFLAGS_DIRECTORY: i32 = 1 << 1;
+/// `path_open` flag: fail if the file already exists.
+const OFLAGS_EXCL: i32 = 1 << 2;
+/// `path_open` flag: truncate the file.
+const OFLAGS_TRUNC: i32 = 1 << 3;
+/// `path_open` descriptor flag: append writes.
+const FDFLAGS_APPEND: i32 = 1 << 0;
+/// Right to read from a descriptor.
+const RIGHTS_FD_READ: i64 = 1 << 1;
+/// Right to write to a descriptor.
+const RIGHTS_FD_WRITE: i64 = 1 << 6;
+/// Every right defined by preview1.
+const RIGHTS_ALL: i64 = (1 << 29) - 1;
+
+/// File type of stdio.
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+/// File type of a directory.
+const FILETYPE_DIRECTORY: u8 = 3;
+/// File type of a regular file.
+const FILETYPE_REGULAR_FILE: u8 = 4;
+
+/// `fd_seek` whence: from the start of the file.
+const WHENCE_SET: i32 = 0;
+/// `fd_seek` whence: from the current position.
+const WHENCE_CUR: i32 = 1;
+/// `fd_seek` whence: from the end of the file.
+const WHENCE_END: i32 = 2;
+
+/// `poll_oneoff` event type of a clock timeout.
+const EVENTTYPE_CLOCK: u8 = 0;
+/// `poll_oneoff` event type of a readable descriptor.
+const EVENTTYPE_FD_READ: u8 = 1;
+/// `poll_oneoff` event type of a writable descriptor.
+const EVENTTYPE_FD_WRITE: u8 = 2;
+/// `poll_oneoff` clock flag: the timeout is an absolute time.
+const SUBCLOCKFLAGS_ABSTIME: u16 = 1 << 0;
+/// `poll_oneoff` event flag: the peer hung up.
+const EVENTRWFLAGS_HANGUP: u16 = 1 << 0;
+/// Size of a `poll_oneoff` subscription.
+const SUBSCRIPTION_SIZE: usize = 48;
+/// Size of a `poll_oneoff` event.
+const EVENT_SIZE: usize = 32;
+
+/// First descriptor used for preopened directories.
+const FIRST_PREOPEN_FD: u32 = 3;
+
+/// WASI preview1 support for a sandbox.
+///
+/// The imports are backed by the sandbox's capabilities rather than by
+/// ambient host access:
+///
+/// - `fd_write` to stdout and stderr is captured (see
+///   [`output`](Self::output)) and needs the logging capability to permit
+///   an `Info` or `Warn` message respectively.
+/// - Every non-glob `PathPermission` of the filesystem capability is
+///   preopened as a directory, starting at descriptor 3. `path_open`
+///   resolves symlinks, then checks the read, write and create actions it
+///   needs on the resolved path with the filesystem capability before
+///   opening it. The open does not follow a symlink in the last component,
+///   and on Linux the opened descriptor's real path is checked again.
+/// - Opened files support `fd_read`, `fd_write`, `fd_seek`, `fd_fdstat_get`,
+///   `fd_filestat_get` and `fd_close`.
+/// - `clock_time_get`, `clock_res_get` and `random_get` use the clock and
+///   random capabilities, or the sources given to
+///   [`with_entropy`](Self::with_entropy). `poll_oneoff` sleeps on the same
+///   clock, returning at once when it is simulated; descriptors are always
+///   ready. A real sleep blocks the host thread, so a deadline only ends
+///   the call once it returns.
+/// - The `sock_*` calls return `ENOTCAPABLE` without the network
+///   capability and `EBADF` with it, as no sockets are preopened.
+/// - `proc_exit` ends the call with `HostError::Exit`.
+///
+/// Open files belong to the linker the imports were installed in, so each
+/// sandbox has its own descriptors. Clones share the output buffers.
+///
+/// # Example
+///
+/// ```ignore
+/// use aegis_host::WasiCapability;
+///
+/// let wasi = WasiCapability::new().with_args(["app", "--verbose"]);
+/// let sandbox = SandboxBuilder::new(engine)
+///     .with_capabilities(capabilities)
+///     .with_registry(Arc::new(wasi.clone()))
+///     .build()?;
+///
+/// sandbox.call_void("_start")?;
+/// println!("{}", wasi.output().stdout_lossy());
+/// ```
+#[derive(Clone, Default)]
+pub struct WasiCapability {
+    /// Buffers for stdout and stderr.
+    output: OutputCapture,
+    /// Clock and random sources overriding the sandbox's capabilities.
+    entropy: Option<EntropySources>,
+    /// Command-line arguments, including the program name.
+    args: Vec<String>,
+    /// Environment variables as `KEY=value` strings.
+    env: Vec<String>,
+}
+
+impl WasiCapability {
+    /// Create WASI support with no arguments or environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Capture stdio into `output` instead of fresh buffers.
+    pub fn with_output(mut self, output: OutputCapture) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Use `entropy` for clocks and randomness instead of the sandbox's
+    /// clock and random capabilities.
+    pub fn with_entropy(mut self, entropy: EntropySources) -> Self {
+        self.entropy = Some(entropy);
+        self
+    }
+
+    /// Set the command-line arguments, starting with the program name.
+    pub fn with_args<I, A>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add an environment variable.
+    pub fn with_env(mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.env
+            .push(format!("{}={}", key.as_ref(), value.as_ref()));
+        self
+    }
+
+    /// Get the stdout and stderr buffers.
+    pub fn output(&self) -> &OutputCapture {
+        &self.output
+    }
+
+    /// Register the WASI imports with the linker.
+    ///
+    /// Preopened directories and the default clock and random sources are
+    /// taken from `capabilities`, which should be the set granted to the
+    /// sandbox.
+    pub fn add_to_linker<S: Send + 'static>(
+        &self,
+        linker: &mut Linker<SandboxData<S>>,
+        capabilities: &CapabilityView,
+    ) -> HostResult<()> {
+        let entropy = self
+            .entropy
+            .clone()
+            .unwrap_or_else(|| entropy_from(capabilities));
+        entropy.add_to_linker(linker)?;
+
+        let state = Arc::new(WasiState {
+            output: self.output.clone(),
+            entropy,
+            args: self.args.clone(),
+            env: self.env.clone(),
+            preopens: preopens_from(capabilities),
+            files: Mutex::new(FileTable::default()),
+        });
+        debug!(preopens = state.preopens.len(), "Registering WASI imports");
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_write",
+                move |caller: Caller<'_, SandboxData<S>>,
+                      fd: i32,
+                      iovs: i32,
+                      len: i32,
+                      out: i32| { s.fd_write(caller, fd, iovs, len, out) },
+            )
+            .map_err(|e| registration_failed("fd_write", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_read",
+                move |caller: Caller<'_, SandboxData<S>>,
+                      fd: i32,
+                      iovs: i32,
+                      len: i32,
+                      out: i32| { s.fd_read(caller, fd, iovs, len, out) },
+            )
+            .map_err(|e| registration_failed("fd_read", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_close",
+                move |_: Caller<'_, SandboxData<S>>, fd: i32| s.fd_close(fd),
+            )
+            .map_err(|e| registration_failed("fd_close", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_seek",
+                move |caller: Caller<'_, SandboxData<S>>,
+                      fd: i32,
+                      offset: i64,
+                      whence: i32,
+                      out: i32| { s.fd_seek(caller, fd, offset, whence, out) },
+            )
+            .map_err(|e| registration_failed("fd_seek", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_fdstat_get",
+                move |caller: Caller<'_, SandboxData<S>>, fd: i32, buf: i32| {
+                    s.fd_fdstat_get(caller, fd, buf)
+                },
+            )
+            .map_err(|e| registration_failed("fd_fdstat_get", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_filestat_get",
+                move |caller: Caller<'_, SandboxData<S>>, fd: i32, buf: i32| {
+                    s.fd_filestat_get(caller, fd, buf)
+                },
+            )
+            .map_err(|e| registration_failed("fd_filestat_get", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_prestat_get",
+                move |caller: Caller<'_, SandboxData<S>>, fd: i32, buf: i32| {
+                    s.fd_prestat_get(caller, fd, buf)
+                },
+            )
+            .map_err(|e| registration_failed("fd_prestat_get", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "fd_prestat_dir_name",
+                move |caller: Caller<'_, SandboxData<S>>, fd: i32, path: i32, len: i32| {
+                    s.fd_prestat_dir_name(caller, fd, path, len)
+                },
+            )
+            .map_err(|e| registration_failed("fd_prestat_dir_name", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "path_open",
+                move |caller: Caller<'_, SandboxData<S>>,
+                      fd: i32,
+                      _dirflags: i32,
+                      path: i32,
+                      path_len: i32,
+                      oflags: i32,
+                      rights: i64,
+                      _inheriting: i64,
+                      fdflags: i32,
+                      opened: i32| {
+                    let flags = OpenFlags {
+                        oflags,
+                        rights,
+                        fdflags,
+                    };
+                    s.path_open(caller, fd, (path, path_len), flags, opened)
+                },
+            )
+            .map_err(|e| registration_failed("path_open", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "args_sizes_get",
+                move |caller: Caller<'_, SandboxData<S>>, count: i32, size: i32| {
+                    write_sizes(caller, &s.args, count, size)
+                },
+            )
+            .map_err(|e| registration_failed("args_sizes_get", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "args_get",
+                move |caller: Caller<'_, SandboxData<S>>, ptrs: i32, buf: i32| {
+                    write_strings(caller, &s.args, ptrs, buf)
+                },
+            )
+            .map_err(|e| registration_failed("args_get", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "environ_sizes_get",
+                move |caller: Caller<'_, SandboxData<S>>, count: i32, size: i32| {
+                    write_sizes(caller, &s.env, count, size)
+                },
+            )
+            .map_err(|e| registration_failed("environ_sizes_get", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "environ_get",
+                move |caller: Caller<'_, SandboxData<S>>, ptrs: i32, buf: i32| {
+                    write_strings(caller, &s.env, ptrs, buf)
+                },
+            )
+            .map_err(|e| registration_failed("environ_get", e))?;
+
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "proc_exit",
+                |_: Caller<'_, SandboxData<S>>, code: i32| -> wasmtime::Result<()> {
+                    Err(HostFunctionError::from(HostError::Exit(code)).into())
+                },
+            )
+            .map_err(|e| registration_failed("proc_exit", e))?;
+
+        let s = Arc::clone(&state);
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "poll_oneoff",
+                move |caller: Caller<'_, SandboxData<S>>,
+                      subscriptions: i32,
+                      events: i32,
+                      count: i32,
+                      out: i32| {
+                    s.poll_oneoff(caller, subscriptions, events, count, out)
+                },
+            )
+            .map_err(|e| registration_failed("poll_oneoff", e))?;
+
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "sched_yield",
+                |_: Caller<'_, SandboxData<S>>| {
+                    std::thread::yield_now();
+                    ERRNO_SUCCESS
+                },
+            )
+            .map_err(|e| registration_failed("sched_yield", e))?;
+
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "sock_accept",
+                |caller: Caller<'_, SandboxData<S>>, _fd: i32, _flags: i32, _out: i32| {
+                    socket_errno(&caller)
+                },
+            )
+            .map_err(|e| registration_failed("sock_accept", e))?;
+
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "sock_recv",
+                |caller: Caller<'_, SandboxData<S>>,
+                 _fd: i32,
+                 _iovs: i32,
+                 _len: i32,
+                 _flags: i32,
+                 _out_len: i32,
+                 _out_flags: i32| { socket_errno(&caller) },
+            )
+            .map_err(|e| registration_failed("sock_recv", e))?;
+
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "sock_send",
+                |caller: Caller<'_, SandboxData<S>>,
+                 _fd: i32,
+                 _iovs: i32,
+                 _len: i32,
+                 _flags: i32,
+                 _out_len: i32| { socket_errno(&caller) },
+            )
+            .map_err(|e| registration_failed("sock_send", e))?;
+
+        linker
+            .func_wrap(
+                WASI_MODULE,
+                "sock_shutdown",
+                |caller: Caller<'_, SandboxData<S>>, _fd: i32, _how: i32| socket_errno(&caller),
+            )
+            .map_err(|e| registration_failed("sock_shutdown", e))?;
+
+        Ok(())
+    }
+}
+
+impl<S: Send + 'static> HostFunctions<S> for WasiCapability {
+    fn install(
+        &self,
+        linker: &mut Linker<SandboxData<S>>,
+        capabilities: &CapabilityView,
+    ) -> wasmtime::Result<()> {
+        self.add_to_linker(linker, capabilities)?;
+        Ok(())
+    }
+
+    fn registered_functions(&self) -> Vec<RegisteredFunction> {
+        let filesystem = || Some(standard_ids::FILESYSTEM);
+        let network = || Some(standard_ids::NETWORK);
+
+        let mut functions = EntropySources::new().registered_functions();
+        functions.extend([
+            wasi_function("fd_write", Some(standard_ids::LOGGING)),
+            wasi_function("fd_read", filesystem()),
+            wasi_function("fd_close", None),
+            wasi_function("fd_seek", filesystem()),
+            wasi_function("fd_fdstat_get", None),
+            wasi_function("fd_filestat_get", filesystem()),
+            wasi_function("fd_prestat_get", filesystem()),
+            wasi_function("fd_prestat_dir_name", filesystem()),
+            wasi_function("path_open", filesystem()),
+            wasi_function("args_sizes_get", None),
+            wasi_function("args_get", None),
+            wasi_function("environ_sizes_get", None),
+            wasi_function("environ_get", None),
+            wasi_function("proc_exit", None),
+            wasi_function("poll_oneoff", Some(standard_ids::CLOCK)),
+            wasi_function("sched_yield", None),
+            wasi_function("sock_accept", network()),
+            wasi_function("sock_recv", network()),
+            wasi_function("sock_send", network()),
+            wasi_function("sock_shutdown", network()),
+        ]);
+        functions
+    }
+}
+
+impl std::fmt::Debug for WasiCapability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasiCapability")
+            .field("output", &self.output)
+            .field("entropy", &self.entropy)
+            .field("args", &self.args)
+            .field("env", &self.env.len())
+            .finish()
+    }
+}
+
+/// Flags of a `path_open` call.
+struct OpenFlags {
+    /// How to open the file (`OFLAGS_*`).
+    oflags: i32,
+    /// Rights requested for the new descriptor (`RIGHTS_*`).
+    rights: i64,
+    /// Flags of the new descriptor (`FDFLAGS_*`).
+    fdflags: i32,
+}
+
+/// A file opened by the guest.
+struct OpenFile {
+    /// The host file.
+    file: File,
+    /// Rights the descriptor was opened with (`RIGHTS_*`).
+    rights: i64,
+    /// Flags the descriptor was opened with (`FDFLAGS_*`).
+    fdflags: i32,
+}
+
+/// Files opened by the guest.
+#[derive(Default)]
+struct FileTable {
+    /// Open files by descriptor.
+    files: HashMap<u32, OpenFile>,
+    /// Descriptor to hand out next, relative to the first free one.
+    next: u32,
+}
+
+/// State shared by the imports of one linker.
+struct WasiState {
+    /// Buffers for stdout and stderr.
+    output: OutputCapture,
+    /// Clock `poll_oneoff` sleeps on.
+    entropy: EntropySources,
+    /// Command-line arguments.
+    args: Vec<String>,
+    /// Environment variables as `KEY=value` strings.
+    env: Vec<String>,
+    /// Preopened directories, in descriptor order.
+    preopens: Vec<PathBuf>,
+    /// Files opened by the guest.
+    files: Mutex<FileTable>,
+}
+
+impl WasiState {
+    /// Get the preopened directory for a descriptor.
+    fn preopen(&self, fd: i32) -> Option<&PathBuf> {
+        let index = (fd as u32).checked_sub(FIRST_PREOPEN_FD)?;
+        self.preopens.get(index as usize)
+    }
+
+    /// Implementation of WASI `fd_write`, returning a WASI errno.
+    fn fd_write<S>(
+        &self,
+        caller: Caller<'_, SandboxData<S>>,
+        fd: i32,
+        iovs: i32,
+        iovs_len: i32,
+        nwritten: i32,
+    ) -> i32 {
+        let mut ctx = HostContext::new(caller);
+        let Ok(bytes) = gather_iovs(&mut ctx, iovs as u32 as usize, iovs_len as u32 as usize)
+        else {
+            return ERRNO_FAULT;
+        };
+
+        match fd {
+            1 | 2 => {
+                let level = if fd == 1 {
+                    LogLevel::Info
+                } else {
+                    LogLevel::Warn
+                };
+                let action = LoggingAction::Log {
+                    level,
+                    message_len: bytes.len(),
+                };
+                if !ctx.data().check(&action).is_allowed() {
+                    return ERRNO_NOTCAPABLE;
+                }
+                self.output.write(fd, &bytes);
+            }
+            _ => {
+                let mut files = self.files.lock();
+                let Some(OpenFile { file, .. }) = files.files.get_mut(&(fd as u32)) else {
+                    return ERRNO_BADF;
+                };
+                if let Err(e) = file.write_all(&bytes) {
+                    return io_errno(&e);
+                }
+            }
+        }
+
+        let written = (bytes.len() as u32).to_le_bytes();
+        match ctx.write_memory(nwritten as u32 as usize, &written) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `fd_read`, returning a WASI errno.
+    ///
+    /// Stdin is always at end of file.
+    fn fd_read<S>(
+        &self,
+        caller: Caller<'_, SandboxData<S>>,
+        fd: i32,
+        iovs: i32,
+        iovs_len: i32,
+        nread: i32,
+    ) -> i32 {
+        let mut ctx = HostContext::new(caller);
+        let mut total = 0usize;
+
+        if fd != 0 {
+            let Ok(table) = read_iovs(&mut ctx, iovs as u32 as usize, iovs_len as u32 as usize)
+            else {
+                return ERRNO_FAULT;
+            };
+
+            let mut files = self.files.lock();
+            let Some(OpenFile { file, .. }) = files.files.get_mut(&(fd as u32)) else {
+                return ERRNO_BADF;
+            };
+            for (ptr, len) in table {
+                // Check the guest buffer before allocating for it
+                if ctx.with_memory_slice(ptr, len, |_| ()).is_err() {
+                    return ERRNO_FAULT;
+                }
+                let len = len.min(MAX_IO_BYTES - total);
+                let mut buf = vec![0u8; len];
+                let read = match file.read(&mut buf) {
+                    Ok(read) => read,
+                    Err(e) => return io_errno(&e),
+                };
+                if ctx.write_memory(ptr, &buf[..read]).is_err() {
+                    return ERRNO_FAULT;
+                }
+                total += read;
+                if read < len || total == MAX_IO_BYTES {
+                    break;
+                }
+            }
+        }
+
+        match ctx.write_memory(nread as u32 as usize, &(total as u32).to_le_bytes()) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `fd_close`, returning a WASI errno.
+    fn fd_close(&self, fd: i32) -> i32 {
+        match self.files.lock().files.remove(&(fd as u32)) {
+            Some(_) => ERRNO_SUCCESS,
+            None => ERRNO_BADF,
+        }
+    }
+
+    /// Implementation of WASI `fd_seek`, returning a WASI errno.
+    fn fd_seek<S>(
+        &self,
+        caller: Caller<'_, SandboxData<S>>,
+        fd: i32,
+        offset: i64,
+        whence: i32,
+        newoffset: i32,
+    ) -> i32 {
+        if (0..=2).contains(&fd) {
+            return ERRNO_SPIPE;
+        }
+        let position = match whence {
+            WHENCE_SET => match u64::try_from(offset) {
+                Ok(offset) => SeekFrom::Start(offset),
+                Err(_) => return ERRNO_INVAL,
+            },
+            WHENCE_CUR => SeekFrom::Current(offset),
+            WHENCE_END => SeekFrom::End(offset),
+            _ => return ERRNO_INVAL,
+        };
+
+        let position = {
+            let mut files = self.files.lock();
+            let Some(OpenFile { file, .. }) = files.files.get_mut(&(fd as u32)) else {
+                return ERRNO_BADF;
+            };
+            match file.seek(position) {
+                Ok(position) => position,
+                Err(e) => return io_errno(&e),
+            }
+        };
+
+        let mut ctx = HostContext::new(caller);
+        match ctx.write_memory(newoffset as u32 as usize, &position.to_le_bytes()) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `fd_fdstat_get`, returning a WASI errno.
+    fn fd_fdstat_get<S>(&self, caller: Caller<'_, SandboxData<S>>, fd: i32, buf: i32) -> i32 {
+        let (filetype, fdflags, rights, inheriting) = match fd {
+            0 => (FILETYPE_CHARACTER_DEVICE, 0, RIGHTS_FD_READ, 0),
+            1 | 2 => (FILETYPE_CHARACTER_DEVICE, 0, RIGHTS_FD_WRITE, 0),
+            _ if self.preopen(fd).is_some() => (FILETYPE_DIRECTORY, 0, RIGHTS_ALL, RIGHTS_ALL),
+            _ => match self.files.lock().files.get(&(fd as u32)) {
+                Some(open) => (FILETYPE_REGULAR_FILE, open.fdflags, open.rights, 0),
+                None => return ERRNO_BADF,
+            },
+        };
+
+        // An `fdstat` is the file type, flags and the base and inheriting
+        // rights
+        let mut fdstat = [0u8; 24];
+        fdstat[0] = filetype;
+        fdstat[2..4].copy_from_slice(&(fdflags as u16).to_le_bytes());
+        fdstat[8..16].copy_from_slice(&rights.to_le_bytes());
+        fdstat[16..].copy_from_slice(&inheriting.to_le_bytes());
+
+        let mut ctx = HostContext::new(caller);
+        match ctx.write_memory(buf as u32 as usize, &fdstat) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `fd_filestat_get`, returning a WASI errno.
+    fn fd_filestat_get<S>(&self, caller: Caller<'_, SandboxData<S>>, fd: i32, buf: i32) -> i32 {
+        let filestat = if (0..=2).contains(&fd) {
+            let mut filestat = [0u8; 64];
+            filestat[16] = FILETYPE_CHARACTER_DEVICE;
+            filestat
+        } else {
+            let metadata = match self.preopen(fd) {
+                Some(dir) => std::fs::metadata(dir),
+                None => match self.files.lock().files.get(&(fd as u32)) {
+                    Some(open) => open.file.metadata(),
+                    None => return ERRNO_BADF,
+                },
+            };
+            match metadata {
+                Ok(metadata) => filestat(&metadata),
+                Err(e) => return io_errno(&e),
+            }
+        };
+
+        let mut ctx = HostContext::new(caller);
+        match ctx.write_memory(buf as u32 as usize, &filestat) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `fd_prestat_get`, returning a WASI errno.
+    fn fd_prestat_get<S>(&self, caller: Caller<'_, SandboxData<S>>, fd: i32, buf: i32) -> i32 {
+        let Some(dir) = self.preopen(fd) else {
+            return ERRNO_BADF;
+        };
+
+        // A `prestat` is a tag byte (0 for a directory) and the name length
+        let mut prestat = [0u8; 8];
+        let name_len = dir.to_string_lossy().len() as u32;
+        prestat[4..].copy_from_slice(&name_len.to_le_bytes());
+
+        let mut ctx = HostContext::new(caller);
+        match ctx.write_memory(buf as u32 as usize, &prestat) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `fd_prestat_dir_name`, returning a WASI errno.
+    fn fd_prestat_dir_name<S>(
+        &self,
+        caller: Caller<'_, SandboxData<S>>,
+        fd: i32,
+        path: i32,
+        path_len: i32,
+    ) -> i32 {
+        let Some(dir) = self.preopen(fd) else {
+            return ERRNO_BADF;
+        };
+        let name = dir.to_string_lossy();
+        if name.len() > path_len as u32 as usize {
+            return ERRNO_INVAL;
+        }
+
+        let mut ctx = HostContext::new(caller);
+        match ctx.write_memory(path as u32 as usize, name.as_bytes()) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `path_open`, returning a WASI errno.
+    ///
+    /// The path is resolved against a preopened directory and every action
+    /// the open needs is checked with the sandbox's capabilities first.
+    fn path_open<S>(
+        &self,
+        caller: Caller<'_, SandboxData<S>>,
+        fd: i32,
+        (path, path_len): (i32, i32),
+        flags: OpenFlags,
+        opened: i32,
+    ) -> i32 {
+        let OpenFlags {
+            oflags,
+            rights,
+            fdflags,
+        } = flags;
+        let Some(dir) = self.preopen(fd) else {
+            return ERRNO_BADF;
+        };
+        if oflags & OFLAGS_DIRECTORY != 0 {
+            return ERRNO_NOTSUP;
+        }
+
+        let mut ctx = HostContext::new(caller);
+        let Ok(relative) = ctx.read_string_with_len(path as u32 as usize, path_len as u32 as usize)
+        else {
+            return ERRNO_FAULT;
+        };
+        // Check and open the path with symlinks resolved, so a link inside
+        // the preopen cannot reach outside the granted tree
+        let full = match resolve_under(dir, &dir.join(&relative)) {
+            Ok(full) => full,
+            Err(e) => return io_errno(&e),
+        };
+
+        let create = oflags & OFLAGS_CREAT != 0;
+        let truncate = oflags & OFLAGS_TRUNC != 0;
+        let append = fdflags & FDFLAGS_APPEND != 0;
+        let write = rights & RIGHTS_FD_WRITE != 0 || truncate || append;
+        let read = rights & RIGHTS_FD_READ != 0 || !write;
+
+        // The actions opening `path` needs, besides creating it
+        let access = |path: &Path| {
+            let mut actions = Vec::new();
+            if read {
+                actions.push(FilesystemAction::Read {
+                    path: path.to_path_buf(),
+                });
+            }
+            if write {
+                actions.push(FilesystemAction::Write {
+                    path: path.to_path_buf(),
+                });
+            }
+            actions
+        };
+        let allowed =
+            |ctx: &HostContext<'_, SandboxData<S>>, path: &Path, actions: Vec<_>| match actions
+                .into_iter()
+                .find(|action| !ctx.data().check(action).is_allowed())
+            {
+                Some(denied) => {
+                    debug!(path = %path.display(), action = ?denied, "WASI path_open denied");
+                    false
+                }
+                None => true,
+            };
+
+        let mut actions = access(&full);
+        if create && !full.exists() {
+            actions.push(FilesystemAction::Create { path: full.clone() });
+        }
+        if !allowed(&ctx, &full, actions) {
+            return ERRNO_NOTCAPABLE;
+        }
+
+        let mut options = OpenOptions::new();
+        options
+            .read(read)
+            .write(write && !append)
+            .append(append)
+            .create(create)
+            .create_new(create && oflags & OFLAGS_EXCL != 0)
+            .truncate(truncate);
+        // A symlink swapped in for the checked name since the check is
+        // refused rather than followed
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.custom_flags(libc::O_NOFOLLOW);
+        }
+        let file = match options.open(&full) {
+            Ok(file) => file,
+            Err(e) => return io_errno(&e),
+        };
+
+        // A directory above the name can still have been swapped, so check
+        // where the descriptor actually points
+        if let Some(opened) = opened_path(&file) {
+            let opened = match resolve_under(dir, &opened) {
+                Ok(opened) => opened,
+                Err(e) => return io_errno(&e),
+            };
+            if opened != full && !allowed(&ctx, &opened, access(&opened)) {
+                return ERRNO_NOTCAPABLE;
+            }
+        }
+
+        let new_fd = {
+            let mut files = self.files.lock();
+            let new_fd = FIRST_PREOPEN_FD + self.preopens.len() as u32 + files.next;
+            files.next += 1;
+            files.files.insert(
+                new_fd,
+                OpenFile {
+                    file,
+                    rights,
+                    fdflags,
+                },
+            );
+            new_fd
+        };
+        debug!(path = %full.display(), fd = new_fd, "WASI path_open");
+
+        match ctx.write_memory(opened as u32 as usize, &new_fd.to_le_bytes()) {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Implementation of WASI `poll_oneoff`, returning a WASI errno.
+    ///
+    /// Descriptor subscriptions are ready at once, stdin hung up. Otherwise
+    /// the call sleeps until the earliest clock timeout and reports every
+    /// timeout due by then.
+    fn poll_oneoff<S>(
+        &self,
+        caller: Caller<'_, SandboxData<S>>,
+        subscriptions: i32,
+        events: i32,
+        count: i32,
+        nevents: i32,
+    ) -> i32 {
+        let mut ctx = HostContext::new(caller);
+        let count = count as u32 as usize;
+        if count == 0 {
+            return ERRNO_INVAL;
+        }
+        let Ok(table) = ctx.read_memory(
+            subscriptions as u32 as usize,
+            count.saturating_mul(SUBSCRIPTION_SIZE),
+        ) else {
+            return ERRNO_FAULT;
+        };
+
+        // Events as (userdata, errno, type, flags), and timeouts not yet due
+        let mut ready = Vec::new();
+        let mut timers = Vec::new();
+        for subscription in table.chunks_exact(SUBSCRIPTION_SIZE) {
+            let userdata = le_u64(subscription, 0);
+            let kind = subscription[8];
+            match kind {
+                EVENTTYPE_CLOCK => {
+                    let id = le_u64(subscription, 16) as u32 as i32;
+                    let timeout = le_u64(subscription, 24);
+                    let flags = le_u64(subscription, 40) as u16;
+                    match self.clock_wait(id, timeout, flags) {
+                        Ok(wait) => timers.push((userdata, wait)),
+                        Err(errno) => ready.push((userdata, errno, kind, 0)),
+                    }
+                }
+                EVENTTYPE_FD_READ | EVENTTYPE_FD_WRITE => {
+                    let fd = le_u64(subscription, 16) as u32;
+                    let open = fd <= 2 || self.files.lock().files.contains_key(&fd);
+                    let errno = if open { ERRNO_SUCCESS } else { ERRNO_BADF };
+                    let flags = if fd == 0 { EVENTRWFLAGS_HANGUP } else { 0 };
+                    ready.push((userdata, errno, kind, flags));
+                }
+                _ => return ERRNO_INVAL,
+            }
+        }
+
+        if ready.is_empty() {
+            let wait = timers
+                .iter()
+                .map(|&(_, wait)| wait)
+                .min()
+                .unwrap_or_default();
+            if !wait.is_zero() {
+                debug!(?wait, "WASI poll_oneoff sleeping");
+                std::thread::sleep(wait);
+            }
+            ready.extend(
+                timers
+                    .into_iter()
+                    .filter(|&(_, due)| due <= wait)
+                    .map(|(userdata, _)| (userdata, ERRNO_SUCCESS, EVENTTYPE_CLOCK, 0)),
+            );
+        }
+
+        let mut out = vec![0u8; ready.len() * EVENT_SIZE];
+        for (event, (userdata, errno, kind, flags)) in out.chunks_exact_mut(EVENT_SIZE).zip(&ready)
+        {
+            event[..8].copy_from_slice(&userdata.to_le_bytes());
+            event[8..10].copy_from_slice(&(*errno as u16).to_le_bytes());
+            event[10] = *kind;
+            event[24..26].copy_from_slice(&flags.to_le_bytes());
+        }
+        let written = ctx
+            .write_memory(events as u32 as usize, &out)
+            .and_then(|()| {
+                ctx.write_memory(nevents as u32 as usize, &(ready.len() as u32).to_le_bytes())
+            });
+        match written {
+            Ok(()) => ERRNO_SUCCESS,
+            Err(_) => ERRNO_FAULT,
+        }
+    }
+
+    /// Get how long a clock subscription waits, or the errno it fails with.
+    ///
+    /// Simulated clocks never wait.
+    fn clock_wait(&self, id: i32, timeout: u64, flags: u16) -> Result<Duration, i32> {
+        let Some(clock) = self.entropy.clock() else {
+            return Err(ERRNO_NOTCAPABLE);
+        };
+        let allowed = match id {
+            CLOCK_REALTIME => clock.allows_realtime(),
+            CLOCK_MONOTONIC => clock.allows_monotonic(),
+            _ => return Err(ERRNO_INVAL),
+        };
+        if !allowed {
+            return Err(ERRNO_NOTCAPABLE);
+        }
+        if !matches!(
+            clock.clock_type(),
+            ClockType::RealTime | ClockType::Monotonic
+        ) {
+            return Ok(Duration::ZERO);
+        }
+        if flags & SUBCLOCKFLAGS_ABSTIME == 0 {
+            return Ok(Duration::from_nanos(timeout));
+        }
+
+        let now = if id == CLOCK_REALTIME {
+            clock.get_realtime_nanos()
+        } else {
+            clock.get_monotonic_nanos()
+        };
+        Ok(Duration::from_nanos(
+            timeout.saturating_sub(now.unwrap_or(timeout)),
+        ))
+    }
+}
+
+/// Build clock and random sources from a capability set.
+fn entropy_from(capabilities: &CapabilityView) -> EntropySources {
+    let mut sources = EntropySources::new();
+
+    let policy = |id| capabilities.get(id).and_then(|cap| cap.to_policy());
+    if let Some(CapabilityPolicy::Clock { clock_type }) = policy(&standard_ids::CLOCK) {
+        sources = sources.with_clock(ClockCapability::new(clock_type));
+    }
+    if let Some(CapabilityPolicy::Random {
+        source,
+        max_bytes_per_call,
+    }) = policy(&standard_ids::RANDOM)
+    {
+        let mut random = RandomCapability::new(source);
+        if let Some(max_bytes) = max_bytes_per_call {
+            random = random.with_max_bytes_per_call(max_bytes);
+        }
+        sources = sources.with_random(random);
+    }
+
+    sources
+}
+
+/// Get the directories to preopen: every non-glob path permission of the
+/// filesystem capability.
+fn preopens_from(capabilities: &CapabilityView) -> Vec<PathBuf> {
+    let policy = capabilities
+        .get(&standard_ids::FILESYSTEM)
+        .and_then(|cap| cap.to_policy());
+
+    match policy {
+        Some(CapabilityPolicy::Filesystem { permissions, .. }) => permissions
+            .into_iter()
+            .filter(|permission| permission.pattern.is_none())
+            .map(|permission| permission.path)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Errno for the socket calls: no sockets are ever preopened.
+fn socket_errno<S>(caller: &Caller<'_, SandboxData<S>>) -> i32 {
+    if caller.data().capabilities.has(&standard_ids::NETWORK) {
+        ERRNO_BADF
+    } else {
+        ERRNO_NOTCAPABLE
+    }
+}
+
+/// Write the count and total NUL-terminated size of `strings`.
+fn write_sizes<T>(caller: Caller<'_, T>, strings: &[String], count: i32, size: i32) -> i32 {
+    let total: usize = strings.iter().map(|s| s.len() + 1).sum();
+
+    let mut ctx = HostContext::new(caller);
+    let written = ctx
+        .write_memory(count as u32 as usize, &(strings.len() as u32).to_le_bytes())
+        .and_then(|()| ctx.write_memory(size as u32 as usize, &(total as u32).to_le_bytes()));
+    match written {
+        Ok(()) => ERRNO_SUCCESS,
+        Err(_) => ERRNO_FAULT,
+    }
+}
+
+/// Write `strings` NUL-terminated to `buf` and pointers to them to `ptrs`.
+fn write_strings<T>(caller: Caller<'_, T>, strings: &[String], ptrs: i32, buf: i32) -> i32 {
+    let mut ctx = HostContext::new(caller);
+    let mut offset = buf as u32 as usize;
+
+    for (i, string) in strings.iter().enumerate() {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.push(0);
+        let written = ctx
+            .write_memory(ptrs as u32 as usize + i * 4, &(offset as u32).to_le_bytes())
+            .and_then(|()| ctx.write_memory(offset, &bytes));
+        if written.is_err() {
+            return ERRNO_FAULT;
+        }
+        offset += bytes.len();
+    }
+
+    ERRNO_SUCCESS
+}
+
+/// Resolve symlinks in `path`, opened under the preopened directory `dir`.
+///
+/// A file that does not exist yet is resolved through its parent, unless
+/// the name is a dangling symlink. Paths that stay inside the preopen are
+/// re-rooted under `dir` so they keep matching the permission it was
+/// granted by; paths that leave it are returned fully resolved.
+fn resolve_under(dir: &Path, path: &Path) -> std::io::Result<PathBuf> {
+    let resolved = match std::fs::canonicalize(path) {
+        Ok(resolved) => resolved,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                return Err(e);
+            };
+            if std::fs::symlink_metadata(path).is_ok() {
+                return Err(e);
+            }
+            std::fs::canonicalize(parent)?.join(name)
+        }
+        Err(e) => return Err(e),
+    };
+
+    let root = std::fs::canonicalize(dir)?;
+    Ok(match resolved.strip_prefix(&root) {
+        Ok(relative) => dir.join(relative),
+        Err(_) => resolved,
+    })
+}
+
+/// Get the path an open file currently has, where the host can tell.
+#[cfg(target_os = "linux")]
+fn opened_path(file: &File) -> Option<PathBuf> {
+    use std::os::fd::AsRawFd;
+
+    std::fs::read_link(format!("/proc/self/fd/{}", file.as_raw_fd())).ok()
+}
+
+/// Get the path an open file currently has, where the host can tell.
+#[cfg(not(target_os = "linux"))]
+fn opened_path(_file: &File) -> Option<PathBuf> {
+    None
+}
+
+/// Encode host metadata as a WASI `filestat`.
+fn filestat(metadata: &Metadata) -> [u8; 64] {
+    let nanos = |time: std::io::Result<SystemTime>| {
+        time.ok()
+            .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos() as u64)
+    };
+    #[cfg(unix)]
+    let (dev, ino, nlink) = {
+        use std::os::unix::fs::MetadataExt;
+        (metadata.dev(), metadata.ino(), metadata.nlink())
+    };
+    #[cfg(not(unix))]
+    let (dev, ino, nlink) = (0, 0, 1);
+    let filetype = if metadata.is_dir() {
+        FILETYPE_DIRECTORY
+    } else {
+        FILETYPE_REGULAR_FILE
+    };
+
+    // Device, inode, type, links, size and access, modification and
+    // status change times, each in an 8-byte slot
+    let fields = [
+        dev,
+        ino,
+        u64::from(filetype),
+        nlink,
+        metadata.len(),
+        nanos(metadata.accessed()),
+        nanos(metadata.modified()),
+        nanos(metadata.modified()),
+    ];
+    let mut filestat = [0u8; 64];
+    for (slot, field) in filestat.chunks_exact_mut(8).zip(fields) {
+        slot.copy_from_slice(&field.to_le_bytes());
+    }
+    filestat
+}
+
+/// Read a little-endian `u64` at `offset` of `bytes`.
+fn le_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(value)
+}
+
+/// Translate a host I/O error into a WASI errno.
+fn io_errno(error: &std::io::Error) -> i32 {
+    #[cfg(unix)]
+    if error.raw_os_error() == Some(libc::ELOOP) {
+        return ERRNO_LOOP;
+    }
+    match error.kind() {
+        std::io::ErrorKind::NotFound => ERRNO_NOENT,
+        std::io::ErrorKind::PermissionDenied => ERRNO_ACCES,
+        std::io::ErrorKind::AlreadyExists => ERRNO_EXIST,
+        std::io::ErrorKind::InvalidInput => ERRNO_INVAL,
+        _ => ERRNO_IO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aegis_capability::CapabilitySet;
+    use aegis_capability::builtin::{FilesystemCapability, LoggingCapability};
+    use aegis_core::{AegisEngine, IntoShared, ModuleLoader, SandboxBuilder};
+
+    /// `tests/fixtures/wasi_cat.rs` built for `wasm32-wasip1`.
+    const WASI_CAT: &[u8] = include_bytes!("../tests/fixtures/wasi_cat.wasm");
+
+    /// The calls a Rust `main` that prints a greeting and then the contents
+    /// of `data.txt` from its first preopen makes, followed by an attempt to
+    /// create `out.txt`.
+    const CAT_WAT: &str = r#"
+        (module
+            (import "wasi_snapshot_preview1" "fd_write"
+                (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_read"
+                (func $fd_read (param i32 i32 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_close"
+                (func $fd_close (param i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_prestat_get"
+                (func $fd_prestat_get (param i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "path_open"
+                (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "proc_exit"
+                (func $proc_exit (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 64) "hello\n")
+            (data (i32.const 80) "data.txt")
+            (data (i32.const 96) "out.txt")
+            (data (i32.const 112) "link.txt")
+            (func $print (param $ptr i32) (param $len i32)
+                (i32.store (i32.const 0) (local.get $ptr))
+                (i32.store (i32.const 4) (local.get $len))
+                (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8))))
+            ;; Print the greeting and the file, returning the last errno
+            (func (export "cat") (result i32)
+                (local $errno i32)
+                (call $print (i32.const 64) (i32.const 6))
+                (local.set $errno (call $fd_prestat_get (i32.const 3) (i32.const 16)))
+                (if (local.get $errno) (then (return (local.get $errno))))
+                ;; open data.txt for reading; the new descriptor goes to 24
+                (local.set $errno
+                    (call $path_open (i32.const 3) (i32.const 0) (i32.const 80) (i32.const 8)
+                        (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 24)))
+                (if (local.get $errno) (then (return (local.get $errno))))
+                ;; read up to 64 bytes to 256
+                (i32.store (i32.const 0) (i32.const 256))
+                (i32.store (i32.const 4) (i32.const 64))
+                (local.set $errno
+                    (call $fd_read (i32.load (i32.const 24)) (i32.const 0) (i32.const 1) (i32.const 28)))
+                (if (local.get $errno) (then (return (local.get $errno))))
+                (call $print (i32.const 256) (i32.load (i32.const 28)))
+                (call $fd_close (i32.load (i32.const 24))))
+            ;; Try to create out.txt for writing
+            (func (export "create") (result i32)
+                (call $path_open (i32.const 3) (i32.const 0) (i32.const 96) (i32.const 7)
+                    (i32.const 1) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 24)))
+            ;; Open link.txt for reading
+            (func (export "open_link") (result i32)
+                (call $path_open (i32.const 3) (i32.const 0) (i32.const 112) (i32.const 8)
+                    (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 24)))
+            ;; Read data.txt into a buffer far larger than memory
+            (func (export "read_huge") (result i32)
+                (drop
+                    (call $path_open (i32.const 3) (i32.const 0) (i32.const 80) (i32.const 8)
+                        (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 24)))
+                (i32.store (i32.const 0) (i32.const 256))
+                (i32.store (i32.const 4) (i32.const -1))
+                (call $fd_read (i32.load (i32.const 24)) (i32.const 0) (i32.const 1) (i32.const 28)))
+            (func (export "exit")
+                (call $proc_exit (i32.const 3)))
+        )
+    "#;
+
+    #[test]
+    fn test_wasi_stdio_and_preopens() {
+        let dir = std::env::temp_dir().join(format!("aegis-wasi-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("data.txt"), "from the host\n").unwrap();
+
+        let engine = AegisEngine::default_engine().unwrap().into_shared();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(CAT_WAT)
+            .unwrap();
+
+        let capabilities = CapabilitySet::new();
+        capabilities
+            .grant(FilesystemCapability::read_only(&[&dir]))
+            .unwrap();
+        capabilities.grant(LoggingCapability::allow_all()).unwrap();
+
+        let wasi = WasiCapability::new();
+        let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+            .with_capabilities(Arc::new(capabilities))
+            .with_registry(Arc::new(wasi.clone()))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        assert_eq!(sandbox.call::<(), i32>("cat", ()).unwrap(), ERRNO_SUCCESS);
+        assert_eq!(wasi.output().stdout_lossy(), "hello\nfrom the host\n");
+
+        // The filesystem capability only allows reading
+        assert_eq!(
+            sandbox.call::<(), i32>("create", ()).unwrap(),
+            ERRNO_NOTCAPABLE
+        );
+        assert!(!dir.join("out.txt").exists());
+
+        // The buffer is checked against guest memory before anything is
+        // allocated for it
+        assert_eq!(
+            sandbox.call::<(), i32>("read_huge", ()).unwrap(),
+            ERRNO_FAULT
+        );
+
+        let err = sandbox.call::<(), ()>("exit", ()).unwrap_err();
+        assert!(matches!(
+            err.host_error::<HostError>(),
+            Some(HostError::Exit(3))
+        ));
+
+        // Without capabilities nothing is preopened and stdout is refused
+        let bare = WasiCapability::new();
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_registry(Arc::new(bare.clone()))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+        assert_eq!(sandbox.call::<(), i32>("cat", ()).unwrap(), ERRNO_BADF);
+        assert!(bare.output().stdout().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_open_symlink_escape_denied() {
+        let root = std::env::temp_dir().join(format!("aegis-wasi-link-{}", std::process::id()));
+        let dir = root.join("granted");
+        let outside = root.join("secret.txt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&outside, "secret\n").unwrap();
+        std::fs::write(dir.join("data.txt"), "inside\n").unwrap();
+        std::os::unix::fs::symlink(&outside, dir.join("link.txt")).unwrap();
+
+        let engine = AegisEngine::default_engine().unwrap().into_shared();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(CAT_WAT)
+            .unwrap();
+
+        let capabilities = CapabilitySet::new();
+        capabilities
+            .grant(FilesystemCapability::read_only(&[&dir]))
+            .unwrap();
+        let mut sandbox = SandboxBuilder::<()>::new(engine)
+            .with_capabilities(Arc::new(capabilities))
+            .with_registry(Arc::new(WasiCapability::new()))
+            .build()
+            .unwrap();
+        sandbox.load_module(&module).unwrap();
+
+        assert_eq!(
+            sandbox.call::<(), i32>("open_link", ()).unwrap(),
+            ERRNO_NOTCAPABLE
+        );
+
+        // A link that stays inside the preopen is still followed
+        std::fs::remove_file(dir.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.join("data.txt"), dir.join("link.txt")).unwrap();
+        assert_eq!(
+            sandbox.call::<(), i32>("open_link", ()).unwrap(),
+            ERRNO_SUCCESS
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_rust_wasip1_program() {
+        let dir = std::env::temp_dir().join(format!("aegis-wasi-rust-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("data.txt"), "from the host\n").unwrap();
+        let dir_arg = dir.to_string_lossy().into_owned();
+
+        let engine = AegisEngine::default_engine().unwrap().into_shared();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_bytes(WASI_CAT)
+            .unwrap();
+
+        let run = |filesystem: FilesystemCapability| {
+            let capabilities = CapabilitySet::new();
+            capabilities.grant(filesystem).unwrap();
+            capabilities.grant(LoggingCapability::allow_all()).unwrap();
+            capabilities
+                .grant(ClockCapability::monotonic_only())
+                .unwrap();
+
+            let wasi = WasiCapability::new()
+                .with_args(["wasi_cat", dir_arg.as_str()])
+                .with_env("GREETER", "aegis");
+            let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(&engine))
+                .with_capabilities(Arc::new(capabilities))
+                .with_registry(Arc::new(wasi.clone()))
+                .build()
+                .unwrap();
+            sandbox.load_module(&module).unwrap();
+            (sandbox.call_void("_start"), wasi)
+        };
+
+        // Reading is allowed but creating out.txt is not
+        let (result, wasi) = run(FilesystemCapability::read_only(&[&dir]));
+        assert!(matches!(
+            result.unwrap_err().host_error::<HostError>(),
+            Some(HostError::Exit(3))
+        ));
+        assert_eq!(
+            wasi.output().stdout_lossy(),
+            "hello from aegis\n14 bytes: from the host\nfrom byte 5: the host\n"
+        );
+        assert_eq!(
+            wasi.output().stderr_lossy(),
+            format!("create failed: errno {ERRNO_NOTCAPABLE}\n")
+        );
+        assert!(!dir.join("out.txt").exists());
+
+        let (result, wasi) = run(FilesystemCapability::read_write(&[&dir]));
+        result.unwrap();
+        assert!(wasi.output().stderr().is_empty());
+        assert!(dir.join("out.txt").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
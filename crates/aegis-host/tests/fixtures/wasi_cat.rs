@@ -0,0 +1,38 @@
+//! Guest for the WASI tests in `src/wasi.rs`.
+//!
+//! Prints a greeting and `data.txt` from the directory given as its first
+//! argument, sleeps briefly, then tries to create `out.txt` there, exiting
+//! with status 3 if that fails. Rebuild `wasi_cat.wasm` with:
+//!
+//! ```text
+//! rustc --edition 2021 --target wasm32-wasip1 -C opt-level=s -C strip=symbols \
+//!     wasi_cat.rs -o wasi_cat.wasm
+//! ```
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::time::{Duration, Instant};
+
+fn main() {
+    let dir = std::env::args().nth(1).expect("missing directory");
+    println!("hello from {}", std::env::var("GREETER").unwrap_or_default());
+
+    let mut file = File::open(format!("{dir}/data.txt")).expect("open data.txt");
+    let len = file.metadata().expect("stat data.txt").len();
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).expect("read data.txt");
+    file.seek(SeekFrom::Start(5)).expect("seek data.txt");
+    let mut rest = String::new();
+    file.read_to_string(&mut rest).expect("reread data.txt");
+    print!("{len} bytes: {contents}from byte 5: {rest}");
+
+    let start = Instant::now();
+    std::thread::sleep(Duration::from_millis(1));
+    std::thread::yield_now();
+    assert!(start.elapsed() >= Duration::from_millis(1));
+
+    if let Err(e) = File::create(format!("{dir}/out.txt")) {
+        eprintln!("create failed: errno {}", e.raw_os_error().unwrap_or(0));
+        std::process::exit(3);
+    }
+}
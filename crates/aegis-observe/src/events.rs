@@ -1,15 +1,25 @@
 //! Observable events during sandbox execution.
 
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+use crate::metrics::duration_serde;
 use crate::report::ExecutionOutcome;
 use aegis_capability::CapabilityId;
 
 /// Events that can be observed during sandbox execution.
-#[derive(Debug, Clone)]
+///
+/// Events serialize as JSON objects tagged with their `type`, which matches
+/// [`SandboxEvent::event_type`]. Durations are serialized as nanoseconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum SandboxEvent {
     /// Module was loaded.
     ModuleLoaded {
@@ -17,6 +27,9 @@ pub enum SandboxEvent {
         name: Option<String>,
         /// Number of exports.
         export_count: usize,
+        /// Hex SHA-256 hash identifying the module, if known.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hash: Option<String>,
     },
     /// Execution started.
     ExecutionStarted {
@@ -30,6 +43,7 @@ pub enum SandboxEvent {
         /// Function name.
         name: String,
         /// Call duration.
+        #[serde(with = "duration_serde")]
         duration: Duration,
     },
     /// Capability was checked.
@@ -62,6 +76,7 @@ pub enum SandboxEvent {
         /// Execution outcome.
         outcome: ExecutionOutcome,
         /// Total duration.
+        #[serde(with = "duration_serde")]
         duration: Duration,
     },
     /// An error occurred.
@@ -100,6 +115,15 @@ pub trait EventSubscriber: Send + Sync {
     /// Called when an event occurs.
     fn on_event(&self, event: &SandboxEvent);
 
+    /// Called when an event occurs in the sandbox identified by `sandbox`.
+    ///
+    /// Events dispatched with [`EventDispatcher::emit_for`] arrive here;
+    /// by default they are passed on to [`on_event`](Self::on_event).
+    fn on_sandbox_event(&self, sandbox: Uuid, event: &SandboxEvent) {
+        let _ = sandbox;
+        self.on_event(event);
+    }
+
     /// Filter for event types this subscriber is interested in.
     /// Returns true to receive all events.
     fn event_filter(&self) -> Option<Vec<&'static str>> {
@@ -137,11 +161,16 @@ impl Default for LoggingSubscriber {
 impl EventSubscriber for LoggingSubscriber {
     fn on_event(&self, event: &SandboxEvent) {
         match event {
-            SandboxEvent::ModuleLoaded { name, export_count } => {
+            SandboxEvent::ModuleLoaded {
+                name,
+                export_count,
+                hash,
+            } => {
                 tracing::debug!(
                     event = "module_loaded",
                     name = ?name,
                     exports = export_count,
+                    hash = ?hash,
                     "Module loaded"
                 );
             }
@@ -235,6 +264,141 @@ impl EventSubscriber for LoggingSubscriber {
     }
 }
 
+/// An open execution span tracked by [`TracingSubscriber`].
+struct OpenSpan {
+    function: String,
+    span: tracing::Span,
+}
+
+/// A subscriber that turns the execution lifecycle into `tracing` spans.
+///
+/// `ExecutionStarted` opens an `aegis.execution` span whose `otel.name` is
+/// the function name, nested under the sandbox's innermost span that is
+/// still open or, if there is none, under the caller's current span.
+/// Host function calls and errors are recorded as events inside the
+/// sandbox's innermost span, and `ExecutionCompleted` closes the sandbox's
+/// most recent span for its function with the outcome and duration as
+/// attributes.
+///
+/// Open spans are tracked per sandbox, so sandboxes sharing a dispatcher
+/// do not nest under or close each other's spans. Events emitted without a
+/// sandbox, through [`EventDispatcher::emit`], share one stack.
+///
+/// Because spans are created through the `tracing` API, an OpenTelemetry
+/// layer installed on the global subscriber exports them without further
+/// setup.
+#[derive(Default)]
+pub struct TracingSubscriber {
+    /// Open spans by sandbox, innermost last.
+    open: Mutex<HashMap<Option<Uuid>, Vec<OpenSpan>>>,
+}
+
+impl TracingSubscriber {
+    /// Create a new tracing subscriber.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of spans that are still open.
+    pub fn open_spans(&self) -> usize {
+        self.open.lock().values().map(Vec::len).sum()
+    }
+
+    /// Handle an event of `sandbox`, or of no particular sandbox.
+    fn handle(&self, sandbox: Option<Uuid>, event: &SandboxEvent) {
+        match event {
+            SandboxEvent::ExecutionStarted { function } => {
+                let mut open = self.open.lock();
+                let open = open.entry(sandbox).or_default();
+                let parent = open
+                    .last()
+                    .and_then(|o| o.span.id())
+                    .or_else(|| tracing::Span::current().id());
+                let span = tracing::info_span!(
+                    parent: parent,
+                    "aegis.execution",
+                    otel.name = %function,
+                    function = %function,
+                    outcome = tracing::field::Empty,
+                    duration_us = tracing::field::Empty,
+                    otel.status_code = tracing::field::Empty,
+                );
+                open.push(OpenSpan {
+                    function: function.clone(),
+                    span,
+                });
+            }
+            SandboxEvent::HostFunctionCalled {
+                module,
+                name,
+                duration,
+            } => {
+                let open = self.open.lock();
+                if let Some(current) = open.get(&sandbox).and_then(|o| o.last()) {
+                    tracing::info!(
+                        parent: &current.span,
+                        module = %module,
+                        name = %name,
+                        duration_us = duration.as_micros() as u64,
+                        "host_function_called"
+                    );
+                }
+            }
+            SandboxEvent::Error { message } => {
+                let open = self.open.lock();
+                if let Some(current) = open.get(&sandbox).and_then(|o| o.last()) {
+                    tracing::error!(parent: &current.span, message = %message, "error");
+                }
+            }
+            SandboxEvent::ExecutionCompleted {
+                function,
+                outcome,
+                duration,
+            } => {
+                let closed = {
+                    let mut open = self.open.lock();
+                    let stack = open.get_mut(&sandbox);
+                    let closed = stack.and_then(|stack| {
+                        stack
+                            .iter()
+                            .rposition(|o| o.function == *function)
+                            .map(|index| stack.remove(index))
+                    });
+                    if open.get(&sandbox).is_some_and(Vec::is_empty) {
+                        open.remove(&sandbox);
+                    }
+                    closed
+                };
+
+                let Some(closed) = closed else {
+                    tracing::debug!(function = %function, "No open span for completed execution");
+                    return;
+                };
+
+                closed.span.record("outcome", outcome.kind());
+                closed
+                    .span
+                    .record("duration_us", duration.as_micros() as u64);
+                closed.span.record(
+                    "otel.status_code",
+                    if outcome.is_success() { "OK" } else { "ERROR" },
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+impl EventSubscriber for TracingSubscriber {
+    fn on_event(&self, event: &SandboxEvent) {
+        self.handle(None, event);
+    }
+
+    fn on_sandbox_event(&self, sandbox: Uuid, event: &SandboxEvent) {
+        self.handle(Some(sandbox), event);
+    }
+}
+
 /// A subscriber that collects events for later analysis.
 pub struct CollectingSubscriber {
     events: RwLock<Vec<(Instant, SandboxEvent)>>,
@@ -280,10 +444,211 @@ impl EventSubscriber for CollectingSubscriber {
     }
 }
 
+/// A subscriber that writes each event as a line of JSON.
+///
+/// Lines are flushed as events arrive, so the output can be consumed
+/// incrementally while execution is still running.
+pub struct JsonLinesSubscriber<W: Write + Send> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLinesSubscriber<W> {
+    /// Create a subscriber writing to the given writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Consume the subscriber and return the writer.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+impl<W: Write + Send> EventSubscriber for JsonLinesSubscriber<W> {
+    fn on_event(&self, event: &SandboxEvent) {
+        let mut writer = self.writer.lock();
+        let result = serde_json::to_writer(&mut *writer, event)
+            .map_err(std::io::Error::from)
+            .and_then(|()| writer.write_all(b"\n"))
+            .and_then(|()| writer.flush());
+
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "Failed to write event");
+        }
+    }
+}
+
+/// A single capability check recorded by [`AuditingSubscriber`].
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// Sequence number, starting at 0 and never reused.
+    ///
+    /// Gaps at the start of the buffer show how many entries were overwritten.
+    pub sequence: u64,
+    /// When the check was recorded.
+    pub timestamp: Instant,
+    /// Capability ID.
+    pub capability: CapabilityId,
+    /// Action being checked.
+    pub action: String,
+    /// Whether it was permitted.
+    pub permitted: bool,
+}
+
+/// Ring buffer state for [`AuditingSubscriber`].
+struct AuditLog {
+    entries: VecDeque<AuditEntry>,
+    next_sequence: u64,
+}
+
+/// A subscriber that records capability checks for security review.
+///
+/// Only `CapabilityChecked` events are kept. When the buffer is full, the
+/// oldest entry is overwritten so the most recent checks are always
+/// available.
+pub struct AuditingSubscriber {
+    log: Mutex<AuditLog>,
+    capacity: usize,
+    created: Instant,
+}
+
+impl AuditingSubscriber {
+    /// Create a new auditing subscriber holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            log: Mutex::new(AuditLog {
+                entries: VecDeque::with_capacity(capacity),
+                next_sequence: 0,
+            }),
+            capacity,
+            created: Instant::now(),
+        }
+    }
+
+    /// Get all recorded entries, oldest first.
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.log.lock().entries.iter().cloned().collect()
+    }
+
+    /// Get recorded entries for denied checks, oldest first.
+    pub fn denied_events(&self) -> Vec<AuditEntry> {
+        self.log
+            .lock()
+            .entries
+            .iter()
+            .filter(|e| !e.permitted)
+            .cloned()
+            .collect()
+    }
+
+    /// Get the total number of checks seen, including overwritten ones.
+    pub fn total_recorded(&self) -> u64 {
+        self.log.lock().next_sequence
+    }
+
+    /// Render the entries as JSON Lines, oldest first.
+    ///
+    /// Timestamps are reported as microseconds since the subscriber was created.
+    pub fn to_jsonl(&self) -> String {
+        let log = self.log.lock();
+        let mut output = String::new();
+
+        for entry in &log.entries {
+            let line = serde_json::json!({
+                "sequence": entry.sequence,
+                "elapsed_us": entry.timestamp.duration_since(self.created).as_micros() as u64,
+                "capability": entry.capability.as_str(),
+                "action": entry.action,
+                "permitted": entry.permitted,
+            });
+            output.push_str(&line.to_string());
+            output.push('\n');
+        }
+
+        output
+    }
+
+    /// Get the buffer capacity.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Get the number of entries currently held.
+    pub fn len(&self) -> usize {
+        self.log.lock().entries.len()
+    }
+
+    /// Check if empty.
+    pub fn is_empty(&self) -> bool {
+        self.log.lock().entries.is_empty()
+    }
+}
+
+impl EventSubscriber for AuditingSubscriber {
+    fn on_event(&self, event: &SandboxEvent) {
+        let SandboxEvent::CapabilityChecked {
+            id,
+            action,
+            permitted,
+        } = event
+        else {
+            return;
+        };
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut log = self.log.lock();
+        if log.entries.len() == self.capacity {
+            log.entries.pop_front();
+        }
+
+        let sequence = log.next_sequence;
+        log.next_sequence += 1;
+        log.entries.push_back(AuditEntry {
+            sequence,
+            timestamp: Instant::now(),
+            capability: id.clone(),
+            action: action.clone(),
+            permitted: *permitted,
+        });
+    }
+
+    fn event_filter(&self) -> Option<Vec<&'static str>> {
+        Some(vec!["capability_checked"])
+    }
+}
+
+impl std::fmt::Debug for AuditingSubscriber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuditingSubscriber")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Identifies a subscription made with [`EventDispatcher::subscribe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+impl std::fmt::Display for SubscriptionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "subscription-{}", self.0)
+    }
+}
+
 /// Event dispatcher that manages subscribers.
 #[derive(Default)]
 pub struct EventDispatcher {
-    subscribers: RwLock<Vec<Arc<dyn EventSubscriber>>>,
+    subscribers: RwLock<Vec<(SubscriptionId, Arc<dyn EventSubscriber>)>>,
+    next_id: AtomicU64,
+    /// Number of times the subscriber list was read-locked.
+    #[cfg(test)]
+    read_locks: AtomicU64,
 }
 
 impl EventDispatcher {
@@ -293,8 +658,22 @@ impl EventDispatcher {
     }
 
     /// Add a subscriber.
-    pub fn subscribe(&self, subscriber: Arc<dyn EventSubscriber>) {
-        self.subscribers.write().push(subscriber);
+    ///
+    /// Returns an ID that can be passed to [`unsubscribe`](Self::unsubscribe).
+    pub fn subscribe(&self, subscriber: Arc<dyn EventSubscriber>) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.subscribers.write().push((id, subscriber));
+        id
+    }
+
+    /// Remove a subscriber.
+    ///
+    /// Returns `false` if the subscription was not found.
+    pub fn unsubscribe(&self, id: SubscriptionId) -> bool {
+        let mut subscribers = self.subscribers.write();
+        let before = subscribers.len();
+        subscribers.retain(|(sub_id, _)| *sub_id != id);
+        subscribers.len() != before
     }
 
     /// Remove all subscribers.
@@ -307,17 +686,62 @@ impl EventDispatcher {
         self.subscribers.read().len()
     }
 
+    /// Read-lock the subscriber list for dispatch.
+    fn read_subscribers(
+        &self,
+    ) -> parking_lot::RwLockReadGuard<'_, Vec<(SubscriptionId, Arc<dyn EventSubscriber>)>> {
+        #[cfg(test)]
+        self.read_locks.fetch_add(1, Ordering::Relaxed);
+        self.subscribers.read()
+    }
+
+    /// Emit a batch of events to all subscribers.
+    ///
+    /// The subscriber list is locked once for the whole batch, and each
+    /// subscriber's filter is evaluated once. Events are delivered in order.
+    pub fn emit_batch(&self, events: impl IntoIterator<Item = SandboxEvent>) {
+        self.dispatch(None, events);
+    }
+
     /// Emit an event to all subscribers.
     pub fn emit(&self, event: SandboxEvent) {
-        let subscribers = self.subscribers.read();
-        for subscriber in subscribers.iter() {
-            // Check filter
-            if let Some(filter) = subscriber.event_filter() {
-                if !filter.contains(&event.event_type()) {
+        self.dispatch(None, [event]);
+    }
+
+    /// Emit an event that occurred in the sandbox identified by `sandbox`.
+    ///
+    /// Subscribers receive it through [`EventSubscriber::on_sandbox_event`].
+    pub fn emit_for(&self, sandbox: Uuid, event: SandboxEvent) {
+        self.dispatch(Some(sandbox), [event]);
+    }
+
+    /// Emit a batch of events that occurred in the sandbox identified by
+    /// `sandbox`, as [`emit_batch`](Self::emit_batch) does.
+    pub fn emit_batch_for(&self, sandbox: Uuid, events: impl IntoIterator<Item = SandboxEvent>) {
+        self.dispatch(Some(sandbox), events);
+    }
+
+    /// Deliver events to each subscriber whose filter accepts them.
+    fn dispatch(&self, sandbox: Option<Uuid>, events: impl IntoIterator<Item = SandboxEvent>) {
+        let subscribers = self.read_subscribers();
+        let filters: Vec<_> = subscribers
+            .iter()
+            .map(|(_, subscriber)| subscriber.event_filter())
+            .collect();
+
+        for event in events {
+            for ((_, subscriber), filter) in subscribers.iter().zip(&filters) {
+                if filter
+                    .as_ref()
+                    .is_some_and(|f| !f.contains(&event.event_type()))
+                {
                     continue;
                 }
+                match sandbox {
+                    Some(sandbox) => subscriber.on_sandbox_event(sandbox, &event),
+                    None => subscriber.on_event(&event),
+                }
             }
-            subscriber.on_event(&event);
         }
     }
 }
@@ -339,6 +763,7 @@ mod tests {
         let event = SandboxEvent::ModuleLoaded {
             name: Some("test".to_string()),
             export_count: 5,
+            hash: None,
         };
         assert_eq!(event.event_type(), "module_loaded");
     }
@@ -406,4 +831,384 @@ mod tests {
         assert_eq!(collector1.len(), 1);
         assert_eq!(collector2.len(), 1);
     }
+
+    #[test]
+    fn test_auditing_subscriber_overwrites_oldest() {
+        let dispatcher = EventDispatcher::new();
+        let auditor = Arc::new(AuditingSubscriber::new(3));
+        dispatcher.subscribe(Arc::clone(&auditor) as Arc<dyn EventSubscriber>);
+
+        for i in 0..5 {
+            dispatcher.emit(SandboxEvent::CapabilityChecked {
+                id: CapabilityId::new("filesystem"),
+                action: format!("fs:read:{}", i),
+                permitted: i % 2 == 0,
+            });
+        }
+
+        // Non-capability events are ignored
+        dispatcher.emit(SandboxEvent::ExecutionStarted {
+            function: "main".to_string(),
+        });
+
+        assert_eq!(auditor.len(), 3);
+        assert_eq!(auditor.total_recorded(), 5);
+
+        let entries = auditor.entries();
+        let actions: Vec<_> = entries.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["fs:read:2", "fs:read:3", "fs:read:4"]);
+        assert_eq!(entries[0].sequence, 2);
+
+        let denied = auditor.denied_events();
+        assert_eq!(denied.len(), 1);
+        assert_eq!(denied[0].action, "fs:read:3");
+    }
+
+    #[test]
+    fn test_auditing_subscriber_jsonl() {
+        let auditor = AuditingSubscriber::new(10);
+        auditor.on_event(&SandboxEvent::CapabilityChecked {
+            id: CapabilityId::new("network"),
+            action: "net:connect".to_string(),
+            permitted: false,
+        });
+        auditor.on_event(&SandboxEvent::CapabilityChecked {
+            id: CapabilityId::new("logging"),
+            action: "log:write".to_string(),
+            permitted: true,
+        });
+
+        let jsonl = auditor.to_jsonl();
+        let lines: Vec<serde_json::Value> = jsonl
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["capability"], "network");
+        assert_eq!(lines[0]["permitted"], false);
+        assert_eq!(lines[1]["sequence"], 1);
+    }
+
+    #[test]
+    fn test_json_lines_subscriber() {
+        let subscriber = JsonLinesSubscriber::new(Vec::new());
+        subscriber.on_event(&SandboxEvent::ExecutionStarted {
+            function: "main".to_string(),
+        });
+        subscriber.on_event(&SandboxEvent::Custom {
+            name: "progress".to_string(),
+            data: serde_json::json!({ "step": 1 }),
+        });
+
+        let output = String::from_utf8(subscriber.into_inner()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["type"], "execution_started");
+        assert_eq!(lines[0]["function"], "main");
+        assert_eq!(lines[1]["type"], "custom");
+        assert_eq!(lines[1]["data"]["step"], 1);
+    }
+
+    fn sample_events() -> Vec<SandboxEvent> {
+        vec![
+            SandboxEvent::ModuleLoaded {
+                name: Some("test".to_string()),
+                export_count: 3,
+                hash: None,
+            },
+            SandboxEvent::ExecutionStarted {
+                function: "main".to_string(),
+            },
+            SandboxEvent::HostFunctionCalled {
+                module: "env".to_string(),
+                name: "log".to_string(),
+                duration: Duration::from_micros(1500),
+            },
+            SandboxEvent::CapabilityChecked {
+                id: CapabilityId::new("logging"),
+                action: "log".to_string(),
+                permitted: false,
+            },
+            SandboxEvent::MemoryGrew {
+                from_bytes: 65536,
+                to_bytes: 131072,
+            },
+            SandboxEvent::FuelConsumed {
+                amount: 1000,
+                remaining: 9000,
+            },
+            SandboxEvent::ExecutionCompleted {
+                function: "main".to_string(),
+                outcome: ExecutionOutcome::Success {
+                    return_value: Some(serde_json::json!(42)),
+                },
+                duration: Duration::from_millis(12),
+            },
+            SandboxEvent::Error {
+                message: "boom".to_string(),
+            },
+            SandboxEvent::Custom {
+                name: "progress".to_string(),
+                data: serde_json::json!({ "step": 1, "items": ["a", "b"] }),
+            },
+        ]
+    }
+
+    /// Index of the variant in `sample_events`.
+    ///
+    /// The match is exhaustive so adding a variant fails to compile until
+    /// it has a sample.
+    fn variant_index(event: &SandboxEvent) -> usize {
+        match event {
+            SandboxEvent::ModuleLoaded { .. } => 0,
+            SandboxEvent::ExecutionStarted { .. } => 1,
+            SandboxEvent::HostFunctionCalled { .. } => 2,
+            SandboxEvent::CapabilityChecked { .. } => 3,
+            SandboxEvent::MemoryGrew { .. } => 4,
+            SandboxEvent::FuelConsumed { .. } => 5,
+            SandboxEvent::ExecutionCompleted { .. } => 6,
+            SandboxEvent::Error { .. } => 7,
+            SandboxEvent::Custom { .. } => 8,
+        }
+    }
+
+    #[test]
+    fn test_sandbox_event_round_trip() {
+        let events = sample_events();
+
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(variant_index(event), i);
+
+            let json = serde_json::to_value(event).unwrap();
+            assert_eq!(json["type"], event.event_type());
+
+            let decoded: SandboxEvent = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(decoded.event_type(), event.event_type());
+            assert_eq!(serde_json::to_value(&decoded).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_sandbox_event_duration_as_nanos() {
+        let event = SandboxEvent::HostFunctionCalled {
+            module: "env".to_string(),
+            name: "log".to_string(),
+            duration: Duration::from_micros(1500),
+        };
+
+        let line = serde_json::to_string(&event).unwrap();
+        let decoded: SandboxEvent = serde_json::from_str(&line).unwrap();
+
+        assert!(line.contains("\"duration\":1500000"));
+        match decoded {
+            SandboxEvent::HostFunctionCalled { duration, .. } => {
+                assert_eq!(duration, Duration::from_micros(1500));
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+
+    mod tracing_subscriber_spans {
+        use super::*;
+        use std::sync::Arc;
+        use tracing::span::{Attributes, Id};
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::registry::LookupSpan;
+
+        /// Recorded span: function name and parent function name.
+        type SpanRecord = (String, Option<String>);
+
+        #[derive(Clone, Default)]
+        struct RecordingLayer {
+            opened: Arc<Mutex<Vec<SpanRecord>>>,
+            closed: Arc<Mutex<Vec<String>>>,
+        }
+
+        struct FunctionName(String);
+
+        struct FunctionVisitor(Option<String>);
+
+        impl tracing::field::Visit for FunctionVisitor {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "function" {
+                    self.0 = Some(format!("{value:?}"));
+                }
+            }
+        }
+
+        impl<S> tracing_subscriber::Layer<S> for RecordingLayer
+        where
+            S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+        {
+            fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+                let mut visitor = FunctionVisitor(None);
+                attrs.record(&mut visitor);
+                let function = visitor.0.unwrap_or_default();
+
+                let span = ctx.span(id).unwrap();
+                let parent = span
+                    .parent()
+                    .and_then(|p| p.extensions().get::<FunctionName>().map(|f| f.0.clone()));
+                span.extensions_mut().insert(FunctionName(function.clone()));
+                self.opened.lock().push((function, parent));
+            }
+
+            fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+                let span = ctx.span(&id).unwrap();
+                if let Some(name) = span.extensions().get::<FunctionName>() {
+                    self.closed.lock().push(name.0.clone());
+                }
+            }
+        }
+
+        fn started(function: &str) -> SandboxEvent {
+            SandboxEvent::ExecutionStarted {
+                function: function.to_string(),
+            }
+        }
+
+        fn completed(function: &str) -> SandboxEvent {
+            SandboxEvent::ExecutionCompleted {
+                function: function.to_string(),
+                outcome: ExecutionOutcome::Success { return_value: None },
+                duration: Duration::from_millis(1),
+            }
+        }
+
+        #[test]
+        fn test_spans_nest_across_emits() {
+            let layer = RecordingLayer::default();
+            let registry = tracing_subscriber::registry().with(layer.clone());
+
+            tracing::subscriber::with_default(registry, || {
+                let subscriber = Arc::new(TracingSubscriber::new());
+                let dispatcher = EventDispatcher::new();
+                dispatcher.subscribe(Arc::clone(&subscriber) as Arc<dyn EventSubscriber>);
+
+                dispatcher.emit(started("outer"));
+                dispatcher.emit(started("inner"));
+                assert_eq!(subscriber.open_spans(), 2);
+
+                dispatcher.emit(completed("inner"));
+                dispatcher.emit(completed("outer"));
+                dispatcher.emit(completed("unknown"));
+                assert_eq!(subscriber.open_spans(), 0);
+            });
+
+            assert_eq!(
+                *layer.opened.lock(),
+                vec![
+                    ("outer".to_string(), None),
+                    ("inner".to_string(), Some("outer".to_string())),
+                ]
+            );
+            assert_eq!(*layer.closed.lock(), vec!["inner", "outer"]);
+        }
+
+        #[test]
+        fn test_spans_kept_per_sandbox() {
+            let layer = RecordingLayer::default();
+            let registry = tracing_subscriber::registry().with(layer.clone());
+            let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+
+            tracing::subscriber::with_default(registry, || {
+                let subscriber = Arc::new(TracingSubscriber::new());
+                let dispatcher = EventDispatcher::new();
+                dispatcher.subscribe(Arc::clone(&subscriber) as Arc<dyn EventSubscriber>);
+
+                // Interleaved executions of the same function do not nest
+                dispatcher.emit_for(first, started("run"));
+                dispatcher.emit_for(second, started("run"));
+                dispatcher.emit_for(first, started("step"));
+                assert_eq!(subscriber.open_spans(), 3);
+
+                // Each completion closes its own sandbox's span
+                dispatcher.emit_for(second, completed("step"));
+                assert_eq!(subscriber.open_spans(), 3);
+                dispatcher.emit_for(second, completed("run"));
+                dispatcher.emit_for(first, completed("step"));
+                assert_eq!(subscriber.open_spans(), 1);
+                dispatcher.emit(completed("run"));
+                assert_eq!(subscriber.open_spans(), 1);
+                dispatcher.emit_for(first, completed("run"));
+                assert_eq!(subscriber.open_spans(), 0);
+            });
+
+            assert_eq!(
+                *layer.opened.lock(),
+                vec![
+                    ("run".to_string(), None),
+                    ("run".to_string(), None),
+                    ("step".to_string(), Some("run".to_string())),
+                ]
+            );
+            assert_eq!(*layer.closed.lock(), vec!["run", "step", "run"]);
+        }
+    }
+
+    #[test]
+    fn test_event_dispatcher_unsubscribe() {
+        let dispatcher = EventDispatcher::new();
+        let kept = Arc::new(CollectingSubscriber::new(10));
+        let removed = Arc::new(CollectingSubscriber::new(10));
+
+        let kept_id = dispatcher.subscribe(kept.clone());
+        let removed_id = dispatcher.subscribe(removed.clone());
+        assert_ne!(kept_id, removed_id);
+
+        assert!(dispatcher.unsubscribe(removed_id));
+        assert!(!dispatcher.unsubscribe(removed_id));
+        assert_eq!(dispatcher.subscriber_count(), 1);
+
+        dispatcher.emit(SandboxEvent::Error {
+            message: "test".to_string(),
+        });
+
+        assert_eq!(kept.len(), 1);
+        assert!(removed.is_empty());
+    }
+
+    #[test]
+    fn test_emit_batch_locks_once() {
+        struct ErrorsOnly(CollectingSubscriber);
+
+        impl EventSubscriber for ErrorsOnly {
+            fn on_event(&self, event: &SandboxEvent) {
+                self.0.on_event(event);
+            }
+
+            fn event_filter(&self) -> Option<Vec<&'static str>> {
+                Some(vec!["error"])
+            }
+        }
+
+        let dispatcher = EventDispatcher::new();
+        let all = Arc::new(CollectingSubscriber::new(100));
+        let errors = Arc::new(ErrorsOnly(CollectingSubscriber::new(100)));
+        dispatcher.subscribe(all.clone());
+        dispatcher.subscribe(errors.clone());
+
+        let events = (0..10).map(|i| {
+            if i % 5 == 0 {
+                SandboxEvent::Error {
+                    message: format!("error {i}"),
+                }
+            } else {
+                SandboxEvent::FuelConsumed {
+                    amount: i,
+                    remaining: 100 - i,
+                }
+            }
+        });
+        dispatcher.emit_batch(events);
+
+        assert_eq!(dispatcher.read_locks.load(Ordering::Relaxed), 1);
+        assert_eq!(all.len(), 10);
+        assert_eq!(errors.0.len(), 2);
+    }
 }
@@ -6,6 +6,9 @@
 //! - [`MetricsCollector`]: Collects execution metrics
 //! - [`ExecutionReport`]: Complete execution reports
 //! - [`EventDispatcher`]: Observable event system
+//! - [`AuditingSubscriber`]: Bounded audit trail of capability checks
+//! - [`JsonLinesSubscriber`]: Streams events as JSON Lines
+//! - [`TracingSubscriber`]: Execution lifecycle as `tracing` spans
 //!
 //! # Metrics Collection
 //!
@@ -55,15 +58,17 @@ pub mod report;
 
 // Re-export main types
 pub use events::{
-    CollectingSubscriber, EventDispatcher, EventSubscriber, LoggingSubscriber, SandboxEvent,
+    AuditEntry, AuditingSubscriber, CollectingSubscriber, EventDispatcher, EventSubscriber,
+    JsonLinesSubscriber, LoggingSubscriber, SandboxEvent, SubscriptionId, TracingSubscriber,
 };
 pub use metrics::{
-    CapabilityUsageMetrics, FuelMetrics, HostCallMetrics, MemoryMetrics, MetricsCollector,
+    CallFuel, CapabilityUsageMetrics, DeniedSummary, Distribution, FuelMetrics, HostCallMetrics,
+    IterationMetrics, IterationSample, LatencyHistogram, MemoryMetrics, MetricsCollector,
     MetricsSnapshot, TimingMetrics,
 };
 pub use report::{
     Diagnostic, DiagnosticLevel, ExecutionId, ExecutionOutcome, ExecutionReport, ModuleInfo,
-    ResourceType, TrapInfo,
+    ReportRedaction, ResourceType, TrapFrame, TrapInfo,
 };
 
 /// Prelude module for convenient imports.
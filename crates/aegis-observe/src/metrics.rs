@@ -1,11 +1,12 @@
 //! Metrics collection during sandbox execution.
 
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 
+use crate::report::ExecutionId;
 use aegis_capability::CapabilityId;
 
 /// Collects metrics during sandbox execution.
@@ -21,6 +22,8 @@ pub struct MetricsCollector {
     capability_usage: RwLock<CapabilityUsageMetrics>,
     /// Host call metrics.
     host_calls: RwLock<HostCallMetrics>,
+    /// Per-iteration samples from repeated runs.
+    iterations: RwLock<IterationMetrics>,
 }
 
 impl MetricsCollector {
@@ -77,6 +80,15 @@ impl MetricsCollector {
         fuel.consumed_fuel = initial.saturating_sub(remaining);
     }
 
+    /// Record the fuel consumed by one call of an exported function.
+    pub fn record_call_fuel(&self, function: &str, fuel_consumed: u64, duration: Duration) {
+        self.fuel.write().per_call.push(CallFuel {
+            function: function.to_string(),
+            fuel_consumed,
+            duration,
+        });
+    }
+
     /// Record a refuel event.
     pub fn record_refuel(&self, amount: u64) {
         let mut fuel = self.fuel.write();
@@ -99,15 +111,28 @@ impl MetricsCollector {
         action: String,
         reason: String,
     ) {
-        self.capability_usage
-            .write()
-            .denied_attempts
-            .push(DeniedAttempt {
+        let mut usage = self.capability_usage.write();
+
+        match usage
+            .denied
+            .iter_mut()
+            .find(|d| d.capability == *capability && d.action == action && d.reason == reason)
+        {
+            Some(summary) => summary.count += 1,
+            None => usage.denied.push(DeniedSummary {
                 capability: capability.clone(),
-                action,
-                reason,
-                timestamp: Instant::now(),
-            });
+                action: action.clone(),
+                reason: reason.clone(),
+                count: 1,
+            }),
+        }
+
+        usage.denied_attempts.push(DeniedAttempt {
+            capability: capability.clone(),
+            action,
+            reason,
+            timestamp: Instant::now(),
+        });
     }
 
     /// Record a host function call.
@@ -118,6 +143,19 @@ impl MetricsCollector {
             .call_durations
             .entry(function.to_string())
             .or_insert(Duration::ZERO) += duration;
+        calls
+            .call_histograms
+            .entry(function.to_string())
+            .or_default()
+            .record(duration);
+    }
+
+    /// Record one iteration of a repeated run.
+    pub fn record_iteration(&self, wall_time: Duration, fuel_consumed: u64) {
+        self.iterations.write().samples.push(IterationSample {
+            wall_time,
+            fuel_consumed,
+        });
     }
 
     /// Get a snapshot of all metrics.
@@ -128,6 +166,7 @@ impl MetricsCollector {
             fuel: self.fuel.read().clone(),
             capability_usage: self.capability_usage.read().clone(),
             host_calls: self.host_calls.read().clone(),
+            iterations: self.iterations.read().clone(),
         }
     }
 
@@ -138,6 +177,7 @@ impl MetricsCollector {
         *self.fuel.write() = FuelMetrics::default();
         *self.capability_usage.write() = CapabilityUsageMetrics::default();
         *self.host_calls.write() = HostCallMetrics::default();
+        *self.iterations.write() = IterationMetrics::default();
     }
 }
 
@@ -164,6 +204,198 @@ pub struct MetricsSnapshot {
     pub capability_usage: CapabilityUsageMetrics,
     /// Host call metrics.
     pub host_calls: HostCallMetrics,
+    /// Per-iteration samples from repeated runs.
+    #[serde(default)]
+    pub iterations: IterationMetrics,
+}
+
+impl MetricsSnapshot {
+    /// Render the snapshot in the Prometheus text exposition format.
+    ///
+    /// `labels` are attached to every sample, e.g. `[("module", "plugin")]`.
+    /// Host call samples additionally carry a `function` label.
+    pub fn to_prometheus(&self, labels: &[(&str, &str)]) -> String {
+        self.write_text(labels, &TextFormat::PROMETHEUS)
+    }
+
+    /// Render the snapshot in the OpenMetrics text format, with an exemplar
+    /// linking the fuel counter to `execution_id`.
+    ///
+    /// The samples match [`to_prometheus`](Self::to_prometheus). The fuel
+    /// sample is annotated with `# {execution_id="..."} <value> <timestamp>`
+    /// so that a dashboard can jump from the metric to the execution, and
+    /// the output ends with `# EOF`.
+    pub fn to_openmetrics(&self, execution_id: &ExecutionId, labels: &[(&str, &str)]) -> String {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        self.render_openmetrics(execution_id, labels, timestamp)
+    }
+
+    /// Render the OpenMetrics text with the exemplar stamped at `timestamp`,
+    /// in seconds since the Unix epoch.
+    fn render_openmetrics(
+        &self,
+        execution_id: &ExecutionId,
+        labels: &[(&str, &str)],
+        timestamp: f64,
+    ) -> String {
+        let format = TextFormat {
+            exemplar: Some((execution_id, timestamp)),
+            ..TextFormat::OPENMETRICS
+        };
+        self.write_text(labels, &format)
+    }
+
+    /// Render the snapshot in the text format described by `format`.
+    fn write_text(&self, labels: &[(&str, &str)], format: &TextFormat<'_>) -> String {
+        use std::fmt::Write;
+
+        let base = format_labels(labels, None);
+        let mut out = String::new();
+        let family = |out: &mut String, name: &str, kind: &str, unit: Option<&str>, help: &str| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            if let Some(unit) = unit.filter(|_| format.units) {
+                let _ = writeln!(out, "# UNIT {} {}", name, unit);
+            }
+        };
+        let counter = |name: &str| format!("{}{}", name, format.counter_family_suffix);
+
+        family(
+            &mut out,
+            &counter("aegis_fuel_consumed"),
+            "counter",
+            None,
+            "Fuel consumed during execution.",
+        );
+        let _ = write!(
+            out,
+            "aegis_fuel_consumed_total{} {}",
+            base, self.fuel.consumed_fuel
+        );
+        if let Some((execution_id, timestamp)) = format.exemplar {
+            let _ = write!(
+                out,
+                " # {} {} {:.3}",
+                format_labels(&[("execution_id", &execution_id.to_string())], None),
+                self.fuel.consumed_fuel,
+                timestamp
+            );
+        }
+        out.push('\n');
+
+        family(
+            &mut out,
+            &counter("aegis_host_calls"),
+            "counter",
+            None,
+            "Host function calls by function.",
+        );
+        let mut calls: Vec<_> = self.host_calls.call_counts.iter().collect();
+        calls.sort_by(|a, b| a.0.cmp(b.0));
+        for (function, count) in calls {
+            let _ = writeln!(
+                out,
+                "aegis_host_calls_total{} {}",
+                format_labels(labels, Some(("function", function))),
+                count
+            );
+        }
+
+        family(
+            &mut out,
+            "aegis_peak_memory_bytes",
+            "gauge",
+            Some("bytes"),
+            "Peak linear memory size in bytes.",
+        );
+        let _ = writeln!(
+            out,
+            "aegis_peak_memory_bytes{} {}",
+            base, self.memory.peak_memory
+        );
+
+        let count = u64::from(self.timing.end_time.is_some());
+        family(
+            &mut out,
+            "aegis_execution_time_seconds",
+            "summary",
+            Some("seconds"),
+            "Wall-clock execution time.",
+        );
+        let _ = writeln!(
+            out,
+            "aegis_execution_time_seconds_sum{} {}",
+            base,
+            self.timing.execution_time.as_secs_f64()
+        );
+        let _ = writeln!(out, "aegis_execution_time_seconds_count{} {}", base, count);
+
+        if format.eof {
+            let _ = writeln!(out, "# EOF");
+        }
+        out
+    }
+}
+
+/// Differences between the text formats rendered by
+/// [`MetricsSnapshot::write_text`].
+struct TextFormat<'a> {
+    /// Suffix of counter family names; OpenMetrics names the family without
+    /// the `_total` carried by its samples.
+    counter_family_suffix: &'static str,
+    /// Whether to emit `# UNIT` lines.
+    units: bool,
+    /// Whether to end the output with `# EOF`.
+    eof: bool,
+    /// Execution and timestamp of the exemplar on the fuel sample.
+    exemplar: Option<(&'a ExecutionId, f64)>,
+}
+
+impl TextFormat<'_> {
+    /// The Prometheus text exposition format.
+    const PROMETHEUS: Self = Self {
+        counter_family_suffix: "_total",
+        units: false,
+        eof: false,
+        exemplar: None,
+    };
+
+    /// The OpenMetrics text format, without an exemplar.
+    const OPENMETRICS: Self = Self {
+        counter_family_suffix: "",
+        units: true,
+        eof: true,
+        exemplar: None,
+    };
+}
+
+/// Format a Prometheus label set, including the braces.
+///
+/// Returns an empty string when there are no labels.
+fn format_labels(labels: &[(&str, &str)], extra: Option<(&str, &str)>) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .copied()
+        .chain(extra)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect();
+
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+/// Escape a label value per the Prometheus text format.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
 }
 
 /// Timing-related metrics.
@@ -211,6 +443,41 @@ pub struct FuelMetrics {
     /// Refuel events.
     #[serde(skip)]
     pub refuel_events: Vec<RefuelEvent>,
+    /// Fuel attributed to each call of an exported function, in call order.
+    #[serde(default)]
+    pub per_call: Vec<CallFuel>,
+}
+
+impl FuelMetrics {
+    /// Get the function that consumed the most fuel across its calls.
+    ///
+    /// Returns the function name, its total fuel and its number of calls.
+    /// Ties are broken by name.
+    pub fn hottest_function(&self) -> Option<(&str, u64, usize)> {
+        let mut totals: HashMap<&str, (u64, usize)> = HashMap::new();
+        for call in &self.per_call {
+            let entry = totals.entry(call.function.as_str()).or_default();
+            entry.0 = entry.0.saturating_add(call.fuel_consumed);
+            entry.1 += 1;
+        }
+
+        totals
+            .into_iter()
+            .max_by(|(a, (a_fuel, _)), (b, (b_fuel, _))| a_fuel.cmp(b_fuel).then(b.cmp(a)))
+            .map(|(name, (fuel, calls))| (name, fuel, calls))
+    }
+}
+
+/// Fuel and time attributed to one call of an exported function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallFuel {
+    /// Name of the called export.
+    pub function: String,
+    /// Fuel consumed by the call.
+    pub fuel_consumed: u64,
+    /// Wall time spent in the call.
+    #[serde(with = "duration_serde")]
+    pub duration: Duration,
 }
 
 /// A refuel event.
@@ -230,6 +497,23 @@ pub struct CapabilityUsageMetrics {
     /// Denied permission attempts.
     #[serde(skip)]
     pub denied_attempts: Vec<DeniedAttempt>,
+    /// Denied attempts grouped by capability, action and reason, in order
+    /// of first occurrence.
+    #[serde(default)]
+    pub denied: Vec<DeniedSummary>,
+}
+
+/// Denied capability attempts with the same capability, action and reason.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeniedSummary {
+    /// The capability that denied the action.
+    pub capability: CapabilityId,
+    /// The action that was attempted.
+    pub action: String,
+    /// The reason for denial.
+    pub reason: String,
+    /// Number of times the action was denied.
+    pub count: u64,
 }
 
 /// A denied capability attempt.
@@ -253,10 +537,183 @@ pub struct HostCallMetrics {
     /// Per-function total time.
     #[serde(skip)]
     pub call_durations: HashMap<String, Duration>,
+    /// Per-function latency distribution.
+    ///
+    /// Unlike `call_durations`, histograms are serialized, since they are
+    /// needed to compute tail latency from an exported snapshot. Snapshots
+    /// without this field deserialize with empty histograms.
+    #[serde(default)]
+    pub call_histograms: HashMap<String, LatencyHistogram>,
+}
+
+impl HostCallMetrics {
+    /// Estimate a latency percentile for a host function.
+    ///
+    /// `p` is a percentage in `0.0..=100.0`. The result is the upper bound
+    /// of the bucket holding the requested rank, capped at the slowest
+    /// observed call. Returns `None` if the function has no recorded calls
+    /// or `p` is out of range.
+    pub fn percentile(&self, function: &str, p: f64) -> Option<Duration> {
+        self.call_histograms.get(function)?.percentile(p)
+    }
+}
+
+/// Upper bounds of the latency histogram buckets, in microseconds.
+///
+/// Calls slower than the last bound fall into an overflow bucket.
+pub const LATENCY_BUCKETS_US: [u64; 19] = [
+    1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000, 100_000,
+    200_000, 500_000, 1_000_000,
+];
+
+/// Latency histogram with fixed microsecond buckets.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    /// Call counts per bucket in `LATENCY_BUCKETS_US`, followed by the
+    /// overflow bucket.
+    pub buckets: Vec<u64>,
+    /// Slowest recorded call.
+    #[serde(with = "duration_serde")]
+    pub max: Duration,
+}
+
+impl LatencyHistogram {
+    /// Record a call duration.
+    pub fn record(&mut self, duration: Duration) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS_US.len() + 1];
+        }
+
+        let micros = duration.as_micros();
+        let index = LATENCY_BUCKETS_US
+            .iter()
+            .position(|&bound| micros <= u128::from(bound))
+            .unwrap_or(LATENCY_BUCKETS_US.len());
+
+        self.buckets[index] += 1;
+        self.max = self.max.max(duration);
+    }
+
+    /// Get the number of recorded calls.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Estimate a latency percentile.
+    ///
+    /// See [`HostCallMetrics::percentile`].
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        if !(0.0..=100.0).contains(&p) {
+            return None;
+        }
+
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        let rank = ((p / 100.0) * count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, &bucket) in self.buckets.iter().enumerate() {
+            seen += bucket;
+            if seen >= rank {
+                let bound = LATENCY_BUCKETS_US
+                    .get(index)
+                    .map(|&us| Duration::from_micros(us))
+                    .unwrap_or(self.max);
+                return Some(bound.min(self.max));
+            }
+        }
+
+        Some(self.max)
+    }
+}
+
+/// Samples from repeated runs of the same function.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IterationMetrics {
+    /// One sample per iteration, in order.
+    pub samples: Vec<IterationSample>,
+}
+
+impl IterationMetrics {
+    /// Summarize wall times, in nanoseconds.
+    pub fn wall_time(&self) -> Option<Distribution> {
+        Distribution::from_values(
+            self.samples
+                .iter()
+                .map(|s| u64::try_from(s.wall_time.as_nanos()).unwrap_or(u64::MAX)),
+        )
+    }
+
+    /// Summarize fuel consumption.
+    pub fn fuel(&self) -> Option<Distribution> {
+        Distribution::from_values(self.samples.iter().map(|s| s.fuel_consumed))
+    }
+}
+
+/// Measurements from a single iteration.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct IterationSample {
+    /// Time spent in the call.
+    #[serde(with = "duration_serde")]
+    pub wall_time: Duration,
+    /// Fuel consumed by the call.
+    pub fuel_consumed: u64,
+}
+
+/// Summary statistics of a set of values.
+///
+/// Percentiles use the nearest-rank method, so they are always one of the
+/// recorded values.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Distribution {
+    /// Number of values.
+    pub count: usize,
+    /// Smallest value.
+    pub min: u64,
+    /// Arithmetic mean.
+    pub mean: f64,
+    /// 50th percentile.
+    pub median: u64,
+    /// 95th percentile.
+    pub p95: u64,
+    /// Largest value.
+    pub max: u64,
+}
+
+impl Distribution {
+    /// Summarize a set of values, or return `None` if it is empty.
+    pub fn from_values(values: impl IntoIterator<Item = u64>) -> Option<Self> {
+        let mut values: Vec<u64> = values.into_iter().collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable();
+
+        let count = values.len();
+        let rank = |p: f64| {
+            let rank = ((p / 100.0) * count as f64).ceil() as usize;
+            values[rank.clamp(1, count) - 1]
+        };
+        let sum: u128 = values.iter().map(|&v| u128::from(v)).sum();
+
+        Some(Self {
+            count,
+            min: values[0],
+            mean: sum as f64 / count as f64,
+            median: rank(50.0),
+            p95: rank(95.0),
+            max: values[count - 1],
+        })
+    }
 }
 
 /// Custom serde for Duration.
-mod duration_serde {
+///
+/// Durations are written as `u64` nanoseconds rather than `u128`, since
+/// serde cannot buffer `u128` values inside internally tagged enums.
+pub(crate) mod duration_serde {
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
     use std::time::Duration;
 
@@ -264,15 +721,16 @@ mod duration_serde {
     where
         S: Serializer,
     {
-        duration.as_nanos().serialize(serializer)
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        nanos.serialize(serializer)
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let nanos = u128::deserialize(deserializer)?;
-        Ok(Duration::from_nanos(nanos as u64))
+        let nanos = u64::deserialize(deserializer)?;
+        Ok(Duration::from_nanos(nanos))
     }
 }
 
@@ -319,6 +777,21 @@ mod tests {
         assert_eq!(snapshot.fuel.remaining_fuel, 750);
     }
 
+    #[test]
+    fn test_hottest_function() {
+        let collector = MetricsCollector::new();
+        assert_eq!(collector.snapshot().fuel.hottest_function(), None);
+
+        let ms = Duration::from_millis(1);
+        collector.record_call_fuel("parse", 300, ms);
+        collector.record_call_fuel("render", 500, ms);
+        collector.record_call_fuel("parse", 400, ms);
+
+        let fuel = collector.snapshot().fuel;
+        assert_eq!(fuel.per_call.len(), 3);
+        assert_eq!(fuel.hottest_function(), Some(("parse", 700, 2)));
+    }
+
     #[test]
     fn test_metrics_collector_capability_usage() {
         let collector = MetricsCollector::new();
@@ -344,4 +817,170 @@ mod tests {
         let snapshot = collector.snapshot();
         assert_eq!(snapshot.fuel.initial_fuel, 0);
     }
+
+    #[test]
+    fn test_to_prometheus() {
+        let collector = MetricsCollector::new();
+        collector.record_start();
+        collector.record_end();
+        collector.record_fuel_consumed(1000, 400);
+        collector.record_memory_allocation(65536);
+        collector.record_host_call("log", Duration::from_micros(5));
+        collector.record_host_call("log", Duration::from_micros(5));
+        collector.record_host_call("read", Duration::from_micros(5));
+
+        let text = collector
+            .snapshot()
+            .to_prometheus(&[("module", "plugin \"a\"")]);
+
+        assert!(text.contains("# TYPE aegis_fuel_consumed_total counter\n"));
+        assert!(text.contains("# TYPE aegis_host_calls_total counter\n"));
+        assert!(text.contains("# TYPE aegis_peak_memory_bytes gauge\n"));
+        assert!(text.contains("# TYPE aegis_execution_time_seconds summary\n"));
+
+        assert!(text.contains("aegis_fuel_consumed_total{module=\"plugin \\\"a\\\"\"} 600\n"));
+        assert!(
+            text.contains(
+                "aegis_host_calls_total{module=\"plugin \\\"a\\\"\",function=\"log\"} 2\n"
+            )
+        );
+        assert!(
+            text.contains(
+                "aegis_host_calls_total{module=\"plugin \\\"a\\\"\",function=\"read\"} 1\n"
+            )
+        );
+        assert!(text.contains("aegis_peak_memory_bytes{module=\"plugin \\\"a\\\"\"} 65536\n"));
+        assert!(
+            text.contains("aegis_execution_time_seconds_count{module=\"plugin \\\"a\\\"\"} 1\n")
+        );
+
+        for line in text.lines().filter(|l| l.starts_with("# TYPE")) {
+            assert_eq!(line.split_whitespace().count(), 4, "malformed: {}", line);
+        }
+    }
+
+    #[test]
+    fn test_to_openmetrics_exemplar() {
+        let collector = MetricsCollector::new();
+        collector.record_fuel_consumed(1000, 400);
+        collector.record_host_call("log", Duration::from_micros(5));
+
+        let id = ExecutionId::new();
+        let text =
+            collector
+                .snapshot()
+                .render_openmetrics(&id, &[("module", "plugin")], 1_700_000_000.5);
+
+        assert!(text.contains("# TYPE aegis_fuel_consumed counter\n"));
+        let expected = format!(
+            "aegis_fuel_consumed_total{{module=\"plugin\"}} 600 # {{execution_id=\"{}\"}} 600 1700000000.500\n",
+            id
+        );
+        assert!(text.contains(&expected), "missing exemplar in:\n{}", text);
+        assert!(text.contains("aegis_host_calls_total{module=\"plugin\",function=\"log\"} 1\n"));
+        assert!(text.contains("# UNIT aegis_peak_memory_bytes bytes\n"));
+        assert!(text.ends_with("# EOF\n"));
+
+        // The exemplar is the only annotated sample and is well-formed
+        let exemplars: Vec<_> = text
+            .lines()
+            .filter(|l| !l.starts_with('#') && l.contains(" # "))
+            .collect();
+        assert_eq!(exemplars.len(), 1);
+        let (_, exemplar) = exemplars[0].split_once(" # ").unwrap();
+        let (labels, rest) = exemplar.split_once("} ").unwrap();
+        assert_eq!(labels, format!("{{execution_id=\"{}\"", id));
+        let parts: Vec<_> = rest.split(' ').collect();
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].parse::<u64>().is_ok());
+        assert!(parts[1].parse::<f64>().is_ok());
+
+        // The plain exporter has no exemplars or EOF marker
+        let plain = collector.snapshot().to_prometheus(&[]);
+        assert!(!plain.contains("execution_id"));
+        assert!(!plain.contains("# EOF"));
+        assert!(!plain.contains("# UNIT"));
+        assert!(plain.contains("# TYPE aegis_fuel_consumed_total counter\n"));
+        assert!(
+            collector
+                .snapshot()
+                .to_openmetrics(&id, &[])
+                .contains(&format!(
+                    "aegis_fuel_consumed_total 600 # {{execution_id=\"{}\"}} 600 ",
+                    id
+                ))
+        );
+    }
+
+    #[test]
+    fn test_to_prometheus_without_labels() {
+        let text = MetricsCollector::new().snapshot().to_prometheus(&[]);
+        assert!(text.contains("aegis_fuel_consumed_total 0\n"));
+        assert!(text.contains("aegis_execution_time_seconds_count 0\n"));
+    }
+
+    #[test]
+    fn test_host_call_percentiles() {
+        let collector = MetricsCollector::new();
+
+        // 95 fast calls and 5 slow ones.
+        for _ in 0..95 {
+            collector.record_host_call("fs_read", Duration::from_micros(40));
+        }
+        for _ in 0..5 {
+            collector.record_host_call("fs_read", Duration::from_millis(30));
+        }
+
+        let snapshot = collector.snapshot();
+        let calls = &snapshot.host_calls;
+
+        assert_eq!(calls.call_counts["fs_read"], 100);
+        assert_eq!(
+            calls.call_durations["fs_read"],
+            Duration::from_micros(153_800)
+        );
+        assert_eq!(
+            calls.percentile("fs_read", 50.0),
+            Some(Duration::from_micros(50))
+        );
+        assert_eq!(
+            calls.percentile("fs_read", 99.0),
+            Some(Duration::from_millis(30))
+        );
+        assert_eq!(calls.percentile("fs_read", 101.0), None);
+        assert_eq!(calls.percentile("missing", 50.0), None);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let decoded: MetricsSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            decoded.host_calls.percentile("fs_read", 99.0),
+            Some(Duration::from_millis(30))
+        );
+    }
+
+    #[test]
+    fn test_iteration_distribution() {
+        let collector = MetricsCollector::new();
+        assert!(collector.snapshot().iterations.wall_time().is_none());
+
+        for i in 1..=20u64 {
+            collector.record_iteration(Duration::from_micros(i), i * 100);
+        }
+
+        let iterations = collector.snapshot().iterations;
+        let fuel = iterations.fuel().unwrap();
+        assert_eq!(fuel.count, 20);
+        assert_eq!(fuel.min, 100);
+        assert_eq!(fuel.median, 1_000);
+        assert_eq!(fuel.p95, 1_900);
+        assert_eq!(fuel.max, 2_000);
+        assert_eq!(fuel.mean, 1_050.0);
+
+        let wall_time = iterations.wall_time().unwrap();
+        assert_eq!(wall_time.min, 1_000);
+        assert_eq!(wall_time.max, 20_000);
+
+        collector.reset();
+        assert!(collector.snapshot().iterations.samples.is_empty());
+    }
 }
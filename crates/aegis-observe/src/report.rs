@@ -40,6 +40,9 @@ pub struct ModuleInfo {
     pub export_count: usize,
     /// Number of imports.
     pub import_count: usize,
+    /// Hex SHA-256 hash identifying the module, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
 }
 
 /// Result of an execution.
@@ -48,6 +51,9 @@ pub enum ExecutionOutcome {
     /// Execution completed successfully.
     Success {
         /// Return value, if any (serialized as JSON).
+        ///
+        /// The CLI stores an array of `{"type", "value"}` objects, one per
+        /// result.
         return_value: Option<serde_json::Value>,
     },
     /// Execution trapped.
@@ -95,6 +101,18 @@ impl ExecutionOutcome {
     pub fn is_failure(&self) -> bool {
         !self.is_success()
     }
+
+    /// Get the outcome kind name.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ExecutionOutcome::Success { .. } => "success",
+            ExecutionOutcome::Trapped { .. } => "trapped",
+            ExecutionOutcome::Timeout { .. } => "timeout",
+            ExecutionOutcome::ResourceExhausted { .. } => "resource_exhausted",
+            ExecutionOutcome::CapabilityDenied { .. } => "capability_denied",
+            ExecutionOutcome::Error { .. } => "error",
+        }
+    }
 }
 
 /// Information about a trap.
@@ -106,6 +124,22 @@ pub struct TrapInfo {
     pub message: String,
     /// Stack backtrace, if available.
     pub backtrace: Option<String>,
+    /// Frames of the backtrace, innermost first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<TrapFrame>,
+}
+
+/// A WASM frame in a trap backtrace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrapFrame {
+    /// Name of the module, from its name section.
+    pub module: Option<String>,
+    /// Index of the function in the module.
+    pub func_index: u32,
+    /// Name of the function, from the name section.
+    pub func_name: Option<String>,
+    /// Offset of the instruction in the module's bytes.
+    pub offset: Option<usize>,
 }
 
 /// Type of resource that was exhausted.
@@ -157,6 +191,141 @@ pub enum DiagnosticLevel {
     Error,
 }
 
+/// Escape text for use in a Markdown table cell.
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Placeholder that replaces host paths in redacted reports.
+const PATH_PLACEHOLDER: &str = "<path>";
+
+/// Redaction applied to a report before it reaches untrusted users.
+///
+/// Trap messages, backtraces, error messages, diagnostics and capability
+/// denials can carry host paths and other internal details. The default
+/// policy redacts nothing; [`strict`](Self::strict) applies every rule.
+///
+/// # Example
+///
+/// ```
+/// use aegis_observe::ReportRedaction;
+///
+/// let policy = ReportRedaction::new().with_mask_paths(true);
+/// assert_eq!(
+///     policy.message("cannot open /home/user/secret.txt"),
+///     "cannot open <path>"
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportRedaction {
+    /// Drop trap backtraces and frames.
+    pub strip_backtraces: bool,
+    /// Truncate messages to this many characters.
+    pub max_message_len: Option<usize>,
+    /// Replace absolute host paths in messages with `<path>`.
+    pub mask_paths: bool,
+}
+
+impl ReportRedaction {
+    /// Create a policy that redacts nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a policy that strips backtraces, masks host paths and
+    /// truncates messages to 200 characters.
+    pub fn strict() -> Self {
+        Self {
+            strip_backtraces: true,
+            max_message_len: Some(200),
+            mask_paths: true,
+        }
+    }
+
+    /// Enable or disable stripping of trap backtraces.
+    pub fn with_strip_backtraces(mut self, enabled: bool) -> Self {
+        self.strip_backtraces = enabled;
+        self
+    }
+
+    /// Truncate messages to at most `len` characters.
+    pub fn with_max_message_len(mut self, len: usize) -> Self {
+        self.max_message_len = Some(len);
+        self
+    }
+
+    /// Enable or disable masking of absolute host paths.
+    pub fn with_mask_paths(mut self, enabled: bool) -> Self {
+        self.mask_paths = enabled;
+        self
+    }
+
+    /// Apply the policy to a message.
+    ///
+    /// Paths are masked before truncation, so a path cut off by the limit
+    /// cannot leak its prefix.
+    pub fn message(&self, text: &str) -> String {
+        let masked = if self.mask_paths {
+            mask_host_paths(text)
+        } else {
+            text.to_string()
+        };
+
+        let Some(max) = self.max_message_len else {
+            return masked;
+        };
+        match masked.char_indices().nth(max) {
+            Some((end, _)) => format!("{}...", &masked[..end]),
+            None => masked,
+        }
+    }
+}
+
+/// Replace every absolute Unix or Windows path in `text` with
+/// [`PATH_PLACEHOLDER`].
+///
+/// A path must start a word, so ratios such as `1/2` are left alone.
+fn mask_host_paths(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut at_word_start = true;
+
+    while let Some(c) = rest.chars().next() {
+        let path_len = if at_word_start {
+            host_path_len(rest)
+        } else {
+            0
+        };
+        if path_len > 0 {
+            output.push_str(PATH_PLACEHOLDER);
+            rest = &rest[path_len..];
+            at_word_start = false;
+            continue;
+        }
+
+        output.push(c);
+        at_word_start = c.is_whitespace() || "'\"`(<[=,:".contains(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    output
+}
+
+/// Length in bytes of the absolute path at the start of `text`, or 0 if
+/// there is none.
+fn host_path_len(text: &str) -> usize {
+    let is_path_byte = |b: &u8| b.is_ascii_alphanumeric() || b"/\\._-~+@".contains(b);
+    let bytes = text.as_bytes();
+    let root = match bytes {
+        [b'/', ..] => 1,
+        [drive, b':', b'\\' | b'/', ..] if drive.is_ascii_alphabetic() => 3,
+        _ => return 0,
+    };
+
+    let len = root + bytes[root..].iter().take_while(|b| is_path_byte(b)).count();
+    if len > root { len } else { 0 }
+}
+
 /// Complete execution report.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionReport {
@@ -170,6 +339,9 @@ pub struct ExecutionReport {
     pub metrics: MetricsSnapshot,
     /// Diagnostic messages.
     pub diagnostics: Vec<Diagnostic>,
+    /// Redaction applied when the report is formatted, if any.
+    #[serde(skip)]
+    pub redaction: Option<ReportRedaction>,
 }
 
 impl ExecutionReport {
@@ -181,9 +353,65 @@ impl ExecutionReport {
             outcome,
             metrics,
             diagnostics: Vec::new(),
+            redaction: None,
         }
     }
 
+    /// Redact the report whenever it is formatted as text, Markdown or
+    /// JSON.
+    pub fn with_redaction(mut self, policy: ReportRedaction) -> Self {
+        self.redaction = Some(policy);
+        self
+    }
+
+    /// Get a copy of the report with `policy` applied.
+    ///
+    /// The outcome keeps its variant and numbers; only messages,
+    /// backtraces and other free text are redacted. The copy has no
+    /// pending redaction of its own.
+    pub fn redacted(&self, policy: &ReportRedaction) -> Self {
+        let mut report = self.clone();
+        report.redaction = None;
+
+        match &mut report.outcome {
+            ExecutionOutcome::Trapped { trap } => {
+                trap.message = policy.message(&trap.message);
+                if policy.strip_backtraces {
+                    trap.backtrace = None;
+                    trap.frames.clear();
+                } else if policy.mask_paths {
+                    trap.backtrace = trap.backtrace.as_deref().map(mask_host_paths);
+                }
+            }
+            ExecutionOutcome::CapabilityDenied { action, .. } => {
+                *action = policy.message(action);
+            }
+            ExecutionOutcome::Error { message } => {
+                *message = policy.message(message);
+            }
+            ExecutionOutcome::Success { .. }
+            | ExecutionOutcome::Timeout { .. }
+            | ExecutionOutcome::ResourceExhausted { .. } => {}
+        }
+
+        let usage = &mut report.metrics.capability_usage;
+        for denied in &mut usage.denied {
+            denied.action = policy.message(&denied.action);
+            denied.reason = policy.message(&denied.reason);
+        }
+        for attempt in &mut usage.denied_attempts {
+            attempt.action = policy.message(&attempt.action);
+            attempt.reason = policy.message(&attempt.reason);
+        }
+
+        for diag in &mut report.diagnostics {
+            diag.message = policy.message(&diag.message);
+            diag.context = diag.context.as_deref().map(|c| policy.message(c));
+        }
+
+        report
+    }
+
     /// Add a diagnostic message.
     pub fn add_diagnostic(&mut self, diagnostic: Diagnostic) {
         self.diagnostics.push(diagnostic);
@@ -223,10 +451,17 @@ impl ExecutionReport {
 
     /// Format as human-readable text.
     pub fn to_text(&self) -> String {
+        if let Some(policy) = &self.redaction {
+            return self.redacted(policy).to_text();
+        }
+
         let mut output = String::new();
 
         output.push_str(&format!("Execution Report: {}\n", self.execution_id));
         output.push_str(&format!("Module: {:?}\n", self.module.name));
+        if let Some(hash) = &self.module.hash {
+            output.push_str(&format!("Module Hash: {}\n", hash));
+        }
         output.push('\n');
 
         output.push_str("Outcome: ");
@@ -278,6 +513,20 @@ impl ExecutionReport {
             "  Fuel Consumed: {}\n",
             self.metrics.fuel.consumed_fuel
         ));
+        if let Some(hottest) = self.hottest_function_summary() {
+            output.push_str(&format!("  Hottest Function: {}\n", hottest));
+        }
+
+        let denied = &self.metrics.capability_usage.denied;
+        if !denied.is_empty() {
+            output.push_str("\nDenied Capability Attempts:\n");
+            for d in denied {
+                output.push_str(&format!(
+                    "  {} '{}': {} ({}x)\n",
+                    d.capability, d.action, d.reason, d.count
+                ));
+            }
+        }
 
         if !self.diagnostics.is_empty() {
             output.push_str("\nDiagnostics:\n");
@@ -294,13 +543,148 @@ impl ExecutionReport {
         output
     }
 
+    /// Format as Markdown, for posting into pull requests and dashboards.
+    ///
+    /// The output starts with an outcome badge line, followed by a metrics
+    /// table, any capability denials as a list, and diagnostics in a fenced
+    /// block.
+    pub fn to_markdown(&self) -> String {
+        if let Some(policy) = &self.redaction {
+            return self.redacted(policy).to_markdown();
+        }
+
+        let mut output = String::new();
+
+        output.push_str("## Execution Report\n\n");
+        let badge = if self.is_success() { "✅" } else { "❌" };
+        output.push_str(&format!(
+            "**Outcome:** {} {}\n\n",
+            badge,
+            self.outcome_summary()
+        ));
+
+        output.push_str("### Metrics\n\n");
+        output.push_str("| Metric | Value |\n");
+        output.push_str("| --- | --- |\n");
+        let module = self.module.name.as_deref().unwrap_or("(unnamed)");
+        let rows = [
+            ("Execution ID", format!("`{}`", self.execution_id)),
+            ("Module", markdown_cell(module)),
+            ("Outcome", self.outcome.kind().to_string()),
+            (
+                "Execution Time",
+                format!("{:?}", self.metrics.timing.execution_time),
+            ),
+            (
+                "Compilation Time",
+                format!("{:?}", self.metrics.timing.compilation_time),
+            ),
+            (
+                "Instantiation Time",
+                format!("{:?}", self.metrics.timing.instantiation_time),
+            ),
+            (
+                "Peak Memory",
+                format!("{} bytes", self.metrics.memory.peak_memory),
+            ),
+            ("Fuel Consumed", self.metrics.fuel.consumed_fuel.to_string()),
+        ];
+        for (name, value) in rows {
+            output.push_str(&format!("| {} | {} |\n", name, value));
+        }
+        if let Some(hottest) = self.hottest_function_summary() {
+            output.push_str(&format!(
+                "| Hottest Function | {} |\n",
+                markdown_cell(&hottest)
+            ));
+        }
+
+        let mut denials = Vec::new();
+        if let ExecutionOutcome::CapabilityDenied { capability, action } = &self.outcome {
+            denials.push(format!("- `{}`: action `{}`", capability, action));
+        }
+        for denied in &self.metrics.capability_usage.denied {
+            denials.push(format!(
+                "- `{}`: action `{}` ({}, {}x)",
+                denied.capability, denied.action, denied.reason, denied.count
+            ));
+        }
+        if !denials.is_empty() {
+            output.push_str("\n### Capability Denials\n\n");
+            for denial in denials {
+                output.push_str(&denial);
+                output.push('\n');
+            }
+        }
+
+        if !self.diagnostics.is_empty() {
+            output.push_str("\n### Diagnostics\n\n```text\n");
+            for diag in &self.diagnostics {
+                let level = match diag.level {
+                    DiagnosticLevel::Info => "INFO",
+                    DiagnosticLevel::Warning => "WARN",
+                    DiagnosticLevel::Error => "ERROR",
+                };
+                output.push_str(&format!("[{}] {}\n", level, diag.message));
+            }
+            output.push_str("```\n");
+        }
+
+        output
+    }
+
+    /// Describe the function that consumed the most fuel, if any calls
+    /// were recorded.
+    fn hottest_function_summary(&self) -> Option<String> {
+        let (name, fuel, calls) = self.metrics.fuel.hottest_function()?;
+        let plural = if calls == 1 { "" } else { "s" };
+        Some(format!(
+            "{} ({} fuel over {} call{})",
+            name, fuel, calls, plural
+        ))
+    }
+
+    /// One-line description of the outcome.
+    fn outcome_summary(&self) -> String {
+        match &self.outcome {
+            ExecutionOutcome::Success { return_value } => match return_value {
+                Some(value) => format!("Success (returned `{}`)", value),
+                None => "Success".to_string(),
+            },
+            ExecutionOutcome::Trapped { trap } => format!("Trapped: {}", trap.message),
+            ExecutionOutcome::Timeout { elapsed, limit } => {
+                format!("Timeout: {:?} / {:?}", elapsed, limit)
+            }
+            ExecutionOutcome::ResourceExhausted {
+                resource,
+                used,
+                limit,
+            } => format!("Resource Exhausted: {} ({} / {})", resource, used, limit),
+            ExecutionOutcome::CapabilityDenied { capability, action } => {
+                format!(
+                    "Capability Denied: `{}` for action `{}`",
+                    capability, action
+                )
+            }
+            ExecutionOutcome::Error { message } => format!("Error: {}", message),
+        }
+    }
+
     /// Format as JSON.
     pub fn to_json(&self) -> serde_json::Value {
+        if let Some(policy) = &self.redaction {
+            return self.redacted(policy).to_json();
+        }
+
         serde_json::to_value(self).unwrap_or(serde_json::Value::Null)
     }
 
     /// Format as pretty JSON string.
     pub fn to_json_pretty(&self) -> String {
+        if let Some(policy) = &self.redaction {
+            return self.redacted(policy).to_json_pretty();
+        }
+
         serde_json::to_string_pretty(self).unwrap_or_default()
     }
 }
@@ -334,6 +718,7 @@ mod tests {
             name: Some("test".to_string()),
             export_count: 5,
             import_count: 2,
+            hash: None,
         };
         let outcome = ExecutionOutcome::Success { return_value: None };
         let metrics = MetricsCollector::new().snapshot();
@@ -348,6 +733,7 @@ mod tests {
             name: None,
             export_count: 0,
             import_count: 0,
+            hash: None,
         };
         let metrics = MetricsCollector::new().snapshot();
         let mut report = ExecutionReport::new(
@@ -368,6 +754,7 @@ mod tests {
             name: Some("test_module".to_string()),
             export_count: 1,
             import_count: 0,
+            hash: None,
         };
         let metrics = MetricsCollector::new().snapshot();
         let report = ExecutionReport::new(
@@ -379,5 +766,175 @@ mod tests {
         let text = report.to_text();
         assert!(text.contains("test_module"));
         assert!(text.contains("Success"));
+        assert!(!text.contains("Hottest Function"));
+    }
+
+    #[test]
+    fn test_execution_report_hottest_function() {
+        let collector = MetricsCollector::new();
+        collector.record_call_fuel("init", 50, Duration::from_millis(1));
+        collector.record_call_fuel("run", 900, Duration::from_millis(3));
+
+        let report = ExecutionReport::new(
+            ModuleInfo {
+                name: Some("plugin".to_string()),
+                export_count: 2,
+                import_count: 0,
+                hash: None,
+            },
+            ExecutionOutcome::Success { return_value: None },
+            collector.snapshot(),
+        );
+
+        assert!(
+            report
+                .to_text()
+                .contains("Hottest Function: run (900 fuel over 1 call)")
+        );
+        assert!(
+            report
+                .to_markdown()
+                .contains("| Hottest Function | run (900 fuel over 1 call) |")
+        );
+        assert_eq!(
+            report.to_json()["metrics"]["fuel"]["per_call"][1]["function"],
+            "run"
+        );
+    }
+
+    #[test]
+    fn test_execution_report_markdown() {
+        let collector = MetricsCollector::new();
+        let mut report = ExecutionReport::new(
+            ModuleInfo {
+                name: Some("plugin".to_string()),
+                export_count: 1,
+                import_count: 2,
+                hash: None,
+            },
+            ExecutionOutcome::CapabilityDenied {
+                capability: CapabilityId::new("filesystem"),
+                action: "read /etc/shadow".to_string(),
+            },
+            collector.snapshot(),
+        );
+        report.add_warning("guest attempted a denied read");
+
+        let markdown = report.to_markdown();
+
+        assert!(markdown.starts_with("## Execution Report\n"));
+        assert!(markdown.contains("**Outcome:** ❌ Capability Denied"));
+        assert!(markdown.contains("### Metrics"));
+        assert!(markdown.contains(&format!("| Execution ID | `{}` |", report.execution_id)));
+        assert!(markdown.contains("| Module | plugin |"));
+        assert!(markdown.contains("| Peak Memory | 0 bytes |"));
+        assert!(markdown.contains("| Fuel Consumed | 0 |"));
+        assert!(markdown.contains("### Capability Denials"));
+        assert!(markdown.contains("- `filesystem`: action `read /etc/shadow`"));
+        assert!(
+            markdown
+                .contains("### Diagnostics\n\n```text\n[WARN] guest attempted a denied read\n```")
+        );
+    }
+
+    #[test]
+    fn test_execution_report_denied_attempts() {
+        let collector = MetricsCollector::new();
+        let fs = CapabilityId::new("filesystem");
+        for _ in 0..2 {
+            collector.record_capability_denied(
+                &fs,
+                "read /etc/shadow".to_string(),
+                "path not allowed".to_string(),
+            );
+        }
+        collector.record_capability_denied(
+            &CapabilityId::new("network"),
+            "connect example.com:443".to_string(),
+            "host not allowed".to_string(),
+        );
+
+        let report = ExecutionReport::new(
+            ModuleInfo {
+                name: Some("plugin".to_string()),
+                export_count: 1,
+                import_count: 0,
+                hash: None,
+            },
+            ExecutionOutcome::Success { return_value: None },
+            collector.snapshot(),
+        );
+
+        let text = report.to_text();
+        assert!(text.contains("Denied Capability Attempts:"));
+        assert!(text.contains("filesystem 'read /etc/shadow': path not allowed (2x)"));
+        assert!(text.contains("network 'connect example.com:443': host not allowed (1x)"));
+
+        let json = report.to_json();
+        let denied = &json["metrics"]["capability_usage"]["denied"];
+        assert_eq!(denied.as_array().unwrap().len(), 2);
+        assert_eq!(denied[0]["capability"], "filesystem");
+        assert_eq!(denied[0]["action"], "read /etc/shadow");
+        assert_eq!(denied[0]["reason"], "path not allowed");
+        assert_eq!(denied[0]["count"], 2);
+        assert_eq!(denied[1]["capability"], "network");
+        assert_eq!(denied[1]["count"], 1);
+    }
+
+    #[test]
+    fn test_execution_report_redaction() {
+        let report = ExecutionReport::new(
+            ModuleInfo {
+                name: Some("plugin".to_string()),
+                export_count: 1,
+                import_count: 0,
+                hash: None,
+            },
+            ExecutionOutcome::Trapped {
+                trap: TrapInfo {
+                    code: Some("unreachable".to_string()),
+                    message: "unreachable in /home/user/plugins/secret.wasm".to_string(),
+                    backtrace: Some("0: run at /home/user/src/lib.rs:10".to_string()),
+                    frames: vec![TrapFrame {
+                        module: None,
+                        func_index: 0,
+                        func_name: Some("run".to_string()),
+                        offset: Some(42),
+                    }],
+                },
+            },
+            MetricsCollector::new().snapshot(),
+        );
+
+        let redacted = report.redacted(&ReportRedaction::strict());
+        let ExecutionOutcome::Trapped { trap } = &redacted.outcome else {
+            panic!("Unexpected outcome: {:?}", redacted.outcome);
+        };
+        assert_eq!(trap.code.as_deref(), Some("unreachable"));
+        assert_eq!(trap.message, "unreachable in <path>");
+        assert!(trap.backtrace.is_none());
+        assert!(trap.frames.is_empty());
+
+        // Formatting applies a pending redaction; the original is untouched
+        let report = report.with_redaction(ReportRedaction::strict());
+        assert!(!report.to_text().contains("/home/user"));
+        let json = report.to_json();
+        assert_eq!(
+            json["outcome"]["Trapped"]["trap"]["backtrace"],
+            serde_json::Value::Null
+        );
+        assert_eq!(
+            json["outcome"]["Trapped"]["trap"]["message"],
+            "unreachable in <path>"
+        );
+
+        let truncate = ReportRedaction::new().with_max_message_len(4);
+        assert_eq!(truncate.message("trapped"), "trap...");
+        assert_eq!(truncate.message("trap"), "trap");
+        let mask = ReportRedaction::new().with_mask_paths(true);
+        assert_eq!(
+            mask.message("ratio 1/2 at C:\\Users\\me"),
+            "ratio 1/2 at <path>"
+        );
     }
 }
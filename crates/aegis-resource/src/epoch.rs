@@ -4,13 +4,15 @@
 //! The engine periodically increments an epoch counter, and stores can
 //! be configured with a deadline that causes execution to trap when exceeded.
 
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
 use parking_lot::Mutex;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
 use crate::error::{ResourceError, ResourceResult};
 use aegis_core::engine::SharedEngine;
@@ -27,6 +29,14 @@ pub struct EpochConfig {
     pub default_timeout: Duration,
     /// Whether to start the epoch incrementer automatically.
     pub auto_start: bool,
+    /// Longest sleep between checks while no sandbox is executing.
+    ///
+    /// When set, the incrementer stops incrementing while the engine has no
+    /// active executions and doubles its sleep, with jitter, up to this
+    /// interval. It is woken as soon as an execution starts and returns to
+    /// `tick_interval`, so timeouts of running executions are unaffected.
+    /// `None` ticks at `tick_interval` unconditionally.
+    pub max_idle_interval: Option<Duration>,
 }
 
 impl Default for EpochConfig {
@@ -35,6 +45,7 @@ impl Default for EpochConfig {
             tick_interval: Duration::from_millis(10),
             default_timeout: Duration::from_secs(30),
             auto_start: true,
+            max_idle_interval: None,
         }
     }
 }
@@ -63,6 +74,14 @@ impl EpochConfig {
         self
     }
 
+    /// Back off while idle, sleeping up to `max_interval` between checks.
+    ///
+    /// See [`max_idle_interval`](Self::max_idle_interval).
+    pub fn with_idle_backoff(mut self, max_interval: Duration) -> Self {
+        self.max_idle_interval = Some(max_interval);
+        self
+    }
+
     /// Calculate the number of epochs for a given duration.
     pub fn epochs_for_duration(&self, duration: Duration) -> u64 {
         let ticks = duration.as_nanos() / self.tick_interval.as_nanos();
@@ -98,8 +117,11 @@ pub struct EpochManager {
     thread_handle: Mutex<Option<JoinHandle<()>>>,
     /// Whether the manager is running.
     running: AtomicBool,
-    /// Total epochs incremented.
-    total_epochs: AtomicU64,
+    /// Total epochs incremented, shared with the incrementer thread.
+    total_epochs: Arc<AtomicU64>,
+    /// Current sleep between ticks in nanoseconds, shared with the
+    /// incrementer thread.
+    current_interval: Arc<AtomicU64>,
     /// Number of timeout events detected.
     timeout_count: AtomicU64,
 }
@@ -114,13 +136,17 @@ impl EpochManager {
             return Err(ResourceError::EpochsDisabled);
         }
 
+        // Sandboxes convert timeouts to epochs using the engine's interval
+        engine.set_epoch_tick_interval(config.tick_interval);
+
         let manager = Self {
             engine,
             config: config.clone(),
             shutdown: Arc::new(AtomicBool::new(false)),
             thread_handle: Mutex::new(None),
             running: AtomicBool::new(false),
-            total_epochs: AtomicU64::new(0),
+            total_epochs: Arc::new(AtomicU64::new(0)),
+            current_interval: Arc::new(AtomicU64::new(nanos(config.tick_interval))),
             timeout_count: AtomicU64::new(0),
         };
 
@@ -140,9 +166,10 @@ impl EpochManager {
         let engine = Arc::clone(&self.engine);
         let shutdown = Arc::clone(&self.shutdown);
         let tick_interval = self.config.tick_interval;
-        let total_epochs = &self.total_epochs as *const AtomicU64 as usize;
+        let max_idle_interval = self.config.max_idle_interval;
+        let total_epochs = Arc::clone(&self.total_epochs);
+        let current_interval = Arc::clone(&self.current_interval);
 
-        // Safety: We ensure the EpochManager outlives the thread by joining in drop
         let handle = thread::Builder::new()
             .name("aegis-epoch-incrementer".to_string())
             .spawn(move || {
@@ -150,18 +177,37 @@ impl EpochManager {
                     tick_interval_ms = tick_interval.as_millis(),
                     "Epoch incrementer thread started"
                 );
+                if max_idle_interval.is_some() {
+                    engine.set_epoch_ticker(Some(thread::current()));
+                }
 
+                let mut jitter = Jitter::new();
+                let mut interval = tick_interval;
                 while !shutdown.load(Ordering::Relaxed) {
-                    thread::sleep(tick_interval);
-                    engine.increment_epoch();
-
-                    // Update counter (safe because we ensure thread doesn't outlive manager)
-                    unsafe {
-                        let counter = &*(total_epochs as *const AtomicU64);
-                        counter.fetch_add(1, Ordering::Relaxed);
+                    match max_idle_interval {
+                        Some(max) if engine.active_executions() == 0 => {
+                            // Nothing has a deadline running; an execution
+                            // that starts unparks the thread
+                            interval = interval.saturating_mul(2).min(max).max(tick_interval);
+                            current_interval.store(nanos(interval), Ordering::Relaxed);
+                            thread::park_timeout(jitter.apply(interval));
+                        }
+                        _ => {
+                            if interval != tick_interval {
+                                debug!("Epoch incrementer resumed from idle");
+                                interval = tick_interval;
+                                current_interval.store(nanos(interval), Ordering::Relaxed);
+                            }
+                            thread::sleep(tick_interval);
+                            engine.increment_epoch();
+                            total_epochs.fetch_add(1, Ordering::Relaxed);
+                        }
                     }
                 }
 
+                if max_idle_interval.is_some() {
+                    engine.set_epoch_ticker(None);
+                }
                 info!("Epoch incrementer thread stopped");
             })
             .map_err(|e| ResourceError::ThreadSpawnFailed(e.to_string()))?;
@@ -185,6 +231,8 @@ impl EpochManager {
         self.shutdown.store(true, Ordering::SeqCst);
 
         if let Some(handle) = self.thread_handle.lock().take() {
+            // Wake the thread if it is parked while idle
+            handle.thread().unpark();
             if let Err(e) = handle.join() {
                 warn!("Failed to join epoch incrementer thread: {:?}", e);
             }
@@ -206,6 +254,14 @@ impl EpochManager {
         self.config.tick_interval
     }
 
+    /// Get the incrementer's current sleep between ticks.
+    ///
+    /// This is the tick interval unless the incrementer is backing off
+    /// while idle.
+    pub fn current_tick_interval(&self) -> Duration {
+        Duration::from_nanos(self.current_interval.load(Ordering::Relaxed))
+    }
+
     /// Get the default timeout.
     pub fn default_timeout(&self) -> Duration {
         self.config.default_timeout
@@ -260,6 +316,7 @@ impl EpochManager {
             timeout_count: self.timeout_count(),
             is_running: self.is_running(),
             tick_interval: self.config.tick_interval,
+            current_tick_interval: self.current_tick_interval(),
         }
     }
 }
@@ -294,6 +351,9 @@ pub struct EpochStats {
     pub is_running: bool,
     /// Tick interval.
     pub tick_interval: Duration,
+    /// Current sleep between ticks, longer than `tick_interval` while the
+    /// incrementer is backing off.
+    pub current_tick_interval: Duration,
 }
 
 impl EpochStats {
@@ -304,6 +364,42 @@ impl EpochStats {
     }
 }
 
+/// Convert a duration to nanoseconds, saturating at `u64::MAX`.
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Random jitter for idle sleeps, so that idle incrementers do not wake in
+/// lockstep.
+struct Jitter {
+    state: u64,
+}
+
+impl Jitter {
+    /// Create a generator with a random seed.
+    fn new() -> Self {
+        Self {
+            state: RandomState::new().build_hasher().finish() | 1,
+        }
+    }
+
+    /// Shorten `interval` by a random amount of up to a quarter.
+    fn apply(&mut self, interval: Duration) -> Duration {
+        // xorshift64
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        let quarter = nanos(interval) / 4;
+        let cut = if quarter == 0 {
+            0
+        } else {
+            self.state % quarter
+        };
+        interval.saturating_sub(Duration::from_nanos(cut))
+    }
+}
+
 /// A guard that ensures execution completes within a timeout.
 ///
 /// When created, it calculates the epoch deadline. The caller is responsible
@@ -463,4 +559,82 @@ mod tests {
         let result = EpochManager::new(engine, EpochConfig::default());
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_restart_accumulates_epochs() {
+        let engine = create_engine();
+        let config = EpochConfig::new()
+            .with_tick_interval(Duration::from_millis(1))
+            .with_auto_start(false);
+        let manager = EpochManager::new(engine, config).unwrap();
+
+        manager.start().unwrap();
+        thread::sleep(Duration::from_millis(30));
+        manager.stop();
+        let first = manager.total_epochs();
+        assert!(first > 0);
+        assert!(!manager.is_running());
+
+        // No ticks while stopped
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(manager.total_epochs(), first);
+
+        manager.start().unwrap();
+        thread::sleep(Duration::from_millis(30));
+        manager.stop();
+        let second = manager.total_epochs();
+        assert!(second > first);
+
+        manager.increment();
+        assert_eq!(manager.total_epochs(), second + 1);
+        assert_eq!(manager.current_epoch(), second + 1);
+    }
+
+    #[test]
+    fn test_idle_backoff_wakes_for_execution() {
+        use aegis_core::{ModuleLoader, ResourceLimits, Sandbox, SandboxConfig};
+
+        let engine = AegisEngine::new(EngineConfig::default().with_epochs(true).with_fuel(false))
+            .unwrap()
+            .into_shared();
+        let config = EpochConfig::new()
+            .with_tick_interval(Duration::from_millis(1))
+            .with_idle_backoff(Duration::from_millis(500))
+            .with_auto_start(false);
+        let manager = EpochManager::new(Arc::clone(&engine), config).unwrap();
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(
+                r#"(module
+                    (func (export "noop"))
+                    (func (export "spin") (loop (br 0))))"#,
+            )
+            .unwrap();
+        let limits = ResourceLimits::default().with_timeout(Duration::from_millis(50));
+        let mut sandbox = Sandbox::<()>::new(
+            Arc::clone(&engine),
+            (),
+            SandboxConfig::default().with_limits(limits),
+        )
+        .unwrap();
+        sandbox.load_module(&module).unwrap();
+        sandbox.call_void("noop").unwrap();
+        assert_eq!(engine.active_executions(), 0);
+
+        // With nothing executing, the incrementer backs off and stops ticking
+        manager.start().unwrap();
+        thread::sleep(Duration::from_millis(300));
+        assert!(manager.current_tick_interval() > manager.tick_interval());
+        let idle_epochs = manager.total_epochs();
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(manager.total_epochs(), idle_epochs);
+
+        // A call wakes it, so the timeout is not stretched by the backoff
+        let start = Instant::now();
+        assert!(sandbox.call_void("spin").is_err());
+        assert!(start.elapsed() < Duration::from_millis(300));
+        assert!(manager.total_epochs() > idle_epochs);
+        assert_eq!(engine.active_executions(), 0);
+
+        manager.stop();
+    }
 }
@@ -69,6 +69,10 @@ pub enum ResourceError {
     #[error("Fuel consumption is disabled in the engine configuration")]
     FuelDisabled,
 
+    /// Fuel calibration did not run to exhaustion.
+    #[error("Fuel calibration failed: {0}")]
+    CalibrationFailed(String),
+
     /// Failed to spawn thread.
     #[error("Failed to spawn thread: {0}")]
     ThreadSpawnFailed(String),
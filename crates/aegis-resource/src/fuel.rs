@@ -4,8 +4,13 @@
 //! Each WASM instruction consumes a certain amount of fuel, and execution traps
 //! when fuel is exhausted.
 
+use std::collections::VecDeque;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
+use aegis_core::{ExecutionError, ModuleLoader, RefuelPolicy, SandboxBuilder, SharedEngine};
+use parking_lot::Mutex;
 use tracing::{debug, info, warn};
 
 use crate::error::{ResourceError, ResourceResult};
@@ -21,6 +26,8 @@ pub struct FuelConfig {
     pub max_refuel: u64,
     /// Optional fuel reserve that triggers a warning callback.
     pub low_fuel_threshold: Option<u64>,
+    /// Number of recent executions kept for fuel suggestions.
+    pub history_window: usize,
 }
 
 impl Default for FuelConfig {
@@ -30,6 +37,7 @@ impl Default for FuelConfig {
             allow_refuel: false,
             max_refuel: 0,
             low_fuel_threshold: None,
+            history_window: 100,
         }
     }
 }
@@ -44,6 +52,9 @@ impl FuelConfig {
     }
 
     /// Allow refueling with the specified maximum amount.
+    ///
+    /// A sandbox only refuels when a host function returns, so this does
+    /// not extend guest code that makes no host calls.
     pub fn with_refuel(mut self, max_refuel: u64) -> Self {
         self.allow_refuel = true;
         self.max_refuel = max_refuel;
@@ -56,6 +67,12 @@ impl FuelConfig {
         self
     }
 
+    /// Set the number of recent executions kept for fuel suggestions.
+    pub fn with_history_window(mut self, window: usize) -> Self {
+        self.history_window = window;
+        self
+    }
+
     /// Create a minimal fuel configuration for testing.
     pub fn minimal() -> Self {
         Self::new(10_000)
@@ -72,6 +89,42 @@ impl FuelConfig {
     }
 }
 
+/// Fuel burned by the calibration loop.
+const CALIBRATION_FUEL: u64 = 20_000_000;
+
+/// A tight loop that runs until its fuel is exhausted.
+const CALIBRATION_WAT: &str = r#"
+    (module
+        (func (export "spin")
+            (local $i i32)
+            (loop $loop
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $loop))))
+"#;
+
+/// Measured rate at which an engine burns fuel.
+///
+/// This is a heuristic: the rate comes from a single tight loop on the
+/// current machine, and real guests burn fuel faster or slower depending on
+/// the instructions they execute, host calls and load on the machine. Use it
+/// to pick timeouts of the right order of magnitude, not as a guarantee.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FuelCalibration {
+    /// Fuel burned per microsecond of wall time.
+    pub fuel_per_microsecond: f64,
+    /// Fuel burned by the calibration run.
+    pub fuel: u64,
+    /// Wall time of the calibration run.
+    pub elapsed: Duration,
+}
+
+impl FuelCalibration {
+    /// Estimate the wall time needed to burn `fuel`.
+    pub fn wall_time_for(&self, fuel: u64) -> Duration {
+        Duration::from_secs_f64(fuel as f64 / self.fuel_per_microsecond / 1_000_000.0)
+    }
+}
+
 /// Callback type for low fuel warnings.
 pub type LowFuelCallback = Box<dyn Fn(u64) + Send + Sync>;
 
@@ -90,6 +143,10 @@ pub struct FuelManager {
     refuel_count: AtomicU64,
     /// Total fuel added via refuel.
     total_refueled: AtomicU64,
+    /// Per-execution consumption for the most recent executions.
+    history: Mutex<VecDeque<u64>>,
+    /// Result of the last calibration, if any.
+    calibration: Mutex<Option<FuelCalibration>>,
 }
 
 impl FuelManager {
@@ -107,6 +164,8 @@ impl FuelManager {
             exhaustion_count: AtomicU64::new(0),
             refuel_count: AtomicU64::new(0),
             total_refueled: AtomicU64::new(0),
+            history: Mutex::new(VecDeque::new()),
+            calibration: Mutex::new(None),
         }
     }
 
@@ -140,6 +199,47 @@ impl FuelManager {
         );
     }
 
+    /// Record the fuel consumed by a single execution.
+    ///
+    /// Adds to the running total and to the bounded history used by
+    /// [`suggest_initial_fuel`](Self::suggest_initial_fuel).
+    pub fn record_execution(&self, consumed: u64) {
+        self.record_consumption(consumed);
+
+        let window = self.config.history_window;
+        if window == 0 {
+            return;
+        }
+
+        let mut history = self.history.lock();
+        while history.len() >= window {
+            history.pop_front();
+        }
+        history.push_back(consumed);
+    }
+
+    /// Suggest a starting fuel budget from recent executions.
+    ///
+    /// The suggestion is the larger of the mean and the 95th percentile of
+    /// the recorded history, increased by `headroom_percent`. Falls back to
+    /// the configured initial fuel when no executions have been recorded.
+    pub fn suggest_initial_fuel(&self, headroom_percent: u8) -> u64 {
+        let stats = self.stats();
+        let (Some(p95), Some(mean)) = (stats.p95_consumed(), stats.average_consumed()) else {
+            return self.config.initial_fuel;
+        };
+
+        let base = p95.max(mean);
+        let headroom = base
+            .saturating_mul(u64::from(headroom_percent))
+            .div_ceil(100);
+        let suggested = base.saturating_add(headroom);
+
+        debug!(base, headroom_percent, suggested, "Suggested initial fuel");
+
+        suggested
+    }
+
     /// Record a fuel exhaustion event.
     pub fn record_exhaustion(&self) {
         self.exhaustion_count.fetch_add(1, Ordering::Relaxed);
@@ -169,6 +269,64 @@ impl FuelManager {
         Ok(amount)
     }
 
+    /// Measure how fast `engine` burns fuel on this machine.
+    ///
+    /// Runs a tight loop with a fixed fuel budget until it runs out of fuel
+    /// and times it. The result is stored so [`FuelStats::estimated_wall_time`]
+    /// can convert fuel into time. See [`FuelCalibration`] for the caveats.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ResourceError::FuelDisabled` if the engine does not meter
+    /// fuel, or `ResourceError::CalibrationFailed` if the loop fails for any
+    /// reason other than running out of fuel.
+    pub fn calibrate(&self, engine: &SharedEngine) -> ResourceResult<FuelCalibration> {
+        if !engine.fuel_enabled() {
+            return Err(ResourceError::FuelDisabled);
+        }
+
+        let failed = |e: &dyn std::fmt::Display| ResourceError::CalibrationFailed(e.to_string());
+        let module = ModuleLoader::new(Arc::clone(engine))
+            .load_wat(CALIBRATION_WAT)
+            .map_err(|e| failed(&e))?;
+        let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(engine))
+            .with_fuel_limit(CALIBRATION_FUEL)
+            .build()
+            .map_err(|e| failed(&e))?;
+        sandbox.load_module(&module).map_err(|e| failed(&e))?;
+
+        let start = Instant::now();
+        let result = sandbox.call_void("spin");
+        let elapsed = start.elapsed();
+
+        match result {
+            Err(ExecutionError::OutOfFuel { .. }) => {}
+            Err(e) => return Err(failed(&e)),
+            Ok(()) => return Err(failed(&"calibration loop returned")),
+        }
+
+        let micros = elapsed.as_secs_f64() * 1_000_000.0;
+        let calibration = FuelCalibration {
+            fuel_per_microsecond: CALIBRATION_FUEL as f64 / micros.max(f64::MIN_POSITIVE),
+            fuel: CALIBRATION_FUEL,
+            elapsed,
+        };
+        *self.calibration.lock() = Some(calibration);
+
+        info!(
+            fuel_per_microsecond = calibration.fuel_per_microsecond,
+            elapsed = ?elapsed,
+            "Calibrated fuel rate"
+        );
+
+        Ok(calibration)
+    }
+
+    /// Get the result of the last [`calibrate`](Self::calibrate), if any.
+    pub fn calibration(&self) -> Option<FuelCalibration> {
+        *self.calibration.lock()
+    }
+
     /// Get total fuel consumed across all executions.
     pub fn total_consumed(&self) -> u64 {
         self.total_consumed.load(Ordering::Relaxed)
@@ -195,6 +353,7 @@ impl FuelManager {
         self.exhaustion_count.store(0, Ordering::Relaxed);
         self.refuel_count.store(0, Ordering::Relaxed);
         self.total_refueled.store(0, Ordering::Relaxed);
+        self.history.lock().clear();
     }
 
     /// Get a snapshot of fuel statistics.
@@ -205,6 +364,24 @@ impl FuelManager {
             exhaustion_count: self.exhaustion_count(),
             refuel_count: self.refuel_count(),
             total_refueled: self.total_refueled(),
+            recent_consumption: self.history.lock().iter().copied().collect(),
+            calibration: self.calibration(),
+        }
+    }
+}
+
+impl RefuelPolicy for FuelManager {
+    /// Record the call running low as an exhaustion and grant up to
+    /// `max_refuel` if refueling is allowed.
+    fn refuel(&self, consumed: u64) -> Option<u64> {
+        self.record_exhaustion();
+
+        match self.request_refuel(self.config.max_refuel) {
+            Ok(granted) => Some(granted),
+            Err(err) => {
+                debug!(consumed, error = %err, "Refuel not granted");
+                None
+            }
         }
     }
 }
@@ -232,6 +409,10 @@ pub struct FuelStats {
     pub refuel_count: u64,
     /// Total fuel added via refueling.
     pub total_refueled: u64,
+    /// Per-execution consumption for recent executions, oldest first.
+    pub recent_consumption: Vec<u64>,
+    /// Fuel rate from the manager's last calibration, if any.
+    pub calibration: Option<FuelCalibration>,
 }
 
 impl FuelStats {
@@ -245,6 +426,42 @@ impl FuelStats {
     pub fn had_exhaustions(&self) -> bool {
         self.exhaustion_count > 0
     }
+
+    /// Mean consumption over recent executions, rounded up.
+    ///
+    /// Returns `None` if no executions have been recorded.
+    pub fn average_consumed(&self) -> Option<u64> {
+        if self.recent_consumption.is_empty() {
+            return None;
+        }
+
+        let sum: u128 = self.recent_consumption.iter().map(|&c| u128::from(c)).sum();
+        let count = self.recent_consumption.len() as u128;
+        Some(sum.div_ceil(count) as u64)
+    }
+
+    /// Estimate the wall time the initial fuel allocation buys.
+    ///
+    /// A heuristic based on [`FuelCalibration`]; returns `None` if the
+    /// manager has not been calibrated.
+    pub fn estimated_wall_time(&self) -> Option<Duration> {
+        self.calibration
+            .map(|calibration| calibration.wall_time_for(self.initial_fuel))
+    }
+
+    /// 95th percentile consumption over recent executions (nearest rank).
+    ///
+    /// Returns `None` if no executions have been recorded.
+    pub fn p95_consumed(&self) -> Option<u64> {
+        if self.recent_consumption.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.recent_consumption.clone();
+        sorted.sort_unstable();
+        let rank = (sorted.len() * 95).div_ceil(100);
+        Some(sorted[rank.saturating_sub(1)])
+    }
 }
 
 /// Estimates for fuel costs of common operations.
@@ -385,4 +602,186 @@ mod tests {
         assert_eq!(estimates.estimate_memory_pages(10), 10_000);
         assert_eq!(estimates.estimate_host_calls(100), 10_000);
     }
+
+    #[test]
+    fn test_p95_and_suggestion() {
+        let manager = FuelManager::new(FuelConfig::new(1_000));
+
+        // No history yet: fall back to the configured budget
+        assert_eq!(manager.suggest_initial_fuel(20), 1_000);
+        assert_eq!(manager.stats().p95_consumed(), None);
+
+        // 1k..=100k, recorded out of order
+        for i in (1..=100u64).rev() {
+            manager.record_execution(i * 1_000);
+        }
+
+        let stats = manager.stats();
+        assert_eq!(stats.p95_consumed(), Some(95_000));
+        assert_eq!(stats.total_consumed, 5_050_000);
+
+        let suggested = manager.suggest_initial_fuel(20);
+        assert_eq!(suggested, 114_000);
+        assert!(suggested >= stats.p95_consumed().unwrap() * 120 / 100);
+    }
+
+    #[test]
+    fn test_history_window_is_bounded() {
+        let manager = FuelManager::new(FuelConfig::default().with_history_window(3));
+
+        for consumed in [1_000_000, 10, 20, 30] {
+            manager.record_execution(consumed);
+        }
+
+        let stats = manager.stats();
+        assert_eq!(stats.recent_consumption, vec![10, 20, 30]);
+        assert_eq!(stats.p95_consumed(), Some(30));
+        assert_eq!(manager.suggest_initial_fuel(0), 30);
+
+        manager.reset_stats();
+        assert!(manager.stats().recent_consumption.is_empty());
+    }
+
+    mod refuel_policy {
+        use std::sync::Arc;
+
+        use aegis_core::{
+            AegisEngine, EngineConfig, ExecutionError, ModuleLoader, Sandbox, SandboxBuilder,
+            ValidatedModule,
+        };
+
+        use super::*;
+
+        // Fuel is only topped up when a host function returns, so `spin`
+        // calls the host on every iteration; `spin_guest` never does
+        const SPIN_WAT: &str = r#"
+            (module
+                (import "env" "step" (func $step (param i32) (result i32)))
+                (func (export "spin") (param $n i32) (result i32)
+                    (local $i i32)
+                    (block $done
+                        (loop $loop
+                            (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                            (local.set $i (call $step (local.get $i)))
+                            (br $loop)
+                        )
+                    )
+                    (local.get $i)
+                )
+                (func (export "spin_guest") (param $n i32) (result i32)
+                    (local $i i32)
+                    (block $done
+                        (loop $loop
+                            (br_if $done (i32.ge_u (local.get $i) (local.get $n)))
+                            (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                            (br $loop)
+                        )
+                    )
+                    (local.get $i)
+                )
+            )
+        "#;
+
+        fn spin_sandbox(engine: &SharedEngine, module: &ValidatedModule, fuel: u64) -> Sandbox {
+            let mut sandbox = SandboxBuilder::<()>::new(Arc::clone(engine))
+                .with_fuel_limit(fuel)
+                .build()
+                .unwrap();
+            sandbox
+                .register_func("env", "step", |i: i32| i + 1)
+                .unwrap();
+            sandbox.load_module(module).unwrap();
+            sandbox
+        }
+
+        fn spin_module() -> (SharedEngine, ValidatedModule, u64) {
+            let engine = Arc::new(AegisEngine::new(EngineConfig::default()).unwrap());
+            let module = ModuleLoader::new(Arc::clone(&engine))
+                .load_wat(SPIN_WAT)
+                .unwrap();
+
+            let mut sandbox = spin_sandbox(&engine, &module, 10_000_000);
+            sandbox.call::<i32, i32>("spin", 1000).unwrap();
+            let cost = sandbox.metrics().fuel_consumed;
+            (engine, module, cost)
+        }
+
+        #[test]
+        fn test_call_completes_after_refuels() {
+            let (engine, module, cost) = spin_module();
+            let mut sandbox = spin_sandbox(&engine, &module, cost / 2);
+
+            // Budgets of cost/2, 5/6 cost, then 7/6 cost: two refuels
+            let manager = Arc::new(FuelManager::new(
+                FuelConfig::new(cost / 2).with_refuel(cost / 3),
+            ));
+            sandbox.set_refuel_policy(Arc::clone(&manager));
+
+            let result: i32 = sandbox.call("spin", 1000).unwrap();
+            assert_eq!(result, 1000);
+            assert_eq!(sandbox.metrics().refuels, 2);
+            assert_eq!(sandbox.metrics().fuel_refueled, 2 * (cost / 3));
+            assert_eq!(sandbox.metrics().fuel_consumed, cost);
+            assert_eq!(manager.refuel_count(), 2);
+            assert_eq!(manager.exhaustion_count(), 2);
+        }
+
+        #[test]
+        fn test_refuel_rounds_are_bounded() {
+            let (engine, module, cost) = spin_module();
+            let mut sandbox = spin_sandbox(&engine, &module, cost / 2);
+            sandbox.set_refuel_policy(FuelManager::new(
+                FuelConfig::new(cost / 2).with_refuel(cost / 3),
+            ));
+            sandbox.set_max_refuel_rounds(1);
+
+            let result = sandbox.call::<i32, i32>("spin", 1000);
+            assert!(matches!(result, Err(ExecutionError::OutOfFuel { .. })));
+            assert_eq!(sandbox.metrics().refuels, 1);
+        }
+
+        #[test]
+        fn test_guest_only_loop_is_not_refueled() {
+            let (engine, module, cost) = spin_module();
+            let mut sandbox = spin_sandbox(&engine, &module, cost / 2);
+            sandbox.set_refuel_policy(FuelManager::new(
+                FuelConfig::new(cost / 2).with_refuel(u64::MAX / 4),
+            ));
+
+            // Without host calls nothing gives the policy a chance to run
+            let result = sandbox.call::<i32, i32>("spin_guest", i32::MAX);
+            assert!(matches!(result, Err(ExecutionError::OutOfFuel { .. })));
+            assert_eq!(sandbox.metrics().refuels, 0);
+        }
+
+        #[test]
+        fn test_refuel_denied_by_config() {
+            let (engine, module, _) = spin_module();
+            let mut sandbox = spin_sandbox(&engine, &module, 100);
+            sandbox.set_refuel_policy(FuelManager::new(FuelConfig::new(100)));
+
+            let result = sandbox.call::<i32, i32>("spin", 1000);
+            assert!(matches!(result, Err(ExecutionError::OutOfFuel { .. })));
+            assert_eq!(sandbox.metrics().refuels, 0);
+        }
+    }
+
+    #[test]
+    fn test_calibration_rate_is_positive() {
+        use aegis_core::IntoShared;
+
+        let engine = aegis_core::AegisEngine::new(aegis_core::EngineConfig::default())
+            .unwrap()
+            .into_shared();
+        let manager = FuelManager::new(FuelConfig::new(1_000_000));
+        assert!(manager.stats().estimated_wall_time().is_none());
+
+        let calibration = manager.calibrate(&engine).unwrap();
+        assert!(calibration.fuel_per_microsecond.is_finite());
+        assert!(calibration.fuel_per_microsecond > 0.0);
+        assert_eq!(manager.calibration(), Some(calibration));
+
+        let estimate = manager.stats().estimated_wall_time().unwrap();
+        assert_eq!(estimate, calibration.wall_time_for(1_000_000));
+    }
 }
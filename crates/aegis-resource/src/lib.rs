@@ -60,7 +60,7 @@ pub mod limiter;
 // Re-export main types
 pub use epoch::{EpochConfig, EpochManager, EpochStats, TimeoutGuard};
 pub use error::{ResourceError, ResourceResult};
-pub use fuel::{FuelConfig, FuelCostEstimates, FuelManager, FuelStats};
+pub use fuel::{FuelCalibration, FuelConfig, FuelCostEstimates, FuelManager, FuelStats};
 pub use limiter::{AegisResourceLimiter, LimiterConfig, LimiterStats, MemoryGrowthEvent};
 
 /// Prelude module for convenient imports.
@@ -1,12 +1,14 @@
 //! Memory resource limiter implementation.
 //!
 //! This module provides the `AegisResourceLimiter` which implements Wasmtime's
-//! `ResourceLimiter` trait to enforce memory and table size limits.
+//! `ResourceLimiter` trait to enforce memory and table size limits. Each
+//! linear memory of a multi-memory module is tracked and bounded separately.
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use parking_lot::Mutex;
 use tracing::{debug, warn};
+use wasmtime::ResourceLimiter;
 
 /// Callback type for memory growth events.
 pub type MemoryGrowthCallback = Box<dyn Fn(MemoryGrowthEvent) + Send + Sync>;
@@ -14,34 +16,43 @@ pub type MemoryGrowthCallback = Box<dyn Fn(MemoryGrowthEvent) + Send + Sync>;
 /// Event emitted when memory grows.
 #[derive(Debug, Clone)]
 pub struct MemoryGrowthEvent {
+    /// Index of the memory that grew, in instantiation order.
+    pub memory_index: u32,
     /// Previous memory size in bytes.
     pub from_bytes: usize,
     /// New memory size in bytes.
     pub to_bytes: usize,
-    /// Maximum allowed memory in bytes.
+    /// Maximum allowed size of this memory in bytes.
     pub max_bytes: usize,
 }
 
 /// Configuration for the resource limiter.
 #[derive(Debug, Clone)]
 pub struct LimiterConfig {
-    /// Maximum memory in bytes.
+    /// Maximum size of each memory in bytes, unless overridden in
+    /// `memory_limits`.
     pub max_memory_bytes: usize,
+    /// Per-memory maximum sizes in bytes, by memory index.
+    pub memory_limits: Vec<Option<usize>>,
     /// Maximum table elements.
     pub max_table_elements: u32,
     /// Maximum number of memory instances.
     pub max_memories: u32,
     /// Maximum number of tables.
     pub max_tables: u32,
+    /// Trap instead of returning -1 from `memory.grow` when growth is denied.
+    pub trap_on_grow_failure: bool,
 }
 
 impl Default for LimiterConfig {
     fn default() -> Self {
         Self {
             max_memory_bytes: 64 * 1024 * 1024, // 64MB
+            memory_limits: Vec::new(),
             max_table_elements: 10_000,
             max_memories: 1,
             max_tables: 10,
+            trap_on_grow_failure: false,
         }
     }
 }
@@ -58,20 +69,75 @@ impl LimiterConfig {
         self
     }
 
+    /// Set the maximum size of the memory at `index`, overriding
+    /// `max_memory_bytes` for that memory.
+    ///
+    /// Which memory grows is inferred from sizes; see
+    /// [`AegisResourceLimiter`] for when a stricter limit applies.
+    pub fn with_memory_limit(mut self, index: u32, bytes: usize) -> Self {
+        let index = index as usize;
+        if self.memory_limits.len() <= index {
+            self.memory_limits.resize(index + 1, None);
+        }
+        self.memory_limits[index] = Some(bytes);
+        self
+    }
+
+    /// Set the maximum number of memories.
+    pub fn with_max_memories(mut self, memories: u32) -> Self {
+        self.max_memories = memories;
+        self
+    }
+
+    /// Get the maximum size of the memory at `index` in bytes.
+    pub fn memory_limit(&self, index: u32) -> usize {
+        self.memory_limits
+            .get(index as usize)
+            .copied()
+            .flatten()
+            .unwrap_or(self.max_memory_bytes)
+    }
+
     /// Set the maximum table elements.
     pub fn with_max_table_elements(mut self, elements: u32) -> Self {
         self.max_table_elements = elements;
         self
     }
+
+    /// Trap instead of failing `memory.grow` and `table.grow` when growth
+    /// is denied.
+    pub fn with_trap_on_grow_failure(mut self, trap: bool) -> Self {
+        self.trap_on_grow_failure = trap;
+        self
+    }
 }
 
 /// Resource limiter that enforces memory and table limits.
 ///
-/// This struct implements tracking of memory usage and can be used
-/// to monitor resource consumption during WASM execution.
+/// This struct tracks memory usage and implements Wasmtime's
+/// `ResourceLimiter`, so it can be installed on a `Store` with
+/// `Store::limiter` or on a sandbox with `Sandbox::set_resource_limiter`.
+///
+/// Memories are numbered in the order they are created, which for a single
+/// instance is the module's memory index order. Wasmtime does not say which
+/// memory is growing, so the attribution of growth to an index is a
+/// heuristic: growth is attributed to a memory whose tracked size matches.
+/// When several memories have that size, including zero-size memories that
+/// may also be a new memory being created, the smallest of their limits is
+/// enforced and they all keep it from then on. A memory therefore never
+/// grows past its own limit, but may be held to a smaller one than
+/// configured for its index.
+///
+/// Growth past a memory's declared maximum is denied. Wasmtime can still
+/// fail a growth the limiter permitted, e.g. when allocation fails; the
+/// tracked sizes and statistics are then rolled back. The growth callback
+/// is deferred until the growth is confirmed by the next growth check, by
+/// [`confirm_growth`](Self::confirm_growth) or when the limiter is dropped.
 pub struct AegisResourceLimiter {
     /// Configuration.
     config: LimiterConfig,
+    /// Tracked memories, by index.
+    memories: Mutex<MemoryTable>,
     /// Current total memory usage in bytes.
     current_memory: AtomicUsize,
     /// Peak memory usage in bytes.
@@ -80,6 +146,38 @@ pub struct AegisResourceLimiter {
     allocation_count: AtomicUsize,
     /// Optional callback for memory growth events.
     on_memory_grow: Mutex<Option<MemoryGrowthCallback>>,
+    /// Growth permitted by the last memory growth check and not yet
+    /// confirmed.
+    pending_growth: Mutex<Option<PendingGrowth>>,
+}
+
+/// A memory growth the limiter permitted but Wasmtime may still fail.
+#[derive(Debug)]
+struct PendingGrowth {
+    /// Tracked memories from before the growth, restored if it fails.
+    previous: MemoryTable,
+    /// Peak memory from before the growth in bytes.
+    previous_peak: usize,
+    /// Event passed to the growth callback once the growth is confirmed.
+    event: MemoryGrowthEvent,
+}
+
+/// Sizes and enforced limits of the tracked memories.
+#[derive(Debug, Default, Clone)]
+struct MemoryTable {
+    /// Current size and enforced limit of each memory in bytes.
+    entries: Vec<TrackedMemory>,
+    /// Memories known to have been created; growth from zero size that may
+    /// have created a memory is not counted, so there may be more.
+    created: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TrackedMemory {
+    /// Current size in bytes.
+    size: usize,
+    /// Limit enforced on growth in bytes.
+    limit: usize,
 }
 
 impl AegisResourceLimiter {
@@ -87,10 +185,12 @@ impl AegisResourceLimiter {
     pub fn new(config: LimiterConfig) -> Self {
         Self {
             config,
+            memories: Mutex::new(MemoryTable::default()),
             current_memory: AtomicUsize::new(0),
             peak_memory: AtomicUsize::new(0),
             allocation_count: AtomicUsize::new(0),
             on_memory_grow: Mutex::new(None),
+            pending_growth: Mutex::new(None),
         }
     }
 
@@ -104,12 +204,22 @@ impl AegisResourceLimiter {
         *self.on_memory_grow.lock() = Some(callback);
     }
 
-    /// Get the current memory usage in bytes.
+    /// Get the current memory usage across all memories in bytes.
     pub fn current_memory(&self) -> usize {
         self.current_memory.load(Ordering::Relaxed)
     }
 
-    /// Get the peak memory usage in bytes.
+    /// Get the current size of each memory in bytes, by index.
+    pub fn memory_sizes(&self) -> Vec<usize> {
+        self.memories
+            .lock()
+            .entries
+            .iter()
+            .map(|memory| memory.size)
+            .collect()
+    }
+
+    /// Get the peak memory usage across all memories in bytes.
     pub fn peak_memory(&self) -> usize {
         self.peak_memory.load(Ordering::Relaxed)
     }
@@ -131,30 +241,137 @@ impl AegisResourceLimiter {
         self.config.max_memory_bytes
     }
 
+    /// Get the smallest limit of the memories at `first..=last`.
+    fn min_memory_limit(&self, first: usize, last: usize) -> usize {
+        (first..=last)
+            .map(|index| {
+                self.config
+                    .memory_limit(u32::try_from(index).unwrap_or(u32::MAX))
+            })
+            .min()
+            .unwrap_or(self.config.max_memory_bytes)
+    }
+
+    /// Record the pending memory growth, if any, as having happened and
+    /// pass it to the growth callback.
+    pub fn confirm_growth(&self) {
+        let Some(pending) = self.pending_growth.lock().take() else {
+            return;
+        };
+        if let Some(callback) = self.on_memory_grow.lock().as_ref() {
+            callback(pending.event);
+        }
+    }
+
+    /// Roll back the pending memory growth, which Wasmtime failed to make.
+    fn revert_growth(&self) {
+        let Some(pending) = self.pending_growth.lock().take() else {
+            return;
+        };
+        let total: usize = pending.previous.entries.iter().map(|m| m.size).sum();
+        *self.memories.lock() = pending.previous;
+        self.current_memory.store(total, Ordering::Relaxed);
+        self.peak_memory
+            .store(pending.previous_peak, Ordering::Relaxed);
+        self.allocation_count.fetch_sub(1, Ordering::Relaxed);
+        debug!(
+            memory_index = pending.event.memory_index,
+            from_bytes = pending.event.from_bytes,
+            to_bytes = pending.event.to_bytes,
+            "Memory growth reverted"
+        );
+    }
+
     /// Check if memory growth is allowed.
     ///
+    /// Growth from zero size creates a new memory unless a tracked memory
+    /// has zero size, in which case either may be growing. A new memory's
+    /// index is only known while every creation has been unambiguous;
+    /// otherwise the smallest limit of the indices it may have is enforced.
+    ///
+    /// A permitted growth is tracked at once but only passed to the growth
+    /// callback once confirmed, since Wasmtime may still fail it; a
+    /// previous pending growth is confirmed first.
+    ///
     /// Returns `true` if the growth is permitted, `false` otherwise.
     pub fn check_memory_growth(&self, current: usize, desired: usize) -> bool {
-        if desired > self.config.max_memory_bytes {
+        self.confirm_growth();
+
+        let mut memories = self.memories.lock();
+        let mut candidates: Vec<usize> = memories
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, memory)| memory.size == current)
+            .map(|(index, _)| index)
+            .collect();
+
+        // Growth from zero to zero only happens when a memory is created,
+        // and growth from a size no memory has can only be a new memory
+        let creates = (current == 0 && desired == 0) || candidates.is_empty();
+        if creates {
+            candidates.clear();
+        }
+        let may_create = creates || current == 0;
+        let mut max_bytes = candidates
+            .iter()
+            .map(|&index| memories.entries[index].limit)
+            .min()
+            .unwrap_or(usize::MAX);
+        if may_create {
+            let created = memories.created;
+            let tracked = memories.entries.len();
+            max_bytes = max_bytes.min(self.min_memory_limit(created, tracked));
+        }
+
+        let index = if may_create {
+            memories.entries.len()
+        } else {
+            candidates[0]
+        };
+        let memory_index = u32::try_from(index).unwrap_or(u32::MAX);
+
+        if desired > max_bytes {
             warn!(
+                memory_index,
                 current_bytes = current,
                 desired_bytes = desired,
-                max_bytes = self.config.max_memory_bytes,
+                max_bytes,
                 "Memory growth denied: exceeds limit"
             );
             return false;
         }
 
-        // Update tracking
-        self.current_memory.store(desired, Ordering::Relaxed);
+        // Update tracking; memories that may be the one that grew keep the
+        // limit just enforced
+        let previous = memories.clone();
+        for &candidate in &candidates {
+            memories.entries[candidate].limit = max_bytes;
+        }
+        let grown = TrackedMemory {
+            size: desired,
+            limit: max_bytes,
+        };
+        if index == memories.entries.len() {
+            memories.entries.push(grown);
+        } else {
+            memories.entries[index] = grown;
+        }
+        if creates {
+            memories.created += 1;
+        }
+        let total: usize = memories.entries.iter().map(|memory| memory.size).sum();
+        drop(memories);
+        self.current_memory.store(total, Ordering::Relaxed);
         self.allocation_count.fetch_add(1, Ordering::Relaxed);
 
         // Update peak if necessary
-        let mut peak = self.peak_memory.load(Ordering::Relaxed);
-        while desired > peak {
+        let previous_peak = self.peak_memory.load(Ordering::Relaxed);
+        let mut peak = previous_peak;
+        while total > peak {
             match self.peak_memory.compare_exchange_weak(
                 peak,
-                desired,
+                total,
                 Ordering::Relaxed,
                 Ordering::Relaxed,
             ) {
@@ -163,16 +380,19 @@ impl AegisResourceLimiter {
             }
         }
 
-        // Emit callback if set
-        if let Some(callback) = self.on_memory_grow.lock().as_ref() {
-            callback(MemoryGrowthEvent {
+        *self.pending_growth.lock() = Some(PendingGrowth {
+            previous,
+            previous_peak,
+            event: MemoryGrowthEvent {
+                memory_index,
                 from_bytes: current,
                 to_bytes: desired,
-                max_bytes: self.config.max_memory_bytes,
-            });
-        }
+                max_bytes,
+            },
+        });
 
         debug!(
+            memory_index,
             from_bytes = current,
             to_bytes = desired,
             peak_bytes = self.peak_memory(),
@@ -184,6 +404,8 @@ impl AegisResourceLimiter {
 
     /// Check if table growth is allowed.
     pub fn check_table_growth(&self, current: u32, desired: u32) -> bool {
+        self.confirm_growth();
+
         if desired > self.config.max_table_elements {
             warn!(
                 current_elements = current,
@@ -205,6 +427,8 @@ impl AegisResourceLimiter {
 
     /// Reset the limiter statistics.
     pub fn reset(&self) {
+        *self.pending_growth.lock() = None;
+        *self.memories.lock() = MemoryTable::default();
         self.current_memory.store(0, Ordering::Relaxed);
         self.peak_memory.store(0, Ordering::Relaxed);
         self.allocation_count.store(0, Ordering::Relaxed);
@@ -245,6 +469,90 @@ impl LimiterStats {
     }
 }
 
+impl ResourceLimiter for AegisResourceLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        // Wasmtime fails growth past the declared maximum even if permitted
+        let within_maximum = maximum.is_none_or(|max| desired <= max);
+        if !within_maximum {
+            self.confirm_growth();
+            warn!(
+                current_bytes = current,
+                desired_bytes = desired,
+                maximum,
+                "Memory growth denied: exceeds declared maximum"
+            );
+        }
+        if within_maximum && self.check_memory_growth(current, desired) {
+            return Ok(true);
+        }
+
+        if self.config.trap_on_grow_failure {
+            return Err(wasmtime::Error::msg(format!(
+                "memory growth to {} bytes exceeds limit",
+                desired
+            )));
+        }
+        Ok(false)
+    }
+
+    fn memory_grow_failed(&mut self, error: wasmtime::Error) -> wasmtime::Result<()> {
+        warn!(error = %error, "Memory growth failed");
+        self.revert_growth();
+        if self.config.trap_on_grow_failure {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        let current = u32::try_from(current).unwrap_or(u32::MAX);
+        let desired = u32::try_from(desired).unwrap_or(u32::MAX);
+        if self.check_table_growth(current, desired) {
+            return Ok(true);
+        }
+
+        if self.config.trap_on_grow_failure {
+            return Err(wasmtime::Error::msg(format!(
+                "table growth to {} elements exceeds limit of {} elements",
+                desired, self.config.max_table_elements
+            )));
+        }
+        Ok(false)
+    }
+
+    fn table_grow_failed(&mut self, error: wasmtime::Error) -> wasmtime::Result<()> {
+        warn!(error = %error, "Table growth failed");
+        if self.config.trap_on_grow_failure {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn tables(&self) -> usize {
+        self.config.max_tables as usize
+    }
+
+    fn memories(&self) -> usize {
+        self.config.max_memories as usize
+    }
+}
+
+impl Drop for AegisResourceLimiter {
+    fn drop(&mut self) {
+        self.confirm_growth();
+    }
+}
+
 impl std::fmt::Debug for AegisResourceLimiter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AegisResourceLimiter")
@@ -311,9 +619,69 @@ mod tests {
         }));
 
         limiter.check_memory_growth(0, 1024);
+        assert!(!callback_called.load(Ordering::SeqCst));
+        limiter.confirm_growth();
         assert!(callback_called.load(Ordering::SeqCst));
     }
 
+    #[test]
+    fn test_failed_growth_reverted() {
+        const PAGE: usize = 65536;
+
+        let engine = wasmtime::Engine::default();
+        let module = wasmtime::Module::new(
+            &engine,
+            r#"(module
+                (memory (export "memory") 1 2)
+                (func (export "grow") (param i32) (result i32)
+                    (memory.grow (local.get 0))))"#,
+        )
+        .unwrap();
+
+        let grown = Arc::new(AtomicUsize::new(0));
+        let mut limiter = AegisResourceLimiter::new(LimiterConfig::default());
+        {
+            let grown = Arc::clone(&grown);
+            limiter.set_memory_growth_callback(Box::new(move |_| {
+                grown.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+
+        // A growth Wasmtime fails after it was permitted is rolled back
+        assert!(
+            limiter
+                .memory_growing(PAGE, 3 * PAGE, None)
+                .unwrap_or(false)
+        );
+        limiter
+            .memory_grow_failed(wasmtime::Error::msg("allocation failed"))
+            .unwrap();
+        limiter.confirm_growth();
+        assert_eq!(grown.load(Ordering::SeqCst), 0);
+        assert!(limiter.memory_sizes().is_empty());
+        assert_eq!(limiter.current_memory(), 0);
+        assert_eq!(limiter.peak_memory(), 0);
+        assert_eq!(limiter.allocation_count(), 0);
+
+        let mut store = wasmtime::Store::new(&engine, limiter);
+        store.limiter(|limiter| limiter);
+        let instance = wasmtime::Instance::new(&mut store, &module, &[]).unwrap();
+        let grow = instance
+            .get_typed_func::<i32, i32>(&mut store, "grow")
+            .unwrap();
+
+        // Growth past the declared maximum leaves the tracked size alone
+        assert_eq!(grow.call(&mut store, 2).unwrap(), -1);
+        assert_eq!(store.data().memory_sizes(), [PAGE]);
+        assert_eq!(grow.call(&mut store, 1).unwrap(), 1);
+        assert_eq!(store.data().memory_sizes(), [2 * PAGE]);
+        assert_eq!(store.data().current_memory(), 2 * PAGE);
+        assert_eq!(store.data().remaining_memory(), 64 * 1024 * 1024 - 2 * PAGE);
+
+        drop(store);
+        assert_eq!(grown.load(Ordering::SeqCst), 2);
+    }
+
     #[test]
     fn test_table_growth() {
         let config = LimiterConfig::default().with_max_table_elements(1000);
@@ -336,4 +704,170 @@ mod tests {
         assert_eq!(stats.max_memory, 1024);
         assert!((stats.utilization_percent() - 50.0).abs() < 0.01);
     }
+
+    const GROW_WAT: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "grow") (param $pages i32) (result i32)
+                (memory.grow (local.get $pages))
+            )
+        )
+    "#;
+
+    #[test]
+    fn test_installed_on_store() {
+        let engine = wasmtime::Engine::default();
+        let module = wasmtime::Module::new(&engine, GROW_WAT).unwrap();
+
+        let config = LimiterConfig::default()
+            .with_max_memory(2 * 65536)
+            .with_trap_on_grow_failure(true);
+        let mut store = wasmtime::Store::new(&engine, AegisResourceLimiter::new(config));
+        store.limiter(|limiter| limiter);
+
+        let instance = wasmtime::Instance::new(&mut store, &module, &[]).unwrap();
+        let grow = instance
+            .get_typed_func::<i32, i32>(&mut store, "grow")
+            .unwrap();
+
+        assert_eq!(grow.call(&mut store, 1).unwrap(), 1);
+        assert_eq!(store.data().peak_memory(), 2 * 65536);
+        assert_eq!(store.data().allocation_count(), 2);
+
+        let err = grow.call(&mut store, 1).unwrap_err();
+        assert!(format!("{:?}", err).contains("exceeds limit"));
+        assert_eq!(store.data().peak_memory(), 2 * 65536);
+    }
+
+    #[test]
+    fn test_denied_growth_without_trap() {
+        let engine = wasmtime::Engine::default();
+        let module = wasmtime::Module::new(&engine, GROW_WAT).unwrap();
+
+        let config = LimiterConfig::default().with_max_memory(2 * 65536);
+        let mut store = wasmtime::Store::new(&engine, AegisResourceLimiter::new(config));
+        store.limiter(|limiter| limiter);
+
+        let instance = wasmtime::Instance::new(&mut store, &module, &[]).unwrap();
+        let grow = instance
+            .get_typed_func::<i32, i32>(&mut store, "grow")
+            .unwrap();
+
+        assert_eq!(grow.call(&mut store, 4).unwrap(), -1);
+    }
+
+    #[test]
+    fn test_installed_on_sandbox() {
+        use aegis_core::{AegisEngine, EngineConfig, ExecutionError, ModuleLoader, SandboxBuilder};
+
+        let engine = Arc::new(AegisEngine::new(EngineConfig::default()).unwrap());
+        let module = ModuleLoader::new(Arc::clone(&engine))
+            .load_wat(GROW_WAT)
+            .unwrap();
+
+        let mut sandbox = SandboxBuilder::<()>::new(engine).build().unwrap();
+        sandbox.set_resource_limiter(AegisResourceLimiter::new(
+            LimiterConfig::default()
+                .with_max_memory(2 * 65536)
+                .with_trap_on_grow_failure(true),
+        ));
+        sandbox.load_module(&module).unwrap();
+
+        assert_eq!(sandbox.call::<i32, i32>("grow", 1).unwrap(), 1);
+        assert_eq!(sandbox.metrics().peak_memory, 2 * 65536);
+
+        let result = sandbox.call::<i32, i32>("grow", 1);
+        assert!(matches!(result, Err(ExecutionError::Wasmtime(_))));
+    }
+
+    #[test]
+    fn test_memories_bounded_separately() {
+        const PAGE: usize = 65536;
+
+        let engine = wasmtime::Engine::default();
+        let module = wasmtime::Module::new(
+            &engine,
+            r#"(module
+                (memory $heap (export "memory") 1)
+                (memory $scratch (export "scratch") 3)
+                (func (export "grow_heap") (param i32) (result i32)
+                    (memory.grow $heap (local.get 0)))
+                (func (export "grow_scratch") (param i32) (result i32)
+                    (memory.grow $scratch (local.get 0))))"#,
+        )
+        .unwrap();
+
+        let config = LimiterConfig::default()
+            .with_max_memories(2)
+            .with_memory_limit(0, 2 * PAGE)
+            .with_memory_limit(1, 6 * PAGE);
+        let mut store = wasmtime::Store::new(&engine, AegisResourceLimiter::new(config));
+        store.limiter(|limiter| limiter);
+
+        let instance = wasmtime::Instance::new(&mut store, &module, &[]).unwrap();
+        let grow_heap = instance
+            .get_typed_func::<i32, i32>(&mut store, "grow_heap")
+            .unwrap();
+        let grow_scratch = instance
+            .get_typed_func::<i32, i32>(&mut store, "grow_scratch")
+            .unwrap();
+        assert_eq!(store.data().memory_sizes(), [PAGE, 3 * PAGE]);
+
+        assert_eq!(grow_heap.call(&mut store, 1).unwrap(), 1);
+        assert_eq!(grow_heap.call(&mut store, 1).unwrap(), -1);
+        assert_eq!(grow_scratch.call(&mut store, 3).unwrap(), 3);
+        assert_eq!(grow_scratch.call(&mut store, 1).unwrap(), -1);
+
+        assert_eq!(store.data().memory_sizes(), [2 * PAGE, 6 * PAGE]);
+        assert_eq!(store.data().current_memory(), 8 * PAGE);
+    }
+
+    #[test]
+    fn test_equal_size_memories_use_smallest_limit() {
+        const PAGE: usize = 65536;
+
+        let engine = wasmtime::Engine::default();
+        let config = LimiterConfig::default()
+            .with_max_memories(2)
+            .with_memory_limit(0, 6 * PAGE)
+            .with_memory_limit(1, 2 * PAGE);
+
+        for pages in [1, 0] {
+            let module = wasmtime::Module::new(
+                &engine,
+                format!(
+                    r#"(module
+                        (memory $heap (export "memory") {pages})
+                        (memory $scratch (export "scratch") {pages})
+                        (func (export "grow_heap") (param i32) (result i32)
+                            (memory.grow $heap (local.get 0)))
+                        (func (export "grow_scratch") (param i32) (result i32)
+                            (memory.grow $scratch (local.get 0))))"#
+                ),
+            )
+            .unwrap();
+
+            let mut store =
+                wasmtime::Store::new(&engine, AegisResourceLimiter::new(config.clone()));
+            store.limiter(|limiter| limiter);
+
+            let instance = wasmtime::Instance::new(&mut store, &module, &[]).unwrap();
+            let grow_heap = instance
+                .get_typed_func::<i32, i32>(&mut store, "grow_heap")
+                .unwrap();
+            let grow_scratch = instance
+                .get_typed_func::<i32, i32>(&mut store, "grow_scratch")
+                .unwrap();
+            assert_eq!(store.data().memory_sizes(), [pages * PAGE, pages * PAGE]);
+
+            // Either memory may be growing, so scratch's limit applies
+            assert_eq!(grow_scratch.call(&mut store, 5 - pages as i32).unwrap(), -1);
+            assert_eq!(
+                grow_heap.call(&mut store, 2 - pages as i32).unwrap(),
+                pages as i32
+            );
+            assert_eq!(grow_scratch.call(&mut store, 5 - pages as i32).unwrap(), -1);
+            assert_eq!(store.data().current_memory(), (2 + pages) * PAGE);
+        }
+    }
 }
@@ -68,14 +68,15 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use aegis_capability::{
-    CapabilitySet, CapabilitySetBuilder, ClockCapability, FilesystemCapability, LoggingCapability,
-    NetworkCapability,
+    CapabilitySet, CapabilitySetBuilder, CapabilityView, ClockCapability, FilesystemCapability,
+    FrozenCapabilitySet, LoggingCapability, NetworkCapability, RandomCapability,
 };
 use aegis_core::{
     AegisEngine, EngineConfig, ExecutionError, ModuleLoader, ResourceLimits, Sandbox,
     SandboxConfig, SharedEngine, ValidatedModule,
 };
-use aegis_observe::{EventDispatcher, EventSubscriber};
+use aegis_host::WasiCapability;
+use aegis_observe::{EventDispatcher, EventSubscriber, MetricsCollector};
 
 // Re-export from sub-crates
 pub use aegis_capability;
@@ -104,6 +105,7 @@ pub struct AegisBuilder {
     engine_config: EngineConfig,
     resource_limits: ResourceLimits,
     capabilities: CapabilitySetBuilder,
+    wasi: Option<WasiCapability>,
     event_subscribers: Vec<Arc<dyn EventSubscriber>>,
 }
 
@@ -114,11 +116,39 @@ impl AegisBuilder {
             engine_config: EngineConfig::default(),
             resource_limits: ResourceLimits::default(),
             capabilities: CapabilitySetBuilder::new(),
+            wasi: None,
             event_subscribers: Vec::new(),
         }
     }
 
     // Engine configuration
+    //
+    // Profiles and `with_engine_config` replace the whole engine
+    // configuration, while the individual setters change one field. Calls
+    // apply in order, so the last one to touch a field wins.
+
+    /// Replace the engine configuration.
+    pub fn with_engine_config(mut self, config: EngineConfig) -> Self {
+        self.engine_config = config;
+        self
+    }
+
+    /// Use the [`EngineConfig::secure`] preset.
+    ///
+    /// Replaces any engine settings made earlier; setters called afterwards
+    /// still apply.
+    pub fn secure_profile(self) -> Self {
+        self.with_engine_config(EngineConfig::secure())
+    }
+
+    /// Use the [`EngineConfig::performance`] preset.
+    ///
+    /// Replaces any engine settings made earlier; setters called afterwards
+    /// still apply. Fuel is disabled in this preset, so fuel limits have no
+    /// effect.
+    pub fn performance_profile(self) -> Self {
+        self.with_engine_config(EngineConfig::performance())
+    }
 
     /// Enable or disable async execution support.
     pub fn with_async_support(mut self, enabled: bool) -> Self {
@@ -190,12 +220,27 @@ impl AegisBuilder {
         self
     }
 
+    /// Add the random capability.
+    pub fn with_random(mut self, config: RandomCapability) -> Self {
+        self.capabilities = self.capabilities.with(config);
+        self
+    }
+
     /// Add a custom capability.
     pub fn with_capability<C: aegis_capability::Capability + 'static>(mut self, cap: C) -> Self {
         self.capabilities = self.capabilities.with(cap);
         self
     }
 
+    /// Define WASI preview1 imports in every sandbox.
+    ///
+    /// Each WASI call is checked against the sandbox's capabilities; see
+    /// [`WasiCapability`].
+    pub fn with_wasi(mut self, wasi: WasiCapability) -> Self {
+        self.wasi = Some(wasi);
+        self
+    }
+
     // Observability
 
     /// Add an event subscriber.
@@ -221,6 +266,7 @@ impl AegisBuilder {
             default_limits: self.resource_limits,
             default_capabilities: Arc::new(capabilities),
             event_dispatcher: Arc::new(event_dispatcher),
+            wasi: self.wasi,
         })
     }
 }
@@ -237,6 +283,7 @@ pub struct AegisRuntime {
     default_limits: ResourceLimits,
     default_capabilities: Arc<CapabilitySet>,
     event_dispatcher: Arc<EventDispatcher>,
+    wasi: Option<WasiCapability>,
 }
 
 impl AegisRuntime {
@@ -260,6 +307,11 @@ impl AegisRuntime {
         &self.event_dispatcher
     }
 
+    /// Get the WASI support installed in sandboxes, if any.
+    pub fn wasi(&self) -> Option<&WasiCapability> {
+        self.wasi.as_ref()
+    }
+
     /// Create a module loader.
     pub fn loader(&self) -> ModuleLoader {
         ModuleLoader::new(Arc::clone(&self.engine))
@@ -277,6 +329,13 @@ impl AegisRuntime {
             .map_err(AegisError::Module)
     }
 
+    /// Load a module from a reader.
+    pub fn load_reader(&self, reader: impl std::io::Read) -> Result<ValidatedModule, AegisError> {
+        self.loader()
+            .load_reader(reader)
+            .map_err(AegisError::Module)
+    }
+
     /// Load a module from WAT text format.
     pub fn load_wat(&self, wat: &str) -> Result<ValidatedModule, AegisError> {
         self.loader().load_wat(wat).map_err(AegisError::Module)
@@ -313,7 +372,8 @@ impl std::fmt::Debug for AegisRuntime {
 pub struct RuntimeSandboxBuilder<'a> {
     runtime: &'a AegisRuntime,
     limits: Option<ResourceLimits>,
-    capabilities: Option<Arc<CapabilitySet>>,
+    capabilities: Option<CapabilityView>,
+    metrics_collector: Option<Arc<MetricsCollector>>,
 }
 
 impl<'a> RuntimeSandboxBuilder<'a> {
@@ -322,6 +382,7 @@ impl<'a> RuntimeSandboxBuilder<'a> {
             runtime,
             limits: None,
             capabilities: None,
+            metrics_collector: None,
         }
     }
 
@@ -365,29 +426,57 @@ impl<'a> RuntimeSandboxBuilder<'a> {
     }
 
     /// Override capabilities.
-    pub fn with_capabilities(mut self, capabilities: Arc<CapabilitySet>) -> Self {
-        self.capabilities = Some(capabilities);
+    pub fn with_capabilities(mut self, capabilities: impl Into<CapabilityView>) -> Self {
+        self.capabilities = Some(capabilities.into());
+        self
+    }
+
+    /// Override capabilities with a frozen set.
+    pub fn with_frozen_capabilities(mut self, capabilities: FrozenCapabilitySet) -> Self {
+        self.capabilities = Some(capabilities.into());
+        self
+    }
+
+    /// Feed the sandbox's metrics to `collector`.
+    pub fn with_metrics_collector(mut self, collector: Arc<MetricsCollector>) -> Self {
+        self.metrics_collector = Some(collector);
         self
     }
 
     /// Build the sandbox.
     pub fn build(self) -> Result<Sandbox<()>, AegisError> {
-        let limits = self
-            .limits
-            .unwrap_or_else(|| self.runtime.default_limits.clone());
-        let config = SandboxConfig::default().with_limits(limits);
-
-        Sandbox::new(Arc::clone(&self.runtime.engine), (), config).map_err(AegisError::Execution)
+        self.build_with_state(())
     }
 
     /// Build the sandbox with custom state.
+    ///
+    /// The sandbox receives the overridden capabilities if set, otherwise the
+    /// runtime's default capabilities, along with the runtime's event
+    /// dispatcher and WASI imports.
     pub fn build_with_state<S: Send + 'static>(self, state: S) -> Result<Sandbox<S>, AegisError> {
         let limits = self
             .limits
             .unwrap_or_else(|| self.runtime.default_limits.clone());
-        let config = SandboxConfig::default().with_limits(limits);
+        let capabilities = self
+            .capabilities
+            .unwrap_or_else(|| CapabilityView::new(Arc::clone(&self.runtime.default_capabilities)));
+        let mut config = SandboxConfig::default()
+            .with_limits(limits)
+            .with_capabilities(capabilities)
+            .with_event_dispatcher(Arc::clone(&self.runtime.event_dispatcher));
+        if let Some(collector) = self.metrics_collector {
+            config = config.with_metrics_collector(collector);
+        }
 
-        Sandbox::new(Arc::clone(&self.runtime.engine), state, config).map_err(AegisError::Execution)
+        let mut sandbox = Sandbox::new(Arc::clone(&self.runtime.engine), state, config)
+            .map_err(AegisError::Execution)?;
+        if let Some(wasi) = &self.runtime.wasi {
+            sandbox
+                .install_host_functions(wasi)
+                .map_err(AegisError::Execution)?;
+        }
+
+        Ok(sandbox)
     }
 }
 
@@ -425,7 +514,7 @@ pub mod prelude {
     // Capability types
     pub use aegis_capability::{
         Capability, CapabilityId, CapabilitySet, ClockCapability, FilesystemCapability,
-        LoggingCapability, NetworkCapability, PathPermission, PermissionResult,
+        LoggingCapability, NetworkCapability, PathPermission, PermissionResult, RandomCapability,
     };
 
     // Resource types
@@ -459,6 +548,28 @@ mod tests {
         assert_eq!(runtime.default_limits().initial_fuel, 100_000);
     }
 
+    #[test]
+    fn test_engine_profiles() {
+        let runtime = Aegis::builder().secure_profile().build().unwrap();
+        let config = runtime.engine().config();
+        assert_eq!(config.max_wasm_stack, 512 * 1024);
+        assert!(config.fuel_enabled);
+
+        let runtime = Aegis::builder().performance_profile().build().unwrap();
+        assert!(!runtime.engine().config().fuel_enabled);
+
+        // Setters after a profile refine it; a profile after setters replaces them
+        let builder = Aegis::builder().secure_profile().with_async_support(true);
+        assert!(builder.engine_config.async_support);
+        assert_eq!(builder.engine_config.max_wasm_stack, 512 * 1024);
+
+        let builder = Aegis::builder().with_component_model(true).secure_profile();
+        assert!(!builder.engine_config.component_model);
+
+        let builder = Aegis::builder().with_engine_config(EngineConfig::new().with_fuel(false));
+        assert!(!builder.engine_config.fuel_enabled);
+    }
+
     #[test]
     fn test_load_and_execute() {
         let runtime = Aegis::builder().build().unwrap();
@@ -503,4 +614,101 @@ mod tests {
 
         let _runtime = Aegis::builder().build().unwrap();
     }
+
+    const READ_DATA_WAT: &str = r#"
+        (module
+            (import "env" "read_data" (func $read_data (result i32)))
+            (func (export "main") (result i32)
+                call $read_data
+            )
+        )
+    "#;
+
+    fn call_read_data(sandbox: &mut Sandbox<()>, module: &ValidatedModule) -> i32 {
+        use aegis_capability::builtin::FilesystemAction;
+        use aegis_core::SandboxData;
+        use aegis_host::HostContext;
+
+        sandbox
+            .register_func(
+                "env",
+                "read_data",
+                |caller: wasmtime::Caller<'_, SandboxData<()>>| -> i32 {
+                    let capabilities = caller.data().capabilities.clone();
+                    let ctx = HostContext::with_capabilities(caller, capabilities);
+                    let action = FilesystemAction::Read {
+                        path: "/data/input.txt".into(),
+                    };
+                    ctx.require_permission(&action).is_ok() as i32
+                },
+            )
+            .unwrap();
+        sandbox.load_module(module).unwrap();
+        sandbox.call("main", ()).unwrap()
+    }
+
+    #[test]
+    fn test_sandbox_receives_capabilities() {
+        let runtime = Aegis::builder()
+            .with_filesystem(FilesystemCapability::read_only(&["/data"]))
+            .build()
+            .unwrap();
+        let module = runtime.load_wat(READ_DATA_WAT).unwrap();
+
+        let mut sandbox = runtime.sandbox().build().unwrap();
+        assert_eq!(call_read_data(&mut sandbox, &module), 1);
+    }
+
+    #[test]
+    fn test_sandbox_without_grant_is_denied() {
+        let runtime = Aegis::builder().build().unwrap();
+        let module = runtime.load_wat(READ_DATA_WAT).unwrap();
+
+        let mut sandbox = runtime.sandbox().build().unwrap();
+        assert_eq!(call_read_data(&mut sandbox, &module), 0);
+    }
+
+    #[test]
+    fn test_sandbox_capability_override() {
+        let runtime = Aegis::builder()
+            .with_filesystem(FilesystemCapability::read_only(&["/data"]))
+            .build()
+            .unwrap();
+        let module = runtime.load_wat(READ_DATA_WAT).unwrap();
+
+        let mut sandbox = runtime
+            .sandbox()
+            .with_capabilities(Arc::new(CapabilitySet::new()))
+            .build()
+            .unwrap();
+        assert_eq!(call_read_data(&mut sandbox, &module), 0);
+    }
+
+    #[test]
+    fn test_runtime_wasi_stdout() {
+        const HELLO_WAT: &str = r#"
+            (module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "hi\n")
+                (func (export "_start") (result i32)
+                    (i32.store (i32.const 0) (i32.const 16))
+                    (i32.store (i32.const 4) (i32.const 3))
+                    (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+            )
+        "#;
+
+        let runtime = Aegis::builder()
+            .with_logging(LoggingCapability::allow_all())
+            .with_wasi(WasiCapability::new())
+            .build()
+            .unwrap();
+        let module = runtime.load_wat(HELLO_WAT).unwrap();
+
+        let mut sandbox = runtime.sandbox().build().unwrap();
+        sandbox.load_module(&module).unwrap();
+        assert_eq!(sandbox.call::<(), i32>("_start", ()).unwrap(), 0);
+        assert_eq!(runtime.wasi().unwrap().output().stdout_lossy(), "hi\n");
+    }
 }